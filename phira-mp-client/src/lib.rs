@@ -1,8 +1,10 @@
 use anyhow::{Context, Error, Result};
 use dashmap::DashMap;
 use phira_mp_common::{
-    ClientCommand, ClientRoomState, HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT, JoinRoomResponse,
-    JudgeEvent, Message, RoomId, RoomState, ServerCommand, Stream, TouchFrame, UserInfo,
+    ChartId, ClientCommand, ClientRoomState, HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT,
+    JoinRoomResponse, JudgeEvent, Message, PROTOCOL_VERSION, ROOM_RENAMED_CHANNEL, ResultRow,
+    RoomId, RoomName, RoomState, SCHEDULED_ANNOUNCEMENT_CHANNEL, ScheduledAnnouncement,
+    ServerCommand, Stream, TouchFrame, UserInfo,
 };
 use std::{
     sync::{
@@ -64,9 +66,15 @@ struct State {
     cb_cancel_ready: RCallback<()>,
     cb_played: RCallback<()>,
     cb_abort: RCallback<()>,
+    cb_join_channel: RCallback<()>,
+    cb_leave_channel: RCallback<()>,
 
     live_players: DashMap<i32, Arc<LivePlayer>>,
     messages: Mutex<Vec<Message>>,
+    game_results: Mutex<Option<Vec<ResultRow>>>,
+    plugin_notifications: Mutex<Vec<(String, String)>>,
+    scheduled_announcement: Mutex<Option<ScheduledAnnouncement>>,
+    room_name: Mutex<Option<RoomName>>,
 }
 
 impl State {
@@ -113,17 +121,28 @@ impl Client {
             cb_cancel_ready: Callback::default(),
             cb_played: Callback::default(),
             cb_abort: Callback::default(),
+            cb_join_channel: Callback::default(),
+            cb_leave_channel: Callback::default(),
 
             live_players: DashMap::new(),
             messages: Mutex::default(),
+            game_results: Mutex::default(),
+            plugin_notifications: Mutex::default(),
+            scheduled_announcement: Mutex::default(),
+            room_name: Mutex::default(),
         });
         let stream = Arc::new(
             Stream::new(
-                Some(1),
+                Some(PROTOCOL_VERSION),
                 stream,
                 Box::new({
                     let state = Arc::clone(&state);
-                    move |_send_tx, cmd| process(Arc::clone(&state), cmd)
+                    move |send_tx, cmd| {
+                        if matches!(cmd, ServerCommand::Ping) {
+                            let _ = send_tx.send(ClientCommand::Pong);
+                        }
+                        process(Arc::clone(&state), cmd)
+                    }
                 }),
             )
             .await?,
@@ -187,6 +206,33 @@ impl Client {
         self.state.messages.blocking_lock().drain(..).collect()
     }
 
+    pub fn blocking_take_game_results(&self) -> Option<Vec<ResultRow>> {
+        self.state.game_results.blocking_lock().take()
+    }
+
+    /// Take pending plugin notifications as `(channel, data)` pairs.
+    pub fn blocking_take_plugin_notifications(&self) -> Vec<(String, String)> {
+        self.state
+            .plugin_notifications
+            .blocking_lock()
+            .drain(..)
+            .collect()
+    }
+
+    /// Upcoming event to show a countdown banner for, if announced.
+    pub fn blocking_scheduled_announcement(&self) -> Option<ScheduledAnnouncement> {
+        self.state.scheduled_announcement.blocking_lock().clone()
+    }
+
+    /// Display name of the current room, its ID unless renamed.
+    pub fn blocking_room_name(&self) -> Option<String> {
+        let id = self.blocking_room_id()?.to_string();
+        match self.state.room_name.blocking_lock().as_ref() {
+            Some(room) if room.room_id == id => Some(room.name.clone()),
+            _ => Some(id),
+        }
+    }
+
     pub fn blocking_state(&self) -> Option<ClientRoomState> {
         self.state.room.blocking_read().clone()
     }
@@ -274,13 +320,56 @@ impl Client {
         .await
     }
 
+    /// Send a chat message to members of `channel` in the current room.
     #[inline]
-    pub async fn create_room(&self, id: RoomId) -> Result<()> {
+    pub async fn chat_in(&self, channel: String, message: String) -> Result<()> {
+        self.rcall(
+            ClientCommand::ChannelChat {
+                channel: channel.try_into()?,
+                message: message.try_into()?,
+            },
+            &self.state.cb_chat,
+        )
+        .await
+    }
+
+    #[inline]
+    pub async fn join_channel(&self, channel: String) -> Result<()> {
+        self.rcall(
+            ClientCommand::JoinChannel {
+                channel: channel.try_into()?,
+            },
+            &self.state.cb_join_channel,
+        )
+        .await
+    }
+
+    #[inline]
+    pub async fn leave_channel(&self, channel: String) -> Result<()> {
         self.rcall(
-            ClientCommand::CreateRoom { id: id.clone() },
-            &self.state.cb_create_room,
+            ClientCommand::LeaveChannel {
+                channel: channel.try_into()?,
+            },
+            &self.state.cb_leave_channel,
         )
-        .await?;
+        .await
+    }
+
+    #[inline]
+    pub async fn create_room(&self, id: RoomId) -> Result<()> {
+        self.open_room(ClientCommand::CreateRoom { id: id.clone() }, id)
+            .await
+    }
+
+    /// Creates a room whose lone player starts without the ready handshake.
+    #[inline]
+    pub async fn create_practice_room(&self, id: RoomId) -> Result<()> {
+        self.open_room(ClientCommand::CreatePracticeRoom { id: id.clone() }, id)
+            .await
+    }
+
+    async fn open_room(&self, cmd: ClientCommand, id: RoomId) -> Result<()> {
+        self.rcall(cmd, &self.state.cb_create_room).await?;
         let me = self.state.me.read().await.clone().unwrap();
         *self.state.room.write().await = Some(ClientRoomState {
             id,
@@ -324,6 +413,7 @@ impl Client {
         self.rcall(ClientCommand::LeaveRoom, &self.state.cb_leave_room)
             .await?;
         *self.state.room.write().await = None;
+        *self.state.room_name.lock().await = None;
         Ok(())
     }
 
@@ -343,7 +433,7 @@ impl Client {
     }
 
     #[inline]
-    pub async fn select_chart(&self, id: i32) -> Result<()> {
+    pub async fn select_chart(&self, id: ChartId) -> Result<()> {
         self.rcall(
             ClientCommand::SelectChart { id },
             &self.state.cb_select_chart,
@@ -512,5 +602,48 @@ async fn process(state: Arc<State>, cmd: ServerCommand) {
         ServerCommand::Abort(res) => {
             cb(&state.cb_abort, res).await;
         }
+
+        ServerCommand::GameResults { rows, .. } => {
+            *state.game_results.lock().await = Some(rows);
+        }
+        ServerCommand::PluginNotification { channel, data }
+            if channel == SCHEDULED_ANNOUNCEMENT_CHANNEL =>
+        {
+            match ScheduledAnnouncement::from_notification(&data) {
+                Ok(announcement) => *state.scheduled_announcement.lock().await = announcement,
+                Err(err) => warn!("invalid scheduled announcement: {err:?}"),
+            }
+        }
+        ServerCommand::PluginNotification { channel, data } if channel == ROOM_RENAMED_CHANNEL => {
+            match RoomName::from_notification(&data) {
+                Ok(room) => *state.room_name.lock().await = Some(room),
+                Err(err) => warn!("invalid room name: {err:?}"),
+            }
+        }
+        ServerCommand::PluginNotification { channel, data } => {
+            state
+                .plugin_notifications
+                .lock()
+                .await
+                .push((channel, data));
+        }
+        ServerCommand::RoomStatePatch(patch) => {
+            let mut guard = state.room.write().await;
+            let Some(room) = guard.as_mut() else {
+                return;
+            };
+            if patch.state.is_some() {
+                state.live_players.clear();
+                room.is_ready = room.is_host;
+            }
+            patch.apply(room);
+        }
+        ServerCommand::JoinChannel(res) => {
+            cb(&state.cb_join_channel, res).await;
+        }
+        ServerCommand::LeaveChannel(res) => {
+            cb(&state.cb_leave_channel, res).await;
+        }
+        ServerCommand::Ping => {}
     }
 }
@@ -1,8 +1,9 @@
-use crate::{BinaryData, BinaryReader, BinaryWriter};
+use crate::{BinaryData, BinaryReader, BinaryWriter, CommandPriority, Prioritized, TraceFrame};
 use anyhow::{Result, bail};
 use half::f16;
 use phira_mp_macros::BinaryData;
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Arc, time::Duration};
 
 type SResult<T> = Result<T, String>;
 
@@ -122,6 +123,83 @@ impl BinaryData for RoomId {
     }
 }
 
+/// ID of a chart on the Phira server, always positive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "i64", into = "i32")]
+pub struct ChartId(i32);
+
+impl ChartId {
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<i64> for ChartId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i64) -> Result<Self> {
+        match i32::try_from(value) {
+            Ok(id) if id > 0 => Ok(Self(id)),
+            _ => bail!("invalid chart id: {value}"),
+        }
+    }
+}
+
+impl TryFrom<i32> for ChartId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> Result<Self> {
+        i64::from(value).try_into()
+    }
+}
+
+impl TryFrom<u32> for ChartId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        i64::from(value).try_into()
+    }
+}
+
+impl From<ChartId> for i32 {
+    fn from(value: ChartId) -> Self {
+        value.0
+    }
+}
+
+impl From<ChartId> for u32 {
+    fn from(value: ChartId) -> Self {
+        value.0 as u32
+    }
+}
+
+impl FromStr for ChartId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.trim()
+            .parse::<i64>()
+            .map_err(|_| anyhow::anyhow!("invalid chart id: {s}"))?
+            .try_into()
+    }
+}
+
+impl Display for ChartId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl BinaryData for ChartId {
+    fn read_binary(r: &mut BinaryReader<'_>) -> Result<Self> {
+        i32::read_binary(r)?.try_into()
+    }
+
+    fn write_binary(&self, w: &mut BinaryWriter<'_>) -> Result<()> {
+        self.0.write_binary(w)
+    }
+}
+
 impl<const N: usize> Varchar<N> {
     pub fn into_inner(self) -> String {
         self.0
@@ -157,24 +235,174 @@ pub struct JudgeEvent {
 pub enum ClientCommand {
     Ping,
 
-    Authenticate { token: Varchar<32> },
-    Chat { message: Varchar<200> },
+    Authenticate {
+        token: Varchar<32>,
+    },
+    Chat {
+        message: Varchar<200>,
+    },
 
-    Touches { frames: Arc<Vec<TouchFrame>> },
-    Judges { judges: Arc<Vec<JudgeEvent>> },
+    Touches {
+        frames: Arc<Vec<TouchFrame>>,
+    },
+    Judges {
+        judges: Arc<Vec<JudgeEvent>>,
+    },
 
-    CreateRoom { id: RoomId },
-    JoinRoom { id: RoomId, monitor: bool },
+    CreateRoom {
+        id: RoomId,
+    },
+    JoinRoom {
+        id: RoomId,
+        monitor: bool,
+    },
     LeaveRoom,
-    LockRoom { lock: bool },
-    CycleRoom { cycle: bool },
+    LockRoom {
+        lock: bool,
+    },
+    CycleRoom {
+        cycle: bool,
+    },
 
-    SelectChart { id: i32 },
+    SelectChart {
+        id: ChartId,
+    },
     RequestStart,
     Ready,
     CancelReady,
-    Played { id: i32 },
+    Played {
+        id: i32,
+    },
     Abort,
+
+    ChannelChat {
+        channel: Varchar<20>,
+        message: Varchar<200>,
+    },
+    JoinChannel {
+        channel: Varchar<20>,
+    },
+    LeaveChannel {
+        channel: Varchar<20>,
+    },
+    /// Answer to `ServerCommand::Ping`.
+    Pong,
+    /// Like `CreateRoom`, but a lone player of the room starts without the ready handshake
+    /// whatever the server's default. Answered with `ServerCommand::CreateRoom`.
+    CreatePracticeRoom {
+        id: RoomId,
+    },
+}
+
+/// Announcement text color, from a palette clients map to their own theme.
+#[derive(Debug, BinaryData, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementColor {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl FromStr for AnnouncementColor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "red" => Self::Red,
+            "orange" => Self::Orange,
+            "yellow" => Self::Yellow,
+            "green" => Self::Green,
+            "blue" => Self::Blue,
+            "purple" => Self::Purple,
+            "gray" | "grey" => Self::Gray,
+            _ => bail!("unknown color: {s}"),
+        })
+    }
+}
+
+/// Rendering hints of an announcement.
+#[derive(Debug, BinaryData, Clone, Default, PartialEq, Eq)]
+pub struct AnnouncementStyle {
+    pub bold: bool,
+    pub color: Option<AnnouncementColor>,
+}
+
+/// Strips control characters and tag-like markup (`<b>`, `</color>`) from announcement
+/// text, since styling is only conveyed through [`AnnouncementStyle`].
+pub fn sanitize_announcement(content: &str) -> String {
+    /// Length of the tag at the start of `s`, if it looks like markup.
+    fn tag_len(s: &str) -> Option<usize> {
+        let end = s.find('>')?;
+        let name = s[1..end].trim_start_matches('/');
+        (name.starts_with(|c: char| c.is_ascii_alphabetic()) && !name.contains('<'))
+            .then_some(end + 1)
+    }
+
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(len) = tag_len(rest)
+        {
+            rest = &rest[len..];
+            continue;
+        }
+        if !c.is_control() {
+            result.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    result.trim().to_owned()
+}
+
+/// Plugin notification channel of the [`ScheduledAnnouncement`].
+pub const SCHEDULED_ANNOUNCEMENT_CHANNEL: &str = "scheduled_announcement";
+
+/// An upcoming event, shown by clients as a countdown banner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledAnnouncement {
+    pub title: String,
+    /// Start time in seconds since the Unix epoch.
+    pub starts_at: i64,
+}
+
+impl ScheduledAnnouncement {
+    /// Time left at `now` (seconds since the Unix epoch), zero once started.
+    pub fn remaining(&self, now: i64) -> Duration {
+        Duration::from_secs(self.starts_at.saturating_sub(now).max(0) as u64)
+    }
+
+    /// Notification data of an announcement, `null` clearing the banner.
+    pub fn to_notification(announcement: Option<&Self>) -> String {
+        serde_json::to_string(&announcement).unwrap()
+    }
+
+    pub fn from_notification(data: &str) -> Result<Option<Self>> {
+        Ok(serde_json::from_str(data)?)
+    }
+}
+
+/// Plugin notification channel of a [`RoomName`].
+pub const ROOM_RENAMED_CHANNEL: &str = "room_renamed";
+
+/// Display name of a renamed room, sent to its members and to users joining it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomName {
+    pub room_id: String,
+    pub name: String,
+}
+
+impl RoomName {
+    pub fn to_notification(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_notification(data: &str) -> Result<Self> {
+        Ok(serde_json::from_str(data)?)
+    }
 }
 
 #[derive(Clone, Debug, BinaryData)]
@@ -200,7 +428,7 @@ pub enum Message {
     SelectChart {
         user: i32,
         name: String,
-        id: i32,
+        id: ChartId,
     },
     GameStart {
         user: i32,
@@ -231,11 +459,20 @@ pub enum Message {
     CycleRoom {
         cycle: bool,
     },
+    ChannelChat {
+        user: i32,
+        channel: String,
+        content: String,
+    },
+    Announcement {
+        content: String,
+        style: AnnouncementStyle,
+    },
 }
 
-#[derive(Debug, BinaryData, Clone, Copy)]
+#[derive(Debug, BinaryData, Clone, Copy, PartialEq, Eq)]
 pub enum RoomState {
-    SelectChart(Option<i32>),
+    SelectChart(Option<ChartId>),
     WaitingForReady,
     Playing,
 }
@@ -246,7 +483,7 @@ impl Default for RoomState {
     }
 }
 
-#[derive(Clone, Debug, BinaryData)]
+#[derive(Clone, Debug, BinaryData, PartialEq, Eq)]
 pub struct UserInfo {
     pub id: i32,
     pub name: String,
@@ -265,6 +502,86 @@ pub struct ClientRoomState {
     pub users: HashMap<i32, UserInfo>,
 }
 
+/// Room-wide fields of a [`ClientRoomState`] that changed, each carrying its new value.
+///
+/// Per-client fields (`is_host`, `is_ready`) are not covered.
+#[derive(Debug, BinaryData, Clone, Default, PartialEq)]
+pub struct RoomStatePatch {
+    pub state: Option<RoomState>,
+    pub live: Option<bool>,
+    pub locked: Option<bool>,
+    pub cycle: Option<bool>,
+    /// Changed users; `None` means the user left.
+    pub users: Vec<(i32, Option<UserInfo>)>,
+}
+
+impl RoomStatePatch {
+    pub fn diff(old: &ClientRoomState, new: &ClientRoomState) -> Self {
+        fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+            (old != new).then(|| new.clone())
+        }
+        let mut users: Vec<_> = new
+            .users
+            .iter()
+            .filter(|(id, info)| old.users.get(id) != Some(info))
+            .map(|(id, info)| (*id, Some(info.clone())))
+            .chain(
+                old.users
+                    .keys()
+                    .filter(|id| !new.users.contains_key(id))
+                    .map(|id| (*id, None)),
+            )
+            .collect();
+        users.sort_by_key(|(id, _)| *id);
+        Self {
+            state: changed(&old.state, &new.state),
+            live: changed(&old.live, &new.live),
+            locked: changed(&old.locked, &new.locked),
+            cycle: changed(&old.cycle, &new.cycle),
+            users,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(self, state: &mut ClientRoomState) {
+        if let Some(room_state) = self.state {
+            state.state = room_state;
+        }
+        if let Some(live) = self.live {
+            state.live = live;
+        }
+        if let Some(locked) = self.locked {
+            state.locked = locked;
+        }
+        if let Some(cycle) = self.cycle {
+            state.cycle = cycle;
+        }
+        for (id, info) in self.users {
+            match info {
+                Some(info) => {
+                    state.users.insert(id, info);
+                }
+                None => {
+                    state.users.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, BinaryData, Clone)]
+pub struct ResultRow {
+    pub rank: u32,
+    pub player: i32,
+    pub score: i32,
+    pub accuracy: f32,
+    pub full_combo: bool,
+    pub aborted: bool,
+}
+
 #[derive(Debug, BinaryData, Clone)]
 pub struct JoinRoomResponse {
     pub state: RoomState,
@@ -305,4 +622,138 @@ pub enum ServerCommand {
     CancelReady(SResult<()>),
     Played(SResult<()>),
     Abort(SResult<()>),
+
+    GameResults {
+        room: RoomId,
+        rows: Vec<ResultRow>,
+    },
+    PluginNotification {
+        channel: String,
+        data: String,
+    },
+    RoomStatePatch(RoomStatePatch),
+    JoinChannel(SResult<()>),
+    LeaveChannel(SResult<()>),
+    /// Latency probe, answered with `ClientCommand::Pong`.
+    Ping,
+}
+
+impl Prioritized for ClientCommand {
+    fn priority(&self) -> CommandPriority {
+        match self {
+            Self::Touches { .. } | Self::Judges { .. } => CommandPriority::Low,
+            Self::Chat { .. } | Self::ChannelChat { .. } => CommandPriority::Normal,
+            _ => CommandPriority::Critical,
+        }
+    }
+}
+
+impl Prioritized for ServerCommand {
+    fn priority(&self) -> CommandPriority {
+        match self {
+            Self::Touches { .. } | Self::Judges { .. } => CommandPriority::Low,
+            Self::Message(Message::Chat { .. } | Message::ChannelChat { .. })
+            | Self::PluginNotification { .. } => CommandPriority::Normal,
+            _ => CommandPriority::Critical,
+        }
+    }
+}
+
+impl TraceFrame for ClientCommand {
+    fn is_sensitive(&self) -> bool {
+        matches!(self, Self::Authenticate { .. })
+    }
+}
+
+impl TraceFrame for ServerCommand {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_packet, encode_packet};
+
+    #[test]
+    fn test_announcement_markup() {
+        assert_eq!(
+            sanitize_announcement(" <b>Hi</b> <color=red>all</color>\u{1b}[0m "),
+            "Hi all[0m"
+        );
+        assert_eq!(sanitize_announcement("1 < 2 > 0 <3"), "1 < 2 > 0 <3");
+        assert_eq!(
+            "Grey".parse::<AnnouncementColor>().unwrap(),
+            AnnouncementColor::Gray
+        );
+        assert!("#ff0000".parse::<AnnouncementColor>().is_err());
+
+        let style = AnnouncementStyle {
+            bold: true,
+            color: Some(AnnouncementColor::Purple),
+        };
+        let mut buf = Vec::new();
+        encode_packet(
+            &Message::Announcement {
+                content: "hi".to_owned(),
+                style: style.clone(),
+            },
+            &mut buf,
+        );
+        let Message::Announcement {
+            content,
+            style: decoded,
+        } = decode_packet(&buf).unwrap()
+        else {
+            panic!("decoded another message");
+        };
+        assert_eq!(content, "hi");
+        assert_eq!(decoded, style);
+    }
+
+    #[test]
+    fn test_scheduled_announcement() {
+        let announcement = ScheduledAnnouncement {
+            title: "Weekly cup".to_owned(),
+            starts_at: 1_000,
+        };
+        let data = ScheduledAnnouncement::to_notification(Some(&announcement));
+        assert_eq!(
+            ScheduledAnnouncement::from_notification(&data).unwrap(),
+            Some(announcement.clone())
+        );
+        assert_eq!(
+            ScheduledAnnouncement::from_notification(&ScheduledAnnouncement::to_notification(None))
+                .unwrap(),
+            None
+        );
+        assert!(ScheduledAnnouncement::from_notification("{}").is_err());
+
+        assert_eq!(announcement.remaining(400), Duration::from_secs(600));
+        assert_eq!(announcement.remaining(2_000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_chart_id() {
+        for invalid in ["0", "-1", "2147483648", "abc", ""] {
+            assert!(invalid.parse::<ChartId>().is_err(), "{invalid}");
+        }
+        assert!(ChartId::try_from(-5i32).is_err());
+        assert!(ChartId::try_from(u32::MAX).is_err());
+
+        let id: ChartId = " 42 ".parse().unwrap();
+        assert_eq!(ChartId::try_from(42u32).unwrap(), id);
+        assert_eq!(i32::from(id), 42);
+        assert_eq!(u32::from(id), 42);
+        assert_eq!(id.to_string(), "42");
+
+        let mut buf = Vec::new();
+        encode_packet(&ClientCommand::SelectChart { id }, &mut buf);
+        let ClientCommand::SelectChart { id: decoded } = decode_packet(&buf).unwrap() else {
+            panic!("decoded another command");
+        };
+        assert_eq!(decoded, id);
+
+        // A negative id on the wire is rejected instead of wrapping
+        let mut raw = Vec::new();
+        BinaryWriter::new(&mut raw).write(&-1i32).unwrap();
+        assert!(BinaryReader::new(&raw).read::<ChartId>().is_err());
+    }
 }
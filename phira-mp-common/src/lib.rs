@@ -4,12 +4,23 @@ pub use bin::*;
 mod command;
 pub use command::*;
 
+mod queue;
+pub use queue::*;
+
 use anyhow::{Error, Result, bail};
-use std::{future::Future, marker::PhantomData, sync::Arc, time::Duration};
+use std::{
+    fmt::Write,
+    future::Future,
+    marker::PhantomData,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
-    sync::mpsc,
     task::JoinHandle,
 };
 use tracing::{error, trace, warn};
@@ -18,6 +29,100 @@ pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
 pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(2);
 pub const HEARTBEAT_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Outgoing commands buffered per stream before low-priority ones are dropped.
+pub const SEND_QUEUE_CAPACITY: usize = 1024;
+
+/// The latest protocol version, sent by clients as the first byte of a connection.
+pub const PROTOCOL_VERSION: u8 = 8;
+
+/// Chat channel every room member is in, used by plain `Chat` commands.
+pub const DEFAULT_CHAT_CHANNEL: &str = "all";
+
+/// Optional protocol features a peer understands, derived from its protocol version.
+///
+/// Commands appended to the protocol after version 1 must only be sent to peers
+/// having the corresponding capability, since older peers fail to decode them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// `ServerCommand::GameResults`
+    pub const GAME_RESULTS: Self = Self(1 << 0);
+    /// `ServerCommand::PluginNotification`
+    pub const PLUGIN_NOTIFICATION: Self = Self(1 << 1);
+    /// `ServerCommand::RoomStatePatch`
+    pub const ROOM_STATE_PATCH: Self = Self(1 << 2);
+    /// `Message::ChannelChat` and the channel commands
+    pub const CHAT_CHANNELS: Self = Self(1 << 3);
+    /// `ServerCommand::Ping`, answered with `ClientCommand::Pong`
+    pub const LATENCY_PROBE: Self = Self(1 << 4);
+    /// `Message::Announcement`
+    pub const ANNOUNCEMENTS: Self = Self(1 << 5);
+    /// `ClientCommand::CreatePracticeRoom`
+    pub const PRACTICE_ROOMS: Self = Self(1 << 6);
+
+    pub fn from_version(version: u8) -> Self {
+        let mut caps = Self::default();
+        if version >= 2 {
+            caps = caps | Self::GAME_RESULTS;
+        }
+        if version >= 3 {
+            caps = caps | Self::PLUGIN_NOTIFICATION;
+        }
+        if version >= 4 {
+            caps = caps | Self::ROOM_STATE_PATCH;
+        }
+        if version >= 5 {
+            caps = caps | Self::CHAT_CHANNELS;
+        }
+        if version >= 6 {
+            caps = caps | Self::LATENCY_PROBE;
+        }
+        if version >= 7 {
+            caps = caps | Self::ANNOUNCEMENTS;
+        }
+        if version >= 8 {
+            caps = caps | Self::PRACTICE_ROOMS;
+        }
+        caps
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Log target of frame traces, enabled per stream with [`Stream::set_trace`].
+pub const FRAME_TRACE_TARGET: &str = "phira_mp::frames";
+
+/// Commands logged by frame tracing.
+pub trait TraceFrame: std::fmt::Debug {
+    /// Whether the command carries secrets, in which case only its size is traced.
+    fn is_sensitive(&self) -> bool {
+        false
+    }
+}
+
+fn trace_frame(direction: &str, payload: &impl TraceFrame, data: &[u8]) {
+    if payload.is_sensitive() {
+        trace!(target: FRAME_TRACE_TARGET, "{direction} <redacted> ({} bytes)", data.len());
+        return;
+    }
+    let hex = data.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+    trace!(target: FRAME_TRACE_TARGET, "{direction} {payload:?} ({} bytes): {hex}", data.len());
+}
+
 pub fn encode_packet(payload: &impl BinaryData, vec: &mut Vec<u8>) {
     BinaryWriter::new(vec).write(payload).unwrap();
 }
@@ -32,7 +137,9 @@ where
 pub struct Stream<S, R> {
     version: u8,
 
-    send_tx: Arc<mpsc::Sender<S>>,
+    send_queue: Arc<SendQueue<S>>,
+    /// Whether frames are logged to [`FRAME_TRACE_TARGET`].
+    trace: Arc<AtomicBool>,
 
     send_task_handle: JoinHandle<()>,
     recv_task_handle: JoinHandle<Result<()>>,
@@ -42,13 +149,13 @@ pub struct Stream<S, R> {
 
 impl<S, R> Stream<S, R>
 where
-    S: BinaryData + std::fmt::Debug + Send + Sync + 'static,
-    R: BinaryData + std::fmt::Debug + Send + 'static,
+    S: BinaryData + Prioritized + TraceFrame + Send + Sync + 'static,
+    R: BinaryData + TraceFrame + Send + 'static,
 {
     pub async fn new<F>(
         version: Option<u8>,
         stream: TcpStream,
-        mut handler: Box<dyn FnMut(Arc<mpsc::Sender<S>>, R) -> F + Send + Sync>,
+        mut handler: Box<dyn FnMut(Arc<SendQueue<S>>, R) -> F + Send + Sync>,
     ) -> Result<Self>
     where
         F: Future<Output = ()> + Send + 'static,
@@ -62,16 +169,21 @@ where
             read.read_u8().await?
         };
 
-        let (send_tx, mut send_rx) = mpsc::channel(1024);
-        let send_tx = Arc::new(send_tx);
+        let send_queue = Arc::new(SendQueue::new(SEND_QUEUE_CAPACITY));
+        let trace = Arc::new(AtomicBool::new(false));
         let send_task_handle = tokio::spawn({
+            let send_queue = Arc::clone(&send_queue);
+            let trace = Arc::clone(&trace);
             async move {
                 let mut buffer = Vec::new();
                 let mut len_buf = [0u8; 5];
-                while let Some(payload) = send_rx.recv().await {
+                while let Some(payload) = send_queue.recv().await {
                     buffer.clear();
                     encode_packet(&payload, &mut buffer);
                     trace!("sending {} bytes ({payload:?}): {buffer:?}", buffer.len());
+                    if trace.load(Ordering::Relaxed) {
+                        trace_frame("send", &payload, &buffer);
+                    }
 
                     let mut x = buffer.len() as u32;
                     let mut n = 0;
@@ -100,7 +212,8 @@ where
         });
 
         let recv_task_handle = tokio::spawn({
-            let send_tx = Arc::clone(&send_tx);
+            let send_queue = Arc::clone(&send_queue);
+            let trace = Arc::clone(&trace);
             #[allow(clippy::read_zero_byte_vec)]
             async move {
                 let mut buffer = Vec::new();
@@ -135,7 +248,10 @@ where
                         }
                     };
                     trace!("decodes to {payload:?}");
-                    handler(Arc::clone(&send_tx), payload).await;
+                    if trace.load(Ordering::Relaxed) {
+                        trace_frame("recv", &payload, &buffer);
+                    }
+                    handler(Arc::clone(&send_queue), payload).await;
                 }
                 Ok(())
             }
@@ -144,7 +260,8 @@ where
         Ok(Self {
             version,
 
-            send_tx,
+            send_queue,
+            trace,
 
             send_task_handle,
             recv_task_handle,
@@ -157,20 +274,144 @@ where
         self.version
     }
 
+    /// Queues a command. A peer not keeping up with critical commands closes the connection
+    /// and fails the send with [`QueueOverflow`].
     pub async fn send(&self, payload: S) -> Result<()> {
-        self.send_tx.send(payload).await?;
-        Ok(())
+        self.blocking_send(payload)
     }
 
     pub fn blocking_send(&self, payload: S) -> Result<()> {
-        self.send_tx.blocking_send(payload)?;
-        Ok(())
+        self.send_queue.send(payload).inspect_err(|err| {
+            if err.is::<QueueOverflow>() {
+                warn!("peer is not keeping up, closing the connection");
+                self.recv_task_handle.abort();
+            }
+        })
+    }
+
+    /// Number of outgoing commands dropped because the peer was not keeping up.
+    pub fn dropped(&self) -> u64 {
+        self.send_queue.dropped()
+    }
+
+    /// Enables or disables logging of this stream's frames at trace level.
+    pub fn set_trace(&self, enabled: bool) {
+        self.trace.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.load(Ordering::Relaxed)
+    }
+
+    /// Stops receiving and closes the connection once the queued commands are sent.
+    pub fn close(&self) {
+        self.send_queue.close();
+        self.recv_task_handle.abort();
     }
 }
 
 impl<S, R> Drop for Stream<S, R> {
     fn drop(&mut self) {
+        self.send_queue.close();
         self.send_task_handle.abort();
         self.recv_task_handle.abort();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{net::TcpListener, sync::mpsc};
+
+    /// Log output shared with the test.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Logs {
+        fn frames(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .filter(|line| line.contains(FRAME_TRACE_TARGET))
+                .map(str::to_owned)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_trace() {
+        let logs = Logs::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_ansi(false)
+                .with_writer({
+                    let logs = logs.clone();
+                    move || logs.clone()
+                })
+                .finish(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (server, client) = tokio::join!(
+            async {
+                let (stream, _) = listener.accept().await.unwrap();
+                Stream::<ServerCommand, ClientCommand>::new(
+                    None,
+                    stream,
+                    Box::new(move |_, cmd| {
+                        let _ = tx.send(cmd);
+                        async {}
+                    }),
+                )
+                .await
+                .unwrap()
+            },
+            async {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                Stream::<ClientCommand, ServerCommand>::new(
+                    Some(PROTOCOL_VERSION),
+                    stream,
+                    Box::new(|_, _| async {}),
+                )
+                .await
+                .unwrap()
+            },
+        );
+
+        // Off by default
+        client.send(ClientCommand::Ping).await.unwrap();
+        rx.recv().await.unwrap();
+        assert!(!server.is_tracing());
+        assert!(logs.frames().is_empty());
+
+        server.set_trace(true);
+        client
+            .send(ClientCommand::Authenticate {
+                token: "secret-token".to_owned().try_into().unwrap(),
+            })
+            .await
+            .unwrap();
+        client.send(ClientCommand::Ping).await.unwrap();
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        let frames = logs.frames();
+        assert_eq!(frames.len(), 2, "{frames:?}");
+        assert!(frames[0].contains("recv <redacted>"));
+        assert!(frames[1].contains("recv Ping (1 bytes): 00"));
+        assert!(!frames.iter().any(|it| it.contains("secret-token")));
+    }
+}
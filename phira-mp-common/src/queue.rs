@@ -0,0 +1,204 @@
+use anyhow::{Result, bail};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::sync::Notify;
+use tracing::trace;
+
+/// How important it is to deliver a command when the peer is not keeping up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandPriority {
+    /// High-volume relays (touches, judges) that are dropped first.
+    Low,
+    /// Chat and notifications, which may evict low-priority commands.
+    Normal,
+    /// Responses and state changes, which are never dropped.
+    Critical,
+}
+
+pub trait Prioritized {
+    fn priority(&self) -> CommandPriority;
+}
+
+/// Multiple of its capacity a queue may grow to with critical commands before it is closed.
+pub const CRITICAL_OVERFLOW_FACTOR: usize = 4;
+
+/// Error of the send that closed a [`SendQueue`] because the peer stopped keeping up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueOverflow;
+
+impl fmt::Display for QueueOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("send queue overflowed with critical commands, closed")
+    }
+}
+
+impl std::error::Error for QueueOverflow {}
+
+/// Bounded outgoing command queue of a stream.
+///
+/// When full, a command evicts the oldest queued command of the lowest priority below its own,
+/// or is dropped if there is none. Critical commands are kept beyond capacity, up to
+/// [`CRITICAL_OVERFLOW_FACTOR`] times it; the critical command past that closes the queue,
+/// discarding everything queued.
+pub struct SendQueue<S> {
+    items: Mutex<(VecDeque<S>, bool)>,
+    notify: Notify,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl<S> SendQueue<S> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Mutex::new((VecDeque::with_capacity(capacity), false)),
+            notify: Notify::new(),
+            capacity,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for the next command, returning `None` once closed.
+    pub async fn recv(&self) -> Option<S> {
+        loop {
+            {
+                let mut guard = self.items.lock().unwrap();
+                let (items, closed) = &mut *guard;
+                if let Some(item) = items.pop_front() {
+                    return Some(item);
+                }
+                if *closed {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    pub fn close(&self) {
+        self.items.lock().unwrap().1 = true;
+        self.notify.notify_one();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of commands dropped or evicted because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: Prioritized> SendQueue<S> {
+    /// Queues a command, failing if the queue is closed or with [`QueueOverflow`] if this
+    /// command closes it.
+    pub fn send(&self, item: S) -> Result<()> {
+        let mut guard = self.items.lock().unwrap();
+        let (items, closed) = &mut *guard;
+        if *closed {
+            bail!("send queue closed");
+        }
+        if items.len() >= self.capacity {
+            let priority = item.priority();
+            let victim = items
+                .iter()
+                .map(Prioritized::priority)
+                .filter(|it| *it < priority)
+                .min()
+                .and_then(|lowest| items.iter().position(|it| it.priority() == lowest));
+            if let Some(index) = victim {
+                items.remove(index);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                trace!("send queue full, evicted a lower priority command");
+            } else if priority != CommandPriority::Critical {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                trace!("send queue full, dropping {priority:?} command");
+                return Ok(());
+            } else if items.len() >= self.capacity * CRITICAL_OVERFLOW_FACTOR {
+                *items = VecDeque::new();
+                *closed = true;
+                drop(guard);
+                self.notify.notify_one();
+                return Err(QueueOverflow.into());
+            }
+        }
+        items.push_back(item);
+        drop(guard);
+        self.notify.notify_one();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Cmd(CommandPriority, u32);
+
+    impl Prioritized for Cmd {
+        fn priority(&self) -> CommandPriority {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overflow_drops_low_priority() {
+        use CommandPriority::*;
+
+        let queue = SendQueue::new(3);
+        queue.send(Cmd(Low, 0)).unwrap();
+        queue.send(Cmd(Critical, 1)).unwrap();
+        queue.send(Cmd(Low, 2)).unwrap();
+
+        // Full: low-priority commands are dropped, others evict the oldest low-priority one
+        queue.send(Cmd(Low, 3)).unwrap();
+        queue.send(Cmd(Normal, 4)).unwrap();
+        queue.send(Cmd(Critical, 5)).unwrap();
+        queue.send(Cmd(Normal, 6)).unwrap();
+        queue.send(Cmd(Critical, 7)).unwrap();
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.dropped(), 5);
+
+        // Critical commands are kept beyond capacity
+        queue.send(Cmd(Critical, 8)).unwrap();
+        assert_eq!(queue.len(), 4);
+
+        queue.close();
+        assert!(queue.send(Cmd(Critical, 9)).is_err());
+        let mut received = Vec::new();
+        while let Some(Cmd(_, id)) = queue.recv().await {
+            received.push(id);
+        }
+        assert_eq!(received, vec![1, 5, 7, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_critical_overflow_closes() {
+        use CommandPriority::*;
+
+        let queue = SendQueue::new(2);
+        for id in 0..2 * CRITICAL_OVERFLOW_FACTOR as u32 {
+            queue.send(Cmd(Critical, id)).unwrap();
+        }
+        assert_eq!(queue.len(), 2 * CRITICAL_OVERFLOW_FACTOR);
+
+        // Past the limit the queue is closed and emptied
+        let err = queue.send(Cmd(Critical, 100)).unwrap_err();
+        assert!(err.is::<QueueOverflow>());
+        assert!(queue.is_empty());
+        assert_eq!(queue.recv().await, None);
+        let err = queue.send(Cmd(Critical, 101)).unwrap_err();
+        assert!(!err.is::<QueueOverflow>());
+    }
+}
@@ -4,10 +4,21 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
+/// Fields the [`PluginMetadata`](derive@PluginMetadata) derive reads
+const REQUIRED_METADATA_FIELDS: [&str; 6] =
+    ["name", "version", "author", "description", "dependencies", "permissions"];
+
 /// Derive macro for plugin metadata
-#[proc_macro_derive(PluginMetadata)]
+///
+/// The struct must have the named fields `name`, `version`, `author`, `description`,
+/// `dependencies` and `permissions`; a missing one is reported at compile time. Fields
+/// marked `#[plugin_meta(skip)]` are not treated as metadata.
+#[proc_macro_derive(PluginMetadata, attributes(plugin_meta))]
 pub fn derive_plugin_metadata(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    if let Err(err) = check_metadata_fields(&input) {
+        return err.to_compile_error().into();
+    }
     let name = &input.ident;
     
     let expanded = quote! {
@@ -41,10 +52,256 @@ pub fn derive_plugin_metadata(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
-/// Macro to declare a plugin
+/// Check that every required metadata field is present and not skipped
+fn check_metadata_fields(input: &DeriveInput) -> syn::Result<()> {
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &input.data
+    else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "PluginMetadata can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut present = Vec::new();
+    for field in &fields.named {
+        if !is_skipped(field)? {
+            present.extend(field.ident.as_ref().map(ToString::to_string));
+        }
+    }
+    let mut errors = REQUIRED_METADATA_FIELDS
+        .iter()
+        .filter(|required| !present.iter().any(|field| field == *required))
+        .map(|missing| {
+            syn::Error::new_spanned(
+                &input.ident,
+                format!("PluginMetadata requires a field named `{}`", missing),
+            )
+        });
+    match errors.next() {
+        Some(mut error) => {
+            error.extend(errors);
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Whether a field is marked `#[plugin_meta(skip)]`
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("plugin_meta")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+/// Declare a plugin on its `impl` block, generating the registration boilerplate
+///
+/// The attribute takes `name`, `version` and `author` (required) and `description`.
+/// Methods in the block can be marked as handlers:
+///
+/// - `#[command]` or `#[command(name = "...", description = "...")]` on
+///   `fn(&self, args: &[String]) -> Result<String>` registers a command, named after the
+///   method unless `name` is given;
+/// - `#[event("type")]` on `fn(&self, event: &Event) -> Result<()>` subscribes to an event.
+///
+/// The block gains `fn plugin_metadata() -> PluginMetadata` built from the attribute and
+/// `fn register(self: Arc<Self>, host_api: &HostApi) -> Result<()>` wiring every handler
+/// into the host API, meant to be called from the plugin's initialization. An exported
+/// `_plugin_init` entry point creates the plugin instance with `Default`, available
+/// afterwards from `fn instance()`; it returns 0 on success and 1 if already initialized.
 #[proc_macro_attribute]
-pub fn plugin(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    // For now, just pass through
-    // TODO: Generate plugin initialization code
-    item
-}
\ No newline at end of file
+pub fn plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = PluginArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+    let mut item = parse_macro_input!(item as syn::ItemImpl);
+    match expand_plugin(args, &mut item) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Arguments of `#[plugin(...)]`
+#[derive(Default)]
+struct PluginArgs {
+    name: Option<syn::LitStr>,
+    version: Option<syn::LitStr>,
+    author: Option<syn::LitStr>,
+    description: Option<syn::LitStr>,
+}
+
+impl PluginArgs {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        let slot = if meta.path.is_ident("name") {
+            &mut self.name
+        } else if meta.path.is_ident("version") {
+            &mut self.version
+        } else if meta.path.is_ident("author") {
+            &mut self.author
+        } else if meta.path.is_ident("description") {
+            &mut self.description
+        } else {
+            return Err(meta.error("expected `name`, `version`, `author` or `description`"));
+        };
+        *slot = Some(meta.value()?.parse()?);
+        Ok(())
+    }
+}
+
+/// A method marked as a handler
+enum Handler {
+    Command {
+        method: syn::Ident,
+        name: String,
+        description: String,
+    },
+    Event {
+        method: syn::Ident,
+        event_type: syn::LitStr,
+    },
+}
+
+/// Remove the handler attribute of a method, if any, and describe the handler
+fn take_handler(method: &mut syn::ImplItemFn) -> syn::Result<Option<Handler>> {
+    let Some(index) = method
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("command") || attr.path().is_ident("event"))
+    else {
+        return Ok(None);
+    };
+    let attr = method.attrs.remove(index);
+    let ident = method.sig.ident.clone();
+    if attr.path().is_ident("event") {
+        return Ok(Some(Handler::Event {
+            method: ident,
+            event_type: attr.parse_args()?,
+        }));
+    }
+
+    let mut name = ident.to_string();
+    let mut description = String::new();
+    if !matches!(attr.meta, syn::Meta::Path(_)) {
+        attr.parse_nested_meta(|meta| {
+            let value: syn::LitStr = meta.value()?.parse()?;
+            if meta.path.is_ident("name") {
+                name = value.value();
+            } else if meta.path.is_ident("description") {
+                description = value.value();
+            } else {
+                return Err(meta.error("expected `name` or `description`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(Some(Handler::Command {
+        method: ident,
+        name,
+        description,
+    }))
+}
+
+fn expand_plugin(
+    args: PluginArgs,
+    item: &mut syn::ItemImpl,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let span = proc_macro2::Span::call_site();
+    let missing = |field: &str| syn::Error::new(span, format!("missing plugin `{}`", field));
+    let name = args.name.ok_or_else(|| missing("name"))?;
+    let version = args.version.ok_or_else(|| missing("version"))?;
+    let author = args.author.ok_or_else(|| missing("author"))?;
+    let description = match args.description {
+        Some(description) => quote! { Some(#description.to_string()) },
+        None => quote! { None },
+    };
+
+    let mut registrations = Vec::new();
+    for impl_item in &mut item.items {
+        let syn::ImplItem::Fn(method) = impl_item else {
+            continue;
+        };
+        registrations.push(match take_handler(method)? {
+            Some(Handler::Command {
+                method,
+                name: command,
+                description,
+            }) => quote! {
+                let this = ::std::sync::Arc::clone(&self);
+                host_api.register_command(
+                    #command,
+                    #description,
+                    Box::new(move |_, args| this.#method(args)),
+                    #name,
+                )?;
+            },
+            Some(Handler::Event { method, event_type }) => quote! {
+                let this = ::std::sync::Arc::clone(&self);
+                host_api.subscribe_event(
+                    #event_type,
+                    Box::new(move |event| this.#method(event)),
+                    #name,
+                )?;
+            },
+            None => continue,
+        });
+    }
+
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "plugins cannot be generic"));
+    }
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        impl #self_ty {
+            /// Metadata declared by `#[plugin]`
+            pub fn plugin_metadata() -> ::phira_mp_plugin::PluginMetadata {
+                ::phira_mp_plugin::PluginMetadata {
+                    name: #name.to_string(),
+                    version: #version.to_string(),
+                    author: #author.to_string(),
+                    description: #description,
+                    ..Default::default()
+                }
+            }
+
+            /// Register the plugin's commands and event handlers
+            pub fn register(
+                self: ::std::sync::Arc<Self>,
+                host_api: &::phira_mp_plugin::HostApi,
+            ) -> ::phira_mp_plugin::Result<()> {
+                #(#registrations)*
+                Ok(())
+            }
+
+            /// The instance created by `_plugin_init`, if it ran
+            pub fn instance() -> Option<::std::sync::Arc<Self>> {
+                __PLUGIN_INSTANCE.get().cloned()
+            }
+        }
+
+        static __PLUGIN_INSTANCE: ::std::sync::OnceLock<::std::sync::Arc<#self_ty>> =
+            ::std::sync::OnceLock::new();
+
+        /// Plugin entry point
+        #[unsafe(no_mangle)]
+        pub extern "C" fn _plugin_init() -> i32 {
+            let instance = ::std::sync::Arc::new(<#self_ty as Default>::default());
+            match __PLUGIN_INSTANCE.set(instance) {
+                Ok(()) => 0,
+                Err(_) => 1,
+            }
+        }
+    })
+}
@@ -0,0 +1,6 @@
+#[test]
+fn test_derive_plugin_metadata() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/metadata_complete.rs");
+    cases.compile_fail("tests/ui/metadata_missing_field.rs");
+}
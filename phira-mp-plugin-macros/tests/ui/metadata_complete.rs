@@ -0,0 +1,28 @@
+include!("metadata_trait.rs");
+
+#[derive(phira_mp_plugin_macros::PluginMetadata)]
+struct Metadata {
+    name: String,
+    version: String,
+    author: String,
+    description: Option<String>,
+    dependencies: Option<Vec<String>>,
+    permissions: Option<Vec<String>>,
+    #[plugin_meta(skip)]
+    #[allow(dead_code)]
+    loaded_at: u64,
+}
+
+fn main() {
+    let metadata = Metadata {
+        name: "greeter".to_string(),
+        version: "1.0.0".to_string(),
+        author: "test".to_string(),
+        description: None,
+        dependencies: None,
+        permissions: Some(vec!["read_users".to_string()]),
+        loaded_at: 0,
+    };
+    assert_eq!(metadata.name(), "greeter");
+    assert_eq!(metadata.permissions().map(Vec::len), Some(1));
+}
@@ -0,0 +1,12 @@
+include!("metadata_trait.rs");
+
+#[derive(phira_mp_plugin_macros::PluginMetadata)]
+struct Metadata {
+    #[plugin_meta(skip)]
+    name: String,
+    version: String,
+    description: Option<String>,
+    dependencies: Option<Vec<String>>,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+pub trait PluginMetadata {
+    fn name(&self) -> &str;
+    fn version(&self) -> &str;
+    fn author(&self) -> &str;
+    fn description(&self) -> Option<&str>;
+    fn dependencies(&self) -> Option<&Vec<String>>;
+    fn permissions(&self) -> Option<&Vec<String>>;
+}
@@ -4,6 +4,43 @@ use parking_lot::RwLock;
 use tracing::{info, debug, warn};
 use serde_json::{Value, json};
 
+/// Maximum nesting depth of a custom data entry exposed through the query API
+const MAX_CUSTOM_DATA_DEPTH: usize = 16;
+/// Maximum serialized size of all custom data exposed for one user or room, in bytes
+const MAX_CUSTOM_DATA_SIZE: usize = 64 * 1024;
+/// Maximum length of a room name, in characters
+pub const MAX_ROOM_NAME_LENGTH: usize = 32;
+/// Timeout of a plugin HTTP request, including redirects
+const HTTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Maximum redirects followed by a plugin HTTP request
+const MAX_HTTP_REDIRECTS: usize = 5;
+/// File in the plugin directory storing user tags
+pub const USER_TAGS_FILE: &str = "user_tags.toml";
+/// Maximum length of a user tag, in characters
+pub const MAX_USER_TAG_LENGTH: usize = 32;
+/// File in the plugin directory storing server-wide bans
+pub const BANS_FILE: &str = "bans.json";
+/// File in the plugin directory storing accumulated playtime
+pub const PLAYTIME_FILE: &str = "playtime.json";
+/// How long timestamped playtime is kept for windowed leaderboards
+pub const PLAYTIME_LOG_RETENTION: std::time::Duration =
+    std::time::Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Server settings plugins may read with [`HostApi::get_server_setting`]
+///
+/// Anything sensitive, such as endpoints or credentials, must never be listed here.
+pub const SERVER_SETTINGS: &[&str] = &[
+    "max_room_users",
+    "monitors",
+    "host_leave_policy",
+    "protocol_version",
+    "otlp_enabled",
+    "max_rooms",
+];
+
+/// Grace period between announcing a shutdown and exiting
+pub const DEFAULT_SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// Host API implementation for plugins
 pub struct HostApi {
     /// Event bus for plugin communication
@@ -14,22 +51,166 @@ pub struct HostApi {
     plugin_manager: Weak<crate::plugin_manager::PluginManager>,
     /// Server state (to be connected to actual server)
     server_state: Arc<RwLock<ServerState>>,
+    /// Bridge to the running server, if connected
+    server_bridge: RwLock<Option<Arc<dyn ServerBridge>>>,
+    /// Chart selection validators
+    chart_validators: crate::chart_validator::ChartSelectionValidators,
+    /// Player input subscriptions
+    player_input: crate::player_input::PlayerInputSubscribers,
+    /// Cooldowns plugins start, apart from command cooldowns
+    plugin_cooldowns: crate::cooldown::CooldownTracker,
+    /// Matchmaking queues, kept across plugin reloads
+    queues: crate::matchmaking::MatchmakingQueues,
+    /// Methods plugins expose to each other
+    rpc: crate::rpc::RpcRegistry,
+    /// HTTP routes served for plugins
+    http_routes: crate::http_routes::HttpRoutes,
+    /// Extra fields plugins add to room info
+    room_info_fields: crate::info_fields::InfoFields,
+    /// Extra fields plugins add to user info
+    user_info_fields: crate::info_fields::InfoFields,
+    /// Hot reload manager, if running
+    hot_reload_manager: RwLock<Option<Arc<crate::hot_reload::HotReloadManager>>>,
+    /// Health monitor, if running
+    health_monitor: RwLock<Option<Arc<crate::monitoring::HealthMonitor>>>,
+    /// Plugin sandboxes
+    sandbox_manager: Arc<crate::sandbox::SandboxManager>,
+    /// Tags by user ID, saved on change once opened with [`Self::open_user_tags`]
+    user_tags: RwLock<crate::config::PluginConfig>,
+    /// Playtime by user ID, kept across restarts once opened with [`Self::open_playtime`]
+    playtime: crate::playtime::PlaytimeStore,
+    /// Server-wide bans, saved on change once opened with [`Self::open_bans`]
+    bans: crate::ban_store::BanStore,
+}
+
+/// Operations delegated to the running server
+///
+/// Implemented by the server and installed with [`HostApi::set_server_bridge`].
+pub trait ServerBridge: Send + Sync {
+    /// Send a plugin notification to capable clients
+    fn push_client_notification(&self, target: &NotificationTarget, channel: &str, data: &str);
+    /// Send a styled announcement to all online users
+    fn broadcast_announcement(&self, content: &str, style: &phira_mp_common::AnnouncementStyle);
+    /// Enable or disable judge timeline recording for a room
+    fn set_judge_recording(&self, room_id: &str, enabled: bool);
+    /// Judge timeline of a recorded round by player, if still kept
+    fn get_game_judges(
+        &self,
+        room_id: &str,
+        round: u32,
+    ) -> Option<std::collections::HashMap<u32, Vec<phira_mp_common::JudgeEvent>>>;
+    /// Remove all users from a room without disbanding it
+    fn clear_room(&self, room_id: &str);
+    /// Mark whether a room is kept when its last user leaves
+    fn set_room_persistent(&self, room_id: &str, persistent: bool);
+    /// Mark whether a room is a lobby, reset to the server's lobby defaults instead of
+    /// being disbanded when its last user leaves
+    fn set_room_lobby(&self, room_id: &str, lobby: bool);
+    /// Set the display name of a room and notify its members on the `room_renamed` client
+    /// notification channel; fails rather than blocking while the room is being updated
+    fn set_room_name(&self, room_id: &str, name: &str) -> Result<()>;
+    /// Lock or unlock a room, recording `locked_by` as the user who locked it, `None` for the
+    /// console or a plugin; fails rather than blocking while the room is being updated
+    fn set_room_lock(&self, room_id: &str, locked: bool, locked_by: Option<u32>) -> Result<()>;
+    /// Switch a room between cycle mode, passing the host on after each round, and normal
+    /// mode; fails rather than blocking while the room list is being updated
+    fn set_room_cycle(&self, room_id: &str, cycle: bool) -> Result<()>;
+    /// Limit a room to weighted charts, drawing a new one after each round, or to any chart
+    /// if `pool` is empty
+    fn set_chart_pool(&self, room_id: &str, pool: &[(phira_mp_common::ChartId, u32)]) -> Result<()>;
+    /// Grant or revoke co-host permissions of a room member
+    fn set_room_cohost(&self, room_id: &str, user_id: u32, cohost: bool);
+    /// Add a user to or remove a user from a chat channel of a room
+    fn set_chat_channel_member(&self, room_id: &str, channel: &str, user_id: u32, joined: bool);
+    /// Send a chat message as a user to the members of a room's chat channel
+    fn send_channel_message(&self, room_id: &str, channel: &str, user_id: u32, content: &str);
+    /// Value of a server setting listed in [`SERVER_SETTINGS`]
+    fn get_server_setting(&self, key: &str) -> Option<Value>;
+    /// Smoothed ping round-trip time of an online user, if measured
+    fn get_user_latency(&self, user_id: u32) -> Option<std::time::Duration>;
+    /// Aggregate statistics buckets overlapping the last `window`, oldest first
+    fn get_server_stats(&self, window: std::time::Duration) -> Vec<Value>;
+    /// Connection details of a user's live session
+    fn get_session_info(&self, user_id: u32) -> Option<Value>;
+    /// Enable or disable frame tracing of a user's live session, returning whether it exists
+    fn set_frame_trace(&self, user_id: u32, enabled: bool) -> bool;
+    /// Disconnect an online user and remove them from their room, returning whether they
+    /// were online
+    fn kick_user(&self, user_id: u32) -> bool;
+    /// Open an empty room owned by the server under the first free numeric ID from
+    /// `first_id`, admitting up to `max_users` players, and return its ID
+    ///
+    /// `practice` lets a lone player start without the ready handshake, `None` leaving it
+    /// to the server's default.
+    fn create_room(&self, first_id: u32, max_users: u32, practice: Option<bool>) -> Result<u32>;
+    /// Snapshot of the server's rooms; fails rather than blocking while they are updated
+    fn get_rooms(&self) -> Result<Vec<LiveRoom>>;
+    /// Send a chat message to a user, `None` if the user is not known to the server
+    fn send_message_to_user(&self, user_id: u32, content: &str) -> Option<DeliveryStatus>;
+    /// Send a chat message to all connected users, or the members of a room if given,
+    /// returning how many were reached
+    fn broadcast_message(&self, room_id: Option<&str>, content: &str) -> Result<usize>;
+    /// Send a chat message to the members of every room, skipping rooms in a game unless
+    /// `include_playing` is set, and return how many were reached
+    fn broadcast_message_to_rooms(&self, content: &str, include_playing: bool) -> Result<usize>;
+    /// Stop accepting connections, warn connected users, and exit after `grace`
+    fn shutdown(&self, grace: std::time::Duration);
+    /// Replace the announced upcoming event, shown to clients now and on connect
+    fn set_scheduled_announcement(
+        &self,
+        announcement: Option<&phira_mp_common::ScheduledAnnouncement>,
+    );
+}
+
+/// Recipients of a client notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationTarget {
+    /// All online users
+    All,
+    /// Users in the room with the given ID
+    Room(String),
+    /// A single user
+    User(u32),
+}
+
+/// Response to a plugin HTTP request, handed to WASM plugins as JSON
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Outcome of sending a message to a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// The user is online and the message was sent
+    Delivered,
+    /// The user is offline and the message was dropped
+    UserOffline,
+}
+
+/// Outcome of one subsystem check of [`HostApi::self_test`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SelfTestResult {
+    /// Checked subsystem
+    pub subsystem: &'static str,
+    /// Whether the check passed
+    pub passed: bool,
+    /// Reason of a failure, empty on success
+    pub detail: String,
 }
 
 /// Server state accessible to plugins
 pub struct ServerState {
     /// Currently online users
     pub online_users: std::collections::HashMap<u32, UserInfo>,
-    /// Currently active rooms
-    pub rooms: std::collections::HashMap<u32, RoomInfo>,
-    /// Banned user IDs
-    pub banned_user_ids: std::collections::HashSet<u32>,
-    /// Banned IPs
-    pub banned_ips: std::collections::HashSet<String>,
+    /// Currently active rooms, by room ID, refreshed from the server on reads
+    pub rooms: std::collections::HashMap<String, RoomInfo>,
     /// Room-specific bans
-    pub room_bans: std::collections::HashMap<u32, std::collections::HashSet<u32>>,
+    pub room_bans: std::collections::HashMap<String, std::collections::HashSet<u32>>,
     /// Room-specific IP bans
-    pub room_ip_bans: std::collections::HashMap<u32, std::collections::HashSet<String>>,
+    pub room_ip_bans: std::collections::HashMap<String, std::collections::HashSet<String>>,
 }
 
 /// User information
@@ -37,29 +218,117 @@ pub struct UserInfo {
     pub id: u32,
     pub name: String,
     pub language: String,
-    pub playtime: u64, // in seconds
     pub session_id: uuid::Uuid,
-    pub room_id: Option<u32>,
-    pub is_playing: bool,
+    /// IP address the user connected from
+    pub ip: Option<String>,
     pub custom_data: std::collections::HashMap<String, Value>,
 }
 
 /// Room information
 pub struct RoomInfo {
-    pub id: u32,
+    pub id: String,
     pub name: String,
     pub host_id: u32,
     pub user_ids: Vec<u32>,
     pub max_users: u32,
     pub locked: bool,
+    /// Who locked the room and when, while locked
+    pub lock: Option<RoomLock>,
     pub cycle: bool,
-    pub chart_id: Option<u32>,
+    pub chart_id: Option<phira_mp_common::ChartId>,
+    /// Weighted charts the room rotates through, see [`HostApi::replace_chart_pool`]
+    pub chart_pool: Vec<(phira_mp_common::ChartId, u32)>,
     pub state: RoomState,
     pub playing_user_ids: Vec<u32>,
     pub rounds: Vec<RoundInfo>,
     pub custom_data: std::collections::HashMap<String, Value>,
 }
 
+impl RoomInfo {
+    /// An empty room without host
+    fn empty(id: String, max_users: u32) -> Self {
+        Self {
+            name: id.clone(),
+            id,
+            host_id: 0,
+            user_ids: Vec::new(),
+            max_users,
+            locked: false,
+            lock: None,
+            cycle: false,
+            chart_id: None,
+            chart_pool: Vec::new(),
+            state: RoomState::SelectingChart,
+            playing_user_ids: Vec::new(),
+            rounds: Vec::new(),
+            custom_data: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Take over the fields the server keeps, leaving plugin data untouched
+    fn update(&mut self, room: LiveRoom) {
+        self.name = room.name;
+        self.host_id = room.host_id;
+        self.user_ids = room.user_ids;
+        self.max_users = room.max_users;
+        self.locked = room.locked;
+        self.lock = room.lock;
+        self.cycle = room.cycle;
+        self.chart_id = room.chart_id;
+        self.chart_pool = room.chart_pool;
+        self.state = room.state;
+        self.playing_user_ids = room.playing_user_ids;
+    }
+}
+
+/// A room as the server sees it, see [`ServerBridge::get_rooms`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveRoom {
+    pub id: String,
+    pub name: String,
+    /// 0 while the room has no host
+    pub host_id: u32,
+    pub user_ids: Vec<u32>,
+    pub max_users: u32,
+    pub locked: bool,
+    pub lock: Option<RoomLock>,
+    pub cycle: bool,
+    pub chart_id: Option<phira_mp_common::ChartId>,
+    pub chart_pool: Vec<(phira_mp_common::ChartId, u32)>,
+    pub state: RoomState,
+    pub playing_user_ids: Vec<u32>,
+}
+
+/// Version of the room templates produced by [`HostApi::export_room_template`]
+pub const ROOM_TEMPLATE_VERSION: u32 = 1;
+
+/// Reusable settings of a room
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RoomTemplate {
+    /// Must equal [`ROOM_TEMPLATE_VERSION`]
+    pub version: u32,
+    pub name: String,
+    pub max_users: u32,
+    pub locked: bool,
+    pub cycle: bool,
+    pub chart_pool: Vec<RoomTemplateChart>,
+}
+
+/// Weighted chart of a [`RoomTemplate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RoomTemplateChart {
+    pub chart_id: phira_mp_common::ChartId,
+    pub weight: u32,
+}
+
+/// Lock metadata of a room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoomLock {
+    /// User who locked the room, `None` for the console or a plugin
+    pub user_id: Option<u32>,
+    pub locked_at: std::time::SystemTime,
+}
+
 /// Room state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RoomState {
@@ -70,7 +339,7 @@ pub enum RoomState {
 
 /// Round information
 pub struct RoundInfo {
-    pub chart_id: u32,
+    pub chart_id: phira_mp_common::ChartId,
     pub records: Vec<RecordInfo>,
 }
 
@@ -113,8 +382,6 @@ impl HostApi {
         let server_state = Arc::new(RwLock::new(ServerState {
             online_users: std::collections::HashMap::new(),
             rooms: std::collections::HashMap::new(),
-            banned_user_ids: std::collections::HashSet::new(),
-            banned_ips: std::collections::HashSet::new(),
             room_bans: std::collections::HashMap::new(),
             room_ip_bans: std::collections::HashMap::new(),
         }));
@@ -124,9 +391,37 @@ impl HostApi {
             command_registry,
             plugin_manager,
             server_state,
+            server_bridge: RwLock::new(None),
+            chart_validators: crate::chart_validator::ChartSelectionValidators::new(),
+            player_input: crate::player_input::PlayerInputSubscribers::new(),
+            plugin_cooldowns: crate::cooldown::CooldownTracker::new(),
+            queues: crate::matchmaking::MatchmakingQueues::new(),
+            rpc: crate::rpc::RpcRegistry::new(),
+            http_routes: crate::http_routes::HttpRoutes::new(),
+            room_info_fields: crate::info_fields::InfoFields::new(),
+            user_info_fields: crate::info_fields::InfoFields::new(),
+            hot_reload_manager: RwLock::new(None),
+            health_monitor: RwLock::new(None),
+            sandbox_manager: Arc::new(crate::sandbox::SandboxManager::new()),
+            user_tags: RwLock::new(crate::config::PluginConfig::new()),
+            playtime: crate::playtime::PlaytimeStore::new(),
+            bans: crate::ban_store::BanStore::new(),
         }
     }
 
+    /// Connect the host API to the running server
+    pub fn set_server_bridge(&self, bridge: Arc<dyn ServerBridge>) {
+        *self.server_bridge.write() = Some(bridge);
+    }
+
+    /// Get server bridge if connected
+    fn get_server_bridge(&self) -> Result<Arc<dyn ServerBridge>> {
+        self.server_bridge
+            .read()
+            .clone()
+            .ok_or_else(|| Error::Api("Server is not connected".to_string()))
+    }
+
     // ===== Helper Methods =====
 
     /// Get plugin manager if available
@@ -169,15 +464,69 @@ impl HostApi {
         self.event_bus.subscribe(event_type, handler, plugin_name)
     }
     
+    /// Subscribe to an event, first replaying the matching events emitted since `since`
+    /// (milliseconds since epoch) that are still in the event history
+    pub fn subscribe_event_with_replay(
+        &self,
+        event_type: &str,
+        handler: crate::event_system::EventHandler,
+        plugin_name: &str,
+        since: i64,
+    ) -> Result<usize> {
+        self.event_bus
+            .subscribe_with_replay(event_type, handler, plugin_name, since)
+    }
+
+    /// Subscribe to an event, running the handler before those with lower priority
+    pub fn subscribe_event_with_priority(
+        &self,
+        event_type: &str,
+        handler: crate::event_system::EventHandler,
+        plugin_name: &str,
+        priority: i32,
+    ) -> Result<()> {
+        self.event_bus
+            .subscribe_with_priority(event_type, handler, plugin_name, priority)
+    }
+
+    /// Subscribe to an event with a handler that may veto or modify it, consulted before
+    /// those with lower priority
+    pub fn subscribe_cancellable_event(
+        &self,
+        event_type: &str,
+        handler: crate::event_system::CancellableEventHandler,
+        plugin_name: &str,
+        priority: i32,
+    ) -> Result<()> {
+        self.event_bus
+            .subscribe_cancellable(event_type, handler, plugin_name, priority)
+    }
+
     /// Unsubscribe from an event
     pub fn unsubscribe_event(&self, event_type: &str, plugin_name: &str) -> Result<()> {
         self.event_bus.unsubscribe(event_type, plugin_name)
     }
     
     /// Emit an event
+    ///
+    /// Plugins may emit at any rate, so handler calls are bounded per plugin, see
+    /// [`Self::emit_bounded`].
     pub fn emit_event(&self, event_type: &str, data: Value, plugin_name: &str) -> Result<()> {
         let event = crate::event_system::Event::plugin(event_type, data, plugin_name);
-        self.event_bus.emit(event)
+        self.emit_bounded(event)
+    }
+
+    /// Emit a possibly high-frequency event, limiting concurrent handler calls per plugin
+    /// as configured with [`crate::event_system::EventBus::set_handler_concurrency`]
+    ///
+    /// Outside a tokio runtime the handlers run inline as with
+    /// [`crate::event_system::EventBus::emit`].
+    fn emit_bounded(&self, event: crate::event_system::Event) -> Result<()> {
+        if tokio::runtime::Handle::try_current().is_err() {
+            return self.event_bus.emit(event);
+        }
+        // The calls run detached, bounded by the plugin's semaphore
+        self.event_bus.emit_spawned(event).map(drop)
     }
     
     // ===== Command System APIs =====
@@ -193,70 +542,659 @@ impl HostApi {
         let command = crate::command_system::Command::new(name, description, handler, plugin_name);
         self.command_registry.register(command)
     }
+
+    /// Register a command carrying aliases, permissions or argument specs
+    pub fn register_command_spec(&self, command: crate::command_system::Command) -> Result<()> {
+        self.command_registry.register(command)
+    }
     
     /// Unregister a command
     pub fn unregister_command(&self, name: &str) -> Result<()> {
         self.command_registry.unregister(name)
     }
+
+    /// Register a command line rewriter; rewriters with higher priority run first
+    pub fn register_command_rewriter(
+        &self,
+        plugin_name: &str,
+        priority: i32,
+        rewriter: crate::command_system::CommandRewriter,
+    ) {
+        self.command_registry.register_rewriter(plugin_name, priority, rewriter);
+    }
+
+    /// Get the owning plugin of every registered command, keyed by command name
+    pub fn get_command_owners(&self) -> std::collections::BTreeMap<String, String> {
+        self.command_registry
+            .get_all_commands()
+            .into_iter()
+            .map(|command| (command.name.clone(), command.plugin.clone()))
+            .collect()
+    }
+
+    /// Get the owning plugin of a command or alias
+    pub fn get_command_owner(&self, name: &str) -> Option<String> {
+        self.command_registry
+            .get_command(name)
+            .map(|command| command.plugin.clone())
+    }
+
+    /// Get all registered commands, sorted by name
+    pub fn get_registered_commands(&self) -> Vec<Arc<crate::command_system::Command>> {
+        let mut commands = self.command_registry.get_all_commands();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    /// Get the catalog of registered commands, sorted by name
+    ///
+    /// Each entry holds the name, aliases, description, arguments, required permissions
+    /// and owning plugin of a command.
+    pub fn get_command_catalog(&self) -> Value {
+        let catalog: Vec<Value> = self.get_registered_commands().into_iter()
+            .map(|command| {
+                let mut aliases = command.aliases.clone();
+                aliases.sort();
+                json!({
+                    "name": command.name,
+                    "aliases": aliases,
+                    "description": command.description,
+                    "args": command.args,
+                    "permissions": command.permissions.clone().unwrap_or_default(),
+                    "plugin": command.plugin,
+                })
+            })
+            .collect();
+        json!(catalog)
+    }
+
+    /// Execute a command line through the plugin command registry on behalf of a caller
+    pub fn execute_command(
+        &self,
+        command_line: &str,
+        context: &crate::command_system::CommandContext,
+    ) -> Result<String> {
+        self.command_registry.execute(command_line, Some(context))
+    }
+
+    /// Command context of a loaded plugin, holding the permissions it declares
+    pub fn plugin_command_context(
+        &self,
+        plugin: &str,
+    ) -> Result<crate::command_system::CommandContext> {
+        let plugin = self
+            .get_plugin_manager()?
+            .get_plugin(plugin)
+            .ok_or_else(|| Error::NotFound(plugin.to_string()))?;
+        let plugin = plugin.read();
+        Ok(crate::command_system::CommandContext::plugin(
+            &plugin.metadata.name,
+            plugin.metadata.permissions.iter().flatten().cloned(),
+        ))
+    }
+
+    // ===== Chart Selection APIs =====
+
+    /// Register a chart selection validator; validators with higher priority run first
+    pub fn register_chart_validator(
+        &self,
+        plugin_name: &str,
+        priority: i32,
+        validator: crate::chart_validator::ChartSelectionValidator,
+    ) {
+        self.chart_validators.register(plugin_name, priority, validator);
+    }
+
+    /// Unregister all chart selection validators from a plugin
+    pub fn unregister_chart_validators(&self, plugin_name: &str) {
+        self.chart_validators.unregister_all_from_plugin(plugin_name);
+    }
+
+    /// Check a chart selection against all validators
+    pub fn validate_chart_selection(
+        &self,
+        room_id: &str,
+        user_id: u32,
+        chart_id: phira_mp_common::ChartId,
+    ) -> crate::chart_validator::ChartSelectionDecision {
+        self.chart_validators
+            .validate(&crate::chart_validator::ChartSelection {
+                room_id: room_id.to_string(),
+                user_id,
+                chart_id,
+            })
+    }
+
+    // ===== Plugin RPC APIs =====
+
+    /// Expose a method other plugins can call with [`HostApi::call_plugin`]
+    pub fn register_rpc_method(
+        &self,
+        plugin_name: &str,
+        method: &str,
+        handler: crate::rpc::RpcHandler,
+    ) -> Result<()> {
+        self.rpc.register(plugin_name, method, handler)
+    }
+
+    /// Unregister all RPC methods of a plugin
+    pub fn unregister_rpc_methods(&self, plugin_name: &str) {
+        self.rpc.unregister_all_from_plugin(plugin_name);
+    }
+
+    /// Call a method another plugin registered, returning its response
+    ///
+    /// Unknown plugins or methods, and call chains nested deeper than
+    /// [`crate::rpc::MAX_RPC_DEPTH`], fail with `Error::Api`.
+    pub fn call_plugin(&self, target: &str, method: &str, args: Value) -> Result<Value> {
+        self.rpc.call(target, method, args)
+    }
+
+    // ===== Player Input APIs =====
+
+    /// Subscribe a plugin to the touch and judge frames of a live room, forwarding every
+    /// `sample_every`-th batch
+    ///
+    /// Plugins must declare the `player_input` permission. Batches are queued per
+    /// subscription and dropped while the plugin falls behind; delivery stops once the
+    /// plugin exceeds its sandbox limits.
+    pub fn subscribe_player_input(
+        &self,
+        plugin: &str,
+        room_id: &str,
+        sample_every: u32,
+        handler: crate::player_input::PlayerInputHandler,
+    ) -> Result<()> {
+        self.require_permission(plugin, "player_input")?;
+        if sample_every == 0 {
+            return Err(Error::Api("Sample rate must be at least 1".to_string()));
+        }
+        let sandbox = self.sandbox_manager.get_sandbox(plugin);
+        self.player_input
+            .subscribe(plugin, room_id, sample_every, sandbox, handler);
+        Ok(())
+    }
+
+    /// Unsubscribe a plugin from the player input of a room
+    pub fn unsubscribe_player_input(&self, plugin: &str, room_id: &str) {
+        self.player_input.unsubscribe(plugin, room_id);
+    }
+
+    /// Unregister all player input subscriptions from a plugin
+    pub fn unregister_player_input(&self, plugin_name: &str) {
+        self.player_input.unregister_all_from_plugin(plugin_name);
+    }
+
+    /// Whether any plugin is subscribed to the player input of a room
+    pub fn has_player_input_subscribers(&self, room_id: &str) -> bool {
+        self.player_input.has_subscribers(room_id)
+    }
+
+    /// Forward input frames of a live room to subscribed plugins
+    pub fn dispatch_player_input(&self, input: &crate::player_input::PlayerInput) {
+        self.player_input.dispatch(input);
+    }
+
+    // ===== Matchmaking APIs =====
+
+    /// Add a user to a matchmaking queue, returning its 1-based position
+    ///
+    /// A user can wait in one queue at a time and is removed when disconnecting.
+    /// Emits a `queue_enqueue` event.
+    pub fn queue_push(&self, queue_name: &str, user_id: u32, metadata: Value) -> Result<usize> {
+        let position = self.queues.push(queue_name, user_id, metadata)?;
+        self.emit_queue_event(
+            crate::event_system::predefined::QUEUE_ENQUEUE,
+            json!({ "queue": queue_name, "user_id": user_id, "position": position }),
+        );
+        Ok(position)
+    }
+
+    /// Take the `n` longest-waiting users of a queue, or nobody if fewer are queued
+    ///
+    /// Emits a `queue_dequeue` event for a non-empty batch.
+    pub fn queue_pop_batch(&self, queue_name: &str, n: usize) -> Vec<crate::matchmaking::QueueEntry> {
+        let batch = self.queues.pop_batch(queue_name, n);
+        if !batch.is_empty() {
+            let user_ids: Vec<_> = batch.iter().map(|entry| entry.user_id).collect();
+            self.emit_queue_event(
+                crate::event_system::predefined::QUEUE_DEQUEUE,
+                json!({ "queue": queue_name, "user_ids": user_ids, "reason": "matched" }),
+            );
+        }
+        batch
+    }
+
+    /// Remove a user from its queue, returning the queue name if it was queued
+    ///
+    /// Emits a `queue_dequeue` event if the user was queued.
+    pub fn queue_remove(&self, user_id: u32) -> Option<String> {
+        self.dequeue_user(user_id, "removed")
+    }
+
+    /// Get the users waiting in a queue, longest-waiting first
+    pub fn queue_entries(&self, queue_name: &str) -> Vec<crate::matchmaking::QueueEntry> {
+        self.queues.entries(queue_name)
+    }
+
+    /// Handle a user authenticating: track it as online and emit `user_connect`
+    ///
+    /// A reconnecting user replaces its previous entry.
+    pub fn on_user_connect(&self, user: UserInfo) {
+        let data = json!({ "user_id": user.id, "name": user.name });
+        self.server_state.write().online_users.insert(user.id, user);
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::USER_CONNECT,
+            data,
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit user connect event: {}", e);
+        }
+    }
+
+    /// Handle a user losing its connection: forget it, leave its queue and emit
+    /// `user_disconnect`
+    pub fn on_user_disconnect(&self, user_id: u32) {
+        self.server_state.write().online_users.remove(&user_id);
+        self.dequeue_user(user_id, "disconnected");
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::USER_DISCONNECT,
+            json!({ "user_id": user_id }),
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit user disconnect event: {}", e);
+        }
+    }
+
+    /// Handle a room being disbanded: forget its mirror entry and bans and emit `room_disband`
+    pub fn on_room_disband(&self, room_id: &str) {
+        {
+            let mut state = self.server_state.write();
+            state.rooms.remove(room_id);
+            state.room_bans.remove(room_id);
+            state.room_ip_bans.remove(room_id);
+        }
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::ROOM_DISBAND,
+            json!({ "room_id": room_id }),
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit room disband event: {}", e);
+        }
+    }
+
+    /// Let plugins veto a user joining a room, emitting `user_join_room` unless vetoed
+    ///
+    /// Returns the reason a plugin gave for blocking the join, if any.
+    pub fn on_user_join_room(&self, user_id: u32, room_id: &str, monitor: bool) -> Option<String> {
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::USER_JOIN_ROOM,
+            json!({ "user_id": user_id, "room_id": room_id, "monitor": monitor }),
+        );
+        match self.event_bus.emit_cancellable(event) {
+            crate::event_system::EmitOutcome::Completed(_) => None,
+            crate::event_system::EmitOutcome::Cancelled(reason) => Some(reason),
+        }
+    }
+
+    /// Notify plugins that the auth server rate limited an authentication and is not asked
+    /// again for `backoff`
+    pub fn on_auth_rate_limited(&self, backoff: std::time::Duration) {
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::AUTH_RATE_LIMITED,
+            json!({ "backoff_secs": backoff.as_secs() }),
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit auth rate limited event: {}", e);
+        }
+    }
+
+    fn dequeue_user(&self, user_id: u32, reason: &str) -> Option<String> {
+        let (queue_name, _) = self.queues.remove(user_id)?;
+        self.emit_queue_event(
+            crate::event_system::predefined::QUEUE_DEQUEUE,
+            json!({ "queue": queue_name, "user_ids": [user_id], "reason": reason }),
+        );
+        Some(queue_name)
+    }
+
+    fn emit_queue_event(&self, event_type: &str, data: Value) {
+        let event = crate::event_system::Event::system(event_type, data);
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit {} event: {}", event_type, e);
+        }
+    }
+
+    // ===== Cooldown APIs =====
+
+    /// Check whether a plugin's `key` is off cooldown, starting a new cooldown of `duration`
+    /// if so
+    ///
+    /// Keys are scoped to the plugin and tracked apart from command cooldowns, so plugins
+    /// can neither collide with nor evict them.
+    pub fn cooldown_check(
+        &self,
+        key: &str,
+        duration: std::time::Duration,
+        plugin_name: &str,
+    ) -> bool {
+        self.plugin_cooldowns
+            .check(&format!("{}:{}", plugin_name, key), duration)
+    }
     
     // ===== User Management APIs =====
     
-    /// Kick a user
+    /// Kick an online user, removing them from their room
+    ///
+    /// The user's session is closed without waiting for a reconnect, and a
+    /// `user_disconnect` event is emitted.
     pub fn kick_user(&self, user_id: u32) -> Result<()> {
         debug!("Kicking user {}", user_id);
-        // TODO: Implement actual user kicking
-        Ok(())
+        if self.get_server_bridge()?.kick_user(user_id) {
+            Ok(())
+        } else {
+            Err(Error::Api(format!("User {} is not online", user_id)))
+        }
     }
     
-    /// Ban a user by ID
+    /// Load server-wide bans from `path`, saving them there on every change, returning the
+    /// number of bans loaded
+    pub fn open_bans(&self, path: impl AsRef<std::path::Path>) -> Result<usize> {
+        self.bans.open(path)
+    }
+
+    /// Ban a user by ID permanently
     pub fn ban_user_by_id(&self, user_id: u32, reason: &str) -> Result<()> {
-        debug!("Banning user {}: {}", user_id, reason);
-        let mut state = self.server_state.write();
-        state.banned_user_ids.insert(user_id);
-        Ok(())
+        self.ban_user_by_id_for(user_id, reason, None)
+    }
+
+    /// Ban a user by ID, for `duration` or permanently, replacing any previous ban
+    pub fn ban_user_by_id_for(
+        &self,
+        user_id: u32,
+        reason: &str,
+        duration: Option<std::time::Duration>,
+    ) -> Result<()> {
+        debug!("Banning user {} for {:?}: {}", user_id, duration, reason);
+        self.bans.ban_id(user_id, reason, duration)
     }
     
     /// Unban a user by ID
     pub fn unban_user_by_id(&self, user_id: u32) -> Result<()> {
         debug!("Unbanning user {}", user_id);
-        let mut state = self.server_state.write();
-        state.banned_user_ids.remove(&user_id);
+        self.bans.unban_id(user_id)?;
         Ok(())
     }
     
-    /// Ban a user by IP
+    /// Ban a user by IP permanently
     pub fn ban_user_by_ip(&self, ip: &str, reason: &str) -> Result<()> {
-        debug!("Banning IP {}: {}", ip, reason);
-        let mut state = self.server_state.write();
-        state.banned_ips.insert(ip.to_string());
-        Ok(())
+        self.ban_user_by_ip_for(ip, reason, None)
+    }
+
+    /// Ban a user by IP, for `duration` or permanently, replacing any previous ban
+    pub fn ban_user_by_ip_for(
+        &self,
+        ip: &str,
+        reason: &str,
+        duration: Option<std::time::Duration>,
+    ) -> Result<()> {
+        debug!("Banning IP {} for {:?}: {}", ip, duration, reason);
+        self.bans.ban_ip(ip, reason, duration)
     }
     
     /// Unban a user by IP
     pub fn unban_user_by_ip(&self, ip: &str) -> Result<()> {
         debug!("Unbanning IP {}", ip);
-        let mut state = self.server_state.write();
-        state.banned_ips.remove(ip);
+        self.bans.unban_ip(ip)?;
         Ok(())
     }
     
     /// Get user information
+    ///
+    /// The user's room is the server's room the user is in.
     pub fn get_user_info(&self, user_id: u32) -> Result<Value> {
+        self.refresh_rooms();
         let state = self.server_state.read();
         if let Some(user) = state.online_users.get(&user_id) {
-            Ok(json!({
+            let (custom_data, truncated) = bounded_custom_data(&user.custom_data);
+            let latency = self.get_user_latency(user_id).ok().flatten();
+            let room = state.rooms.values().find(|room| room.user_ids.contains(&user_id));
+            let mut info = json!({
                 "id": user.id,
                 "name": user.name,
                 "language": user.language,
-                "playtime": user.playtime,
-                "room_id": user.room_id,
-                "is_playing": user.is_playing,
-                "custom_data": user.custom_data,
-            }))
+                "playtime": self.playtime.get(user_id).map_or(0, |it| it.total),
+                "room_id": room.map(|room| &room.id),
+                "is_playing": room.is_some_and(|room| room.playing_user_ids.contains(&user_id)),
+                "latency_ms": latency.map(|it| it.as_millis() as u64),
+                "tags": self.get_user_tags(user_id),
+                "custom_data": custom_data,
+                "custom_data_truncated": truncated,
+            });
+            if let Some(info) = info.as_object_mut() {
+                self.user_info_fields.merge_into(info, &user.custom_data);
+            }
+            Ok(info)
         } else {
             Err(Error::Api(format!("User {} not found", user_id)))
         }
     }
+
+    /// Get the ping round-trip time of a user, `None` until measured or for clients
+    /// too old to answer server pings
+    pub fn get_user_latency(&self, user_id: u32) -> Result<Option<std::time::Duration>> {
+        Ok(self.get_server_bridge()?.get_user_latency(user_id))
+    }
+
+    /// Get hourly server statistics (peak users, rooms created, games completed,
+    /// rate-limited authentications) over the last `window`, bounded by the server's retention
+    pub fn get_server_stats(&self, window: std::time::Duration) -> Result<Value> {
+        let buckets = self.get_server_bridge()?.get_server_stats(window);
+        let counts = |key: &'static str| buckets.iter().filter_map(move |it| it[key].as_u64());
+        Ok(json!({
+            "window_secs": window.as_secs(),
+            "peak_users": counts("peak_users").max().unwrap_or(0),
+            "rooms_created": counts("rooms_created").sum::<u64>(),
+            "games_completed": counts("games_completed").sum::<u64>(),
+            "auth_rate_limited": counts("auth_rate_limited").sum::<u64>(),
+            "buckets": buckets,
+        }))
+    }
+    
+    /// Get the session of an online user: session ID, protocol version, connect time,
+    /// latency, monitor flag and current room
+    ///
+    /// Plugins must declare the `admin` permission; `plugin` is `None` for the server console.
+    pub fn get_session_info(
+        &self,
+        caller: &crate::command_system::CommandContext,
+        user_id: u32,
+    ) -> Result<Value> {
+        if !caller.has_permission("admin") {
+            return Err(Error::SecurityViolation(
+                "Session info requires the admin permission".to_string(),
+            ));
+        }
+        self.get_server_bridge()?
+            .get_session_info(user_id)
+            .ok_or_else(|| Error::Api(format!("User {} has no active session", user_id)))
+    }
+
+    /// Log the protocol frames of a user's live session, with secrets redacted
+    /// (admin only, not exposed to plugins)
+    pub fn set_frame_trace(&self, user_id: u32, enabled: bool) -> Result<()> {
+        if self.get_server_bridge()?.set_frame_trace(user_id, enabled) {
+            Ok(())
+        } else {
+            Err(Error::Api(format!("User {} has no active session", user_id)))
+        }
+    }
+
+    // ===== User Tag APIs =====
+
+    /// Load user tags from `path`, saving them there on every change, returning the number
+    /// of tagged users
+    pub fn open_user_tags(&self, path: impl AsRef<std::path::Path>) -> Result<usize> {
+        let mut tags = crate::config::PluginConfig::from_file(&path)?;
+        tags.path = Some(path.as_ref().to_string_lossy().to_string());
+        let count = tags.values.len();
+        *self.user_tags.write() = tags;
+        Ok(count)
+    }
+
+    /// Add a tag such as "VIP" or "verified" to a user, returning whether it was new
+    ///
+    /// Tags are case-sensitive, at most [`MAX_USER_TAG_LENGTH`] characters and may not
+    /// contain whitespace.
+    pub fn add_user_tag(&self, user_id: u32, tag: &str) -> Result<bool> {
+        if tag.is_empty()
+            || tag.chars().count() > MAX_USER_TAG_LENGTH
+            || tag.chars().any(|c| c.is_whitespace() || c.is_control())
+        {
+            return Err(Error::Api(format!("Invalid user tag '{}'", tag)));
+        }
+        self.update_user_tags(user_id, |tags| {
+            if tags.iter().any(|it| it == tag) {
+                return false;
+            }
+            tags.push(tag.to_string());
+            tags.sort();
+            true
+        })
+    }
+
+    /// Remove a tag from a user, returning whether the user had it
+    pub fn remove_user_tag(&self, user_id: u32, tag: &str) -> Result<bool> {
+        self.update_user_tags(user_id, |tags| {
+            let len = tags.len();
+            tags.retain(|it| it != tag);
+            tags.len() != len
+        })
+    }
+
+    /// Get the tags of a user, sorted
+    pub fn get_user_tags(&self, user_id: u32) -> Vec<String> {
+        self.user_tags
+            .read()
+            .get(&user_id.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Whether a user has a tag, for gating features such as room creation
+    pub fn user_has_tag(&self, user_id: u32, tag: &str) -> bool {
+        self.get_user_tags(user_id).iter().any(|it| it == tag)
+    }
+
+    /// Apply `update` to the tags of a user, saving them if it reports a change
+    fn update_user_tags(&self, user_id: u32, update: impl FnOnce(&mut Vec<String>) -> bool) -> Result<bool> {
+        let mut store = self.user_tags.write();
+        let key = user_id.to_string();
+        let mut tags: Vec<String> = store.get(&key).unwrap_or_default();
+        if !update(&mut tags) {
+            return Ok(false);
+        }
+        if tags.is_empty() {
+            store.remove(&key);
+        } else {
+            store.set(&key, tags)?;
+        }
+        if store.path.is_some() {
+            store.save()?;
+        }
+        Ok(true)
+    }
+
+    // ===== HTTP APIs =====
+
+    /// Send a GET request on behalf of a plugin
+    ///
+    /// The host of the URL and of every redirect must be allowed by the plugin's security
+    /// policy, otherwise the request fails with [`Error::SecurityViolation`] and a sandbox
+    /// violation is counted.
+    pub async fn http_get(&self, plugin: &str, url: &str) -> Result<HttpResponse> {
+        self.http_request(plugin, reqwest::Method::GET, url, None).await
+    }
+
+    /// Send a POST request on behalf of a plugin, checked like [`Self::http_get`]
+    pub async fn http_post(
+        &self,
+        plugin: &str,
+        url: &str,
+        content_type: &str,
+        body: String,
+    ) -> Result<HttpResponse> {
+        self.http_request(plugin, reqwest::Method::POST, url, Some((content_type, body)))
+            .await
+    }
+
+    async fn http_request(
+        &self,
+        plugin: &str,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<(&str, String)>,
+    ) -> Result<HttpResponse> {
+        let sandbox = self.sandbox_manager.get_sandbox(plugin).ok_or_else(|| {
+            Error::SecurityViolation(format!("Plugin {} has no sandbox", plugin))
+        })?;
+        let url = check_http_url(&sandbox, url)?;
+        let client = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::custom({
+                let sandbox = Arc::clone(&sandbox);
+                move |attempt| {
+                    if attempt.previous().len() >= MAX_HTTP_REDIRECTS {
+                        return attempt.error("too many redirects");
+                    }
+                    match check_http_url(&sandbox, attempt.url().as_str()) {
+                        Ok(_) => attempt.follow(),
+                        Err(e) => attempt.error(e),
+                    }
+                }
+            }))
+            .build()
+            .map_err(|e| Error::Api(format!("Failed to create HTTP client: {}", e)))?;
+
+        debug!("Plugin {} sending {} {}", plugin, method, url);
+        let mut request = client.request(method, url);
+        if let Some((content_type, body)) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(body);
+        }
+        let response = request.send().await.map_err(|e| {
+            // A redirect to a disallowed host surfaces as the request's source error
+            let mut source = std::error::Error::source(&e);
+            while let Some(err) = source {
+                if let Some(Error::SecurityViolation(message)) = err.downcast_ref::<Error>() {
+                    return Error::SecurityViolation(message.clone());
+                }
+                source = err.source();
+            }
+            Error::Api(format!("HTTP request failed: {}", e))
+        })?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::Api(format!("Failed to read HTTP response: {}", e)))?;
+        Ok(HttpResponse { status, body })
+    }
+
+    /// Fail unless a loaded plugin declares `permission`
+    fn require_permission(&self, plugin: &str, permission: &str) -> Result<()> {
+        let plugin_manager = self.get_plugin_manager()?;
+        let granted = plugin_manager
+            .get_plugin(plugin)
+            .is_some_and(|it| it.read().metadata.requires_permission(permission));
+        if !granted {
+            return Err(Error::SecurityViolation(format!(
+                "Plugin {} lacks the {} permission",
+                plugin, permission
+            )));
+        }
+        Ok(())
+    }
     
     /// Get username
     pub fn get_username(&self, user_id: u32) -> Result<String> {
@@ -276,137 +1214,367 @@ impl HostApi {
             .ok_or_else(|| Error::Api(format!("User {} not found", user_id)))
     }
     
-    /// Get user playtime
+    /// Get user playtime, in seconds
+    ///
+    /// Fails for users who are neither online nor have played.
     pub fn get_user_playtime(&self, user_id: u32) -> Result<u64> {
-        let state = self.server_state.read();
-        state.online_users
-            .get(&user_id)
-            .map(|user| user.playtime)
-            .ok_or_else(|| Error::Api(format!("User {} not found", user_id)))
+        if let Some(record) = self.playtime.get(user_id) {
+            return Ok(record.total);
+        }
+        if self.server_state.read().online_users.contains_key(&user_id) {
+            return Ok(0);
+        }
+        Err(Error::Api(format!("User {} not found", user_id)))
     }
     
+    /// Credit playtime to a user, counted in total and windowed leaderboards
+    pub fn record_playtime(&self, user_id: u32, seconds: u64) -> Result<()> {
+        let name = self.server_state.read().online_users
+            .get(&user_id)
+            .map(|user| user.name.clone());
+        self.playtime.record(
+            user_id,
+            name.as_deref(),
+            seconds,
+            std::time::SystemTime::now(),
+            PLAYTIME_LOG_RETENTION,
+        );
+        Ok(())
+    }
+
+    /// Called by the server when a user finishes playing, to credit the playtime
+    pub fn on_user_playtime(&self, user_id: u32, name: &str, seconds: u64) {
+        self.playtime.record(
+            user_id,
+            Some(name),
+            seconds,
+            std::time::SystemTime::now(),
+            PLAYTIME_LOG_RETENTION,
+        );
+    }
+
+    /// Load playtime from `path`, flushing it there with [`Self::flush_playtime`], returning
+    /// the number of users loaded
+    pub fn open_playtime(&self, path: impl AsRef<std::path::Path>) -> Result<usize> {
+        self.playtime.open(path)
+    }
+
+    /// Save playtime credited since the last flush, returning whether anything was saved
+    ///
+    /// The server flushes periodically and on shutdown; plugins may force a save earlier.
+    pub fn flush_playtime(&self) -> Result<bool> {
+        self.playtime.flush()
+    }
+
     /// Get playtime leaderboard
-    pub fn get_playtime_leaderboard(&self, limit: u32) -> Result<Value> {
+    ///
+    /// With a `window`, users are ranked by playtime credited within it (`recent_playtime`),
+    /// otherwise by total playtime. Ties are broken by user ID.
+    pub fn get_playtime_leaderboard(
+        &self,
+        limit: u32,
+        window: Option<std::time::Duration>,
+    ) -> Result<Value> {
+        let now = std::time::SystemTime::now();
         let state = self.server_state.read();
-        let mut users: Vec<(&u32, &UserInfo)> = state.online_users.iter().collect();
-        users.sort_by(|a, b| b.1.playtime.cmp(&a.1.playtime));
+        let mut users: Vec<(u32, crate::playtime::PlaytimeRecord, u64)> = self
+            .playtime
+            .all()
+            .into_iter()
+            .map(|(id, record)| {
+                let recent = window.map_or(record.total, |window| record.recent(now, window));
+                (id, record, recent)
+            })
+            .collect();
+        users.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
         
         let limited_users: Vec<Value> = users
             .iter()
             .take(limit as usize)
-            .map(|(id, user)| {
-                json!({
+            .map(|(id, record, recent)| {
+                let name = state.online_users
+                    .get(id)
+                    .map(|user| &user.name)
+                    .or(record.name.as_ref());
+                let mut entry = json!({
                     "id": id,
-                    "name": user.name,
-                    "playtime": user.playtime,
-                })
+                    "name": name,
+                    "playtime": record.total,
+                });
+                if let Some(window) = window {
+                    entry["recent_playtime"] = json!(recent);
+                    entry["window_secs"] = json!(window.as_secs());
+                }
+                entry
             })
             .collect();
         
         Ok(json!(limited_users))
     }
     
-    /// Get banned users by ID
+    /// Get banned users by ID, sorted by ID
+    ///
+    /// Each entry holds the ID, reason, ban and expiry times (seconds since epoch) and the
+    /// seconds remaining; expiry and remaining time are `null` for permanent bans.
     pub fn get_banned_users_by_id(&self) -> Result<Value> {
-        let state = self.server_state.read();
-        let banned_ids: Vec<u32> = state.banned_user_ids.iter().copied().collect();
-        Ok(json!(banned_ids))
+        let now = std::time::SystemTime::now();
+        let banned: Vec<Value> = self.bans.ids().into_iter()
+            .map(|(id, entry)| {
+                let mut info = ban_info(&entry, now);
+                info["id"] = json!(id);
+                info
+            })
+            .collect();
+        Ok(json!(banned))
     }
     
-    /// Get banned users by IP
+    /// Get banned users by IP, sorted by IP, in the form of [`Self::get_banned_users_by_id`]
     pub fn get_banned_users_by_ip(&self) -> Result<Value> {
-        let state = self.server_state.read();
-        let banned_ips: Vec<&String> = state.banned_ips.iter().collect();
-        Ok(json!(banned_ips))
+        let now = std::time::SystemTime::now();
+        let banned: Vec<Value> = self.bans.ips().into_iter()
+            .map(|(ip, entry)| {
+                let mut info = ban_info(&entry, now);
+                info["ip"] = json!(ip);
+                info
+            })
+            .collect();
+        Ok(json!(banned))
     }
     
+    /// Get the ban of a user ID, if banned
+    pub fn get_ban_by_id(&self, user_id: u32) -> Option<crate::ban_store::BanEntry> {
+        self.bans.get_id(user_id)
+    }
+
+    /// Get the ban of an IP, if banned
+    pub fn get_ban_by_ip(&self, ip: &str) -> Option<crate::ban_store::BanEntry> {
+        self.bans.get_ip(ip)
+    }
+
     /// Check if a user is banned by ID
     pub fn is_user_banned_by_id(&self, user_id: u32) -> Result<bool> {
-        let state = self.server_state.read();
-        Ok(state.banned_user_ids.contains(&user_id))
+        Ok(self.bans.get_id(user_id).is_some())
     }
     
     /// Check if an IP is banned
     pub fn is_user_banned_by_ip(&self, ip: &str) -> Result<bool> {
-        let state = self.server_state.read();
-        Ok(state.banned_ips.contains(ip))
+        Ok(self.bans.get_ip(ip).is_some())
     }
     
     /// Ban a user from a specific room by ID
-    pub fn ban_user_from_room_by_id(&self, user_id: u32, room_id: u32) -> Result<()> {
+    pub fn ban_user_from_room_by_id(&self, user_id: u32, room_id: &str) -> Result<()> {
         debug!("Banning user {} from room {}", user_id, room_id);
         let mut state = self.server_state.write();
-        let room_bans = state.room_bans.entry(room_id).or_insert_with(std::collections::HashSet::new);
+        let room_bans = state.room_bans.entry(room_id.to_string()).or_default();
         room_bans.insert(user_id);
         Ok(())
     }
     
     /// Unban a user from a specific room by ID
-    pub fn unban_user_from_room_by_id(&self, user_id: u32, room_id: u32) -> Result<()> {
+    pub fn unban_user_from_room_by_id(&self, user_id: u32, room_id: &str) -> Result<()> {
         debug!("Unbanning user {} from room {}", user_id, room_id);
         let mut state = self.server_state.write();
-        if let Some(room_bans) = state.room_bans.get_mut(&room_id) {
+        if let Some(room_bans) = state.room_bans.get_mut(room_id) {
             room_bans.remove(&user_id);
             if room_bans.is_empty() {
-                state.room_bans.remove(&room_id);
+                state.room_bans.remove(room_id);
             }
         }
         Ok(())
     }
     
     /// Ban a user from a specific room by IP
-    pub fn ban_user_from_room_by_ip(&self, ip: &str, room_id: u32) -> Result<()> {
+    pub fn ban_user_from_room_by_ip(&self, ip: &str, room_id: &str) -> Result<()> {
         debug!("Banning IP {} from room {}", ip, room_id);
         let mut state = self.server_state.write();
-        let room_ip_bans = state.room_ip_bans.entry(room_id).or_insert_with(std::collections::HashSet::new);
+        let room_ip_bans = state.room_ip_bans.entry(room_id.to_string()).or_default();
         room_ip_bans.insert(ip.to_string());
         Ok(())
     }
     
     /// Unban a user from a specific room by IP
-    pub fn unban_user_from_room_by_ip(&self, ip: &str, room_id: u32) -> Result<()> {
+    pub fn unban_user_from_room_by_ip(&self, ip: &str, room_id: &str) -> Result<()> {
         debug!("Unbanning IP {} from room {}", ip, room_id);
         let mut state = self.server_state.write();
-        if let Some(room_ip_bans) = state.room_ip_bans.get_mut(&room_id) {
+        if let Some(room_ip_bans) = state.room_ip_bans.get_mut(room_id) {
             room_ip_bans.remove(ip);
             if room_ip_bans.is_empty() {
-                state.room_ip_bans.remove(&room_id);
+                state.room_ip_bans.remove(room_id);
             }
         }
         Ok(())
     }
     
-    /// Check if a user is banned from a specific room
-    pub fn is_user_banned_from_room(&self, user_id: u32, room_id: u32) -> Result<bool> {
+    /// Check if an IP is banned from a specific room
+    pub fn is_ip_banned_from_room(&self, ip: &str, room_id: &str) -> Result<bool> {
+        let state = self.server_state.read();
+        Ok(state.room_ip_bans
+            .get(room_id)
+            .is_some_and(|bans| bans.contains(ip)))
+    }
+
+    /// Check if a user is banned from a specific room, by ID or by the IP they connected from
+    pub fn is_user_banned_from_room(&self, user_id: u32, room_id: &str) -> Result<bool> {
         let state = self.server_state.read();
         let banned_by_id = state.room_bans
-            .get(&room_id)
-            .map(|bans| bans.contains(&user_id))
-            .unwrap_or(false);
-        
-        // Check IP ban would require mapping user to IP
-        // For now, just check ID bans
-        Ok(banned_by_id)
+            .get(room_id)
+            .is_some_and(|bans| bans.contains(&user_id));
+        let banned_by_ip = state.online_users
+            .get(&user_id)
+            .and_then(|user| user.ip.as_ref())
+            .zip(state.room_ip_bans.get(room_id))
+            .is_some_and(|(ip, bans)| bans.contains(ip));
+        Ok(banned_by_id || banned_by_ip)
     }
     
     // ===== Room Management APIs =====
     
-    /// Create a room
+    /// Create an empty room admitting up to `max_users` players, returning its ID
+    ///
+    /// The room has no host until the first player joins. Fails if the server is at its
+    /// room limit.
     pub fn create_room(&self, max_users: u32) -> Result<u32> {
+        self.create_room_with_practice(max_users, None)
+    }
+
+    /// Like [`Self::create_room`], but a lone player of the room starts without the ready
+    /// handshake
+    pub fn create_practice_room(&self, max_users: u32) -> Result<u32> {
+        self.create_room_with_practice(max_users, Some(true))
+    }
+
+    fn create_room_with_practice(&self, max_users: u32, practice: Option<bool>) -> Result<u32> {
         debug!("Creating room with max users {}", max_users);
-        // TODO: Implement actual room creation
-        // For now, return a dummy ID
-        Ok(1)
+        if max_users == 0 {
+            return Err(Error::Api("Room must admit at least one user".to_string()));
+        }
+        let bridge = self.get_server_bridge()?;
+        let mut state = self.server_state.write();
+        // Skip IDs of rooms only known to plugins, such as ones created from templates
+        let first_id = Self::next_room_id(&state);
+        let room_id = bridge.create_room(first_id, max_users, practice)?;
+        state
+            .rooms
+            .insert(room_id.to_string(), RoomInfo::empty(room_id.to_string(), max_users));
+        info!("Created room {}", room_id);
+        Ok(room_id)
     }
     
     /// Disband a room
     pub fn disband_room(&self, room_id: u32) -> Result<()> {
         debug!("Disbanding room {}", room_id);
         let mut state = self.server_state.write();
-        state.rooms.remove(&room_id);
+        state.rooms.remove(&room_id.to_string());
         Ok(())
     }
     
+    /// Rename a room, returning the name actually set
+    ///
+    /// Control characters and surrounding whitespace are stripped. Room members are
+    /// notified on the `room_renamed` client notification channel.
+    pub fn set_room_name(&self, room_id: u32, name: &str) -> Result<String> {
+        let name = sanitize_room_name(name)?;
+        let name = name.as_str();
+        debug!("Renaming room {} to {}", room_id, name);
+        self.refresh_rooms();
+        let id = room_id.to_string();
+        if !self.server_state.read().rooms.contains_key(&id) {
+            return Err(Error::Api(format!("Room {} not found", room_id)));
+        }
+        if let Ok(bridge) = self.get_server_bridge() {
+            bridge.set_room_name(&id, name)?;
+        }
+        if let Some(room) = self.server_state.write().rooms.get_mut(&id) {
+            room.name = name.to_string();
+        }
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::ROOM_RENAME,
+            json!({ "room_id": room_id, "name": name }),
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit room rename event: {}", e);
+        }
+        Ok(name.to_string())
+    }
+
+    /// Export the settings of a room (name, capacity, lock, cycle mode, chart pool) as a
+    /// template for [`Self::create_room_from_template`]
+    pub fn export_room_template(&self, room_id: u32) -> Result<Value> {
+        self.refresh_rooms();
+        let state = self.server_state.read();
+        let room = state
+            .rooms
+            .get(&room_id.to_string())
+            .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+        let template = RoomTemplate {
+            version: ROOM_TEMPLATE_VERSION,
+            name: room.name.clone(),
+            max_users: room.max_users,
+            locked: room.locked,
+            cycle: room.cycle,
+            chart_pool: room
+                .chart_pool
+                .iter()
+                .map(|&(chart_id, weight)| RoomTemplateChart { chart_id, weight })
+                .collect(),
+        };
+        serde_json::to_value(template)
+            .map_err(|e| Error::Api(format!("Failed to serialize room template: {}", e)))
+    }
+
+    /// Create an empty room on the server with the settings of a template, returning its ID
+    ///
+    /// The template version must be [`ROOM_TEMPLATE_VERSION`]. The name and chart pool are
+    /// checked as by [`Self::set_room_name`] and [`Self::replace_chart_pool`] before the
+    /// room is created.
+    pub fn create_room_from_template(&self, template: &Value) -> Result<u32> {
+        let template: RoomTemplate = serde_json::from_value(template.clone())
+            .map_err(|e| Error::Api(format!("Invalid room template: {}", e)))?;
+        if template.version != ROOM_TEMPLATE_VERSION {
+            return Err(Error::Api(format!(
+                "Unsupported room template version {} (expected {})",
+                template.version, ROOM_TEMPLATE_VERSION
+            )));
+        }
+        if template.max_users == 0 {
+            return Err(Error::Api("Room template allows no users".to_string()));
+        }
+        let name = sanitize_room_name(&template.name)?;
+        let pool: Vec<_> = template
+            .chart_pool
+            .iter()
+            .map(|chart| (chart.chart_id, chart.weight))
+            .collect();
+        // Checked up front, as the server keeps an empty room until someone leaves it
+        let first_id = Self::next_room_id(&self.server_state.read());
+        self.check_chart_pool(&first_id.to_string(), 0, &pool)?;
+
+        let room_id = self.create_room(template.max_users)?;
+        self.set_room_name(room_id, &name)?;
+        if template.locked {
+            self.set_room_lock(room_id, true, None)?;
+        }
+        if template.cycle {
+            self.switch_room_to_cycle_mode(room_id)?;
+        }
+        if !pool.is_empty() {
+            self.replace_chart_pool(room_id, pool)?;
+        }
+        info!("Created room {} from template", room_id);
+        Ok(room_id)
+    }
+
+    /// Rename a room on behalf of a user, who must be its host
+    pub fn rename_room_as_host(&self, user_id: u32, room_id: u32, name: &str) -> Result<String> {
+        if self.get_room_host_id(room_id)? != user_id {
+            return Err(Error::Api(format!(
+                "User {} is not the host of room {}",
+                user_id, room_id
+            )));
+        }
+        self.set_room_name(room_id, name)
+    }
+
     /// Add a user to a room
     pub fn add_user_to_room(&self, user_id: u32, room_id: u32) -> Result<()> {
         debug!("Adding user {} to room {}", user_id, room_id);
@@ -423,17 +1591,29 @@ impl HostApi {
     
     /// Get room information
     pub fn get_room_info(&self, room_id: u32) -> Result<Value> {
+        self.refresh_rooms();
         let state = self.server_state.read();
-        if let Some(room) = state.rooms.get(&room_id) {
-            Ok(json!({
+        if let Some(room) = state.rooms.get(&room_id.to_string()) {
+            let (custom_data, truncated) = bounded_custom_data(&room.custom_data);
+            let mut info = json!({
                 "id": room.id,
                 "name": room.name,
                 "host_id": room.host_id,
                 "user_ids": room.user_ids,
                 "max_users": room.max_users,
                 "locked": room.locked,
+                "locked_by": room.lock.and_then(|lock| lock.user_id),
+                "locked_at": room.lock.map(|lock| {
+                    lock.locked_at
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                }),
                 "cycle": room.cycle,
                 "chart_id": room.chart_id,
+                "chart_pool": room.chart_pool.iter().map(|(chart_id, weight)| {
+                    json!({ "chart_id": chart_id, "weight": weight })
+                }).collect::<Vec<_>>(),
                 "state": match room.state {
                     RoomState::SelectingChart => "SELECTING_CHART",
                     RoomState::WaitingForReady => "WAITING_FOR_READY",
@@ -461,8 +1641,13 @@ impl HostApi {
                         }).collect::<Vec<_>>(),
                     })
                 }).collect::<Vec<_>>(),
-                "custom_data": room.custom_data,
-            }))
+                "custom_data": custom_data,
+                "custom_data_truncated": truncated,
+            });
+            if let Some(info) = info.as_object_mut() {
+                self.room_info_fields.merge_into(info, &room.custom_data);
+            }
+            Ok(info)
         } else {
             Err(Error::Api(format!("Room {} not found", room_id)))
         }
@@ -470,27 +1655,30 @@ impl HostApi {
     
     /// Get room user count
     pub fn get_room_user_count(&self, room_id: u32) -> Result<u32> {
+        self.refresh_rooms();
         let state = self.server_state.read();
         state.rooms
-            .get(&room_id)
+            .get(&room_id.to_string())
             .map(|room| room.user_ids.len() as u32)
             .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))
     }
     
     /// Get room user IDs
     pub fn get_room_user_ids(&self, room_id: u32) -> Result<Value> {
+        self.refresh_rooms();
         let state = self.server_state.read();
         state.rooms
-            .get(&room_id)
+            .get(&room_id.to_string())
             .map(|room| json!(room.user_ids))
             .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))
     }
     
     /// Get room host ID
     pub fn get_room_host_id(&self, room_id: u32) -> Result<u32> {
+        self.refresh_rooms();
         let state = self.server_state.read();
         state.rooms
-            .get(&room_id)
+            .get(&room_id.to_string())
             .map(|room| room.host_id)
             .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))
     }
@@ -499,7 +1687,7 @@ impl HostApi {
     pub fn set_room_max_users(&self, room_id: u32, max_users: u32) -> Result<()> {
         debug!("Setting room {} max users to {}", room_id, max_users);
         let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id.to_string()) {
             room.max_users = max_users;
             Ok(())
         } else {
@@ -511,7 +1699,7 @@ impl HostApi {
     pub fn start_room_preparation(&self, room_id: u32) -> Result<()> {
         debug!("Starting preparation for room {}", room_id);
         let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id.to_string()) {
             room.state = RoomState::WaitingForReady;
             Ok(())
         } else {
@@ -523,7 +1711,7 @@ impl HostApi {
     pub fn end_room_preparation(&self, room_id: u32) -> Result<()> {
         debug!("Ending preparation for room {}", room_id);
         let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id.to_string()) {
             room.state = RoomState::SelectingChart;
             Ok(())
         } else {
@@ -535,7 +1723,7 @@ impl HostApi {
     pub fn force_start_room_game(&self, room_id: u32) -> Result<()> {
         debug!("Force starting game in room {}", room_id);
         let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id.to_string()) {
             room.state = RoomState::Playing;
             Ok(())
         } else {
@@ -543,47 +1731,60 @@ impl HostApi {
         }
     }
     
-    /// Set room lock status
-    pub fn set_room_lock(&self, room_id: u32, locked: bool) -> Result<()> {
+    /// Set room lock status, recording `locked_by` as the user who locked it
+    pub fn set_room_lock(&self, room_id: u32, locked: bool, locked_by: Option<u32>) -> Result<()> {
         debug!("Setting room {} lock to {}", room_id, locked);
-        let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        self.refresh_rooms();
+        let id = room_id.to_string();
+        if !self.server_state.read().rooms.contains_key(&id) {
+            return Err(Error::Api(format!("Room {} not found", room_id)));
+        }
+        // The server's room holds the lock; the mirror is only written without one
+        if let Ok(bridge) = self.get_server_bridge() {
+            bridge.set_room_lock(&id, locked, locked_by)?;
+            self.refresh_rooms();
+        } else if let Some(room) = self.server_state.write().rooms.get_mut(&id) {
             room.locked = locked;
-            Ok(())
-        } else {
-            Err(Error::Api(format!("Room {} not found", room_id)))
+            room.lock = locked.then(|| RoomLock {
+                user_id: locked_by,
+                locked_at: std::time::SystemTime::now(),
+            });
         }
+        Ok(())
     }
     
     /// Switch room to normal mode
     pub fn switch_room_to_normal_mode(&self, room_id: u32) -> Result<()> {
         debug!("Switching room {} to normal mode", room_id);
-        let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
-            room.cycle = false;
-            Ok(())
-        } else {
-            Err(Error::Api(format!("Room {} not found", room_id)))
-        }
+        self.set_room_cycle(room_id, false)
     }
     
     /// Switch room to cycle mode
     pub fn switch_room_to_cycle_mode(&self, room_id: u32) -> Result<()> {
         debug!("Switching room {} to cycle mode", room_id);
-        let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
-            room.cycle = true;
-            Ok(())
-        } else {
-            Err(Error::Api(format!("Room {} not found", room_id)))
+        self.set_room_cycle(room_id, true)
+    }
+
+    fn set_room_cycle(&self, room_id: u32, cycle: bool) -> Result<()> {
+        self.refresh_rooms();
+        let id = room_id.to_string();
+        if !self.server_state.read().rooms.contains_key(&id) {
+            return Err(Error::Api(format!("Room {} not found", room_id)));
+        }
+        if let Ok(bridge) = self.get_server_bridge() {
+            bridge.set_room_cycle(&id, cycle)?;
         }
+        if let Some(room) = self.server_state.write().rooms.get_mut(&id) {
+            room.cycle = cycle;
+        }
+        Ok(())
     }
     
     /// Select room chart
-    pub fn select_room_chart(&self, room_id: u32, chart_id: u32) -> Result<()> {
+    pub fn select_room_chart(&self, room_id: u32, chart_id: phira_mp_common::ChartId) -> Result<()> {
         debug!("Selecting chart {} for room {}", chart_id, room_id);
         let mut state = self.server_state.write();
-        if let Some(room) = state.rooms.get_mut(&room_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id.to_string()) {
             room.chart_id = Some(chart_id);
             Ok(())
         } else {
@@ -591,44 +1792,310 @@ impl HostApi {
         }
     }
     
+    /// Replace the whole chart pool of a room, returning the new pool size
+    ///
+    /// Every entry is checked against the chart validators on behalf of the room host
+    /// and must have a positive weight and a distinct chart; if any entry is rejected
+    /// the previous pool is kept. Emits a `chart_pool_replace` event.
+    pub fn replace_chart_pool(
+        &self,
+        room_id: u32,
+        pool: Vec<(phira_mp_common::ChartId, u32)>,
+    ) -> Result<usize> {
+        let host_id = self.get_room_host_id(room_id)?;
+        self.check_chart_pool(&room_id.to_string(), host_id, &pool)?;
+
+        let size = pool.len();
+        debug!("Replacing chart pool of room {} with {} charts", room_id, size);
+        let id = room_id.to_string();
+        if let Ok(bridge) = self.get_server_bridge() {
+            bridge.set_chart_pool(&id, &pool)?;
+        }
+        if let Some(room) = self.server_state.write().rooms.get_mut(&id) {
+            room.chart_pool = pool;
+        }
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::CHART_POOL_REPLACE,
+            json!({ "room_id": room_id, "size": size }),
+        );
+        if let Err(e) = self.event_bus.emit(event) {
+            warn!("Failed to emit chart pool replace event: {}", e);
+        }
+        Ok(size)
+    }
+
+    fn check_chart_pool(
+        &self,
+        room_id: &str,
+        host_id: u32,
+        pool: &[(phira_mp_common::ChartId, u32)],
+    ) -> Result<()> {
+        let mut seen = std::collections::HashSet::new();
+        for &(chart_id, weight) in pool {
+            if weight == 0 {
+                return Err(Error::Api(format!("Chart {} has a zero weight", chart_id)));
+            }
+            if !seen.insert(chart_id) {
+                return Err(Error::Api(format!("Chart {} is listed twice", chart_id)));
+            }
+            if let crate::chart_validator::ChartSelectionDecision::Deny(reason) =
+                self.validate_chart_selection(room_id, host_id, chart_id)
+            {
+                return Err(Error::Api(format!("Chart {} is not allowed: {}", chart_id, reason)));
+            }
+        }
+        Ok(())
+    }
+
     // ===== Messaging APIs =====
     
-    /// Send message to a user
-    pub fn send_message_to_user(&self, user_id: u32, message: &str) -> Result<()> {
+    /// Send a chat message to a user from the server's configured message sender
+    ///
+    /// Fails if the user is not known to the server. A user who is reconnecting does not
+    /// receive the message. Emits a `message_delivered` event carrying the returned status.
+    pub fn send_message_to_user(&self, user_id: u32, message: &str) -> Result<DeliveryStatus> {
         debug!("Sending message to user {}: {}", user_id, message);
-        // TODO: Implement actual message sending
-        Ok(())
+        let status = self
+            .get_server_bridge()?
+            .send_message_to_user(user_id, message)
+            .ok_or_else(|| Error::Api("User not found".to_string()))?;
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::MESSAGE_DELIVERED,
+            json!({ "user_id": user_id, "status": status }),
+        );
+        if let Err(e) = self.emit_bounded(event) {
+            warn!("Failed to emit message delivery event: {}", e);
+        }
+        Ok(status)
     }
     
-    /// Broadcast message to all users
-    pub fn broadcast_message_to_all(&self, message: &str) -> Result<()> {
+    /// Send a chat message to all connected users, returning how many were reached
+    pub fn broadcast_message_to_all(&self, message: &str) -> Result<usize> {
         debug!("Broadcasting message to all: {}", message);
-        // TODO: Implement actual broadcasting
-        Ok(())
+        self.get_server_bridge()?.broadcast_message(None, message)
     }
     
-    /// Broadcast message to a room
-    pub fn broadcast_message_to_room(&self, room_id: u32, message: &str) -> Result<()> {
-        debug!("Broadcasting message to room {}: {}", room_id, message);
-        // TODO: Implement actual broadcasting
+    /// Broadcast an announcement with style hints to all online users
+    ///
+    /// Markup in the text is stripped, as styling is only conveyed through `style`.
+    /// Clients too old to render announcements receive the plain text as chat.
+    pub fn broadcast_announcement(
+        &self,
+        message: &str,
+        style: phira_mp_common::AnnouncementStyle,
+    ) -> Result<()> {
+        let content = phira_mp_common::sanitize_announcement(message);
+        if content.is_empty() {
+            return Err(Error::Api("Announcement must not be empty".to_string()));
+        }
+        debug!("Broadcasting announcement ({:?}): {}", style, content);
+        self.get_server_bridge()?.broadcast_announcement(&content, &style);
         Ok(())
     }
+
+    /// Send a chat message to the users and monitors of a room, returning how many were
+    /// reached
+    pub fn broadcast_message_to_room(&self, room_id: u32, message: &str) -> Result<usize> {
+        debug!("Broadcasting message to room {}: {}", room_id, message);
+        self.get_server_bridge()?
+            .broadcast_message(Some(&room_id.to_string()), message)
+    }
     
-    /// Broadcast message to all rooms
-    pub fn broadcast_message_to_all_rooms(&self, message: &str) -> Result<()> {
-        debug!("Broadcasting message to all rooms: {}", message);
-        // TODO: Implement actual broadcasting
+    /// Push a notification to game clients on `channel`
+    ///
+    /// Clients too old to understand plugin notifications are skipped.
+    pub fn push_client_notification(
+        &self,
+        target: NotificationTarget,
+        channel: &str,
+        payload: &Value,
+    ) -> Result<()> {
+        debug!("Pushing notification on {} to {:?}", channel, target);
+        let bridge = self.get_server_bridge()?;
+        bridge.push_client_notification(&target, channel, &serde_json::to_string(payload)?);
+        Ok(())
+    }
+
+    /// Announce an upcoming event, or clear it with `None`
+    ///
+    /// Online clients are notified on the `scheduled_announcement` channel right away and
+    /// clients connecting later receive it when authenticated, rendering a countdown.
+    pub fn set_scheduled_announcement(
+        &self,
+        announcement: Option<phira_mp_common::ScheduledAnnouncement>,
+    ) -> Result<()> {
+        let announcement = match announcement {
+            Some(mut announcement) => {
+                announcement.title = phira_mp_common::sanitize_announcement(&announcement.title);
+                if announcement.title.is_empty() {
+                    return Err(Error::Api("Announcement title must not be empty".to_string()));
+                }
+                Some(announcement)
+            }
+            None => None,
+        };
+        debug!("Setting scheduled announcement: {:?}", announcement);
+        self.get_server_bridge()?
+            .set_scheduled_announcement(announcement.as_ref());
+        Ok(())
+    }
+
+    /// Enable or disable keeping judge timelines of a room's rounds
+    pub fn set_judge_recording(&self, room_id: &str, enabled: bool) -> Result<()> {
+        self.get_server_bridge()?.set_judge_recording(room_id, enabled);
+        Ok(())
+    }
+
+    /// Remove all users from a room, notifying them, while keeping the room, its settings
+    /// and its host, who regains the role when rejoining
+    pub fn clear_room(&self, room_id: &str) -> Result<()> {
+        debug!("Clearing room {}", room_id);
+        self.get_server_bridge()?.clear_room(room_id);
+        Ok(())
+    }
+
+    /// Mark whether a room is kept when its last user leaves
+    pub fn set_room_persistent(&self, room_id: &str, persistent: bool) -> Result<()> {
+        debug!("Setting room {} persistent to {}", room_id, persistent);
+        self.get_server_bridge()?.set_room_persistent(room_id, persistent);
+        Ok(())
+    }
+
+    /// Mark whether a room is a lobby
+    ///
+    /// Lobbies are kept when their last user leaves and reset to the server's lobby defaults:
+    /// unlocked, renamed to their ID and without a chart.
+    pub fn set_room_lobby(&self, room_id: &str, lobby: bool) -> Result<()> {
+        debug!("Setting room {} lobby to {}", room_id, lobby);
+        self.get_server_bridge()?.set_room_lobby(room_id, lobby);
+        Ok(())
+    }
+
+    /// Grant a room member co-host permissions
+    ///
+    /// Co-hosts may select charts, lock the room and start games; other actions stay
+    /// reserved for the host.
+    pub fn add_room_cohost(&self, room_id: &str, user_id: u32) -> Result<()> {
+        debug!("Granting user {} co-host of room {}", user_id, room_id);
+        self.get_server_bridge()?.set_room_cohost(room_id, user_id, true);
+        Ok(())
+    }
+
+    /// Revoke co-host permissions of a room member
+    pub fn remove_room_cohost(&self, room_id: &str, user_id: u32) -> Result<()> {
+        debug!("Revoking co-host of room {} from user {}", room_id, user_id);
+        self.get_server_bridge()?.set_room_cohost(room_id, user_id, false);
+        Ok(())
+    }
+
+    /// Add a user to or remove a user from a chat channel of a room, e.g. to form teams
+    pub fn set_chat_channel_member(
+        &self,
+        room_id: &str,
+        channel: &str,
+        user_id: u32,
+        joined: bool,
+    ) -> Result<()> {
+        self.get_server_bridge()?
+            .set_chat_channel_member(room_id, channel, user_id, joined);
+        Ok(())
+    }
+
+    /// Send a chat message as a user to the members of a room's chat channel
+    ///
+    /// The channel `"all"` reaches everyone in the room.
+    pub fn send_channel_message(
+        &self,
+        room_id: &str,
+        channel: &str,
+        user_id: u32,
+        message: &str,
+    ) -> Result<()> {
+        debug!("Sending message to channel {} of room {}: {}", channel, room_id, message);
+        self.get_server_bridge()?
+            .send_channel_message(room_id, channel, user_id, message);
         Ok(())
     }
+
+    /// Get the judge timeline of a recorded round (rounds are numbered from 1)
+    pub fn get_game_judges(&self, room_id: &str, round: u32) -> Result<Value> {
+        let players = self
+            .get_server_bridge()?
+            .get_game_judges(room_id, round)
+            .ok_or_else(|| {
+                Error::Api(format!("No judges recorded for room {} round {}", room_id, round))
+            })?;
+        let players: serde_json::Map<String, Value> = players
+            .into_iter()
+            .map(|(player, judges)| {
+                let judges = judges
+                    .iter()
+                    .map(|judge| {
+                        json!({
+                            "time": judge.time,
+                            "line_id": judge.line_id,
+                            "note_id": judge.note_id,
+                            "judgement": format!("{:?}", judge.judgement),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                (player.to_string(), Value::Array(judges))
+            })
+            .collect();
+        Ok(json!({
+            "room_id": room_id,
+            "round": round,
+            "players": players,
+        }))
+    }
+
+    /// Send a chat message to the members of every room, returning how many were reached
+    ///
+    /// Rooms in a game are skipped unless `force` is set.
+    pub fn broadcast_message_to_all_rooms(&self, message: &str, force: bool) -> Result<usize> {
+        debug!("Broadcasting message to all rooms: {}", message);
+        self.get_server_bridge()?
+            .broadcast_message_to_rooms(message, force)
+    }
     
     // ===== Server Management APIs =====
     
-    /// Shutdown server
-    pub fn shutdown_server(&self) -> Result<()> {
-        info!("Plugin requested server shutdown");
-        // TODO: Implement actual shutdown
+    /// Shut the server down gracefully
+    ///
+    /// New connections are refused at once and connected users are warned. After `grace`
+    /// a `server_shutdown` event is emitted, so plugins can flush their state, and the
+    /// server exits.
+    pub fn shutdown_server(&self, grace: std::time::Duration) -> Result<()> {
+        info!("Plugin requested server shutdown in {}s", grace.as_secs());
+        self.get_server_bridge()?.shutdown(grace);
         Ok(())
     }
+
+    /// Notify plugins that the server is about to exit, then stop hot reload and unload
+    /// all plugins
+    ///
+    /// Returns once every asynchronous `server_shutdown` handler finished.
+    pub async fn on_server_shutdown(&self) {
+        if let Err(e) = self.flush_playtime() {
+            warn!("Failed to flush playtime: {}", e);
+        }
+        let event = crate::event_system::Event::system(
+            crate::event_system::predefined::SERVER_SHUTDOWN,
+            json!({}),
+        );
+        if let Err(e) = self.event_bus.emit_async(event).await {
+            warn!("Failed to emit server shutdown event: {}", e);
+        }
+        let hot_reload = self.hot_reload_manager.read().clone();
+        if let Some(manager) = hot_reload
+            && let Err(e) = manager.stop().await
+        {
+            warn!("Failed to stop hot reload: {}", e);
+        }
+        if let Ok(plugin_manager) = self.get_plugin_manager() {
+            plugin_manager.unload_all().await;
+        }
+    }
     
     /// Restart server
     pub fn restart_server(&self) -> Result<()> {
@@ -644,13 +2111,286 @@ impl HostApi {
         Ok(())
     }
     
-    /// Reload a specific plugin
+    /// Enable or disable a plugin, persisting the flag across restarts
+    ///
+    /// The flag is saved immediately; unloading a disabled plugin or loading an enabled one
+    /// happens in the background.
+    pub fn set_plugin_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let plugin_manager = self.get_plugin_manager()?;
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| Error::Api("No async runtime to apply the change".to_string()))?;
+        plugin_manager.persist_plugin_enabled(name, enabled)?;
+        info!("Plugin {} {}", name, if enabled { "enabled" } else { "disabled" });
+        let name = name.to_string();
+        handle.spawn(async move {
+            if let Err(e) = plugin_manager.apply_plugin_enabled(&name, enabled).await {
+                warn!("Failed to apply enabled state of plugin {}: {}", name, e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Restart the plugin system in the background without touching player sessions
+    ///
+    /// See [`crate::plugin_manager::PluginManager::restart`].
+    pub fn restart_plugin_system(&self) -> Result<()> {
+        let plugin_manager = self.get_plugin_manager()?;
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| Error::Api("No async runtime to restart plugins".to_string()))?;
+        info!("Restarting plugin system");
+        handle.spawn(async move {
+            if let Err(e) = plugin_manager.restart().await {
+                warn!("Failed to restart plugin system: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Reload a specific plugin in the background
+    ///
+    /// See [`crate::plugin_manager::PluginManager::reload_plugin`]. Fails at once if the
+    /// plugin is not loaded; failures of the reload itself are logged.
     pub fn reload_plugin(&self, name: &str) -> Result<()> {
+        let plugin_manager = self.get_plugin_manager()?;
+        if plugin_manager.get_plugin(name).is_none() {
+            return Err(Error::NotFound(name.to_string()));
+        }
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| Error::Api("No async runtime to reload the plugin".to_string()))?;
         info!("Plugin requested reload of plugin: {}", name);
-        // TODO: Implement plugin reloading
+        let name = name.to_string();
+        handle.spawn(async move {
+            if let Err(e) = plugin_manager.reload_plugin(&name).await {
+                warn!("Failed to reload plugin {}: {}", name, e);
+            }
+        });
         Ok(())
     }
     
+    /// Read a server setting, if it is in [`SERVER_SETTINGS`]
+    pub fn get_server_setting(&self, key: &str) -> Option<Value> {
+        if !SERVER_SETTINGS.contains(&key) {
+            warn!("Denied access to server setting {}", key);
+            return None;
+        }
+        self.get_server_bridge().ok()?.get_server_setting(key)
+    }
+
+    /// Get the canonical permission identifiers plugins may declare
+    pub fn get_known_permissions(&self) -> &'static [&'static str] {
+        crate::metadata::KNOWN_PERMISSIONS
+    }
+
+    /// Connect the hot reload manager
+    pub fn set_hot_reload_manager(&self, manager: Arc<crate::hot_reload::HotReloadManager>) {
+        *self.hot_reload_manager.write() = Some(manager);
+    }
+
+    /// Enable or disable hot reload for a plugin
+    pub fn set_hot_reload_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let manager = self
+            .hot_reload_manager
+            .read()
+            .clone()
+            .ok_or_else(|| Error::Api("Hot reload is not available".to_string()))?;
+        manager.set_plugin_enabled(name, enabled)
+    }
+
+    /// Connect the health monitor, tracking plugin event handlers and commands in its
+    /// metrics collector
+    pub fn set_health_monitor(&self, monitor: Arc<crate::monitoring::HealthMonitor>) {
+        let metrics = monitor.metrics_collector();
+        self.event_bus.set_metrics_collector(Arc::clone(metrics));
+        self.command_registry.set_metrics_collector(Arc::clone(metrics));
+        *self.health_monitor.write() = Some(monitor);
+    }
+
+    /// Get the metrics collector of the health monitor, if connected
+    pub fn metrics_collector(&self) -> Option<Arc<crate::monitoring::MetricsCollector>> {
+        self.health_monitor
+            .read()
+            .as_ref()
+            .map(|monitor| Arc::clone(monitor.metrics_collector()))
+    }
+
+    /// Register a health probe, run on every health check and folded into the plugin's status
+    pub fn register_health_probe(
+        &self,
+        plugin_name: &str,
+        probe: crate::monitoring::HealthProbe,
+    ) -> Result<()> {
+        let monitor = self
+            .health_monitor
+            .read()
+            .clone()
+            .ok_or_else(|| Error::Api("Health monitoring is not available".to_string()))?;
+        monitor.register_probe(plugin_name, probe);
+        Ok(())
+    }
+
+    /// Unregister all health probes from a plugin
+    pub fn unregister_health_probes(&self, plugin_name: &str) {
+        if let Some(monitor) = self.health_monitor.read().as_ref() {
+            monitor.unregister_probes(plugin_name);
+        }
+    }
+
+    /// Pause event dispatch to plugins (admin only, not exposed to plugins)
+    pub fn pause_events(&self) {
+        info!("Pausing plugin event dispatch");
+        self.event_bus.pause();
+    }
+
+    /// Resume event dispatch, returning the number of buffered events delivered (admin only)
+    pub fn resume_events(&self) -> usize {
+        let count = self.event_bus.resume();
+        info!("Resumed plugin event dispatch, delivered {} buffered events", count);
+        count
+    }
+
+    /// Receive plugin lifecycle and reload events on a dedicated queue that broadcast lag
+    /// cannot drop events from (admin only, not exposed to plugins)
+    ///
+    /// See [`crate::event_system::EventBus::subscribe_lifecycle`].
+    pub fn subscribe_lifecycle_events(
+        &self,
+        listener: &str,
+        capacity: usize,
+    ) -> tokio::sync::mpsc::Receiver<Arc<crate::event_system::Event>> {
+        self.event_bus.subscribe_lifecycle(listener, capacity)
+    }
+
+    /// Get event bus statistics, including dropped event counts
+    pub fn get_event_bus_stats(&self) -> Value {
+        let stats = self.event_bus.stats();
+        json!({
+            "event_types": stats.total_event_types,
+            "subscriptions": stats.total_subscriptions,
+            "broadcast_receivers": stats.broadcast_receivers,
+            "paused": self.event_bus.is_paused(),
+            "dropped_handler_calls": stats.dropped_handler_calls,
+            "dropped_paused_events": stats.dropped_paused_events,
+            "lifecycle_listeners": stats.lifecycle_listeners,
+            "dropped_lifecycle_events": stats.dropped_lifecycle_events,
+        })
+    }
+
+    /// Run a quick check of the plugin system wiring and core subsystems (admin only)
+    ///
+    /// Every check cleans up after itself, so this is safe to run on a live server.
+    pub fn self_test(&self) -> Vec<SelfTestResult> {
+        const NAME: &str = "__selftest";
+
+        fn result(subsystem: &'static str, res: Result<()>) -> SelfTestResult {
+            SelfTestResult {
+                subsystem,
+                passed: res.is_ok(),
+                detail: res.err().map(|e| e.to_string()).unwrap_or_default(),
+            }
+        }
+
+        let plugin_manager = self.get_plugin_manager().map(|_| ());
+
+        let events = (|| {
+            let received = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let flag = Arc::clone(&received);
+            self.event_bus.subscribe(
+                NAME,
+                Box::new(move |_| {
+                    flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                }),
+                NAME,
+            )?;
+            let emitted = self
+                .event_bus
+                .emit(crate::event_system::Event::system(NAME, json!(null)));
+            self.event_bus.unsubscribe(NAME, NAME)?;
+            emitted?;
+            if !received.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(Error::Api(if self.event_bus.is_paused() {
+                    "Event dispatch is paused".to_string()
+                } else {
+                    "Test event was not delivered".to_string()
+                }));
+            }
+            Ok(())
+        })();
+
+        let commands = (|| {
+            self.register_command(
+                NAME,
+                "Self-test command",
+                Box::new(|_, _| Ok("ok".to_string())),
+                NAME,
+            )?;
+            let output = self.command_registry.execute(NAME, None);
+            self.unregister_command(NAME)?;
+            if output? != "ok" {
+                return Err(Error::Command("Unexpected test command output".to_string()));
+            }
+            if self.command_registry.get_command(NAME).is_some() {
+                return Err(Error::Command("Test command is still registered".to_string()));
+            }
+            Ok(())
+        })();
+
+        let kv = (|| {
+            let mut config = crate::config::PluginConfig::new();
+            config.set(NAME, "ok")?;
+            match config.get::<String>(NAME).as_deref() {
+                Some("ok") => Ok(()),
+                _ => Err(Error::Config("Test value was not read back".to_string())),
+            }
+        })();
+
+        let results = vec![
+            result("plugin_manager", plugin_manager),
+            result("events", events),
+            result("commands", commands),
+            result("kv", kv),
+        ];
+        info!(
+            "Self-test finished, {}/{} checks passed",
+            results.iter().filter(|it| it.passed).count(),
+            results.len()
+        );
+        results
+    }
+
+    /// Get the plugin sandbox manager
+    pub fn sandbox_manager(&self) -> &Arc<crate::sandbox::SandboxManager> {
+        &self.sandbox_manager
+    }
+
+    /// Get the sandbox of a plugin
+    fn get_sandbox(&self, name: &str) -> Result<Arc<crate::sandbox::Sandbox>> {
+        self.sandbox_manager
+            .get_sandbox(name)
+            .ok_or_else(|| Error::NotFound(name.to_string()))
+    }
+
+    /// Get the resource limits of a plugin (admin only, not exposed to plugins)
+    pub fn get_sandbox_limits(&self, name: &str) -> Result<crate::sandbox::ResourceLimits> {
+        Ok(self.get_sandbox(name)?.get_resource_limits())
+    }
+
+    /// Get the resource usage of a plugin (admin only, not exposed to plugins)
+    pub fn get_sandbox_usage(&self, name: &str) -> Result<crate::sandbox::ResourceUsage> {
+        Ok(self.get_sandbox(name)?.get_resource_usage())
+    }
+
+    /// Replace the resource limits of a plugin (admin only, not exposed to plugins)
+    pub fn set_sandbox_limits(
+        &self,
+        name: &str,
+        limits: crate::sandbox::ResourceLimits,
+    ) -> Result<()> {
+        info!("Updating sandbox limits of plugin {}: {:?}", name, limits);
+        self.get_sandbox(name)?.set_resource_limits(limits);
+        Ok(())
+    }
+
     /// Get plugin list
     pub fn get_plugin_list(&self) -> Result<Value> {
         let plugin_manager = self.get_plugin_manager()?;
@@ -681,7 +2421,7 @@ impl HostApi {
     /// Get playtime total leaderboard
     pub fn get_playtime_total_leaderboard(&self) -> Result<Value> {
         // Same as get_playtime_leaderboard for now
-        self.get_playtime_leaderboard(100)
+        self.get_playtime_leaderboard(100, None)
     }
     
     /// Get online user count
@@ -692,6 +2432,7 @@ impl HostApi {
     
     /// Get available room count
     pub fn get_available_room_count(&self) -> Result<u32> {
+        self.refresh_rooms();
         let state = self.server_state.read();
         let available_rooms = state.rooms.values()
             .filter(|room| !room.locked && room.user_ids.len() < room.max_users as usize)
@@ -699,12 +2440,64 @@ impl HostApi {
         Ok(available_rooms as u32)
     }
     
-    /// Get room list
+    /// Rooms sorted by ID, so that listings are stable between calls
+    ///
+    /// Numeric IDs come first in numeric order, then the others in lexicographic order.
+    fn sorted_rooms(state: &ServerState) -> Vec<&RoomInfo> {
+        let mut rooms: Vec<&RoomInfo> = state.rooms.values().collect();
+        rooms.sort_unstable_by(|a, b| {
+            let key = |room: &RoomInfo| (room.id.parse::<u32>().map_err(|_| ()), room.id.clone());
+            key(a).cmp(&key(b))
+        });
+        rooms
+    }
+
+    /// Lowest numeric room ID above those of all known rooms
+    fn next_room_id(state: &ServerState) -> u32 {
+        state
+            .rooms
+            .keys()
+            .filter_map(|id| id.parse::<u32>().ok())
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    /// Bring the room mirror up to date with the rooms of the connected server
+    ///
+    /// Fields the server keeps are replaced and rooms only the server knows are added;
+    /// plugin data such as custom data and rounds is kept. Without a server, or while its
+    /// room list is busy, the mirror is left as is.
+    fn refresh_rooms(&self) {
+        let Ok(bridge) = self.get_server_bridge() else {
+            return;
+        };
+        let live = match bridge.get_rooms() {
+            Ok(live) => live,
+            Err(e) => {
+                debug!("Keeping room mirror: {}", e);
+                return;
+            }
+        };
+        let mut state = self.server_state.write();
+        state
+            .rooms
+            .retain(|id, _| live.iter().any(|room| room.id == *id));
+        for room in live {
+            state
+                .rooms
+                .entry(room.id.clone())
+                .or_insert_with(|| RoomInfo::empty(room.id.clone(), room.max_users))
+                .update(room);
+        }
+    }
+
+    /// Get room list, sorted by room ID
     pub fn get_room_list(&self) -> Result<Value> {
+        self.refresh_rooms();
         let state = self.server_state.read();
-        let room_list: Vec<Value> = state.rooms.values()
+        let room_list: Vec<Value> = Self::sorted_rooms(&state).into_iter()
             .map(|room| {
-                json!({
+                let mut entry = json!({
                     "id": room.id,
                     "name": room.name,
                     "host_id": room.host_id,
@@ -717,20 +2510,25 @@ impl HostApi {
                         RoomState::WaitingForReady => "WAITING_FOR_READY",
                         RoomState::Playing => "PLAYING",
                     },
-                })
+                });
+                if let Some(entry) = entry.as_object_mut() {
+                    self.room_info_fields.merge_into(entry, &room.custom_data);
+                }
+                entry
             })
             .collect();
         
         Ok(json!(room_list))
     }
     
-    /// Get available room list
+    /// Get available room list, sorted by room ID
     pub fn get_available_room_list(&self) -> Result<Value> {
+        self.refresh_rooms();
         let state = self.server_state.read();
-        let available_rooms: Vec<Value> = state.rooms.values()
+        let available_rooms: Vec<Value> = Self::sorted_rooms(&state).into_iter()
             .filter(|room| !room.locked && room.user_ids.len() < room.max_users as usize)
             .map(|room| {
-                json!({
+                let mut entry = json!({
                     "id": room.id,
                     "name": room.name,
                     "host_id": room.host_id,
@@ -742,45 +2540,135 @@ impl HostApi {
                         RoomState::WaitingForReady => "WAITING_FOR_READY",
                         RoomState::Playing => "PLAYING",
                     },
-                })
+                });
+                if let Some(entry) = entry.as_object_mut() {
+                    self.room_info_fields.merge_into(entry, &room.custom_data);
+                }
+                entry
             })
             .collect();
         
         Ok(json!(available_rooms))
     }
     
-    /// Get online user IDs
+    /// Charts of rooms waiting for ready or playing, sorted by room ID
+    ///
+    /// Each entry holds the chart ID, the room ID and the number of players in the room.
+    pub fn get_active_charts(&self) -> Result<Value> {
+        self.refresh_rooms();
+        let state = self.server_state.read();
+        let active_charts: Vec<Value> = Self::sorted_rooms(&state).into_iter()
+            .filter(|room| matches!(room.state, RoomState::WaitingForReady | RoomState::Playing))
+            .filter_map(|room| {
+                Some(json!({
+                    "chart_id": room.chart_id?,
+                    "room_id": room.id,
+                    "player_count": room.user_ids.len(),
+                }))
+            })
+            .collect();
+
+        Ok(json!(active_charts))
+    }
+
+    /// Get online user IDs in ascending order
     pub fn get_online_user_ids(&self) -> Result<Value> {
         let state = self.server_state.read();
-        let user_ids: Vec<u32> = state.online_users.keys().copied().collect();
+        let mut user_ids: Vec<u32> = state.online_users.keys().copied().collect();
+        user_ids.sort_unstable();
         Ok(json!(user_ids))
     }
     
     // ===== Registration APIs =====
     
-    /// Register HTTP route
-    pub fn register_http_route(&self, method: &str, path: &str) -> Result<()> {
-        debug!("Registering HTTP route {} {}", method, path);
-        // TODO: Implement HTTP route registration
-        Ok(())
-    }
-    
-    /// Register room info field
-    pub fn register_room_info_field(&self, name: &str, field_type: &str) -> Result<()> {
-        debug!("Registering room info field {}: {}", name, field_type);
-        // TODO: Implement room info field registration
-        Ok(())
+    /// Register an HTTP route served by the server's HTTP listener
+    ///
+    /// The path is matched exactly. Registering a method and path another plugin
+    /// already serves fails with `Error::Api`.
+    pub fn register_http_route(
+        &self,
+        plugin_name: &str,
+        method: &str,
+        path: &str,
+        handler: crate::http_routes::HttpRouteHandler,
+    ) -> Result<()> {
+        self.http_routes.register(plugin_name, method, path, handler)
     }
-    
-    /// Register user info field
-    pub fn register_user_info_field(&self, name: &str, field_type: &str) -> Result<()> {
-        debug!("Registering user info field {}: {}", name, field_type);
-        // TODO: Implement user info field registration
-        Ok(())
+
+    /// Unregister all HTTP routes of a plugin
+    pub fn unregister_http_routes(&self, plugin_name: &str) {
+        self.http_routes.unregister_all_from_plugin(plugin_name);
     }
-    
-    // ===== Configuration APIs =====
-    
+
+    /// Answer an HTTP request with the plugin route serving it, or `None` if there is none
+    pub fn handle_http_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Value,
+    ) -> Option<Result<Value>> {
+        self.http_routes.handle(method, path, body)
+    }
+    
+    /// Register a field added to room info and room lists from the room's custom data
+    ///
+    /// `field_type` is one of `string`, `number`, `integer`, `boolean`, `array` or
+    /// `object`; values of another type are left out.
+    pub fn register_room_info_field(
+        &self,
+        plugin_name: &str,
+        name: &str,
+        field_type: &str,
+    ) -> Result<()> {
+        self.room_info_fields.register(plugin_name, name, field_type)
+    }
+
+    /// Register a field added to user info from the user's custom data, see
+    /// [`HostApi::register_room_info_field`]
+    pub fn register_user_info_field(
+        &self,
+        plugin_name: &str,
+        name: &str,
+        field_type: &str,
+    ) -> Result<()> {
+        self.user_info_fields.register(plugin_name, name, field_type)
+    }
+
+    /// Set a custom data entry of a room, kept while the room exists
+    ///
+    /// Entries named after a registered room info field appear in room info and room lists.
+    pub fn set_room_custom_data(&self, room_id: u32, key: &str, value: Value) -> Result<()> {
+        self.refresh_rooms();
+        let mut state = self.server_state.write();
+        let room = state
+            .rooms
+            .get_mut(&room_id.to_string())
+            .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+        room.custom_data.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Set a custom data entry of an online user, kept until the user disconnects
+    ///
+    /// Entries named after a registered user info field appear in user info.
+    pub fn set_user_custom_data(&self, user_id: u32, key: &str, value: Value) -> Result<()> {
+        let mut state = self.server_state.write();
+        let user = state
+            .online_users
+            .get_mut(&user_id)
+            .ok_or_else(|| Error::Api(format!("User {} not found", user_id)))?;
+        user.custom_data.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Unregister all room and user info fields of a plugin
+    pub fn unregister_info_fields(&self, plugin_name: &str) {
+        self.room_info_fields.unregister_all_from_plugin(plugin_name);
+        self.user_info_fields.unregister_all_from_plugin(plugin_name);
+    }
+    
+    // ===== Configuration APIs =====
+    
     /// Get plugin configuration
     pub fn get_config(&self, plugin_name: &str, key: &str) -> Result<Option<Value>> {
         let plugin_manager = self.get_plugin_manager()?;
@@ -820,28 +2708,43 @@ impl HostApi {
     
     // ===== Memory Management APIs =====
     
-    /// Allocate memory (dummy implementation for now)
-    pub fn allocate_memory(&self, _size: u32) -> Result<u32> {
-        // TODO: Implement actual memory allocation
-        Ok(0)
+    /// Run `f` on the linear memory of a plugin's instance
+    fn with_guest_memory<T>(
+        &self,
+        plugin_name: &str,
+        f: impl FnOnce(&mut crate::wasm_runtime::GuestMemory) -> Result<T>,
+    ) -> Result<T> {
+        let plugin_manager = self.get_plugin_manager()?;
+        let plugin = plugin_manager
+            .get_plugin(plugin_name)
+            .ok_or_else(|| Error::Api(format!("Plugin {} not found", plugin_name)))?;
+        let mut plugin = plugin.write();
+        let instance = plugin.instance.as_mut().ok_or_else(|| {
+            Error::Runtime(format!("Plugin {} is not instantiated", plugin_name))
+        })?;
+        f(instance.memory()?)
     }
 
-    /// Free memory (dummy implementation for now)
-    pub fn free_memory(&self, _ptr: u32) -> Result<()> {
-        // TODO: Implement actual memory freeing
-        Ok(())
+    /// Allocate guest memory with the plugin's exported `alloc`, returning the pointer
+    pub fn allocate_memory(&self, plugin_name: &str, size: u32) -> Result<u32> {
+        self.with_guest_memory(plugin_name, |memory| memory.allocate(size))
     }
 
-    /// Read memory (dummy implementation for now)
-    pub fn read_memory(&self, _ptr: u32, _size: u32) -> Result<String> {
-        // TODO: Implement actual memory reading
-        Ok(String::new())
+    /// Free guest memory with the plugin's exported `dealloc`
+    pub fn free_memory(&self, plugin_name: &str, ptr: u32, size: u32) -> Result<()> {
+        self.with_guest_memory(plugin_name, |memory| memory.free(ptr, size))
     }
 
-    /// Write memory (dummy implementation for now)
-    pub fn write_memory(&self, _ptr: u32, _data: &str) -> Result<()> {
-        // TODO: Implement actual memory writing
-        Ok(())
+    /// Read a UTF-8 string of `size` bytes at `ptr` from guest memory
+    pub fn read_memory(&self, plugin_name: &str, ptr: u32, size: u32) -> Result<String> {
+        let bytes = self.with_guest_memory(plugin_name, |memory| memory.read(ptr, size))?;
+        String::from_utf8(bytes)
+            .map_err(|e| Error::Runtime(format!("Guest string at {:#x} is not UTF-8: {}", ptr, e)))
+    }
+
+    /// Write a string at `ptr` into guest memory
+    pub fn write_memory(&self, plugin_name: &str, ptr: u32, data: &str) -> Result<()> {
+        self.with_guest_memory(plugin_name, |memory| memory.write(ptr, data.as_bytes()))
     }
 }
 
@@ -850,4 +2753,1334 @@ impl Drop for HostApi {
     fn drop(&mut self) {
         info!("Host API shutting down");
     }
-}
\ No newline at end of file
+}
+/// Copy custom data for a query response, omitting entries that are too deeply nested
+/// or would exceed the size budget. Returns whether any entry was omitted.
+fn bounded_custom_data(data: &std::collections::HashMap<String, Value>) -> (Value, bool) {
+    fn depth(value: &Value) -> usize {
+        match value {
+            Value::Array(items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+            Value::Object(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    let mut keys: Vec<_> = data.keys().collect();
+    keys.sort();
+    let mut result = serde_json::Map::new();
+    let mut size = 0;
+    let mut truncated = false;
+    for key in keys {
+        let value = &data[key];
+        if depth(value) > MAX_CUSTOM_DATA_DEPTH {
+            warn!("Omitting custom data '{}': nested too deeply", key);
+            truncated = true;
+            continue;
+        }
+        let entry_size = key.len() + serde_json::to_vec(value).map_or(usize::MAX, |it| it.len());
+        if entry_size > MAX_CUSTOM_DATA_SIZE - size {
+            warn!("Omitting custom data '{}': {} bytes over budget", key, entry_size);
+            truncated = true;
+            continue;
+        }
+        size += entry_size;
+        result.insert(key.clone(), value.clone());
+    }
+    (Value::Object(result), truncated)
+}
+
+/// Describe a ban for query responses, times in seconds since epoch
+fn ban_info(entry: &crate::ban_store::BanEntry, now: std::time::SystemTime) -> Value {
+    let epoch_secs = |at: std::time::SystemTime| {
+        at.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
+    };
+    json!({
+        "reason": entry.reason,
+        "banned_at": epoch_secs(entry.banned_at),
+        "expires_at": entry.expires_at.map(epoch_secs),
+        "remaining_secs": entry.remaining(now).map(|it| it.as_secs()),
+    })
+}
+
+/// Strip control characters and surrounding whitespace from a room name and check its length
+fn sanitize_room_name(name: &str) -> Result<String> {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::Api("Room name must not be empty".to_string()));
+    }
+    if name.chars().count() > MAX_ROOM_NAME_LENGTH {
+        return Err(Error::Api(format!(
+            "Room name must be at most {} characters",
+            MAX_ROOM_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Parse an HTTP(S) URL whose host the sandbox allows
+fn check_http_url(sandbox: &crate::sandbox::Sandbox, url: &str) -> Result<reqwest::Url> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|e| Error::Api(format!("Invalid URL {}: {}", url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::Api(format!("Unsupported URL scheme: {}", url.scheme())));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Api(format!("URL has no host: {}", url)))?;
+    sandbox.check_network_access(host)?;
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command_system::{CommandContext, CommandRegistry},
+        event_system::EventBus,
+    };
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct RecordingBridge {
+        notifications: Mutex<Vec<(NotificationTarget, String, String)>>,
+        announcements: Mutex<Vec<(String, phira_mp_common::AnnouncementStyle)>>,
+        scheduled: Mutex<Vec<Option<phira_mp_common::ScheduledAnnouncement>>>,
+        messages: Mutex<Vec<(u32, String)>>,
+        rooms: Mutex<Vec<LiveRoom>>,
+    }
+
+    fn live_room(id: &str, host_id: u32) -> LiveRoom {
+        LiveRoom {
+            id: id.to_string(),
+            name: id.to_string(),
+            host_id,
+            user_ids: if host_id == 0 { Vec::new() } else { vec![host_id] },
+            max_users: 8,
+            locked: false,
+            lock: None,
+            cycle: false,
+            chart_id: None,
+            chart_pool: Vec::new(),
+            state: RoomState::SelectingChart,
+            playing_user_ids: Vec::new(),
+        }
+    }
+
+    impl ServerBridge for RecordingBridge {
+        fn push_client_notification(&self, target: &NotificationTarget, channel: &str, data: &str) {
+            self.notifications
+                .lock()
+                .push((target.clone(), channel.to_string(), data.to_string()));
+        }
+
+        fn broadcast_announcement(&self, content: &str, style: &phira_mp_common::AnnouncementStyle) {
+            self.announcements
+                .lock()
+                .push((content.to_string(), style.clone()));
+        }
+
+        fn set_judge_recording(&self, _room_id: &str, _enabled: bool) {}
+
+        fn get_game_judges(
+            &self,
+            _room_id: &str,
+            _round: u32,
+        ) -> Option<std::collections::HashMap<u32, Vec<phira_mp_common::JudgeEvent>>> {
+            None
+        }
+
+        fn clear_room(&self, _room_id: &str) {}
+
+        fn set_room_persistent(&self, _room_id: &str, _persistent: bool) {}
+        fn set_room_lobby(&self, _room_id: &str, _lobby: bool) {}
+
+        fn set_room_name(&self, room_id: &str, name: &str) -> Result<()> {
+            let mut rooms = self.rooms.lock();
+            let room = rooms
+                .iter_mut()
+                .find(|room| room.id == room_id)
+                .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+            room.name = name.to_string();
+            Ok(())
+        }
+
+        fn set_room_lock(&self, room_id: &str, locked: bool, locked_by: Option<u32>) -> Result<()> {
+            let mut rooms = self.rooms.lock();
+            let room = rooms
+                .iter_mut()
+                .find(|room| room.id == room_id)
+                .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+            room.locked = locked;
+            room.lock = locked.then(|| RoomLock {
+                user_id: locked_by,
+                locked_at: std::time::SystemTime::now(),
+            });
+            Ok(())
+        }
+
+        fn set_room_cycle(&self, room_id: &str, cycle: bool) -> Result<()> {
+            let mut rooms = self.rooms.lock();
+            let room = rooms
+                .iter_mut()
+                .find(|room| room.id == room_id)
+                .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+            room.cycle = cycle;
+            Ok(())
+        }
+
+        fn set_chart_pool(&self, room_id: &str, pool: &[(phira_mp_common::ChartId, u32)]) -> Result<()> {
+            let mut rooms = self.rooms.lock();
+            let room = rooms
+                .iter_mut()
+                .find(|room| room.id == room_id)
+                .ok_or_else(|| Error::Api(format!("Room {} not found", room_id)))?;
+            room.chart_pool = pool.to_vec();
+            Ok(())
+        }
+
+        fn set_room_cohost(&self, _room_id: &str, _user_id: u32, _cohost: bool) {}
+
+        fn set_chat_channel_member(&self, _room_id: &str, _channel: &str, _user_id: u32, _joined: bool) {}
+
+        fn send_channel_message(&self, _room_id: &str, _channel: &str, _user_id: u32, _content: &str) {}
+
+        fn get_server_setting(&self, key: &str) -> Option<Value> {
+            Some(json!(format!("{} value", key)))
+        }
+
+        fn get_user_latency(&self, user_id: u32) -> Option<std::time::Duration> {
+            (user_id == 1).then(|| std::time::Duration::from_millis(42))
+        }
+
+        fn get_session_info(&self, user_id: u32) -> Option<Value> {
+            (user_id == 1).then(|| json!({ "user_id": 1, "session_id": "session", "monitor": false }))
+        }
+
+        fn set_frame_trace(&self, user_id: u32, _enabled: bool) -> bool {
+            user_id == 1
+        }
+        fn kick_user(&self, user_id: u32) -> bool {
+            user_id == 1
+        }
+
+        fn create_room(&self, first_id: u32, max_users: u32, _practice: Option<bool>) -> Result<u32> {
+            self.rooms.lock().push(LiveRoom {
+                max_users,
+                ..live_room(&first_id.to_string(), 0)
+            });
+            Ok(first_id)
+        }
+
+        fn get_rooms(&self) -> Result<Vec<LiveRoom>> {
+            Ok(self.rooms.lock().clone())
+        }
+
+        fn send_message_to_user(&self, user_id: u32, content: &str) -> Option<DeliveryStatus> {
+            match user_id {
+                1 => {
+                    self.messages.lock().push((user_id, content.to_string()));
+                    Some(DeliveryStatus::Delivered)
+                }
+                2 => Some(DeliveryStatus::UserOffline),
+                _ => None,
+            }
+        }
+
+        fn broadcast_message(&self, _room_id: Option<&str>, _content: &str) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn broadcast_message_to_rooms(&self, _content: &str, _include_playing: bool) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn shutdown(&self, _grace: std::time::Duration) {}
+
+        fn set_scheduled_announcement(
+            &self,
+            announcement: Option<&phira_mp_common::ScheduledAnnouncement>,
+        ) {
+            self.scheduled.lock().push(announcement.cloned());
+        }
+
+        fn get_server_stats(&self, window: std::time::Duration) -> Vec<Value> {
+            let hours = window.as_secs() / 3600;
+            (0..hours.min(2))
+                .map(|hour| json!({
+                    "start": hour * 3600,
+                    "peak_users": 3 + hour,
+                    "rooms_created": 2,
+                    "games_completed": 1,
+                }))
+                .collect()
+        }
+    }
+
+    fn host_api() -> HostApi {
+        HostApi::new_with_weak(
+            Arc::new(EventBus::new()),
+            Arc::new(CommandRegistry::new()),
+            Weak::new(),
+        )
+    }
+
+    /// Put a room hosted by `host_id` into the room mirror
+    fn insert_room(api: &HostApi, id: &str, host_id: u32) {
+        api.server_state.write().rooms.insert(
+            id.to_string(),
+            RoomInfo {
+                host_id,
+                user_ids: vec![host_id],
+                ..RoomInfo::empty(id.to_string(), 8)
+            },
+        );
+    }
+
+    #[test]
+    fn test_push_client_notification() {
+        let api = host_api();
+        assert!(api
+            .push_client_notification(NotificationTarget::All, "test", &json!(null))
+            .is_err());
+
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        api.push_client_notification(NotificationTarget::User(7), "toast", &json!({ "text": "hi" }))
+            .unwrap();
+        api.push_client_notification(NotificationTarget::Room("room".to_string()), "toast", &json!(1))
+            .unwrap();
+
+        let notifications = bridge.notifications.lock();
+        assert_eq!(
+            *notifications,
+            vec![
+                (NotificationTarget::User(7), "toast".to_string(), r#"{"text":"hi"}"#.to_string()),
+                (NotificationTarget::Room("room".to_string()), "toast".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oversized_custom_data() {
+        let api = host_api();
+        let mut nested = json!(1);
+        for _ in 0..=MAX_CUSTOM_DATA_DEPTH {
+            nested = json!([nested]);
+        }
+        let custom_data = std::collections::HashMap::from([
+            ("small".to_string(), json!({ "level": 3 })),
+            ("huge".to_string(), json!("x".repeat(MAX_CUSTOM_DATA_SIZE))),
+            ("nested".to_string(), nested),
+        ]);
+        api.server_state.write().online_users.insert(
+            1,
+            UserInfo {
+                id: 1,
+                name: "user".to_string(),
+                language: "en-US".to_string(),
+                session_id: uuid::Uuid::nil(),
+                ip: None,
+                custom_data,
+            },
+        );
+
+        let info = api.get_user_info(1).unwrap();
+        assert_eq!(info["custom_data"], json!({ "small": { "level": 3 } }));
+        assert_eq!(info["custom_data_truncated"], json!(true));
+        assert!(serde_json::to_vec(&info).unwrap().len() < MAX_CUSTOM_DATA_SIZE);
+
+        api.server_state.write().online_users.get_mut(&1).unwrap().custom_data.remove("huge");
+        api.server_state.write().online_users.get_mut(&1).unwrap().custom_data.remove("nested");
+        assert_eq!(api.get_user_info(1).unwrap()["custom_data_truncated"], json!(false));
+        assert_eq!(api.get_user_info(1).unwrap()["latency_ms"], json!(null));
+
+        api.set_server_bridge(Arc::new(RecordingBridge::default()));
+        assert_eq!(api.get_user_info(1).unwrap()["latency_ms"], json!(42));
+        assert_eq!(api.get_user_latency(2).unwrap(), None);
+    }
+
+    #[derive(Default)]
+    struct GreeterPlugin {
+        joins: std::sync::atomic::AtomicUsize,
+    }
+
+    #[crate::plugin(name = "greeter", version = "1.2.0", author = "test")]
+    impl GreeterPlugin {
+        #[command(description = "Greet someone")]
+        fn hello(&self, args: &[String]) -> Result<String> {
+            Ok(format!("Hello, {}!", args.join(" ")))
+        }
+
+        #[command(name = "joins")]
+        fn join_count(&self, _args: &[String]) -> Result<String> {
+            Ok(self.joins.load(std::sync::atomic::Ordering::Relaxed).to_string())
+        }
+
+        #[event("user_join")]
+        fn on_user_join(&self, _event: &crate::event_system::Event) -> Result<()> {
+            self.joins.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_emitted_events_are_bounded() {
+        use crate::event_system::{HandlerConcurrency, HandlerOverflow};
+        let api = host_api();
+        api.event_bus.set_handler_concurrency(HandlerConcurrency {
+            max_per_plugin: 1,
+            overflow: HandlerOverflow::Shed,
+        });
+        let calls = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        api.event_bus
+            .subscribe(
+                "touch",
+                Box::new({
+                    let calls = Arc::clone(&calls);
+                    move |_| {
+                        std::thread::sleep(std::time::Duration::from_millis(50));
+                        calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        Ok(())
+                    }
+                }),
+                "listener",
+            )
+            .unwrap();
+
+        for _ in 0..10 {
+            api.emit_event("touch", json!({}), "relay").unwrap();
+        }
+        let dropped = api.event_bus.dropped_handler_calls("listener");
+        assert!(dropped > 0);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst) + dropped, 10);
+    }
+
+    #[test]
+    fn test_plugin_cooldowns() {
+        let api = host_api();
+        let duration = std::time::Duration::from_secs(60);
+        assert!(api.cooldown_check("greet:1", duration, "greeter"));
+        assert!(!api.cooldown_check("greet:1", duration, "greeter"));
+        // Keys are scoped to their plugin, and command cooldowns are left alone
+        assert!(api.cooldown_check("greet:1", duration, "other"));
+        assert!(api.cooldown_check("command:shout:1", duration, "greeter"));
+        assert!(api.command_registry.cooldowns().is_empty());
+    }
+
+    #[test]
+    fn test_plugin_macro() {
+        let metadata = GreeterPlugin::plugin_metadata();
+        assert_eq!(
+            (metadata.name(), metadata.version(), metadata.author()),
+            ("greeter", "1.2.0", "test")
+        );
+
+        assert!(GreeterPlugin::instance().is_none());
+        assert_eq!(_plugin_init(), 0);
+        assert_eq!(_plugin_init(), 1);
+        let api = host_api();
+        GreeterPlugin::instance().unwrap().register(&api).unwrap();
+
+        assert_eq!(api.execute_command("hello world", &CommandContext::console()).unwrap(), "Hello, world!");
+        assert_eq!(api.get_command_owner("hello").as_deref(), Some("greeter"));
+        api.emit_event("user_join", json!({ "user_id": 1 }), "test").unwrap();
+        assert_eq!(api.execute_command("joins", &CommandContext::console()).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_registered_room_info_fields() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().push(live_room("arena", 7));
+        bridge.rooms.lock().push(live_room("1", 7));
+        api.register_room_info_field("ranked", "tier", "string").unwrap();
+
+        api.set_room_custom_data(1, "tier", json!("gold")).unwrap();
+        api.set_room_custom_data(1, "private", json!(true)).unwrap();
+        assert!(api.set_room_custom_data(2, "tier", json!("gold")).is_err());
+        // The data outlives refreshes of the server's room
+        bridge.rooms.lock()[1].name = "Ranked".to_string();
+        let info = api.get_room_info(1).unwrap();
+        assert_eq!((info["name"].clone(), info["tier"].clone()), (json!("Ranked"), json!("gold")));
+        assert!(info.get("private").is_none());
+        for list in [api.get_room_list().unwrap(), api.get_available_room_list().unwrap()] {
+            assert_eq!(list[0]["tier"], json!("gold"));
+            assert!(list[1].get("tier").is_none());
+        }
+
+        api.unregister_info_fields("ranked");
+        assert!(api.get_room_list().unwrap()[0].get("tier").is_none());
+    }
+
+    #[test]
+    fn test_registered_user_info_fields() {
+        let api = host_api();
+        api.server_state.write().online_users.insert(
+            1,
+            UserInfo {
+                id: 1,
+                name: "user".to_string(),
+                language: "en-US".to_string(),
+                session_id: uuid::Uuid::nil(),
+                ip: None,
+                custom_data: std::collections::HashMap::from([
+                    ("elo".to_string(), json!(1500)),
+                    ("division".to_string(), json!(3)),
+                ]),
+            },
+        );
+        api.register_user_info_field("ranked", "elo", "integer").unwrap();
+        api.register_user_info_field("ranked", "division", "string").unwrap();
+        assert!(api.register_user_info_field("other", "elo", "integer").is_err());
+
+        let info = api.get_user_info(1).unwrap();
+        assert_eq!(info["elo"], json!(1500));
+        assert!(info.get("division").is_none());
+
+        api.unregister_info_fields("ranked");
+        assert!(api.get_user_info(1).unwrap().get("elo").is_none());
+    }
+
+    #[test]
+    fn test_rename_room() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().push(live_room("1", 7));
+
+        assert_eq!(api.set_room_name(1, " Weekend\u{7}\n league ").unwrap(), "Weekend league");
+        assert_eq!(api.get_room_info(1).unwrap()["name"], json!("Weekend league"));
+        assert_eq!(bridge.rooms.lock()[0].name, "Weekend league");
+
+        assert!(api.set_room_name(1, "\u{1b}\t").is_err());
+        assert!(api.set_room_name(1, &"x".repeat(MAX_ROOM_NAME_LENGTH + 1)).is_err());
+        assert!(api.set_room_name(2, "missing").is_err());
+        assert!(api.rename_room_as_host(8, 1, "guest").is_err());
+        api.rename_room_as_host(7, 1, "host").unwrap();
+        assert_eq!(api.get_room_info(1).unwrap()["name"], json!("host"));
+    }
+
+    #[test]
+    fn test_broadcast_announcement() {
+        use phira_mp_common::{AnnouncementColor, AnnouncementStyle};
+
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        let style = AnnouncementStyle {
+            bold: true,
+            color: Some(AnnouncementColor::Red),
+        };
+        api.broadcast_announcement("<b>Maintenance</b> at 3 < 4\u{7}", style.clone())
+            .unwrap();
+        assert!(api.broadcast_announcement("<color=red></color>", style.clone()).is_err());
+
+        assert_eq!(
+            *bridge.announcements.lock(),
+            vec![("Maintenance at 3 < 4".to_string(), style)]
+        );
+    }
+
+    #[test]
+    fn test_set_scheduled_announcement() {
+        use phira_mp_common::ScheduledAnnouncement;
+
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        let announcement = |title: &str| ScheduledAnnouncement {
+            title: title.to_string(),
+            starts_at: 1_700_000_000,
+        };
+        api.set_scheduled_announcement(Some(announcement("<b>Weekly cup</b>")))
+            .unwrap();
+        assert!(api.set_scheduled_announcement(Some(announcement(" <i></i> "))).is_err());
+        api.set_scheduled_announcement(None).unwrap();
+
+        assert_eq!(
+            *bridge.scheduled.lock(),
+            vec![Some(announcement("Weekly cup")), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_info_requires_admin() {
+        use crate::plugin_manager::PluginManager;
+
+        let dir = tempfile::tempdir().unwrap();
+        let event_bus = Arc::new(EventBus::new());
+        let command_registry = Arc::new(CommandRegistry::new());
+        let mut host_api = None;
+        let plugin_manager = Arc::new_cyclic(|manager| {
+            let api = Arc::new(HostApi::new_with_weak(
+                Arc::clone(&event_bus),
+                Arc::clone(&command_registry),
+                manager.clone(),
+            ));
+            host_api = Some(Arc::clone(&api));
+            PluginManager::new(dir.path(), event_bus, command_registry, api).unwrap()
+        });
+        let api = host_api.unwrap();
+        for (name, permissions) in [("viewer", ""), ("inspector", "permissions = [\"admin\"]")] {
+            let path = dir.path().join(format!("{name}.wasm"));
+            std::fs::write(
+                &path,
+                format!(
+                    "name = \"{name}\"\nversion = \"1.0.0\"\nauthor = \"test\"\n\
+                     abi_version = \"1.0.0\"\n{permissions}"
+                ),
+            )
+            .unwrap();
+            plugin_manager.load_plugin(&path).await.unwrap();
+        }
+        api.set_server_bridge(Arc::new(RecordingBridge::default()));
+
+        assert_eq!(api.get_session_info(&CommandContext::console(), 1).unwrap()["session_id"], json!("session"));
+        assert!(api.get_session_info(&CommandContext::console(), 2).is_err());
+        let inspector = api.plugin_command_context("inspector").unwrap();
+        assert_eq!(api.get_session_info(&inspector, 1).unwrap()["user_id"], json!(1));
+        let viewer = api.plugin_command_context("viewer").unwrap();
+        assert!(matches!(
+            api.get_session_info(&viewer, 1),
+            Err(Error::SecurityViolation(_))
+        ));
+        assert!(matches!(
+            api.get_session_info(&CommandContext::new(7, Vec::<String>::new()), 1),
+            Err(Error::SecurityViolation(_))
+        ));
+        assert!(api.plugin_command_context("unknown").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restart_plugin_system() {
+        let dir = tempfile::tempdir().unwrap();
+        let (plugin_manager, api) = crate::create_plugin_system(dir.path()).unwrap();
+        std::fs::write(
+            dir.path().join("greeter.wasm"),
+            "name = \"greeter\"\nversion = \"1.0.0\"\nauthor = \"test\"\nabi_version = \"1.0.0\"",
+        )
+        .unwrap();
+        api.set_server_bridge(Arc::new(RecordingBridge::default()));
+
+        api.restart_plugin_system().unwrap();
+        for _ in 0..100 {
+            if plugin_manager.get_plugin("greeter").is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(plugin_manager.get_plugin("greeter").is_some());
+        // Sessions live in the server and survive the restart
+        assert_eq!(api.get_session_info(&CommandContext::console(), 1).unwrap()["session_id"], json!("session"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (plugin_manager, api) = crate::create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("greeter.wasm");
+        let manifest = |version: &str| {
+            format!("name = \"greeter\"\nversion = \"{version}\"\nauthor = \"test\"\nabi_version = \"1.0.0\"")
+        };
+        std::fs::write(&path, manifest("1.0.0")).unwrap();
+        plugin_manager.load_plugin(&path).await.unwrap();
+        assert!(matches!(api.reload_plugin("missing"), Err(Error::NotFound(_))));
+
+        std::fs::write(&path, manifest("1.1.0")).unwrap();
+        api.reload_plugin("greeter").unwrap();
+        let version = || {
+            plugin_manager
+                .get_plugin("greeter")
+                .map(|plugin| plugin.read().metadata.version.clone())
+        };
+        for _ in 0..100 {
+            if version().as_deref() == Some("1.1.0") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(version().as_deref(), Some("1.1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_server_shutdown_awaits_async_handlers() {
+        let dir = tempfile::tempdir().unwrap();
+        let (plugin_manager, api) = crate::create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("greeter.wasm");
+        std::fs::write(
+            &path,
+            "name = \"greeter\"\nversion = \"1.0.0\"\nauthor = \"test\"\nabi_version = \"1.0.0\"",
+        )
+        .unwrap();
+        plugin_manager.load_plugin(&path).await.unwrap();
+        let flushed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = Arc::clone(&flushed);
+        plugin_manager
+            .event_bus()
+            .subscribe_async(
+                crate::event_system::predefined::SERVER_SHUTDOWN,
+                Box::new(move |_| {
+                    let flag = Arc::clone(&flag);
+                    Box::pin(async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                        flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                        Ok(())
+                    })
+                }),
+                "greeter",
+            )
+            .unwrap();
+
+        api.on_server_shutdown().await;
+        assert!(flushed.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(plugin_manager.get_plugin("greeter").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_player_input() {
+        use crate::player_input::{PlayerInput, PlayerInputFrames};
+        use crate::plugin_manager::PluginManager;
+        use phira_mp_common::{JudgeEvent, Judgement};
+
+        let dir = tempfile::tempdir().unwrap();
+        let event_bus = Arc::new(EventBus::new());
+        let command_registry = Arc::new(CommandRegistry::new());
+        let mut host_api = None;
+        let plugin_manager = Arc::new_cyclic(|manager| {
+            let api = Arc::new(HostApi::new_with_weak(
+                Arc::clone(&event_bus),
+                Arc::clone(&command_registry),
+                manager.clone(),
+            ));
+            host_api = Some(Arc::clone(&api));
+            PluginManager::new(dir.path(), event_bus, command_registry, api).unwrap()
+        });
+        let api = host_api.unwrap();
+        for (name, permissions) in [("viewer", ""), ("coach", "permissions = [\"player_input\"]")] {
+            let path = dir.path().join(format!("{name}.wasm"));
+            std::fs::write(
+                &path,
+                format!(
+                    "name = \"{name}\"\nversion = \"1.0.0\"\nauthor = \"test\"\n\
+                     abi_version = \"1.0.0\"\n{permissions}"
+                ),
+            )
+            .unwrap();
+            plugin_manager.load_plugin(&path).await.unwrap();
+        }
+
+        assert!(matches!(
+            api.subscribe_player_input("viewer", "room", 1, Box::new(|_| {})),
+            Err(Error::SecurityViolation(_))
+        ));
+        assert!(api.subscribe_player_input("coach", "room", 0, Box::new(|_| {})).is_err());
+
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        api.subscribe_player_input(
+            "coach",
+            "room",
+            3,
+            Box::new(move |input| {
+                if let PlayerInputFrames::Judges(judges) = &input.frames {
+                    sink.lock().unwrap().push(judges[0].note_id);
+                }
+            }),
+        )
+        .unwrap();
+        assert!(api.has_player_input_subscribers("room"));
+
+        for note_id in 0..7 {
+            api.dispatch_player_input(&PlayerInput {
+                room_id: "room".to_string(),
+                user_id: 1,
+                frames: PlayerInputFrames::Judges(Arc::new(vec![JudgeEvent {
+                    time: 0.,
+                    line_id: 0,
+                    note_id,
+                    judgement: Judgement::Perfect,
+                }])),
+            });
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(*received.lock().unwrap(), vec![0, 3, 6]);
+
+        plugin_manager.unload_plugin("coach").await.unwrap();
+        assert!(!api.has_player_input_subscribers("room"));
+    }
+
+    #[test]
+    fn test_health_probe() {
+        use crate::monitoring::{HealthMonitor, HealthStatus, HealthThresholds, MetricsCollector};
+
+        let api = host_api();
+        let probe = || -> crate::monitoring::HealthProbe {
+            Box::new(|| (HealthStatus::Critical, "database unreachable".to_string()))
+        };
+        assert!(api.register_health_probe("db", probe()).is_err());
+
+        let metrics = Arc::new(MetricsCollector::new(10, std::time::Duration::ZERO));
+        metrics.register_plugin("db".to_string());
+        let monitor = Arc::new(HealthMonitor::new(HealthThresholds::default(), metrics, 10));
+        api.set_health_monitor(Arc::clone(&monitor));
+        assert_eq!(monitor.get_plugin_health("db"), HealthStatus::Healthy);
+
+        api.register_health_probe("db", probe()).unwrap();
+        assert_eq!(monitor.check_health()["db"], HealthStatus::Critical);
+        assert_eq!(monitor.get_plugin_health("db"), HealthStatus::Critical);
+        assert_eq!(monitor.get_probe_details("db"), vec!["database unreachable"]);
+
+        api.unregister_health_probes("db");
+        assert_eq!(monitor.check_health()["db"], HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_get_server_stats() {
+        let api = host_api();
+        assert!(api.get_server_stats(std::time::Duration::from_secs(3600)).is_err());
+
+        api.set_server_bridge(Arc::new(RecordingBridge::default()));
+        let stats = api.get_server_stats(std::time::Duration::from_secs(24 * 3600)).unwrap();
+        assert_eq!(stats["window_secs"], json!(24 * 3600));
+        assert_eq!(stats["peak_users"], json!(4));
+        assert_eq!(stats["rooms_created"], json!(4));
+        assert_eq!(stats["games_completed"], json!(2));
+        assert_eq!(stats["buckets"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_get_server_setting() {
+        let api = host_api();
+        assert_eq!(api.get_server_setting("max_room_users"), None);
+
+        api.set_server_bridge(Arc::new(RecordingBridge::default()));
+        assert_eq!(
+            api.get_server_setting("max_room_users"),
+            Some(json!("max_room_users value"))
+        );
+        assert_eq!(api.get_server_setting("otlp"), None);
+        assert_eq!(api.get_server_setting("token"), None);
+    }
+
+    #[test]
+    fn test_room_lock_metadata() {
+        let api = host_api();
+        insert_room(&api, "1", 7);
+
+        api.set_room_lock(1, true, Some(7)).unwrap();
+        let info = api.get_room_info(1).unwrap();
+        assert_eq!(info["locked"], json!(true));
+        assert_eq!(info["locked_by"], json!(7));
+        assert!(info["locked_at"].as_u64().unwrap() > 0);
+
+        api.set_room_lock(1, false, Some(7)).unwrap();
+        let info = api.get_room_info(1).unwrap();
+        assert_eq!(info["locked_by"], Value::Null);
+        assert_eq!(info["locked_at"], Value::Null);
+    }
+
+    #[test]
+    fn test_room_lock_is_set_on_the_server() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().push(live_room("1", 7));
+
+        api.set_room_lock(1, true, None).unwrap();
+        assert!(bridge.rooms.lock()[0].locked);
+        let info = api.get_room_info(1).unwrap();
+        assert_eq!(info["locked"], json!(true));
+        assert_eq!(info["locked_by"], Value::Null);
+        assert!(api.set_room_lock(2, true, None).is_err());
+    }
+
+    #[test]
+    fn test_replace_chart_pool() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().push(live_room("1", 7));
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        api.subscribe_event(
+            crate::event_system::predefined::CHART_POOL_REPLACE,
+            {
+                let sizes = Arc::clone(&sizes);
+                Box::new(move |event| {
+                    sizes.lock().push(event.data["size"].clone());
+                    Ok(())
+                })
+            },
+            "observer",
+        )
+        .unwrap();
+        let chart = |id: u32| phira_mp_common::ChartId::try_from(id).unwrap();
+        let pool = |api: &HostApi| api.get_room_info(1).unwrap()["chart_pool"].clone();
+
+        assert_eq!(api.replace_chart_pool(1, vec![(chart(1), 1), (chart(2), 3)]).unwrap(), 2);
+        assert_eq!(
+            pool(&api),
+            json!([{ "chart_id": 1, "weight": 1 }, { "chart_id": 2, "weight": 3 }])
+        );
+        assert_eq!(bridge.rooms.lock()[0].chart_pool, vec![(chart(1), 1), (chart(2), 3)]);
+
+        api.register_chart_validator(
+            "blocklist",
+            0,
+            Box::new(|selection| {
+                if selection.chart_id.get() == 13 {
+                    crate::chart_validator::ChartSelectionDecision::Deny("blocked".to_string())
+                } else {
+                    crate::chart_validator::ChartSelectionDecision::Allow
+                }
+            }),
+        );
+        // One blocked chart rejects the whole pool
+        assert!(api.replace_chart_pool(1, vec![(chart(5), 1), (chart(13), 1)]).is_err());
+        assert!(api.replace_chart_pool(1, vec![(chart(5), 0)]).is_err());
+        assert!(api.replace_chart_pool(1, vec![(chart(5), 1), (chart(5), 2)]).is_err());
+        assert!(api.replace_chart_pool(2, vec![(chart(5), 1)]).is_err());
+        assert_eq!(pool(&api)[1], json!({ "chart_id": 2, "weight": 3 }));
+        assert_eq!(*sizes.lock(), vec![json!(2)]);
+    }
+
+    #[test]
+    fn test_user_connect() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().push(LiveRoom {
+            user_ids: vec![7, 8],
+            playing_user_ids: vec![7, 8],
+            state: RoomState::Playing,
+            ..live_room("arena", 7)
+        });
+        let user = |id| UserInfo {
+            id,
+            name: format!("user{}", id),
+            language: "en-US".to_string(),
+            session_id: uuid::Uuid::nil(),
+            ip: Some("10.0.0.1".to_string()),
+            custom_data: std::collections::HashMap::new(),
+        };
+        assert!(api.get_user_info(7).is_err());
+
+        api.on_user_connect(user(7));
+        api.on_user_connect(user(9));
+        let info = api.get_user_info(7).unwrap();
+        assert_eq!(info["room_id"], json!("arena"));
+        assert_eq!(info["is_playing"], json!(true));
+        assert_eq!(api.get_user_info(9).unwrap()["room_id"], Value::Null);
+        api.ban_user_from_room_by_ip("10.0.0.1", "1").unwrap();
+        assert!(api.is_user_banned_from_room(9, "1").unwrap());
+
+        api.on_user_disconnect(9);
+        assert!(api.get_user_info(9).is_err());
+        assert!(!api.is_user_banned_from_room(9, "1").unwrap());
+        assert_eq!(api.get_online_user_ids().unwrap(), json!([7]));
+    }
+
+    #[test]
+    fn test_matchmaking_queue() {
+        let api = host_api();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        for event_type in [
+            crate::event_system::predefined::QUEUE_ENQUEUE,
+            crate::event_system::predefined::QUEUE_DEQUEUE,
+            crate::event_system::predefined::USER_DISCONNECT,
+        ] {
+            let events = Arc::clone(&events);
+            api.subscribe_event(
+                event_type,
+                Box::new(move |event| {
+                    events.lock().push((event.event_type.clone(), event.data.clone()));
+                    Ok(())
+                }),
+                "matchmaker",
+            )
+            .unwrap();
+        }
+
+        for user_id in [3, 1, 4, 5] {
+            api.queue_push("ranked", user_id, json!({ "rating": 1000 })).unwrap();
+        }
+        let batch = api.queue_pop_batch("ranked", 2);
+        assert_eq!(batch.iter().map(|entry| entry.user_id).collect::<Vec<_>>(), vec![3, 1]);
+
+        // Disconnected users leave their queue
+        api.on_user_disconnect(4);
+        assert_eq!(
+            api.queue_entries("ranked").iter().map(|entry| entry.user_id).collect::<Vec<_>>(),
+            vec![5]
+        );
+        assert!(api.queue_pop_batch("ranked", 2).is_empty());
+        assert_eq!(api.queue_remove(5).as_deref(), Some("ranked"));
+        assert_eq!(api.queue_remove(5), None);
+
+        let events = events.lock();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[4].1, json!({ "queue": "ranked", "user_ids": [3, 1], "reason": "matched" }));
+        assert_eq!(events[5].1, json!({ "queue": "ranked", "user_ids": [4], "reason": "disconnected" }));
+        assert_eq!(events[6], ("user_disconnect".to_string(), json!({ "user_id": 4 })));
+    }
+
+    #[tokio::test]
+    async fn test_http_allowlist() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let api = host_api();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+        let sandbox = api.sandbox_manager().create_sandbox(
+            "fetcher".to_string(),
+            crate::sandbox::ResourceLimits::default(),
+            crate::sandbox::SecurityPolicy {
+                allow_network: true,
+                allowed_network_hosts: vec!["127.0.0.1".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let response = api.http_get("fetcher", &format!("http://{}/", addr)).await.unwrap();
+        assert_eq!(response, HttpResponse { status: 200, body: "ok".to_string() });
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({ "status": 200, "body": "ok" })
+        );
+
+        let blocked = api
+            .http_post("fetcher", "http://example.com/", "text/plain", "hi".to_string())
+            .await;
+        assert!(matches!(blocked, Err(Error::SecurityViolation(_))));
+        assert_eq!(sandbox.security_violations(), 1);
+
+        assert!(matches!(
+            api.http_get("unsandboxed", &format!("http://{}/", addr)).await,
+            Err(Error::SecurityViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_stable_listing_order() {
+        let api = host_api();
+        {
+            let mut state = api.server_state.write();
+            for id in [42, 7, 19, 3, 25] {
+                state.rooms.insert(
+                    id.to_string(),
+                    RoomInfo {
+                        name: format!("room{}", id),
+                        host_id: id,
+                        user_ids: vec![id],
+                        ..RoomInfo::empty(id.to_string(), 8)
+                    },
+                );
+                state.online_users.insert(
+                    id,
+                    UserInfo {
+                        id,
+                        name: format!("user{}", id),
+                        language: "en-US".to_string(),
+                        session_id: uuid::Uuid::nil(),
+                        ip: None,
+                        custom_data: std::collections::HashMap::new(),
+                    },
+                );
+            }
+        }
+
+        for id in ["lobby", "arena"] {
+            insert_room(&api, id, 50);
+        }
+
+        let ids = |list: Value| -> Vec<String> {
+            list.as_array()
+                .unwrap()
+                .iter()
+                .map(|room| room["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let expected = ["3", "7", "19", "25", "42", "arena", "lobby"];
+        for _ in 0..3 {
+            assert_eq!(ids(api.get_room_list().unwrap()), expected);
+            assert_eq!(ids(api.get_available_room_list().unwrap()), expected);
+            assert_eq!(api.get_online_user_ids().unwrap(), json!([3, 7, 19, 25, 42]));
+        }
+    }
+
+    #[test]
+    fn test_get_active_charts() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        for (id, chart_id, room_state, user_ids) in [
+            ("3", Some(10), RoomState::Playing, vec![1, 2, 3]),
+            ("1", Some(20), RoomState::WaitingForReady, vec![4]),
+            ("2", Some(30), RoomState::SelectingChart, vec![5]),
+            ("4", None, RoomState::Playing, vec![6]),
+            ("arena", Some(40), RoomState::Playing, vec![7, 8]),
+        ] {
+            bridge.rooms.lock().push(LiveRoom {
+                user_ids,
+                chart_id: chart_id.map(|id: u32| id.try_into().unwrap()),
+                state: room_state,
+                ..live_room(id, 1)
+            });
+        }
+
+        // The server's rooms are seen without plugins tracking them
+        assert_eq!(
+            api.get_active_charts().unwrap(),
+            json!([
+                { "chart_id": 20, "room_id": "1", "player_count": 1 },
+                { "chart_id": 10, "room_id": "3", "player_count": 3 },
+                { "chart_id": 40, "room_id": "arena", "player_count": 2 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_disbanded_rooms_leave_the_mirror() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        bridge.rooms.lock().extend([live_room("1", 1), live_room("arena", 2)]);
+        assert_eq!(api.get_available_room_count().unwrap(), 2);
+
+        // A room the server dropped is pruned on the next read
+        bridge.rooms.lock().retain(|room| room.id != "1");
+        assert_eq!(api.get_available_room_count().unwrap(), 1);
+        assert!(api.get_room_info(1).is_err());
+
+        let disbands = Arc::new(Mutex::new(Vec::new()));
+        api.subscribe_event(
+            crate::event_system::predefined::ROOM_DISBAND,
+            {
+                let disbands = Arc::clone(&disbands);
+                Box::new(move |event| {
+                    disbands.lock().push(event.data["room_id"].clone());
+                    Ok(())
+                })
+            },
+            "observer",
+        )
+        .unwrap();
+        bridge.rooms.lock().clear();
+        api.on_room_disband("arena");
+        assert!(api.server_state.read().rooms.is_empty());
+        assert_eq!(*disbands.lock(), vec![json!("arena")]);
+    }
+
+    #[test]
+    fn test_room_template() {
+        let api = host_api();
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        let chart = |id: u32| phira_mp_common::ChartId::try_from(id).unwrap();
+        bridge.rooms.lock().push(LiveRoom {
+            name: "Weekend cup".to_string(),
+            user_ids: vec![7],
+            max_users: 6,
+            locked: true,
+            lock: Some(RoomLock {
+                user_id: Some(7),
+                locked_at: std::time::SystemTime::now(),
+            }),
+            cycle: true,
+            chart_id: Some(chart(2)),
+            chart_pool: vec![(chart(1), 1), (chart(2), 3)],
+            state: RoomState::Playing,
+            playing_user_ids: vec![7],
+            ..live_room("3", 7)
+        });
+
+        let template = api.export_room_template(3).unwrap();
+        assert_eq!(template["version"], json!(ROOM_TEMPLATE_VERSION));
+        let room_id = api.create_room_from_template(&template).unwrap();
+        assert_eq!(room_id, 4);
+        // Created on the server and read back from it
+        let created = bridge.rooms.lock()[1].clone();
+        assert_eq!((created.name.as_str(), created.max_users), ("Weekend cup", 6));
+        assert!(created.locked && created.cycle);
+        assert_eq!(created.chart_pool, vec![(chart(1), 1), (chart(2), 3)]);
+        assert_eq!(api.export_room_template(room_id).unwrap(), template);
+        let info = api.get_room_info(room_id).unwrap();
+        assert_eq!(info["user_ids"], json!([]));
+        assert_eq!(info["state"], json!("SELECTING_CHART"));
+
+        let mut outdated = template.clone();
+        outdated["version"] = json!(ROOM_TEMPLATE_VERSION + 1);
+        assert!(api.create_room_from_template(&outdated).is_err());
+        let mut invalid = template;
+        invalid["chart_pool"] = json!([{ "chart_id": 1, "weight": 0 }]);
+        assert!(api.create_room_from_template(&invalid).is_err());
+        assert!(api.create_room_from_template(&json!({ "version": 1 })).is_err());
+        assert_eq!(bridge.rooms.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_user_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(USER_TAGS_FILE);
+        let api = host_api();
+        assert_eq!(api.open_user_tags(&path).unwrap(), 0);
+
+        assert!(api.add_user_tag(1, "VIP").unwrap());
+        assert!(!api.add_user_tag(1, "VIP").unwrap());
+        assert!(api.add_user_tag(1, "verified").unwrap());
+        assert!(api.add_user_tag(2, "verified").unwrap());
+        assert!(api.add_user_tag(1, "").is_err());
+        assert!(api.add_user_tag(1, "two words").is_err());
+        assert!(api.remove_user_tag(2, "verified").unwrap());
+        assert!(!api.remove_user_tag(2, "verified").unwrap());
+
+        // Tags survive reopening the store
+        let api = host_api();
+        assert_eq!(api.open_user_tags(&path).unwrap(), 1);
+        assert_eq!(api.get_user_tags(1), ["VIP", "verified"]);
+        assert!(api.get_user_tags(2).is_empty());
+
+        // A gate only VIPs pass
+        let may_create_room = |user_id| api.user_has_tag(user_id, "VIP");
+        assert!(may_create_room(1));
+        assert!(!may_create_room(2));
+    }
+
+    #[test]
+    fn test_room_bans_by_id_and_ip() {
+        let api = host_api();
+        for (id, ip) in [(1, "10.0.0.1"), (2, "10.0.0.2"), (3, "10.0.0.3")] {
+            api.server_state.write().online_users.insert(
+                id,
+                UserInfo {
+                    id,
+                    name: format!("user{}", id),
+                    language: "en-US".to_string(),
+                    session_id: uuid::Uuid::nil(),
+                    ip: Some(ip.to_string()),
+                    custom_data: std::collections::HashMap::new(),
+                },
+            );
+        }
+
+        // Room IDs need not be numeric
+        // ID only
+        api.ban_user_from_room_by_id(1, "arena").unwrap();
+        // IP only
+        api.ban_user_from_room_by_ip("10.0.0.2", "arena").unwrap();
+        // Both
+        api.ban_user_from_room_by_id(3, "arena").unwrap();
+        api.ban_user_from_room_by_ip("10.0.0.3", "arena").unwrap();
+
+        for id in [1, 2, 3] {
+            assert!(api.is_user_banned_from_room(id, "arena").unwrap());
+            assert!(!api.is_user_banned_from_room(id, "8").unwrap());
+        }
+        assert!(!api.is_user_banned_from_room(4, "arena").unwrap());
+
+        api.unban_user_from_room_by_id(3, "arena").unwrap();
+        assert!(api.is_user_banned_from_room(3, "arena").unwrap());
+        api.unban_user_from_room_by_ip("10.0.0.3", "arena").unwrap();
+        assert!(!api.is_user_banned_from_room(3, "arena").unwrap());
+        api.unban_user_from_room_by_ip("10.0.0.2", "arena").unwrap();
+        assert!(!api.is_user_banned_from_room(2, "arena").unwrap());
+    }
+
+    #[test]
+    fn test_playtime_leaderboard() {
+        let api = host_api();
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        {
+            let mut state = api.server_state.write();
+            for id in [3, 1, 2] {
+                state.online_users.insert(
+                    id,
+                    UserInfo {
+                        id,
+                        name: format!("user{}", id),
+                        language: "en-US".to_string(),
+                        session_id: uuid::Uuid::nil(),
+                        ip: None,
+                        custom_data: std::collections::HashMap::new(),
+                    },
+                );
+            }
+        }
+        for id in [3, 1, 2] {
+            api.record_playtime(id, 600).unwrap();
+        }
+        assert!(api.get_user_playtime(4).is_err());
+
+        let ids = |list: Value| -> Vec<u64> {
+            list.as_array().unwrap().iter().map(|it| it["id"].as_u64().unwrap()).collect()
+        };
+        for _ in 0..5 {
+            assert_eq!(ids(api.get_playtime_leaderboard(10, None).unwrap()), vec![1, 2, 3]);
+        }
+        assert_eq!(ids(api.get_playtime_leaderboard(2, None).unwrap()), vec![1, 2]);
+
+        // User 3 played the most overall, but mostly before the window
+        api.playtime.record(
+            3,
+            None,
+            3600,
+            std::time::SystemTime::now() - day * 40,
+            PLAYTIME_LOG_RETENTION,
+        );
+        assert_eq!(ids(api.get_playtime_leaderboard(10, None).unwrap()), vec![3, 1, 2]);
+        let recent = api.get_playtime_leaderboard(10, Some(day * 30)).unwrap();
+        assert_eq!(ids(recent.clone()), vec![1, 2, 3]);
+        assert_eq!(recent[2]["playtime"], json!(4200));
+        assert_eq!(recent[2]["recent_playtime"], json!(600));
+        assert_eq!(recent[2]["window_secs"], json!(30 * 24 * 60 * 60));
+
+        // Offline users keep their playtime under the name they last had
+        api.server_state.write().online_users.remove(&3);
+        api.on_user_playtime(4, "user4", 5000);
+        let board = api.get_playtime_leaderboard(2, None).unwrap();
+        assert_eq!(ids(board.clone()), vec![4, 3]);
+        assert_eq!(board[0]["name"], json!("user4"));
+        assert_eq!(board[1]["name"], json!("user3"));
+        assert_eq!(api.get_user_playtime(3).unwrap(), 4200);
+    }
+
+    #[test]
+    fn test_send_message_delivery_status() {
+        let api = host_api();
+        assert!(api.send_message_to_user(1, "hi").is_err());
+        let bridge = Arc::new(RecordingBridge::default());
+        api.set_server_bridge(bridge.clone());
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        api.event_bus
+            .subscribe(
+                crate::event_system::predefined::MESSAGE_DELIVERED,
+                Box::new({
+                    let statuses = Arc::clone(&statuses);
+                    move |event| {
+                        statuses.lock().push(event.data["status"].clone());
+                        Ok(())
+                    }
+                }),
+                "test",
+            )
+            .unwrap();
+
+        assert_eq!(api.send_message_to_user(1, "hi").unwrap(), DeliveryStatus::Delivered);
+        assert_eq!(api.send_message_to_user(2, "hi").unwrap(), DeliveryStatus::UserOffline);
+        assert!(api.send_message_to_user(3, "hi").is_err());
+        assert_eq!(*statuses.lock(), vec![json!("delivered"), json!("user_offline")]);
+        assert_eq!(*bridge.messages.lock(), vec![(1, "hi".to_string())]);
+    }
+}
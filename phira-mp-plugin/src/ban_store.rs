@@ -0,0 +1,224 @@
+use crate::Result;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, warn};
+
+/// A ban, with why and until when it applies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanEntry {
+    pub reason: String,
+    pub banned_at: SystemTime,
+    /// When the ban lifts, `None` for a permanent ban
+    pub expires_at: Option<SystemTime>,
+}
+
+impl BanEntry {
+    /// Time left until the ban lifts, `None` for a permanent ban
+    pub fn remaining(&self, now: SystemTime) -> Option<Duration> {
+        self.expires_at
+            .map(|at| at.duration_since(now).unwrap_or_default())
+    }
+
+    /// Whether the ban has lifted
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Saved form of a [`BanStore`]
+#[derive(Default, Serialize, Deserialize)]
+struct Bans {
+    ids: HashMap<u32, BanEntry>,
+    ips: HashMap<String, BanEntry>,
+}
+
+/// Server-wide bans by user ID and by IP, saved on every change once opened with
+/// [`BanStore::open`]
+///
+/// Expired bans are dropped when looked up.
+#[derive(Default)]
+pub struct BanStore {
+    bans: RwLock<Bans>,
+    /// File the bans are saved to
+    path: RwLock<Option<PathBuf>>,
+}
+
+impl BanStore {
+    /// Create an empty store that is never saved
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the bans saved at `path`, if any, and save to it from now on, returning the
+    /// number of bans loaded
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let bans: Bans = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Bans::default()
+        };
+        let count = bans.ids.len() + bans.ips.len();
+        *self.bans.write() = bans;
+        *self.path.write() = Some(path.to_path_buf());
+        Ok(count)
+    }
+
+    /// Ban a user ID, for `duration` or permanently, replacing any previous ban
+    pub fn ban_id(&self, user_id: u32, reason: &str, duration: Option<Duration>) -> Result<()> {
+        let entry = Self::entry(reason, duration);
+        self.update(|bans| {
+            bans.ids.insert(user_id, entry);
+            true
+        })?;
+        Ok(())
+    }
+
+    /// Lift the ban of a user ID, returning whether it was banned
+    pub fn unban_id(&self, user_id: u32) -> Result<bool> {
+        self.update(|bans| bans.ids.remove(&user_id).is_some())
+    }
+
+    /// Ban an IP, for `duration` or permanently, replacing any previous ban
+    pub fn ban_ip(&self, ip: &str, reason: &str, duration: Option<Duration>) -> Result<()> {
+        let entry = Self::entry(reason, duration);
+        self.update(|bans| {
+            bans.ips.insert(ip.to_string(), entry);
+            true
+        })?;
+        Ok(())
+    }
+
+    /// Lift the ban of an IP, returning whether it was banned
+    pub fn unban_ip(&self, ip: &str) -> Result<bool> {
+        self.update(|bans| bans.ips.remove(ip).is_some())
+    }
+
+    /// Get the ban of a user ID, if banned
+    pub fn get_id(&self, user_id: u32) -> Option<BanEntry> {
+        self.expire();
+        self.bans.read().ids.get(&user_id).cloned()
+    }
+
+    /// Get the ban of an IP, if banned
+    pub fn get_ip(&self, ip: &str) -> Option<BanEntry> {
+        self.expire();
+        self.bans.read().ips.get(ip).cloned()
+    }
+
+    /// Get all bans by user ID, sorted by ID
+    pub fn ids(&self) -> Vec<(u32, BanEntry)> {
+        self.expire();
+        let mut ids: Vec<_> = self
+            .bans
+            .read()
+            .ids
+            .iter()
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect();
+        ids.sort_unstable_by_key(|(id, _)| *id);
+        ids
+    }
+
+    /// Get all bans by IP, sorted by IP
+    pub fn ips(&self) -> Vec<(String, BanEntry)> {
+        self.expire();
+        let mut ips: Vec<_> = self
+            .bans
+            .read()
+            .ips
+            .iter()
+            .map(|(ip, entry)| (ip.clone(), entry.clone()))
+            .collect();
+        ips.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        ips
+    }
+
+    fn entry(reason: &str, duration: Option<Duration>) -> BanEntry {
+        let now = SystemTime::now();
+        BanEntry {
+            reason: reason.to_string(),
+            banned_at: now,
+            expires_at: duration.map(|duration| now + duration),
+        }
+    }
+
+    /// Drop expired bans, saving if any were dropped
+    fn expire(&self) {
+        let now = SystemTime::now();
+        let has_expired = {
+            let bans = self.bans.read();
+            bans.ids.values().chain(bans.ips.values()).any(|it| it.is_expired(now))
+        };
+        if !has_expired {
+            return;
+        }
+        let result = self.update(|bans| {
+            bans.ids.retain(|_, entry| !entry.is_expired(now));
+            bans.ips.retain(|_, entry| !entry.is_expired(now));
+            true
+        });
+        if let Err(e) = result {
+            warn!("Failed to save bans after expiry: {}", e);
+        }
+    }
+
+    /// Apply `update` to the bans, saving them if it reports a change
+    fn update(&self, update: impl FnOnce(&mut Bans) -> bool) -> Result<bool> {
+        let mut bans = self.bans.write();
+        if !update(&mut bans) {
+            return Ok(false);
+        }
+        if let Some(path) = self.path.read().as_ref() {
+            // Written aside and renamed over, so a crash never leaves a truncated file
+            let temp = path.with_extension("tmp");
+            std::fs::write(&temp, serde_json::to_string(&*bans)?)?;
+            std::fs::rename(&temp, path)?;
+            debug!("Saved bans to {}", path.display());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_and_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bans.json");
+        let store = BanStore::new();
+        assert_eq!(store.open(&path).unwrap(), 0);
+
+        store.ban_id(1, "cheating", None).unwrap();
+        store.ban_id(2, "spam", Some(Duration::from_secs(3600))).unwrap();
+        store.ban_id(3, "spam", Some(Duration::ZERO)).unwrap();
+        store.ban_ip("10.0.0.1", "abuse", Some(Duration::from_secs(60))).unwrap();
+
+        let now = SystemTime::now();
+        let permanent = store.get_id(1).unwrap();
+        assert_eq!(permanent.reason, "cheating");
+        assert_eq!(permanent.remaining(now), None);
+        let remaining = store.get_id(2).unwrap().remaining(now).unwrap();
+        assert!(remaining > Duration::from_secs(3590) && remaining <= Duration::from_secs(3600));
+        assert!(store.get_id(3).is_none());
+        assert_eq!(store.ids().iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![1, 2]);
+
+        let reopened = BanStore::new();
+        assert_eq!(reopened.open(&path).unwrap(), 3);
+        assert_eq!(reopened.get_id(2).unwrap().reason, "spam");
+        assert_eq!(reopened.get_ip("10.0.0.1").unwrap().reason, "abuse");
+
+        assert!(reopened.unban_id(1).unwrap());
+        assert!(!reopened.unban_id(1).unwrap());
+        assert!(reopened.unban_ip("10.0.0.1").unwrap());
+        assert_eq!(BanStore::new().open(&path).unwrap(), 1);
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
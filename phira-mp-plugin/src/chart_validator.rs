@@ -0,0 +1,173 @@
+use parking_lot::RwLock;
+use phira_mp_common::ChartId;
+use std::sync::Arc;
+use tracing::debug;
+
+/// A chart selection to validate
+#[derive(Debug, Clone)]
+pub struct ChartSelection {
+    /// Room ID
+    pub room_id: String,
+    /// User selecting the chart
+    pub user_id: u32,
+    /// Selected chart ID
+    pub chart_id: ChartId,
+}
+
+/// Decision of a chart selection validator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChartSelectionDecision {
+    /// Allow the selection
+    Allow,
+    /// Deny the selection with a reason shown to the user
+    Deny(String),
+}
+
+/// Chart selection validator function signature
+pub type ChartSelectionValidator =
+    Box<dyn Fn(&ChartSelection) -> ChartSelectionDecision + Send + Sync>;
+
+/// A registered validator
+struct ValidatorEntry {
+    /// Plugin that registered this validator
+    plugin: String,
+    /// Validators with higher priority run first
+    priority: i32,
+    /// Validator function
+    validator: Arc<ChartSelectionValidator>,
+}
+
+/// Registry of chart selection validators
+#[derive(Default)]
+pub struct ChartSelectionValidators {
+    /// Validators sorted by descending priority, then registration order
+    validators: RwLock<Vec<ValidatorEntry>>,
+}
+
+impl ChartSelectionValidators {
+    /// Create a new validator registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a validator
+    pub fn register(&self, plugin: &str, priority: i32, validator: ChartSelectionValidator) {
+        debug!(
+            "Registering chart validator from plugin '{}' with priority {}",
+            plugin, priority
+        );
+        let mut validators = self.validators.write();
+        let index = validators.partition_point(|entry| entry.priority >= priority);
+        validators.insert(
+            index,
+            ValidatorEntry {
+                plugin: plugin.to_string(),
+                priority,
+                validator: Arc::new(validator),
+            },
+        );
+    }
+
+    /// Unregister all validators from a plugin
+    pub fn unregister_all_from_plugin(&self, plugin: &str) {
+        self.validators
+            .write()
+            .retain(|entry| entry.plugin != plugin);
+    }
+
+    /// Run validators in priority order; the first denial wins
+    pub fn validate(&self, selection: &ChartSelection) -> ChartSelectionDecision {
+        let validators: Vec<_> = self
+            .validators
+            .read()
+            .iter()
+            .map(|entry| (entry.plugin.clone(), Arc::clone(&entry.validator)))
+            .collect();
+        for (plugin, validator) in validators {
+            if let ChartSelectionDecision::Deny(reason) = validator(selection) {
+                debug!(
+                    "Chart {} in room {} denied by plugin '{}': {}",
+                    selection.chart_id, selection.room_id, plugin, reason
+                );
+                return ChartSelectionDecision::Deny(reason);
+            }
+        }
+        ChartSelectionDecision::Allow
+    }
+
+    /// Number of registered validators
+    pub fn len(&self) -> usize {
+        self.validators.read().len()
+    }
+
+    /// Whether no validators are registered
+    pub fn is_empty(&self) -> bool {
+        self.validators.read().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(chart_id: u32) -> ChartSelection {
+        ChartSelection {
+            room_id: "room".to_string(),
+            user_id: 1,
+            chart_id: ChartId::try_from(chart_id).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_deny_odd_charts() {
+        let validators = ChartSelectionValidators::new();
+        validators.register(
+            "tournament",
+            0,
+            Box::new(|selection| {
+                if selection.chart_id.get() % 2 == 1 {
+                    ChartSelectionDecision::Deny("odd chart".to_string())
+                } else {
+                    ChartSelectionDecision::Allow
+                }
+            }),
+        );
+
+        assert_eq!(
+            validators.validate(&selection(2)),
+            ChartSelectionDecision::Allow
+        );
+        assert_eq!(
+            validators.validate(&selection(3)),
+            ChartSelectionDecision::Deny("odd chart".to_string())
+        );
+
+        validators.unregister_all_from_plugin("tournament");
+        assert_eq!(
+            validators.validate(&selection(3)),
+            ChartSelectionDecision::Allow
+        );
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let validators = ChartSelectionValidators::new();
+        for (plugin, priority) in [("low", -1), ("high", 10), ("default", 0), ("default2", 0)] {
+            validators.register(
+                plugin,
+                priority,
+                Box::new(move |_| ChartSelectionDecision::Deny(plugin.to_string())),
+            );
+        }
+        assert_eq!(
+            validators.validate(&selection(1)),
+            ChartSelectionDecision::Deny("high".to_string())
+        );
+
+        validators.unregister_all_from_plugin("high");
+        assert_eq!(
+            validators.validate(&selection(1)),
+            ChartSelectionDecision::Deny("default".to_string())
+        );
+    }
+}
@@ -1,18 +1,139 @@
-use crate::Error;
+use crate::{Error, cooldown::CooldownTracker, monitoring::MetricsCollector};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    pin::Pin,
     sync::Arc,
+    time::Duration,
 };
 use parking_lot::RwLock;
 use regex::Regex;
-use tracing::{info, debug, warn};
+use tracing::{Instrument, debug, debug_span, info, warn};
 
 /// Command handler function signature
 pub type CommandHandler = Box<dyn Fn(&str, &[String]) -> Result<String, Error> + Send + Sync>;
 
+/// Asynchronous command handler function signature, given the command name and arguments
+pub type AsyncCommandHandler = Box<
+    dyn Fn(String, Vec<String>) -> Pin<Box<dyn Future<Output = Result<String, Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Handler of a command
+pub enum CommandHandlerKind {
+    /// Runs to completion on the calling thread
+    Sync(CommandHandler),
+    /// Awaited by [`CommandRegistry::execute_async`]
+    Async(AsyncCommandHandler),
+    /// No handler of its own, the command only routes to its subcommands
+    Subcommands,
+}
+
 /// Command argument parser
 pub type ArgumentParser = Box<dyn Fn(&str) -> Result<Vec<String>, Error> + Send + Sync>;
 
+/// Command line rewriter, returning the rewritten line or `None` to pass it through
+pub type CommandRewriter = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Argument completer, given the arguments typed so far with the last one possibly partial
+/// (empty after a trailing space), returning candidates for the last one
+pub type CommandCompleter = Box<dyn Fn(&[String]) -> Vec<String> + Send + Sync>;
+
+/// Maximum number of rewrites applied to one command line
+pub const MAX_REWRITE_DEPTH: usize = 8;
+
+/// A registered command rewriter
+struct RewriterEntry {
+    /// Plugin that registered this rewriter
+    plugin: String,
+    /// Rewriters with higher priority run first
+    priority: i32,
+    /// Rewriter function
+    rewriter: Arc<CommandRewriter>,
+}
+
+/// Argument accepted by a command, listed in the command catalog
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ArgSpec {
+    /// Argument name
+    pub name: String,
+    /// Argument description
+    pub description: String,
+    /// Whether the argument must be given
+    pub required: bool,
+}
+
+impl ArgSpec {
+    /// Create a required argument
+    pub fn required(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            required: true,
+        }
+    }
+
+    /// Create an optional argument
+    pub fn optional(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            required: false,
+            ..Self::required(name, description)
+        }
+    }
+}
+
+/// Span covering a command handler call
+fn command_span(command: &Command) -> tracing::Span {
+    debug_span!("command", command = %command.name, plugin = %command.plugin)
+}
+
+/// Caller of a command, checked against the permissions the command declares
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandContext {
+    /// Invoking user ID, 0 for the console and plugins
+    pub user_id: u32,
+    /// Permissions granted to the caller
+    pub permissions: HashSet<String>,
+    /// Plugin issuing the command on its own behalf
+    pub plugin: Option<String>,
+    /// Whether the caller is the server console, which holds every permission
+    pub console: bool,
+}
+
+impl CommandContext {
+    /// Create a context for a user holding `permissions`
+    pub fn new<S: Into<String>>(user_id: u32, permissions: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            user_id,
+            permissions: permissions.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Create a context for the server console
+    pub fn console() -> Self {
+        Self {
+            console: true,
+            ..Self::default()
+        }
+    }
+
+    /// Create a context for a plugin holding the permissions it declares
+    pub fn plugin<S: Into<String>>(name: &str, permissions: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            plugin: Some(name.to_string()),
+            permissions: permissions.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Check if the caller holds a permission
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.console || self.permissions.contains(permission)
+    }
+}
+
 /// Command structure
 pub struct Command {
     /// Command name
@@ -20,13 +141,21 @@ pub struct Command {
     /// Command description
     pub description: String,
     /// Command handler
-    pub handler: CommandHandler,
+    pub handler: CommandHandlerKind,
     /// Argument parser (optional)
     pub argument_parser: Option<ArgumentParser>,
     /// Command permissions (optional)
     pub permissions: Option<Vec<String>>,
     /// Command aliases (optional)
     pub aliases: Vec<String>,
+    /// Accepted arguments, in order
+    pub args: Vec<ArgSpec>,
+    /// Minimum time between invocations by the same caller (optional)
+    pub cooldown: Option<Duration>,
+    /// Child commands by name, selected by the first argument
+    pub subcommands: BTreeMap<String, Arc<Command>>,
+    /// Argument completer (optional)
+    pub completer: Option<CommandCompleter>,
     /// Plugin that registered this command
     pub plugin: String,
 }
@@ -38,6 +167,36 @@ impl Command {
         description: impl Into<String>,
         handler: CommandHandler,
         plugin: impl Into<String>,
+    ) -> Self {
+        Self::with_handler(name, description, CommandHandlerKind::Sync(handler), plugin)
+    }
+
+    /// Create a new command with an asynchronous handler
+    ///
+    /// It can only be run through [`CommandRegistry::execute_async`].
+    pub fn new_async(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: AsyncCommandHandler,
+        plugin: impl Into<String>,
+    ) -> Self {
+        Self::with_handler(name, description, CommandHandlerKind::Async(handler), plugin)
+    }
+
+    /// Create a command that only groups subcommands, added with [`Self::with_subcommand`]
+    pub fn group(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        plugin: impl Into<String>,
+    ) -> Self {
+        Self::with_handler(name, description, CommandHandlerKind::Subcommands, plugin)
+    }
+
+    fn with_handler(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: CommandHandlerKind,
+        plugin: impl Into<String>,
     ) -> Self {
         Self {
             name: name.into(),
@@ -46,6 +205,10 @@ impl Command {
             argument_parser: None,
             permissions: None,
             aliases: Vec::new(),
+            args: Vec::new(),
+            cooldown: None,
+            subcommands: BTreeMap::new(),
+            completer: None,
             plugin: plugin.into(),
         }
     }
@@ -68,6 +231,32 @@ impl Command {
         self
     }
 
+    /// Set the minimum time between invocations by the same caller
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Add a subcommand, run when the first argument is its name or an alias
+    ///
+    /// Other arguments still go to this command's handler, if it has one.
+    pub fn with_subcommand(mut self, command: Command) -> Self {
+        self.subcommands.insert(command.name.clone(), Arc::new(command));
+        self
+    }
+
+    /// Set argument completer
+    pub fn with_completer(mut self, completer: CommandCompleter) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
+    /// Describe accepted arguments
+    pub fn with_args(mut self, args: Vec<ArgSpec>) -> Self {
+        self.args = args;
+        self
+    }
+
     /// Parse command arguments
     pub fn parse_arguments(&self, args_str: &str) -> Result<Vec<String>, Error> {
         if let Some(parser) = &self.argument_parser {
@@ -81,10 +270,117 @@ impl Command {
         }
     }
 
+    /// Fail unless the caller holds every permission the command declares
+    ///
+    /// An unknown caller (`None`) holds no permissions.
+    pub fn check_permissions(&self, context: Option<&CommandContext>) -> Result<(), Error> {
+        let Some(required) = &self.permissions else {
+            return Ok(());
+        };
+        let missing = required
+            .iter()
+            .find(|it| !context.is_some_and(|context| context.has_permission(it)));
+        match (missing, context) {
+            (None, _) => Ok(()),
+            (Some(missing), Some(context)) => Err(Error::Command(format!(
+                "User {} lacks the {} permission for command '{}'",
+                context.user_id, missing, self.name
+            ))),
+            (Some(missing), None) => Err(Error::Command(format!(
+                "Command '{}' requires the {} permission",
+                self.name, missing
+            ))),
+        }
+    }
+
     /// Execute the command
+    ///
+    /// Subcommands are not routed to here, see [`CommandRegistry::execute`].
     pub fn execute(&self, args_str: &str) -> Result<String, Error> {
         let args = self.parse_arguments(args_str)?;
-        (self.handler)(&self.name, &args)
+        match &self.handler {
+            CommandHandlerKind::Sync(handler) => handler(&self.name, &args),
+            CommandHandlerKind::Async(_) => Err(Error::Command(format!(
+                "Command '{}' is asynchronous and must be executed asynchronously",
+                self.name
+            ))),
+            CommandHandlerKind::Subcommands => Err(self.unknown_subcommand(args_str)),
+        }
+    }
+
+    /// Execute the command, awaiting an asynchronous handler
+    pub async fn execute_async(&self, args_str: &str) -> Result<String, Error> {
+        let args = self.parse_arguments(args_str)?;
+        match &self.handler {
+            CommandHandlerKind::Sync(handler) => handler(&self.name, &args),
+            CommandHandlerKind::Async(handler) => handler(self.name.clone(), args).await,
+            CommandHandlerKind::Subcommands => Err(self.unknown_subcommand(args_str)),
+        }
+    }
+
+    /// Get the subcommand named by `name` or one of its aliases
+    pub fn subcommand(&self, name: &str) -> Option<&Arc<Command>> {
+        self.subcommands.values().find(|it| it.matches(name))
+    }
+
+    /// Describe the subcommand tree, one indented `name - description` line per command
+    pub fn subcommand_tree(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.push_subcommand_tree(1, &mut lines);
+        lines
+    }
+
+    fn push_subcommand_tree(&self, depth: usize, lines: &mut Vec<String>) {
+        for command in self.subcommands.values() {
+            lines.push(format!(
+                "{}{} - {}",
+                "  ".repeat(depth),
+                command.name,
+                command.description
+            ));
+            command.push_subcommand_tree(depth + 1, lines);
+        }
+    }
+
+    fn unknown_subcommand(&self, args_str: &str) -> Error {
+        let available = self.subcommands.keys().cloned().collect::<Vec<_>>().join(", ");
+        match args_str.split_whitespace().next() {
+            Some(name) => Error::Command(format!(
+                "unknown subcommand {}, available: {}",
+                name, available
+            )),
+            None => Error::Command(format!("missing subcommand, available: {}", available)),
+        }
+    }
+
+    /// Complete the last of `args`, from subcommand names and aliases and the completer
+    fn complete_args(&self, args: &[String]) -> Vec<String> {
+        if let [first, rest @ ..] = args
+            && !rest.is_empty()
+            && let Some(subcommand) = self.subcommand(first)
+        {
+            return subcommand.complete_args(rest);
+        }
+        let prefix = args.last().map(String::as_str).unwrap_or("");
+        let mut completions = Vec::new();
+        if args.len() <= 1 {
+            for command in self.subcommands.values() {
+                completions.extend(
+                    std::iter::once(&command.name)
+                        .chain(&command.aliases)
+                        .filter(|name| name.starts_with(prefix))
+                        .cloned(),
+                );
+            }
+        }
+        if let Some(completer) = &self.completer {
+            completions.extend(
+                completer(args)
+                    .into_iter()
+                    .filter(|candidate| candidate.starts_with(prefix)),
+            );
+        }
+        completions
     }
 
     /// Check if command matches a name or alias
@@ -99,6 +395,14 @@ pub struct CommandRegistry {
     commands: RwLock<HashMap<String, Arc<Command>>>,
     /// Command aliases mapping
     aliases: RwLock<HashMap<String, String>>,
+    /// Cooldowns of command callers
+    cooldowns: Arc<CooldownTracker>,
+    /// Rewriters sorted by descending priority, then registration order
+    rewriters: RwLock<Vec<RewriterEntry>>,
+    /// Plugins whose commands are rejected until resumed
+    paused_plugins: RwLock<HashSet<String>>,
+    /// Collector command executions are tracked in, if any
+    metrics: RwLock<Option<Arc<MetricsCollector>>>,
 }
 
 impl CommandRegistry {
@@ -107,9 +411,38 @@ impl CommandRegistry {
         Self {
             commands: RwLock::new(HashMap::new()),
             aliases: RwLock::new(HashMap::new()),
+            cooldowns: Arc::new(CooldownTracker::new()),
+            rewriters: RwLock::new(Vec::new()),
+            paused_plugins: RwLock::new(HashSet::new()),
+            metrics: RwLock::new(None),
         }
     }
 
+    /// Track command executions in a metrics collector, see [`MetricsCollector::track`]
+    pub fn set_metrics_collector(&self, metrics: Arc<MetricsCollector>) {
+        *self.metrics.write() = Some(metrics);
+    }
+
+    /// Reject the commands of a plugin until [`Self::resume_plugin`]
+    pub fn pause_plugin(&self, plugin: impl Into<String>) {
+        self.paused_plugins.write().insert(plugin.into());
+    }
+
+    /// Accept the commands of a paused plugin again, returning whether it was paused
+    pub fn resume_plugin(&self, plugin: &str) -> bool {
+        self.paused_plugins.write().remove(plugin)
+    }
+
+    /// Check whether the commands of a plugin are rejected
+    pub fn is_plugin_paused(&self, plugin: &str) -> bool {
+        self.paused_plugins.read().contains(plugin)
+    }
+
+    /// Get the cooldown tracker of command callers
+    pub fn cooldowns(&self) -> &Arc<CooldownTracker> {
+        &self.cooldowns
+    }
+
     /// Register a command
     pub fn register(&self, command: Command) -> Result<(), Error> {
         let name = command.name.clone();
@@ -200,16 +533,120 @@ impl CommandRegistry {
         for name in commands_to_remove {
             self.unregister(&name)?;
         }
+        self.rewriters.write().retain(|entry| entry.plugin != plugin);
         
         info!("All commands from plugin '{}' unregistered", plugin);
         Ok(())
     }
 
-    /// Execute a command
-    pub fn execute(&self, command_line: &str) -> Result<String, Error> {
+    /// Register a command line rewriter, applied before a command line is executed
+    pub fn register_rewriter(&self, plugin: &str, priority: i32, rewriter: CommandRewriter) {
+        debug!(
+            "Registering command rewriter from plugin '{}' with priority {}",
+            plugin, priority
+        );
+        let mut rewriters = self.rewriters.write();
+        let index = rewriters.partition_point(|entry| entry.priority >= priority);
+        rewriters.insert(
+            index,
+            RewriterEntry {
+                plugin: plugin.to_string(),
+                priority,
+                rewriter: Arc::new(rewriter),
+            },
+        );
+    }
+
+    /// Rewrite a command line until no rewriter changes it
+    ///
+    /// Each round applies the first rewriter in priority order that changes the line.
+    /// Fails if the line is still changing after [`MAX_REWRITE_DEPTH`] rewrites.
+    pub fn rewrite(&self, command_line: &str) -> Result<String, Error> {
+        let rewriters: Vec<_> = self
+            .rewriters
+            .read()
+            .iter()
+            .map(|entry| (entry.plugin.clone(), Arc::clone(&entry.rewriter)))
+            .collect();
+        let mut line = command_line.to_string();
+        for _ in 0..=MAX_REWRITE_DEPTH {
+            let rewritten = rewriters.iter().find_map(|(plugin, rewriter)| {
+                rewriter(&line)
+                    .filter(|it| *it != line)
+                    .map(|it| (plugin, it))
+            });
+            let Some((plugin, rewritten)) = rewritten else {
+                return Ok(line);
+            };
+            debug!(
+                "Command line '{}' rewritten to '{}' by plugin '{}'",
+                line, rewritten, plugin
+            );
+            line = rewritten;
+        }
+        Err(Error::Command(format!(
+            "Command '{}' exceeded the rewrite depth of {}",
+            command_line, MAX_REWRITE_DEPTH
+        )))
+    }
+
+    /// Execute a command on behalf of a caller, `None` if the caller is unknown
+    ///
+    /// Commands declaring permissions are refused to callers lacking any of them, and to
+    /// unknown callers.
+    pub fn execute(
+        &self,
+        command_line: &str,
+        context: Option<&CommandContext>,
+    ) -> Result<String, Error> {
         debug!("Executing command line: '{}'", command_line);
-        
-        let (command_name, args_str) = self.parse_command_line(command_line);
+        let (command, args_str, cooldown) = self.resolve(command_line, context)?;
+        let _span = command_span(&command).entered();
+        let metrics = self.metrics.read().clone();
+        let result = match metrics {
+            Some(metrics) => metrics.track(&command.plugin, || command.execute(&args_str)),
+            None => command.execute(&args_str),
+        };
+        self.release_cooldown(cooldown, &result);
+        result
+    }
+
+    /// Like [`Self::execute`], but also runs asynchronous commands, awaiting their handlers
+    pub async fn execute_async(
+        &self,
+        command_line: &str,
+        context: Option<&CommandContext>,
+    ) -> Result<String, Error> {
+        debug!("Executing command line asynchronously: '{}'", command_line);
+        let (command, args_str, cooldown) = self.resolve(command_line, context)?;
+        let tracker = self
+            .metrics
+            .read()
+            .clone()
+            .and_then(|metrics| Some((metrics.start_request(&command.plugin)?, metrics)));
+        let result = command
+            .execute_async(&args_str)
+            .instrument(command_span(&command))
+            .await;
+        if let Some((tracker, metrics)) = tracker {
+            metrics.finish_request(tracker, result.is_ok());
+        }
+        self.release_cooldown(cooldown, &result);
+        result
+    }
+
+    /// Rewrite a command line and look up its command, checking the caller's permissions
+    /// and cooldown
+    ///
+    /// Also returns the key of the cooldown reserved for the caller, to release if the command
+    /// fails.
+    fn resolve(
+        &self,
+        command_line: &str,
+        context: Option<&CommandContext>,
+    ) -> Result<(Arc<Command>, String, Option<String>), Error> {
+        let command_line = self.rewrite(command_line)?;
+        let (command_name, args_str) = self.parse_command_line(&command_line);
         
         // Resolve alias
         let actual_command_name = self.resolve_alias(&command_name)
@@ -221,15 +658,83 @@ impl CommandRegistry {
             commands.get(&actual_command_name).cloned()
         };
         
-        match command {
-            Some(command) => {
-                // TODO: Check permissions here
-                command.execute(args_str)
-            }
-            None => Err(Error::Command(format!("Command '{}' not found", command_name))),
+        let Some(mut command) = command else {
+            return Err(Error::Command(format!("Command '{}' not found", command_name)));
+        };
+        if self.is_plugin_paused(&command.plugin) {
+            return Err(Error::Command(format!("plugin paused: {}", command.plugin)));
+        }
+
+        // Descend into subcommands while the first argument names one
+        let mut path = command.name.clone();
+        let mut args_str = args_str;
+        loop {
+            command.check_permissions(context)?;
+            let (name, rest) = args_str
+                .split_once(char::is_whitespace)
+                .unwrap_or((args_str, ""));
+            let Some(subcommand) = command.subcommand(name).cloned() else {
+                break;
+            };
+            path = format!("{} {}", path, subcommand.name);
+            command = subcommand;
+            args_str = rest.trim();
+        }
+        let cooldown = self.reserve_cooldown(&path, &command, context)?;
+        Ok((command, args_str.to_string(), cooldown))
+    }
+
+    /// Start the caller's cooldown of a command, failing if it is still running, and return
+    /// the cooldown key
+    ///
+    /// The cooldown is started before the handler runs, so that concurrent calls from the
+    /// same caller cannot all pass it. The server console and unknown callers (`None`) are
+    /// not rate limited. Cooldowns live in a [`CooldownTracker`], which bounds the
+    /// number of tracked callers.
+    fn reserve_cooldown(
+        &self,
+        path: &str,
+        command: &Command,
+        context: Option<&CommandContext>,
+    ) -> Result<Option<String>, Error> {
+        let (Some(cooldown), Some(context)) = (command.cooldown, context.filter(|it| !it.console))
+        else {
+            return Ok(None);
+        };
+        let key = match &context.plugin {
+            Some(plugin) => format!("command:{}:plugin:{}", path, plugin),
+            None => format!("command:{}:{}", path, context.user_id),
+        };
+        if self.cooldowns.check(&key, cooldown) {
+            return Ok(Some(key));
+        }
+        let remaining = self.cooldowns.remaining(&key).unwrap_or_default();
+        Err(Error::Command(format!(
+            "command on cooldown, try again in {}s",
+            remaining.as_secs_f64().ceil() as u64
+        )))
+    }
+
+    /// Clear a cooldown reserved by [`Self::reserve_cooldown`] if the command failed, so
+    /// that rejected arguments and failing handlers do not cost the caller a use
+    fn release_cooldown(&self, cooldown: Option<String>, result: &Result<String, Error>) {
+        if let (Some(key), Err(_)) = (cooldown, result) {
+            self.cooldowns.reset(&key);
         }
     }
 
+    /// Execute command lines in order, collecting the result of each executed line
+    ///
+    /// With `stop_on_error` the batch ends at the first failing line.
+    pub fn execute_batch(
+        &self,
+        command_lines: &[String],
+        stop_on_error: bool,
+        context: Option<&CommandContext>,
+    ) -> Vec<Result<String, Error>> {
+        run_batch(command_lines, stop_on_error, |line| self.execute(line, context))
+    }
+
     /// Get a command by name
     pub fn get_command(&self, name: &str) -> Option<Arc<Command>> {
         let actual_name = self.resolve_alias(name).unwrap_or_else(|| name.to_string());
@@ -267,6 +772,47 @@ impl CommandRegistry {
             .collect()
     }
 
+    /// Complete a partially typed command line, sorted
+    ///
+    /// Without a space, command names and aliases starting with `partial` are returned.
+    /// Otherwise the last word is completed from the subcommands and the completer of the
+    /// command it belongs to.
+    pub fn complete(&self, partial: &str) -> Vec<String> {
+        let partial = partial.trim_start();
+        let mut completions = match partial.split_once(char::is_whitespace) {
+            None => {
+                let mut names: Vec<String> = self
+                    .commands
+                    .read()
+                    .keys()
+                    .filter(|name| name.starts_with(partial))
+                    .cloned()
+                    .collect();
+                names.extend(
+                    self.aliases
+                        .read()
+                        .keys()
+                        .filter(|alias| alias.starts_with(partial))
+                        .cloned(),
+                );
+                names
+            }
+            Some((name, rest)) => {
+                let Some(command) = self.get_command(name) else {
+                    return Vec::new();
+                };
+                let mut args: Vec<String> = rest.split_whitespace().map(String::from).collect();
+                if args.is_empty() || rest.ends_with(char::is_whitespace) {
+                    args.push(String::new());
+                }
+                command.complete_args(&args)
+            }
+        };
+        completions.sort();
+        completions.dedup();
+        completions
+    }
+
     /// Parse a command line into command name and arguments string
     fn parse_command_line<'a>(&self, command_line: &'a str) -> (String, &'a str) {
         let command_line = command_line.trim();
@@ -358,6 +904,24 @@ impl CommandRegistry {
     }
 }
 
+/// Run command lines in order with `execute`, ending at the first error if `stop_on_error`
+pub fn run_batch(
+    command_lines: &[String],
+    stop_on_error: bool,
+    mut execute: impl FnMut(&str) -> Result<String, Error>,
+) -> Vec<Result<String, Error>> {
+    let mut results = Vec::with_capacity(command_lines.len());
+    for line in command_lines {
+        let result = execute(line);
+        let failed = result.is_err();
+        results.push(result);
+        if failed && stop_on_error {
+            break;
+        }
+    }
+    results
+}
+
 /// Command registry statistics
 #[derive(Debug, Clone)]
 pub struct CommandRegistryStats {
@@ -457,7 +1021,7 @@ mod tests {
         let command = Command::new("hello", "Say hello", handler, "test_plugin");
         registry.register(command).unwrap();
         
-        let result = registry.execute("hello world").unwrap();
+        let result = registry.execute("hello world", None).unwrap();
         assert_eq!(result, "Hello from hello with args: [\"world\"]");
     }
     
@@ -478,4 +1042,282 @@ mod tests {
         assert!(registry.get_command("testcmd").is_some());
         assert!(registry.get_command("test").is_some());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_command_rewriters() {
+        let registry = CommandRegistry::new();
+        let handler: CommandHandler =
+            Box::new(|name, args| Ok(format!("{} {}", name, args.join(" "))));
+        registry
+            .register(Command::new("say", "Say something", handler, "chat"))
+            .unwrap();
+        registry.register_rewriter(
+            "shortcuts",
+            0,
+            Box::new(|line| (line.trim() == "gg").then(|| "say good game".to_string())),
+        );
+        registry.register_rewriter(
+            "shortcuts",
+            10,
+            Box::new(|line| line.strip_prefix("!").map(|rest| rest.to_string())),
+        );
+
+        assert_eq!(registry.execute("gg", None).unwrap(), "say good game");
+        // Rewrites chain until the line settles
+        assert_eq!(registry.execute("!gg", None).unwrap(), "say good game");
+        assert_eq!(registry.execute("say hi", None).unwrap(), "say hi");
+
+        registry.register_rewriter(
+            "loop",
+            0,
+            Box::new(|line| match line {
+                "ping" => Some("pong".to_string()),
+                "pong" => Some("ping".to_string()),
+                _ => None,
+            }),
+        );
+        assert!(registry.execute("ping", None).is_err());
+
+        registry.unregister_all_from_plugin("shortcuts").unwrap();
+        assert!(registry.execute("gg", None).is_err());
+    }
+
+    #[test]
+    fn test_command_batch() {
+        let registry = CommandRegistry::new();
+        let handler: CommandHandler = Box::new(|_name, args| Ok(args.join(" ")));
+        registry
+            .register(Command::new("echo", "Echo arguments", handler, "test_plugin"))
+            .unwrap();
+        let lines: Vec<String> = ["echo a", "missing", "echo b"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+
+        let results = registry.execute_batch(&lines, true, None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), "a");
+        assert!(results[1].is_err());
+
+        let results = registry.execute_batch(&lines, false, None);
+        assert_eq!(results.len(), 3);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), "b");
+    }
+
+    #[test]
+    fn test_command_permissions() {
+        let registry = CommandRegistry::new();
+        let handler: CommandHandler = Box::new(|_name, _args| Ok("done".to_string()));
+        registry
+            .register(
+                Command::new("ban", "Ban a user", handler, "moderation")
+                    .with_permissions(vec!["admin".to_string()]),
+            )
+            .unwrap();
+        let handler: CommandHandler = Box::new(|_name, _args| Ok("pong".to_string()));
+        registry
+            .register(Command::new("ping", "Ping", handler, "moderation"))
+            .unwrap();
+
+        let user = CommandContext::new(1, ["moderator"]);
+        let admin = CommandContext::new(2, ["admin"]);
+        assert!(matches!(
+            registry.execute("ban 3", Some(&user)),
+            Err(Error::Command(_))
+        ));
+        assert_eq!(registry.execute("ban 3", Some(&admin)).unwrap(), "done");
+        assert_eq!(
+            registry.execute("ban 3", Some(&CommandContext::console())).unwrap(),
+            "done"
+        );
+        assert!(registry.execute("ban 3", None).is_err());
+        let plugin = CommandContext::plugin("moderation", ["admin"]);
+        assert_eq!(registry.execute("ban 3", Some(&plugin)).unwrap(), "done");
+        // Commands without permissions run for anyone
+        assert_eq!(registry.execute("ping", Some(&user)).unwrap(), "pong");
+    }
+
+    #[tokio::test]
+    async fn test_async_command() {
+        let registry = CommandRegistry::new();
+        let handler: AsyncCommandHandler = Box::new(|name, args| {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                Ok(format!("{} {}", name, args.join(" ")))
+            })
+        });
+        registry
+            .register(Command::new_async("fetch", "Fetch something", handler, "net"))
+            .unwrap();
+        let handler: CommandHandler = Box::new(|_name, args| Ok(args.join(" ")));
+        registry
+            .register(Command::new("echo", "Echo arguments", handler, "net"))
+            .unwrap();
+
+        assert_eq!(registry.execute_async("fetch a b", None).await.unwrap(), "fetch a b");
+        assert_eq!(registry.execute_async("echo a", None).await.unwrap(), "a");
+        // The synchronous path cannot await
+        assert!(registry.execute("fetch a", None).is_err());
+        assert_eq!(registry.execute("echo a", None).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_command_cooldown() {
+        let registry = CommandRegistry::new();
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let handler: CommandHandler = Box::new(move |_name, args| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            match args.first().map(String::as_str) {
+                None => Err(Error::Command("nothing to shout".to_string())),
+                Some(_) => Ok("sent".to_string()),
+            }
+        });
+        registry
+            .register(
+                Command::new("shout", "Broadcast a message", handler, "chat")
+                    .with_cooldown(Duration::from_millis(50)),
+            )
+            .unwrap();
+
+        let alice = CommandContext::new(1, Vec::<String>::new());
+        let bob = CommandContext::new(2, Vec::<String>::new());
+        // Failing calls do not start the cooldown
+        assert!(registry.execute("shout", Some(&alice)).is_err());
+        assert!(registry.execute("shout hi", Some(&alice)).is_ok());
+        match registry.execute("shout hi", Some(&alice)) {
+            Err(Error::Command(message)) => {
+                assert_eq!(message, "command on cooldown, try again in 1s")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // Cooldowns are per caller, and the console is exempt
+        assert!(registry.execute("shout hi", Some(&bob)).is_ok());
+        let console = CommandContext::console();
+        assert!(registry.execute("shout hi", Some(&console)).is_ok());
+        assert!(registry.execute("shout hi", Some(&console)).is_ok());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 5);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(registry.execute("shout hi", Some(&alice)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_command_cooldown() {
+        let registry = CommandRegistry::new();
+        let handler: AsyncCommandHandler = Box::new(|_name, args| {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                match args.first().map(String::as_str) {
+                    Some("fail") => Err(Error::Command("failed".to_string())),
+                    _ => Ok("fetched".to_string()),
+                }
+            })
+        });
+        registry
+            .register(
+                Command::new_async("fetch", "Fetch something", handler, "net")
+                    .with_cooldown(Duration::from_secs(60)),
+            )
+            .unwrap();
+
+        let alice = CommandContext::new(1, Vec::<String>::new());
+        // Only one of the calls racing through an awaiting handler passes the cooldown
+        let (first, second) = tokio::join!(
+            registry.execute_async("fetch", Some(&alice)),
+            registry.execute_async("fetch", Some(&alice)),
+        );
+        assert_eq!(first.is_ok() as u8 + second.is_ok() as u8, 1);
+
+        // A failing call releases its reservation
+        let bob = CommandContext::new(2, Vec::<String>::new());
+        assert!(registry.execute_async("fetch fail", Some(&bob)).await.is_err());
+        assert!(registry.execute_async("fetch", Some(&bob)).await.is_ok());
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let registry = CommandRegistry::new();
+        let get: CommandHandler = Box::new(|name, args| Ok(format!("{} {}", name, args.join(" "))));
+        let set: CommandHandler = Box::new(|name, args| Ok(format!("{} {}", name, args.join(" "))));
+        let reset: CommandHandler = Box::new(|_name, _args| Ok("reset".to_string()));
+        let config = Command::group("config", "Manage configuration", "settings")
+            .with_subcommand(Command::new("get", "Read a value", get, "settings"))
+            .with_subcommand(
+                Command::new("set", "Write a value", set, "settings")
+                    .with_aliases(vec!["put".to_string()])
+                    .with_subcommand(Command::new("reset", "Restore the default", reset, "settings")),
+            );
+        registry.register(config).unwrap();
+        let handler: CommandHandler = Box::new(|_name, args| Ok(format!("queue {}", args.join(" "))));
+        let check: CommandHandler = Box::new(|_name, _args| Ok("checked".to_string()));
+        registry
+            .register(
+                Command::new("queue", "Join a queue", handler, "matchmaking")
+                    .with_subcommand(Command::new("check", "Check the queue", check, "matchmaking")),
+            )
+            .unwrap();
+
+        assert_eq!(registry.execute("config get volume", None).unwrap(), "get volume");
+        assert_eq!(registry.execute("config put volume 3", None).unwrap(), "set volume 3");
+        assert_eq!(registry.execute("config set reset", None).unwrap(), "reset");
+        match registry.execute("config drop volume", None) {
+            Err(Error::Command(message)) => {
+                assert_eq!(message, "unknown subcommand drop, available: get, set")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(registry.execute("config", None).is_err());
+        // A parent with its own handler takes arguments naming no subcommand
+        assert_eq!(registry.execute("queue ranked", None).unwrap(), "queue ranked");
+        assert_eq!(registry.execute("queue check", None).unwrap(), "checked");
+
+        let tree = registry.get_command("config").unwrap().subcommand_tree();
+        assert_eq!(
+            tree,
+            vec![
+                "  get - Read a value",
+                "  set - Write a value",
+                "    reset - Restore the default",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_completion() {
+        let registry = CommandRegistry::new();
+        let handler: CommandHandler = Box::new(|_name, _args| Ok(String::new()));
+        registry
+            .register(Command::new("kick", "Kick a player", handler, "moderation").with_aliases(vec!["k".to_string()]))
+            .unwrap();
+        let handler: CommandHandler = Box::new(|_name, _args| Ok(String::new()));
+        registry
+            .register(Command::new("kill", "Stop a room", handler, "moderation"))
+            .unwrap();
+        let get: CommandHandler = Box::new(|_name, _args| Ok(String::new()));
+        let set: CommandHandler = Box::new(|_name, _args| Ok(String::new()));
+        registry
+            .register(
+                Command::group("config", "Manage configuration", "settings")
+                    .with_subcommand(Command::new("get", "Read a value", get, "settings").with_completer(
+                        Box::new(|_args| vec!["volume".to_string(), "language".to_string()]),
+                    ))
+                    .with_subcommand(
+                        Command::new("set", "Write a value", set, "settings")
+                            .with_aliases(vec!["put".to_string()]),
+                    ),
+            )
+            .unwrap();
+
+        assert_eq!(registry.complete("ki"), vec!["kick", "kill"]);
+        assert_eq!(registry.complete("k"), vec!["k", "kick", "kill"]);
+        assert_eq!(registry.complete("c"), vec!["config"]);
+        assert!(registry.complete("x").is_empty());
+        assert_eq!(registry.complete("config "), vec!["get", "put", "set"]);
+        assert_eq!(registry.complete("config p"), vec!["put"]);
+        assert_eq!(registry.complete("config get "), vec!["language", "volume"]);
+        assert_eq!(registry.complete("config get v"), vec!["volume"]);
+        assert!(registry.complete("unknown arg").is_empty());
+    }
+}
@@ -75,16 +75,59 @@ impl PluginConfig {
         Ok(())
     }
 
-    /// Get a configuration value
+    /// Get a configuration value, `None` if missing or of another type
     pub fn get<T>(&self, key: &str) -> Option<T>
     where
         T: serde::de::DeserializeOwned,
     {
-        self.values.get(key).and_then(|value| {
+        self.lookup(key).and_then(|value| {
             value.clone().try_into().ok()
         })
     }
 
+    /// Get a configuration value as `T`, `None` if missing
+    ///
+    /// `key` may be a dotted path into nested tables, such as `limits.max_rooms`. Fails
+    /// if the value cannot be read as `T`.
+    pub fn get_as<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.lookup(key)
+            .map(|value| {
+                value.clone().try_into().map_err(|e| {
+                    Error::Config(format!(
+                        "Config key '{}' cannot be read as {}: {}",
+                        key,
+                        std::any::type_name::<T>(),
+                        e
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Get a configuration value as `T`, `default` if missing, see [`PluginConfig::get_as`]
+    pub fn get_or_default<T>(&self, key: &str, default: T) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Ok(self.get_as(key)?.unwrap_or(default))
+    }
+
+    /// Find a value by key, or by dotted path if no key matches
+    fn lookup(&self, key: &str) -> Option<&toml::Value> {
+        if let Some(value) = self.values.get(key) {
+            return Some(value);
+        }
+        let mut parts = key.split('.');
+        let mut value = self.values.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
     /// Get a configuration value with default
     pub fn get_or<T>(&self, key: &str, default: T) -> T
     where
@@ -98,17 +141,44 @@ impl PluginConfig {
     where
         T: Serialize,
     {
-        // Serialize to JSON first, then convert to TOML
-        let json_value = serde_json::to_value(value)
+        let toml_value = toml::Value::try_from(value)
             .map_err(|e| Error::Config(format!("Failed to serialize value: {}", e)))?;
-        let json_str = serde_json::to_string(&json_value)
-            .map_err(|e| Error::Config(format!("Failed to convert to JSON string: {}", e)))?;
-        let toml_value = toml::from_str(&json_str)
-            .map_err(|e| Error::Config(format!("Failed to convert JSON to TOML: {}", e)))?;
         self.values.insert(key.to_string(), toml_value);
         Ok(())
     }
 
+    /// Set a configuration value at a dotted path, creating the tables along it
+    ///
+    /// Fails if a value along the path is not a table.
+    pub fn set_as<T>(&mut self, key: &str, value: T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let Some((parents, last)) = key.rsplit_once('.') else {
+            return self.set(key, value);
+        };
+        let toml_value = toml::Value::try_from(value)
+            .map_err(|e| Error::Config(format!("Failed to serialize value: {}", e)))?;
+        let mut parts = parents.split('.');
+        let first = parts.next().unwrap_or_default();
+        let mut table = self
+            .values
+            .entry(first.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        for part in parts {
+            table = table
+                .as_table_mut()
+                .ok_or_else(|| Error::Config(format!("Config key '{}' is not a table", first)))?
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        }
+        table
+            .as_table_mut()
+            .ok_or_else(|| Error::Config(format!("Config key '{}' is not a table", parents)))?
+            .insert(last.to_string(), toml_value);
+        Ok(())
+    }
+
     /// Remove a configuration value
     pub fn remove(&mut self, key: &str) -> Option<toml::Value> {
         self.values.remove(key)
@@ -141,6 +211,23 @@ impl PluginConfig {
             .map_err(|e| Error::Config(format!("Failed to convert to TOML: {}", e)))
     }
 
+    /// Validate the configuration against a JSON Schema
+    ///
+    /// Fails with the path of the first mismatching value, such as `/max_rooms`.
+    pub fn validate_against(&self, schema: &serde_json::Value) -> Result<(), Error> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| Error::Config(format!("Invalid config schema: {}", e)))?;
+        let instance = serde_json::to_value(&self.values)
+            .map_err(|e| Error::Config(format!("Failed to convert to JSON: {}", e)))?;
+        validator.validate(&instance).map_err(|e| {
+            let path = match e.instance_path().as_str() {
+                "" => "/",
+                path => path,
+            };
+            Error::Config(format!("Invalid config value at {}: {}", path, e))
+        })
+    }
+
     /// Merge another configuration into this one
     pub fn merge(&mut self, other: &PluginConfig) {
         for (key, value) in &other.values {
@@ -262,4 +349,41 @@ mod tests {
         assert_eq!(loaded_config.get::<String>("key1"), Some("value1".to_string()));
         assert_eq!(loaded_config.get::<i32>("key2"), Some(123));
     }
+
+    #[test]
+    fn test_typed_accessors() {
+        let mut config = PluginConfig::new();
+        config.set_as("limits.max_rooms", 8u32).unwrap();
+        config.set_as("limits.queue.size", 32u32).unwrap();
+        config.set("greeting", "hi").unwrap();
+
+        assert_eq!(config.get_as::<u32>("limits.max_rooms").unwrap(), Some(8));
+        assert_eq!(config.get_as::<u32>("limits.queue.size").unwrap(), Some(32));
+        assert_eq!(config.get_as::<u32>("limits.missing").unwrap(), None);
+        assert_eq!(config.get_or_default("limits.max_users", 4u32).unwrap(), 4);
+        assert_eq!(config.get_or_default("limits.max_rooms", 4u32).unwrap(), 8);
+        assert!(matches!(config.get_as::<u32>("greeting"), Err(Error::Config(_))));
+        assert!(matches!(config.get_or_default("greeting", 0u32), Err(Error::Config(_))));
+        assert!(matches!(config.set_as("greeting.text", "hello"), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_validate_against_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "max_rooms": { "type": "integer" } },
+            "required": ["max_rooms"],
+        });
+        let mut config = PluginConfig::new();
+        config.set("max_rooms", "ten").unwrap();
+        match config.validate_against(&schema) {
+            Err(Error::Config(message)) => assert!(message.contains("/max_rooms"), "{}", message),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        config.set("max_rooms", 10).unwrap();
+        config.validate_against(&schema).unwrap();
+        config.remove("max_rooms");
+        assert!(matches!(config.validate_against(&schema), Err(Error::Config(_))));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Default maximum number of tracked keys
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// Keyed, TTL-based cooldown tracker
+///
+/// Used by command rate limiting and, with a tracker of their own, plugins (through
+/// `HostApi::cooldown_check`).
+pub struct CooldownTracker {
+    /// Expiry time of each active cooldown
+    entries: Mutex<HashMap<String, Instant>>,
+    /// Maximum number of tracked keys
+    max_entries: usize,
+}
+
+impl Default for CooldownTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CooldownTracker {
+    /// Create a new cooldown tracker
+    pub fn new() -> Self {
+        Self::with_max_entries(DEFAULT_MAX_ENTRIES)
+    }
+
+    /// Create a new cooldown tracker holding at most `max_entries` keys
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// Check whether `key` is off cooldown, and if so start a new cooldown of `duration`
+    ///
+    /// Returns `true` if the action is allowed.
+    pub fn check(&self, key: &str, duration: Duration) -> bool {
+        let now = Instant::now();
+        let mut entries = self.entries.lock();
+        if entries.get(key).is_some_and(|expiry| *expiry > now) {
+            return false;
+        }
+        if !entries.contains_key(key) && entries.len() >= self.max_entries {
+            entries.retain(|_, expiry| *expiry > now);
+            if entries.len() >= self.max_entries {
+                // Evict the cooldown closest to expiring
+                if let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, expiry)| **expiry)
+                    .map(|(key, _)| key.clone())
+                {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(key.to_string(), now + duration);
+        true
+    }
+
+    /// Get the remaining cooldown of `key`, if any
+    pub fn remaining(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        self.entries
+            .lock()
+            .get(key)
+            .and_then(|expiry| expiry.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Clear the cooldown of `key`
+    pub fn reset(&self, key: &str) {
+        self.entries.lock().remove(key);
+    }
+
+    /// Remove all expired cooldowns
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        self.entries.lock().retain(|_, expiry| *expiry > now);
+    }
+
+    /// Number of tracked keys (including expired ones not purged yet)
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Whether no keys are tracked
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+    };
+
+    #[test]
+    fn test_check_and_expire() {
+        let tracker = CooldownTracker::new();
+        let duration = Duration::from_millis(50);
+
+        assert!(tracker.check("a", duration));
+        assert!(!tracker.check("a", duration));
+        assert!(tracker.check("b", duration));
+        assert!(tracker.remaining("a").is_some());
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(tracker.remaining("a").is_none());
+        assert!(tracker.check("a", duration));
+
+        tracker.reset("a");
+        assert!(tracker.check("a", duration));
+    }
+
+    #[test]
+    fn test_bounded_entries() {
+        let tracker = CooldownTracker::with_max_entries(2);
+        assert!(tracker.check("a", Duration::from_secs(10)));
+        assert!(tracker.check("b", Duration::from_secs(20)));
+        assert!(tracker.check("c", Duration::from_secs(30)));
+        assert_eq!(tracker.len(), 2);
+        assert!(tracker.remaining("a").is_none());
+        assert!(tracker.remaining("c").is_some());
+    }
+
+    #[test]
+    fn test_concurrent_check() {
+        let tracker = Arc::new(CooldownTracker::new());
+        let allowed = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let tracker = Arc::clone(&tracker);
+                let allowed = Arc::clone(&allowed);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        if tracker.check("shared", Duration::from_secs(60)) {
+                            allowed.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(allowed.load(Ordering::SeqCst), 1);
+    }
+}
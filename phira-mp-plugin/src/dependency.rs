@@ -1,24 +1,28 @@
 use crate::Error;
 use std::collections::{HashMap, HashSet, VecDeque};
-use petgraph::{graph::DiGraph, visit::{Dfs, EdgeRef}, algo::kosaraju_scc};
+use petgraph::{stable_graph::StableDiGraph, visit::{Dfs, EdgeRef, Reversed}, algo::kosaraju_scc};
 
 /// Dependency graph for plugins
 pub struct DependencyGraph {
-    /// Graph of plugin dependencies
-    graph: DiGraph<String, ()>,
+    /// Graph of plugin dependencies, keeping node indices stable across removals
+    graph: StableDiGraph<String, ()>,
     /// Node indices by plugin name
     node_indices: HashMap<String, petgraph::graph::NodeIndex>,
     /// Reverse mapping from node index to plugin name
     index_to_plugin: HashMap<petgraph::graph::NodeIndex, String>,
+    /// Plugins added with [`Self::add_plugin`], as opposed to those only declared as a
+    /// dependency
+    registered: HashSet<String>,
 }
 
 impl DependencyGraph {
     /// Create a new dependency graph
     pub fn new() -> Self {
         Self {
-            graph: DiGraph::new(),
+            graph: StableDiGraph::new(),
             node_indices: HashMap::new(),
             index_to_plugin: HashMap::new(),
+            registered: HashSet::new(),
         }
     }
 
@@ -26,6 +30,7 @@ impl DependencyGraph {
     pub fn add_plugin(&mut self, plugin_name: String, dependencies: Vec<String>) -> Result<(), Error> {
         // Get or create node for the plugin
         let plugin_node = self.get_or_create_node(plugin_name.clone());
+        self.registered.insert(plugin_name);
         
         // Add edges for each dependency
         for dep_name in dependencies {
@@ -37,29 +42,56 @@ impl DependencyGraph {
     }
 
     /// Remove a plugin from the graph
+    ///
+    /// A plugin still declared as a dependency by others stays in the graph as a missing
+    /// dependency of theirs.
     pub fn remove_plugin(&mut self, plugin_name: &str) {
-        if let Some(node_index) = self.node_indices.remove(plugin_name) {
-            self.index_to_plugin.remove(&node_index);
-            
-            // Remove all edges connected to this node
-            let mut edges_to_remove: Vec<petgraph::graph::EdgeIndex> = Vec::new();
-            for edge in self.graph.edges_directed(node_index, petgraph::Direction::Outgoing) {
-                edges_to_remove.push(edge.id());
-            }
-            for edge in self.graph.edges_directed(node_index, petgraph::Direction::Incoming) {
-                edges_to_remove.push(edge.id());
-            }
-            
-            // Remove edges
-            for edge_id in edges_to_remove {
-                self.graph.remove_edge(edge_id);
-            }
-            
-            // Remove the node
-            self.graph.remove_node(node_index);
+        if !self.registered.remove(plugin_name) {
+            return;
+        }
+        let Some(&node_index) = self.node_indices.get(plugin_name) else {
+            return;
+        };
+
+        // Drop the edges from its own dependencies
+        let dependencies: Vec<petgraph::graph::NodeIndex> = self
+            .graph
+            .neighbors_directed(node_index, petgraph::Direction::Incoming)
+            .collect();
+        let edges_to_remove: Vec<petgraph::graph::EdgeIndex> = self
+            .graph
+            .edges_directed(node_index, petgraph::Direction::Incoming)
+            .map(|edge| edge.id())
+            .collect();
+        for edge_id in edges_to_remove {
+            self.graph.remove_edge(edge_id);
+        }
+
+        self.remove_node_if_unused(node_index);
+        for dependency in dependencies {
+            self.remove_node_if_unused(dependency);
         }
     }
 
+    /// Remove a node that is neither a registered plugin nor declared as a dependency
+    fn remove_node_if_unused(&mut self, node_index: petgraph::graph::NodeIndex) {
+        let Some(name) = self.index_to_plugin.get(&node_index) else {
+            return;
+        };
+        if self.registered.contains(name)
+            || self
+                .graph
+                .neighbors_directed(node_index, petgraph::Direction::Outgoing)
+                .next()
+                .is_some()
+        {
+            return;
+        }
+        self.node_indices.remove(name);
+        self.index_to_plugin.remove(&node_index);
+        self.graph.remove_node(node_index);
+    }
+
     /// Check for missing dependencies
     pub fn check_missing_dependencies(&self, plugin_name: &str) -> Vec<String> {
         let mut missing = Vec::new();
@@ -70,7 +102,7 @@ impl DependencyGraph {
                 let dep_name = self.index_to_plugin.get(&neighbor).unwrap();
                 
                 // Check if the dependency plugin is actually loaded
-                if !self.node_indices.contains_key(dep_name) {
+                if !self.registered.contains(dep_name) {
                     missing.push(dep_name.clone());
                 }
             }
@@ -84,9 +116,11 @@ impl DependencyGraph {
         let mut dependencies: HashSet<String> = HashSet::new();
 
         if let Some(start_node) = self.node_indices.get(plugin_name) {
-            let mut dfs = Dfs::new(&self.graph, *start_node);
+            // Edges go from a dependency to its dependents
+            let graph = Reversed(&self.graph);
+            let mut dfs = Dfs::new(graph, *start_node);
             
-            while let Some(node) = dfs.next(&self.graph) {
+            while let Some(node) = dfs.next(graph) {
                 if node != *start_node {
                     if let Some(name) = self.index_to_plugin.get(&node) {
                         dependencies.insert(name.clone());
@@ -128,12 +162,9 @@ impl DependencyGraph {
         dependents.into_iter().collect()
     }
 
-    /// Check for circular dependencies
-    pub fn check_circular_dependencies(&self) -> Result<(), Error> {
-        let scc = kosaraju_scc(&self.graph);
-        
-        // Find strongly connected components with more than one node (circular dependencies)
-        let circular_deps: Vec<Vec<String>> = scc
+    /// Get the groups of plugins that depend on each other in a cycle
+    pub fn circular_groups(&self) -> Vec<Vec<String>> {
+        kosaraju_scc(&self.graph)
             .into_iter()
             .filter(|component: &Vec<petgraph::graph::NodeIndex>| component.len() > 1)
             .map(|component: Vec<petgraph::graph::NodeIndex>| {
@@ -142,7 +173,13 @@ impl DependencyGraph {
                     .filter_map(|node| self.index_to_plugin.get(node).cloned())
                     .collect()
             })
-            .collect();
+            .collect()
+    }
+
+    /// Check for circular dependencies
+    pub fn check_circular_dependencies(&self) -> Result<(), Error> {
+        // Find strongly connected components with more than one node (circular dependencies)
+        let circular_deps = self.circular_groups();
         
         if !circular_deps.is_empty() {
             let error_msg = circular_deps
@@ -211,12 +248,12 @@ impl DependencyGraph {
     /// Get dependency graph statistics
     pub fn stats(&self) -> DependencyGraphStats {
         DependencyGraphStats {
-            total_plugins: self.node_indices.len(),
+            total_plugins: self.registered.len(),
             total_dependencies: self.graph.edge_count(),
-            average_dependencies_per_plugin: if self.node_indices.is_empty() {
+            average_dependencies_per_plugin: if self.registered.is_empty() {
                 0.0
             } else {
-                self.graph.edge_count() as f64 / self.node_indices.len() as f64
+                self.graph.edge_count() as f64 / self.registered.len() as f64
             },
         }
     }
@@ -313,7 +350,7 @@ impl DependencyResolver {
         }
         
         // Check for missing dependencies
-        for plugin_name in self.graph.node_indices.keys() {
+        for plugin_name in &self.graph.registered {
             let missing = self.graph.check_missing_dependencies(plugin_name);
             if !missing.is_empty() {
                 resolution.missing_dependencies.push((plugin_name.clone(), missing));
@@ -379,6 +416,9 @@ mod tests {
         graph.add_plugin("plugin_b".to_string(), vec!["plugin_a".to_string()]).unwrap();
         
         assert!(graph.check_circular_dependencies().is_err());
+        let mut groups = graph.circular_groups();
+        groups[0].sort();
+        assert_eq!(groups, vec![vec!["plugin_a".to_string(), "plugin_b".to_string()]]);
     }
     
     #[test]
@@ -389,5 +429,46 @@ mod tests {
         
         let missing = graph.check_missing_dependencies("plugin_a");
         assert_eq!(missing, vec!["missing_plugin".to_string()]);
+        assert_eq!(graph.stats().total_plugins, 1);
+
+        graph.add_plugin("missing_plugin".to_string(), vec![]).unwrap();
+        assert!(graph.check_missing_dependencies("plugin_a").is_empty());
+    }
+
+    #[test]
+    fn test_remove_plugins() {
+        let mut graph = DependencyGraph::new();
+        graph.add_plugin("base".to_string(), vec![]).unwrap();
+        graph.add_plugin("lib".to_string(), vec!["base".to_string()]).unwrap();
+        graph.add_plugin("app".to_string(), vec!["lib".to_string()]).unwrap();
+        graph.add_plugin("tool".to_string(), vec!["base".to_string()]).unwrap();
+        graph.add_plugin("extra".to_string(), vec!["app".to_string(), "tool".to_string()]).unwrap();
+
+        // Removing nodes in the middle of the graph leaves the others intact
+        graph.remove_plugin("extra");
+        graph.remove_plugin("app");
+        let mut deps = graph.get_all_dependencies("lib");
+        deps.sort();
+        assert_eq!(deps, vec!["base".to_string()]);
+        let mut dependents = graph.get_all_dependents("base");
+        dependents.sort();
+        assert_eq!(dependents, vec!["lib".to_string(), "tool".to_string()]);
+        assert_eq!(graph.get_load_order().unwrap()[0], "base");
+        assert_eq!(graph.stats().total_plugins, 3);
+        assert_eq!(graph.stats().total_dependencies, 2);
+
+        // A removed dependency is missing for the plugins still declaring it
+        graph.remove_plugin("base");
+        assert_eq!(graph.check_missing_dependencies("lib"), vec!["base".to_string()]);
+        assert_eq!(graph.check_missing_dependencies("tool"), vec!["base".to_string()]);
+
+        graph.remove_plugin("tool");
+        graph.remove_plugin("lib");
+        assert!(graph.get_load_order().unwrap().is_empty());
+        assert_eq!(graph.stats().total_dependencies, 0);
+
+        graph.add_plugin("app".to_string(), vec!["lib".to_string()]).unwrap();
+        graph.add_plugin("lib".to_string(), vec![]).unwrap();
+        assert_eq!(graph.get_load_order().unwrap(), vec!["lib", "app"]);
     }
 }
\ No newline at end of file
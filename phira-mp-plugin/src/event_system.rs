@@ -1,11 +1,19 @@
-use crate::Error;
+use crate::{Error, monitoring::MetricsCollector};
 use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
 };
-use parking_lot::RwLock;
-use tokio::sync::broadcast;
-use tracing::debug;
+use parking_lot::{Mutex, RwLock};
+use tokio::{
+    sync::{Semaphore, broadcast, mpsc},
+    task::JoinHandle,
+};
+use tracing::{Instrument, debug, debug_span, warn};
 
 /// Event data type
 pub type EventData = serde_json::Value;
@@ -61,6 +69,35 @@ impl Event {
 /// Event handler function signature
 pub type EventHandler = Box<dyn Fn(&Event) -> Result<(), Error> + Send + Sync>;
 
+/// Asynchronous event handler function signature
+pub type AsyncEventHandler = Box<
+    dyn Fn(Arc<Event>) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>> + Send + Sync,
+>;
+
+/// What a cancellable event handler decided, see [`EventBus::emit_cancellable`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum HandlerResult {
+    /// Let the event go on unchanged
+    Continue,
+    /// Veto the event, skipping later handlers
+    Cancel(String),
+    /// Replace the event data seen by later handlers and the emitter
+    Modify(EventData),
+}
+
+/// Cancellable event handler function signature
+pub type CancellableEventHandler =
+    Box<dyn Fn(&Event) -> Result<HandlerResult, Error> + Send + Sync>;
+
+/// Result of [`EventBus::emit_cancellable`]
+#[derive(Debug, Clone)]
+pub enum EmitOutcome {
+    /// No handler vetoed the event, which carries the data left by the handlers
+    Completed(Event),
+    /// A handler vetoed the event for the given reason
+    Cancelled(String),
+}
+
 /// Event subscription
 pub struct EventSubscription {
     /// Event type
@@ -69,6 +106,8 @@ pub struct EventSubscription {
     pub handler: EventHandler,
     /// Subscriber identifier (plugin name)
     pub subscriber: String,
+    /// Handlers with higher priority run first
+    pub priority: i32,
 }
 
 impl EventSubscription {
@@ -82,56 +121,494 @@ impl EventSubscription {
             event_type: event_type.into(),
             handler,
             subscriber: subscriber.into(),
+            priority: 0,
+        }
+    }
+
+    /// Set priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Asynchronous event subscription, see [`EventBus::subscribe_async`]
+pub struct AsyncEventSubscription {
+    /// Event type
+    pub event_type: String,
+    /// Handler function
+    pub handler: AsyncEventHandler,
+    /// Subscriber identifier (plugin name)
+    pub subscriber: String,
+}
+
+/// Cancellable event subscription, see [`EventBus::subscribe_cancellable`]
+pub struct CancellableEventSubscription {
+    /// Event type
+    pub event_type: String,
+    /// Handler function
+    pub handler: CancellableEventHandler,
+    /// Subscriber identifier (plugin name)
+    pub subscriber: String,
+    /// Handlers with higher priority are consulted first
+    pub priority: i32,
+}
+
+/// What to do with a spawned handler call when its plugin is at the concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandlerOverflow {
+    /// Wait for a running handler of the plugin to finish
+    Queue,
+    /// Drop the call and count it in the stats
+    Shed,
+}
+
+/// Concurrency limits for spawned handler calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct HandlerConcurrency {
+    /// Maximum number of handler calls running at once for each plugin
+    pub max_per_plugin: usize,
+    /// Behavior beyond the limit
+    pub overflow: HandlerOverflow,
+}
+
+impl Default for HandlerConcurrency {
+    fn default() -> Self {
+        Self {
+            max_per_plugin: 16,
+            overflow: HandlerOverflow::Queue,
         }
     }
 }
 
+/// Maximum number of events buffered while the event bus is paused
+pub const MAX_PAUSED_EVENTS: usize = 10_000;
+
+/// Capacity of the broadcast channel, beyond which slow receivers lag and lose events
+pub const BROADCAST_CAPACITY: usize = 100;
+
+/// Default number of recent events kept for replay, see [`EventBus::replay_since`]
+pub const DEFAULT_HISTORY_CAPACITY: usize = 256;
+
+/// A listener of lifecycle events, see [`EventBus::subscribe_lifecycle`]
+struct LifecycleListener {
+    /// Listener name, for drop counts
+    name: String,
+    sender: mpsc::Sender<Arc<Event>>,
+    /// Events dropped because the listener's queue was full
+    dropped: AtomicU64,
+}
+
 /// Event bus for plugin communication
 pub struct EventBus {
     /// Event subscriptions by event type
     subscriptions: RwLock<HashMap<String, Vec<Arc<EventSubscription>>>>,
+    /// Subscriptions to event type patterns such as `room_*`, in subscription order
+    pattern_subscriptions: RwLock<Vec<Arc<EventSubscription>>>,
+    /// Asynchronous event subscriptions by event type
+    async_subscriptions: RwLock<HashMap<String, Vec<Arc<AsyncEventSubscription>>>>,
+    /// Cancellable event subscriptions by event type
+    cancellable_subscriptions: RwLock<HashMap<String, Vec<Arc<CancellableEventSubscription>>>>,
     /// Broadcast channel for real-time event delivery
     broadcast_tx: broadcast::Sender<Arc<Event>>,
     /// List of all registered event types
     event_types: RwLock<HashSet<String>>,
+    /// Concurrency limits for spawned handler calls
+    handler_concurrency: RwLock<HandlerConcurrency>,
+    /// Running spawned handler calls by plugin
+    handler_permits: RwLock<HashMap<String, Arc<Semaphore>>>,
+    /// Spawned handler calls shed due to the limit, by plugin
+    dropped_handler_calls: RwLock<HashMap<String, Arc<AtomicU64>>>,
+    /// Events held back while paused, `None` when dispatching normally
+    paused: Mutex<Option<VecDeque<Arc<Event>>>>,
+    /// Events dropped because the pause buffer was full
+    dropped_paused_events: AtomicU64,
+    /// Subscribers whose handlers are skipped until resumed
+    paused_subscribers: RwLock<HashSet<String>>,
+    /// Listeners of lifecycle events, served apart from the lossy broadcast channel
+    lifecycle_listeners: RwLock<Vec<LifecycleListener>>,
+    /// Recently delivered events, oldest first
+    history: Mutex<VecDeque<Arc<Event>>>,
+    /// Maximum number of events kept in the history
+    history_capacity: AtomicUsize,
+    /// Collector handler calls are tracked in, if any
+    metrics: RwLock<Option<Arc<MetricsCollector>>>,
 }
 
 impl EventBus {
     /// Create a new event bus
     pub fn new() -> Self {
-        let (broadcast_tx, _) = broadcast::channel(100);
+        let (broadcast_tx, _) = broadcast::channel(BROADCAST_CAPACITY);
         Self {
             subscriptions: RwLock::new(HashMap::new()),
+            pattern_subscriptions: RwLock::new(Vec::new()),
+            async_subscriptions: RwLock::new(HashMap::new()),
+            cancellable_subscriptions: RwLock::new(HashMap::new()),
             broadcast_tx,
             event_types: RwLock::new(HashSet::new()),
+            handler_concurrency: RwLock::new(HandlerConcurrency::default()),
+            handler_permits: RwLock::new(HashMap::new()),
+            dropped_handler_calls: RwLock::new(HashMap::new()),
+            paused: Mutex::new(None),
+            dropped_paused_events: AtomicU64::new(0),
+            paused_subscribers: RwLock::new(HashSet::new()),
+            lifecycle_listeners: RwLock::new(Vec::new()),
+            history: Mutex::new(VecDeque::new()),
+            history_capacity: AtomicUsize::new(DEFAULT_HISTORY_CAPACITY),
+            metrics: RwLock::new(None),
         }
     }
 
+    /// Track handler calls in a metrics collector, see [`MetricsCollector::track`]
+    pub fn set_metrics_collector(&self, metrics: Arc<MetricsCollector>) {
+        *self.metrics.write() = Some(metrics);
+    }
+
+    /// Call a handler of `subscriber`, tracking the call if a collector is set
+    fn track<T>(
+        &self,
+        subscriber: &str,
+        event_type: &str,
+        call: impl FnOnce() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let _span = handler_span(subscriber, event_type).entered();
+        let metrics = self.metrics.read().clone();
+        match metrics {
+            Some(metrics) => metrics.track(subscriber, call),
+            None => call(),
+        }
+    }
+
+    /// Receive lifecycle events (see [`predefined::LIFECYCLE`]) on a dedicated queue of
+    /// `capacity` events
+    ///
+    /// Unlike [`Self::subscribe_broadcast`], other traffic can never push these events out:
+    /// they are only dropped, and counted, once this listener has `capacity` events
+    /// unread. They are delivered even while the event bus is paused.
+    pub fn subscribe_lifecycle(
+        &self,
+        name: impl Into<String>,
+        capacity: usize,
+    ) -> mpsc::Receiver<Arc<Event>> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        self.lifecycle_listeners.write().push(LifecycleListener {
+            name: name.into(),
+            sender,
+            dropped: AtomicU64::new(0),
+        });
+        receiver
+    }
+
+    /// Get the number of lifecycle events dropped for a listener that fell behind
+    pub fn dropped_lifecycle_events(&self, name: &str) -> u64 {
+        self.lifecycle_listeners
+            .read()
+            .iter()
+            .filter(|listener| listener.name == name)
+            .map(|listener| listener.dropped.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Queue a lifecycle event for its listeners, forgetting listeners that went away
+    fn notify_lifecycle(&self, event: &Arc<Event>) {
+        if event.source != "system" || !predefined::LIFECYCLE.contains(&event.event_type.as_str()) {
+            return;
+        }
+        let mut closed = false;
+        for listener in self.lifecycle_listeners.read().iter() {
+            match listener.sender.try_send(Arc::clone(event)) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!(
+                        "Lifecycle listener '{}' is full, dropping event '{}'",
+                        listener.name, event.event_type
+                    );
+                    listener.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => closed = true,
+            }
+        }
+        if closed {
+            self.lifecycle_listeners
+                .write()
+                .retain(|listener| !listener.sender.is_closed());
+        }
+    }
+
+    /// Pause event dispatch (admin only)
+    ///
+    /// Emitted events are buffered, up to [`MAX_PAUSED_EVENTS`], until [`Self::resume`].
+    /// Critical system events (see [`predefined::CRITICAL`]) bypass the pause.
+    pub fn pause(&self) {
+        let mut paused = self.paused.lock();
+        if paused.is_none() {
+            debug!("Pausing event bus");
+            *paused = Some(VecDeque::new());
+        }
+    }
+
+    /// Resume event dispatch, delivering buffered events to synchronous handlers in order
+    ///
+    /// Returns the number of events delivered.
+    pub fn resume(&self) -> usize {
+        let Some(events) = self.paused.lock().take() else {
+            return 0;
+        };
+        debug!("Resuming event bus, flushing {} events", events.len());
+        let count = events.len();
+        for event in events {
+            self.dispatch(event);
+        }
+        count
+    }
+
+    /// Check whether event dispatch is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.lock().is_some()
+    }
+
+    /// Buffer an event if paused, returning it back if it should be dispatched now
+    fn hold_if_paused(&self, event: Arc<Event>) -> Option<Arc<Event>> {
+        if event.source == "system" && predefined::CRITICAL.contains(&event.event_type.as_str()) {
+            return Some(event);
+        }
+        let mut paused = self.paused.lock();
+        let Some(events) = paused.as_mut() else {
+            return Some(event);
+        };
+        if events.len() >= MAX_PAUSED_EVENTS {
+            warn!("Event bus pause buffer full, dropping event '{}'", event.event_type);
+            self.dropped_paused_events.fetch_add(1, Ordering::Relaxed);
+        } else {
+            events.push_back(event);
+        }
+        None
+    }
+
+    /// Skip the handlers of a subscriber until [`Self::resume_subscriber`]
+    ///
+    /// Unlike [`Self::pause`], events are not buffered: the subscriber misses them.
+    pub fn pause_subscriber(&self, subscriber: impl Into<String>) {
+        self.paused_subscribers.write().insert(subscriber.into());
+    }
+
+    /// Deliver events to the handlers of a paused subscriber again
+    ///
+    /// Returns whether the subscriber was paused.
+    pub fn resume_subscriber(&self, subscriber: &str) -> bool {
+        self.paused_subscribers.write().remove(subscriber)
+    }
+
+    /// Check whether the handlers of a subscriber are skipped
+    pub fn is_subscriber_paused(&self, subscriber: &str) -> bool {
+        self.paused_subscribers.read().contains(subscriber)
+    }
+
+    /// Set concurrency limits for spawned handler calls
+    ///
+    /// Calls already running or queued keep the previous limit.
+    pub fn set_handler_concurrency(&self, concurrency: HandlerConcurrency) {
+        *self.handler_concurrency.write() = concurrency;
+        self.handler_permits.write().clear();
+    }
+
     /// Subscribe to an event type
+    ///
+    /// `event_type` may be a pattern where `*` matches any characters, such as `room_*` or
+    /// `*` for every event. Pattern subscribers run after the exact subscribers of an event
+    /// with the same priority.
     pub fn subscribe(
         &self,
         event_type: impl Into<String>,
         handler: EventHandler,
         subscriber: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.subscribe_with_priority(event_type, handler, subscriber, 0)
+    }
+
+    /// Subscribe to an event type, running the handler before those with lower priority
+    ///
+    /// Handlers with the same priority run in subscription order.
+    pub fn subscribe_with_priority(
+        &self,
+        event_type: impl Into<String>,
+        handler: EventHandler,
+        subscriber: impl Into<String>,
+        priority: i32,
     ) -> Result<(), Error> {
         let event_type = event_type.into();
         let subscriber = subscriber.into();
         
-        debug!("Plugin '{}' subscribing to event '{}'", subscriber, event_type);
+        debug!(
+            "Plugin '{}' subscribing to event '{}' with priority {}",
+            subscriber, event_type, priority
+        );
         
-        let subscription = Arc::new(EventSubscription::new(
-            event_type.clone(),
-            handler,
-            subscriber.clone(),
+        self.add_subscription(Arc::new(
+            EventSubscription::new(event_type, handler, subscriber).with_priority(priority),
         ));
+        Ok(())
+    }
+
+    /// Subscribe to an event type, first replaying the matching events from the history
+    /// emitted at or after `since` (milliseconds since epoch)
+    ///
+    /// Every event is seen exactly once, either replayed or live. Returns the number of
+    /// replayed events.
+    pub fn subscribe_with_replay(
+        &self,
+        event_type: impl Into<String>,
+        handler: EventHandler,
+        subscriber: impl Into<String>,
+        since: i64,
+    ) -> Result<usize, Error> {
+        let subscription = Arc::new(EventSubscription::new(event_type, handler, subscriber));
+        debug!(
+            "Plugin '{}' subscribing to event '{}' with replay",
+            subscription.subscriber, subscription.event_type
+        );
+
+        let replayed = {
+            let history = self.history.lock();
+            self.add_subscription(Arc::clone(&subscription));
+            history_since(&history, &subscription.event_type, since)
+        };
+        for event in &replayed {
+            if let Err(e) = (subscription.handler)(event) {
+                tracing::error!(
+                    "Event handler failed for plugin '{}' during replay: {}",
+                    subscription.subscriber, e
+                );
+            }
+        }
+        Ok(replayed.len())
+    }
+
+    fn add_subscription(&self, subscription: Arc<EventSubscription>) {
+        if is_pattern(&subscription.event_type) {
+            let mut patterns = self.pattern_subscriptions.write();
+            insert_by_priority(&mut patterns, subscription, |sub| sub.priority);
+            return;
+        }
         
+        let event_type = subscription.event_type.clone();
         let mut subscriptions = self.subscriptions.write();
         let event_subs = subscriptions.entry(event_type.clone()).or_insert_with(Vec::new);
-        event_subs.push(subscription);
+        insert_by_priority(event_subs, subscription, |sub| sub.priority);
         
         // Add to event types set
         self.event_types.write().insert(event_type);
-        
+    }
+
+    /// Call `handler` with the events in the history of a type, which may be a pattern,
+    /// emitted at or after `since` (milliseconds since epoch), oldest first
+    ///
+    /// Returns the number of replayed events.
+    pub fn replay_since(
+        &self,
+        event_type: &str,
+        since: i64,
+        handler: impl Fn(&Event) -> Result<(), Error>,
+    ) -> usize {
+        let replayed = history_since(&self.history.lock(), event_type, since);
+        for event in &replayed {
+            if let Err(e) = handler(event) {
+                tracing::error!("Event handler failed during replay: {}", e);
+            }
+        }
+        replayed.len()
+    }
+
+    /// Set the number of recent events kept for replay, 0 to keep none
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.history_capacity.store(capacity, Ordering::Relaxed);
+        let mut history = self.history.lock();
+        while history.len() > capacity {
+            history.pop_front();
+        }
+    }
+
+    /// Record an event in the history and get its subscriptions
+    ///
+    /// Both happen under the history lock, so that [`Self::subscribe_with_replay`] sees
+    /// each event either in the history or live.
+    fn record_and_match(&self, event: &Arc<Event>) -> Vec<Arc<EventSubscription>> {
+        let mut history = self.history.lock();
+        let capacity = self.history_capacity.load(Ordering::Relaxed);
+        if capacity > 0 {
+            if history.len() >= capacity {
+                history.pop_front();
+            }
+            history.push_back(Arc::clone(event));
+        }
+        self.matching_subscriptions(&event.event_type)
+    }
+
+    /// Subscribe to an event type with an asynchronous handler
+    ///
+    /// Asynchronous handlers are only run by [`Self::emit_async`]; other ways of emitting
+    /// skip them. They run concurrently, so unlike synchronous handlers they take no priority
+    /// and are unordered among each other. Patterns are not supported.
+    pub fn subscribe_async(
+        &self,
+        event_type: impl Into<String>,
+        handler: AsyncEventHandler,
+        subscriber: impl Into<String>,
+    ) -> Result<(), Error> {
+        let event_type = event_type.into();
+        let subscriber = subscriber.into();
+        reject_pattern(&event_type)?;
+
+        debug!("Plugin '{}' subscribing asynchronously to event '{}'", subscriber, event_type);
+
+        self.async_subscriptions
+            .write()
+            .entry(event_type.clone())
+            .or_default()
+            .push(Arc::new(AsyncEventSubscription {
+                event_type: event_type.clone(),
+                handler,
+                subscriber,
+            }));
+        self.event_types.write().insert(event_type);
+
+        Ok(())
+    }
+
+    /// Subscribe to an event type with a handler that may veto or modify the event
+    ///
+    /// Cancellable handlers are only consulted by [`Self::emit_cancellable`], in
+    /// descending priority order, then subscription order. Patterns are not supported.
+    pub fn subscribe_cancellable(
+        &self,
+        event_type: impl Into<String>,
+        handler: CancellableEventHandler,
+        subscriber: impl Into<String>,
+        priority: i32,
+    ) -> Result<(), Error> {
+        let event_type = event_type.into();
+        let subscriber = subscriber.into();
+        reject_pattern(&event_type)?;
+
+        debug!("Plugin '{}' subscribing cancellably to event '{}'", subscriber, event_type);
+
+        insert_by_priority(
+            self.cancellable_subscriptions
+                .write()
+                .entry(event_type.clone())
+                .or_default(),
+            Arc::new(CancellableEventSubscription {
+                event_type: event_type.clone(),
+                handler,
+                subscriber,
+                priority,
+            }),
+            |sub| sub.priority,
+        );
+        self.event_types.write().insert(event_type);
+
         Ok(())
     }
 
@@ -146,16 +623,41 @@ impl EventBus {
         
         debug!("Plugin '{}' unsubscribing from event '{}'", subscriber, event_type);
         
+        if is_pattern(&event_type) {
+            self.pattern_subscriptions
+                .write()
+                .retain(|sub| sub.event_type != event_type || sub.subscriber != subscriber);
+        }
+        
         let mut subscriptions = self.subscriptions.write();
+        let mut async_subscriptions = self.async_subscriptions.write();
+        let mut cancellable_subscriptions = self.cancellable_subscriptions.write();
         if let Some(event_subs) = subscriptions.get_mut(&event_type) {
             event_subs.retain(|sub| sub.subscriber != subscriber);
-            
-            // Remove event type if no subscribers
             if event_subs.is_empty() {
                 subscriptions.remove(&event_type);
-                self.event_types.write().remove(&event_type);
             }
         }
+        if let Some(event_subs) = async_subscriptions.get_mut(&event_type) {
+            event_subs.retain(|sub| sub.subscriber != subscriber);
+            if event_subs.is_empty() {
+                async_subscriptions.remove(&event_type);
+            }
+        }
+        if let Some(event_subs) = cancellable_subscriptions.get_mut(&event_type) {
+            event_subs.retain(|sub| sub.subscriber != subscriber);
+            if event_subs.is_empty() {
+                cancellable_subscriptions.remove(&event_type);
+            }
+        }
+
+        // Remove event type if no subscribers
+        if !subscriptions.contains_key(&event_type)
+            && !async_subscriptions.contains_key(&event_type)
+            && !cancellable_subscriptions.contains_key(&event_type)
+        {
+            self.event_types.write().remove(&event_type);
+        }
         
         Ok(())
     }
@@ -166,25 +668,34 @@ impl EventBus {
         
         debug!("Unsubscribing all events for '{}'", subscriber);
         
+        self.pattern_subscriptions
+            .write()
+            .retain(|sub| sub.subscriber != subscriber);
+        
         let mut subscriptions = self.subscriptions.write();
+        let mut async_subscriptions = self.async_subscriptions.write();
+        let mut cancellable_subscriptions = self.cancellable_subscriptions.write();
         let mut event_types = self.event_types.write();
         
-        // Collect event types to remove
-        let mut empty_event_types = Vec::new();
-        
-        for (event_type, event_subs) in subscriptions.iter_mut() {
+        subscriptions.retain(|_, event_subs| {
             event_subs.retain(|sub| sub.subscriber != subscriber);
-            
-            if event_subs.is_empty() {
-                empty_event_types.push(event_type.clone());
-            }
-        }
+            !event_subs.is_empty()
+        });
+        async_subscriptions.retain(|_, event_subs| {
+            event_subs.retain(|sub| sub.subscriber != subscriber);
+            !event_subs.is_empty()
+        });
+        cancellable_subscriptions.retain(|_, event_subs| {
+            event_subs.retain(|sub| sub.subscriber != subscriber);
+            !event_subs.is_empty()
+        });
         
         // Remove empty event types
-        for event_type in empty_event_types {
-            subscriptions.remove(&event_type);
-            event_types.remove(&event_type);
-        }
+        event_types.retain(|event_type| {
+            subscriptions.contains_key(event_type)
+                || async_subscriptions.contains_key(event_type)
+                || cancellable_subscriptions.contains_key(event_type)
+        });
         
         Ok(())
     }
@@ -192,23 +703,114 @@ impl EventBus {
     /// Emit an event
     pub fn emit(&self, event: Event) -> Result<(), Error> {
         let event = Arc::new(event);
+        self.notify_lifecycle(&event);
+        if let Some(event) = self.hold_if_paused(event) {
+            self.dispatch(event);
+        }
+        Ok(())
+    }
+
+    /// Emit an event and wait for its asynchronous handlers
+    ///
+    /// Synchronous handlers run first, inline as with [`Self::emit`], then all asynchronous
+    /// handlers run concurrently; this returns once every one of them finished. A failing
+    /// handler is logged and does not affect the others. While paused, the event is buffered
+    /// and delivered on resume to synchronous handlers only.
+    pub async fn emit_async(&self, event: Event) -> Result<(), Error> {
+        let event = Arc::new(event);
+        self.notify_lifecycle(&event);
+        let Some(event) = self.hold_if_paused(event) else {
+            return Ok(());
+        };
+        self.dispatch(Arc::clone(&event));
+
+        let mut event_subs = self
+            .async_subscriptions
+            .read()
+            .get(&event.event_type)
+            .cloned()
+            .unwrap_or_default();
+        event_subs.retain(|sub| !self.is_subscriber_paused(&sub.subscriber));
+        let results = futures::future::join_all(
+            event_subs
+                .iter()
+                .map(|subscription| {
+                    (subscription.handler)(Arc::clone(&event))
+                        .instrument(handler_span(&subscription.subscriber, &event.event_type))
+                }),
+        )
+        .await;
+        for (subscription, result) in event_subs.iter().zip(results) {
+            if let Err(e) = result {
+                tracing::error!(
+                    "Async event handler failed for plugin '{}': {}",
+                    subscription.subscriber, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit an event that handlers may veto or modify
+    ///
+    /// Cancellable handlers run first, in descending priority order, then subscription order:
+    /// each sees the data left by the previous ones, and the first to cancel skips the rest. A failing handler is logged and
+    /// treated as continuing. Unless cancelled, the resulting event is then emitted as with
+    /// [`Self::emit`]. Cancellable handlers are consulted even while paused, since the
+    /// emitter waits for their verdict.
+    pub fn emit_cancellable(&self, mut event: Event) -> EmitOutcome {
+        let mut event_subs = self
+            .cancellable_subscriptions
+            .read()
+            .get(&event.event_type)
+            .cloned()
+            .unwrap_or_default();
+        event_subs.retain(|sub| !self.is_subscriber_paused(&sub.subscriber));
+        for subscription in event_subs {
+            match self.track(&subscription.subscriber, &event.event_type, || {
+                (subscription.handler)(&event)
+            }) {
+                Ok(HandlerResult::Continue) => {}
+                Ok(HandlerResult::Cancel(reason)) => {
+                    debug!(
+                        "Plugin '{}' cancelled event '{}': {}",
+                        subscription.subscriber, event.event_type, reason
+                    );
+                    return EmitOutcome::Cancelled(reason);
+                }
+                Ok(HandlerResult::Modify(data)) => event.data = data,
+                Err(e) => tracing::error!(
+                    "Cancellable event handler failed for plugin '{}': {}",
+                    subscription.subscriber, e
+                ),
+            }
+        }
+
+        let event = Arc::new(event);
+        self.notify_lifecycle(&event);
+        if let Some(event) = self.hold_if_paused(Arc::clone(&event)) {
+            self.dispatch(event);
+        }
+        EmitOutcome::Completed(Arc::unwrap_or_clone(event))
+    }
+
+    /// Call synchronous handlers and broadcast an event
+    fn dispatch(&self, event: Arc<Event>) {
         let event_type = event.event_type.clone();
         
         debug!("Emitting event '{}' from '{}'", event_type, event.source);
         
         // Call synchronous handlers
-        {
-            let subscriptions = self.subscriptions.read();
-            if let Some(event_subs) = subscriptions.get(&event_type) {
-                for subscription in event_subs {
-                    if let Err(e) = (subscription.handler)(&event) {
-                        // Log error but continue with other handlers
-                        tracing::error!(
-                            "Event handler failed for plugin '{}': {}",
-                            subscription.subscriber, e
-                        );
-                    }
-                }
+        for subscription in self.record_and_match(&event) {
+            let result = self.track(&subscription.subscriber, &event.event_type, || {
+                (subscription.handler)(&event)
+            });
+            if let Err(e) = result {
+                // Log error but continue with other handlers
+                tracing::error!(
+                    "Event handler failed for plugin '{}': {}",
+                    subscription.subscriber, e
+                );
             }
         }
         
@@ -216,8 +818,119 @@ impl EventBus {
         if self.broadcast_tx.receiver_count() > 0 {
             let _ = self.broadcast_tx.send(event.clone());
         }
-        
-        Ok(())
+    }
+
+    /// Emit an event, running each handler on the blocking thread pool
+    ///
+    /// Concurrent handler calls are limited per plugin according to
+    /// [`HandlerConcurrency`]. Must be called within a tokio runtime. Returns the handles of
+    /// the spawned calls. While paused, the event is buffered and delivered synchronously on
+    /// resume.
+    pub fn emit_spawned(&self, event: Event) -> Result<Vec<JoinHandle<()>>, Error> {
+        let event = Arc::new(event);
+        self.notify_lifecycle(&event);
+        let Some(event) = self.hold_if_paused(event) else {
+            return Ok(Vec::new());
+        };
+        debug!("Emitting spawned event '{}' from '{}'", event.event_type, event.source);
+
+        let event_subs = self.record_and_match(&event);
+        let overflow = self.handler_concurrency.read().overflow;
+        let metrics = self.metrics.read().clone();
+
+        let mut handles = Vec::with_capacity(event_subs.len());
+        for subscription in event_subs {
+            let semaphore = self.handler_semaphore(&subscription.subscriber);
+            let event = Arc::clone(&event);
+            let metrics = metrics.clone();
+            let handle = match overflow {
+                HandlerOverflow::Queue => tokio::spawn(async move {
+                    let Ok(permit) = semaphore.acquire_owned().await else {
+                        return;
+                    };
+                    run_spawned_handler(subscription, event, metrics).await;
+                    drop(permit);
+                }),
+                HandlerOverflow::Shed => {
+                    let Ok(permit) = semaphore.try_acquire_owned() else {
+                        warn!(
+                            "Dropping '{}' handler call for plugin '{}': concurrency limit reached",
+                            event.event_type, subscription.subscriber
+                        );
+                        self.dropped_counter(&subscription.subscriber)
+                            .fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    };
+                    tokio::spawn(async move {
+                        run_spawned_handler(subscription, event, metrics).await;
+                        drop(permit);
+                    })
+                }
+            };
+            handles.push(handle);
+        }
+
+        if self.broadcast_tx.receiver_count() > 0 {
+            let _ = self.broadcast_tx.send(event);
+        }
+
+        Ok(handles)
+    }
+
+    /// Get the subscriptions to an event type by descending priority, exact ones first, then
+    /// matching patterns, leaving out paused subscribers
+    fn matching_subscriptions(&self, event_type: &str) -> Vec<Arc<EventSubscription>> {
+        let mut event_subs = self
+            .subscriptions
+            .read()
+            .get(event_type)
+            .cloned()
+            .unwrap_or_default();
+        let patterns = self.pattern_subscriptions.read();
+        event_subs.extend(
+            patterns
+                .iter()
+                .filter(|sub| pattern_matches(&sub.event_type, event_type))
+                .cloned(),
+        );
+        drop(patterns);
+        event_subs.retain(|sub| !self.is_subscriber_paused(&sub.subscriber));
+        event_subs.sort_by_key(|sub| std::cmp::Reverse(sub.priority));
+        event_subs
+    }
+
+    /// Get the number of spawned handler calls of a plugin shed due to the concurrency limit
+    pub fn dropped_handler_calls(&self, subscriber: &str) -> u64 {
+        self.dropped_handler_calls
+            .read()
+            .get(subscriber)
+            .map(|count| count.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    fn handler_semaphore(&self, subscriber: &str) -> Arc<Semaphore> {
+        if let Some(semaphore) = self.handler_permits.read().get(subscriber) {
+            return Arc::clone(semaphore);
+        }
+        let max_per_plugin = self.handler_concurrency.read().max_per_plugin.max(1);
+        Arc::clone(
+            self.handler_permits
+                .write()
+                .entry(subscriber.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(max_per_plugin))),
+        )
+    }
+
+    fn dropped_counter(&self, subscriber: &str) -> Arc<AtomicU64> {
+        if let Some(count) = self.dropped_handler_calls.read().get(subscriber) {
+            return Arc::clone(count);
+        }
+        Arc::clone(
+            self.dropped_handler_calls
+                .write()
+                .entry(subscriber.to_string())
+                .or_default(),
+        )
     }
 
     /// Get a receiver for broadcast events
@@ -233,10 +946,24 @@ impl EventBus {
     /// Get subscribers for an event type
     pub fn get_subscribers(&self, event_type: &str) -> Vec<String> {
         let subscriptions = self.subscriptions.read();
-        subscriptions
+        let mut subscribers: Vec<String> = subscriptions
             .get(event_type)
             .map(|subs| subs.iter().map(|sub| sub.subscriber.clone()).collect())
-            .unwrap_or_default()
+            .unwrap_or_default();
+        if let Some(subs) = self.async_subscriptions.read().get(event_type) {
+            subscribers.extend(subs.iter().map(|sub| sub.subscriber.clone()));
+        }
+        if let Some(subs) = self.cancellable_subscriptions.read().get(event_type) {
+            subscribers.extend(subs.iter().map(|sub| sub.subscriber.clone()));
+        }
+        subscribers.extend(
+            self.pattern_subscriptions
+                .read()
+                .iter()
+                .filter(|sub| pattern_matches(&sub.event_type, event_type))
+                .map(|sub| sub.subscriber.clone()),
+        );
+        subscribers
     }
 
     /// Check if an event type has any subscribers
@@ -246,6 +973,13 @@ impl EventBus {
             .get(event_type)
             .map(|subs| !subs.is_empty())
             .unwrap_or(false)
+            || self.async_subscriptions.read().contains_key(event_type)
+            || self.cancellable_subscriptions.read().contains_key(event_type)
+            || self
+                .pattern_subscriptions
+                .read()
+                .iter()
+                .any(|sub| pattern_matches(&sub.event_type, event_type))
     }
 
     /// Get statistics about the event bus
@@ -255,18 +989,133 @@ impl EventBus {
         
         EventBusStats {
             total_event_types: event_types.len(),
-            total_subscriptions: subscriptions.values().map(|subs| subs.len()).sum(),
+            total_subscriptions: subscriptions.values().map(|subs| subs.len()).sum::<usize>()
+                + self
+                    .async_subscriptions
+                    .read()
+                    .values()
+                    .map(|subs| subs.len())
+                    .sum::<usize>()
+                + self
+                    .cancellable_subscriptions
+                    .read()
+                    .values()
+                    .map(|subs| subs.len())
+                    .sum::<usize>()
+                + self.pattern_subscriptions.read().len(),
             broadcast_receivers: self.broadcast_tx.receiver_count(),
+            dropped_handler_calls: self
+                .dropped_handler_calls
+                .read()
+                .values()
+                .map(|count| count.load(Ordering::Relaxed))
+                .sum(),
+            dropped_paused_events: self.dropped_paused_events.load(Ordering::Relaxed),
+            lifecycle_listeners: self.lifecycle_listeners.read().len(),
+            dropped_lifecycle_events: self
+                .lifecycle_listeners
+                .read()
+                .iter()
+                .map(|listener| listener.dropped.load(Ordering::Relaxed))
+                .sum(),
         }
     }
 }
 
+/// Insert a subscription after those with higher or equal priority
+fn insert_by_priority<T>(
+    subs: &mut Vec<Arc<T>>,
+    subscription: Arc<T>,
+    priority: impl Fn(&T) -> i32,
+) {
+    let own = priority(&subscription);
+    let pos = subs
+        .iter()
+        .position(|sub| priority(sub) < own)
+        .unwrap_or(subs.len());
+    subs.insert(pos, subscription);
+}
+
+/// Get the events of a type, which may be a pattern, emitted at or after `since`
+fn history_since(history: &VecDeque<Arc<Event>>, event_type: &str, since: i64) -> Vec<Arc<Event>> {
+    history
+        .iter()
+        .filter(|event| event.timestamp >= since && pattern_matches(event_type, &event.event_type))
+        .cloned()
+        .collect()
+}
+
+/// Whether an event type given to [`EventBus::subscribe`] is a pattern
+fn is_pattern(event_type: &str) -> bool {
+    event_type.contains('*')
+}
+
+/// Fail on a pattern given to a kind of subscription that only matches exact event types
+fn reject_pattern(event_type: &str) -> Result<(), Error> {
+    if is_pattern(event_type) {
+        return Err(Error::Event(format!(
+            "event type patterns such as '{}' are only supported by synchronous subscriptions",
+            event_type
+        )));
+    }
+    Ok(())
+}
+
+/// Match an event type against a pattern where `*` matches any characters
+fn pattern_matches(pattern: &str, event_type: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = event_type.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.len() >= part.len() && rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    // No `*` at all
+    rest.is_empty()
+}
+
+/// Span covering a plugin's handler call
+fn handler_span(subscriber: &str, event_type: &str) -> tracing::Span {
+    debug_span!("event handler", plugin = subscriber, event = event_type)
+}
+
+/// Run a handler on the blocking thread pool, logging failures
+async fn run_spawned_handler(
+    subscription: Arc<EventSubscription>,
+    event: Arc<Event>,
+    metrics: Option<Arc<MetricsCollector>>,
+) {
+    let subscriber = subscription.subscriber.clone();
+    let span = handler_span(&subscriber, &event.event_type);
+    let call = move || match metrics {
+        Some(metrics) => metrics.track(&subscription.subscriber, || (subscription.handler)(&event)),
+        None => (subscription.handler)(&event),
+    };
+    match tokio::task::spawn_blocking(move || span.in_scope(call)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("Event handler failed for plugin '{}': {}", subscriber, e),
+        Err(e) => tracing::error!("Event handler panicked for plugin '{}': {}", subscriber, e),
+    }
+}
+
 /// Event bus statistics
 #[derive(Debug, Clone)]
 pub struct EventBusStats {
     pub total_event_types: usize,
     pub total_subscriptions: usize,
     pub broadcast_receivers: usize,
+    pub dropped_handler_calls: u64,
+    pub dropped_paused_events: u64,
+    pub lifecycle_listeners: usize,
+    pub dropped_lifecycle_events: u64,
 }
 
 /// Predefined event types from events.txt
@@ -278,11 +1127,13 @@ pub mod predefined {
     // User connection events
     pub const USER_CONNECT: &str = "user_connect";
     pub const USER_DISCONNECT: &str = "user_disconnect";
+    pub const AUTH_RATE_LIMITED: &str = "auth_rate_limited";
     
     // Room state events
     pub const ROOM_STATE_CHANGE: &str = "room_state_change";
     pub const ROOM_CREATE: &str = "room_create";
     pub const ROOM_DISBAND: &str = "room_disband";
+    pub const ROOM_RENAME: &str = "room_rename";
     pub const USER_JOIN_ROOM: &str = "user_join_room";
     pub const USER_LEAVE_ROOM: &str = "user_leave_room";
     pub const ROOM_START_PREPARATION: &str = "room_start_preparation";
@@ -296,16 +1147,38 @@ pub mod predefined {
     pub const USER_GIVE_UP_GAME: &str = "user_give_up_game";
     pub const ROOM_PREPARE_GAME: &str = "room_prepare_game";
     pub const CHART_SELECT: &str = "chart_select";
+    pub const CHART_POOL_REPLACE: &str = "chart_pool_replace";
+
+    // Matchmaking events
+    pub const QUEUE_ENQUEUE: &str = "queue_enqueue";
+    pub const QUEUE_DEQUEUE: &str = "queue_dequeue";
     
     // Command and message events
     pub const COMMAND_INPUT: &str = "command_input";
     pub const MESSAGE_SEND: &str = "message_send";
+    pub const MESSAGE_DELIVERED: &str = "message_delivered";
     
     // Plugin events
     pub const PLUGIN_LOAD: &str = "plugin_load";
     pub const PLUGIN_UNLOAD: &str = "plugin_unload";
     pub const PLUGIN_ERROR: &str = "plugin_error";
     pub const CONFIG_RELOAD: &str = "config_reload";
+    pub const PLUGIN_HOT_RELOAD: &str = "plugin_hot_reload";
+    pub const PLUGIN_PAUSED: &str = "plugin_paused";
+    pub const PLUGIN_RESUMED: &str = "plugin_resumed";
+
+    /// System events delivered even while the event bus is paused
+    pub const CRITICAL: &[&str] = &[SERVER_SHUTDOWN, PLUGIN_ERROR];
+
+    /// System events also delivered to lifecycle listeners
+    pub const LIFECYCLE: &[&str] = &[
+        PLUGIN_LOAD,
+        PLUGIN_UNLOAD,
+        PLUGIN_ERROR,
+        PLUGIN_HOT_RELOAD,
+        PLUGIN_PAUSED,
+        PLUGIN_RESUMED,
+    ];
 }
 
 #[cfg(test)]
@@ -366,4 +1239,406 @@ mod tests {
         
         assert_eq!(handler_called.load(Ordering::SeqCst), 0);
     }
-}
\ No newline at end of file
+
+    fn concurrency_probe(
+        running: &Arc<AtomicUsize>,
+        peak: &Arc<AtomicUsize>,
+        calls: &Arc<AtomicUsize>,
+    ) -> EventHandler {
+        let (running, peak, calls) = (Arc::clone(running), Arc::clone(peak), Arc::clone(calls));
+        Box::new(move |_event| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_spawned_handlers_queue_at_limit() {
+        let event_bus = EventBus::new();
+        event_bus.set_handler_concurrency(HandlerConcurrency {
+            max_per_plugin: 2,
+            overflow: HandlerOverflow::Queue,
+        });
+        let (running, peak, calls) = Default::default();
+        event_bus
+            .subscribe("touch", concurrency_probe(&running, &peak, &calls), "test_plugin")
+            .unwrap();
+
+        let mut handles = Vec::new();
+        for _ in 0..20 {
+            let event = Event::system("touch", serde_json::json!({}));
+            handles.extend(event_bus.emit_spawned(event).unwrap());
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 20);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        assert_eq!(event_bus.stats().dropped_handler_calls, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_spawned_handlers_shed_at_limit() {
+        let event_bus = EventBus::new();
+        event_bus.set_handler_concurrency(HandlerConcurrency {
+            max_per_plugin: 2,
+            overflow: HandlerOverflow::Shed,
+        });
+        let (running, peak, calls) = Default::default();
+        event_bus
+            .subscribe("touch", concurrency_probe(&running, &peak, &calls), "test_plugin")
+            .unwrap();
+
+        let mut handles = Vec::new();
+        for _ in 0..20 {
+            let event = Event::system("touch", serde_json::json!({}));
+            handles.extend(event_bus.emit_spawned(event).unwrap());
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let dropped = event_bus.dropped_handler_calls("test_plugin");
+        assert!(dropped > 0);
+        assert_eq!(calls.load(Ordering::SeqCst) as u64 + dropped, 20);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        assert_eq!(event_bus.stats().dropped_handler_calls, dropped);
+    }
+
+    #[test]
+    fn test_pause_buffers_events() {
+        let event_bus = EventBus::new();
+        let received = Arc::new(RwLock::new(Vec::new()));
+        for event_type in ["chat", predefined::SERVER_SHUTDOWN] {
+            let received = Arc::clone(&received);
+            event_bus
+                .subscribe(
+                    event_type,
+                    Box::new(move |event| {
+                        received.write().push(event.event_type.clone());
+                        Ok(())
+                    }),
+                    "test_plugin",
+                )
+                .unwrap();
+        }
+
+        event_bus.pause();
+        assert!(event_bus.is_paused());
+        event_bus.emit(Event::system("chat", serde_json::json!(1))).unwrap();
+        event_bus.emit(Event::system("chat", serde_json::json!(2))).unwrap();
+        assert!(received.read().is_empty());
+
+        // Critical system events are not held back
+        event_bus
+            .emit(Event::system(predefined::SERVER_SHUTDOWN, serde_json::json!({})))
+            .unwrap();
+        assert_eq!(*received.read(), vec![predefined::SERVER_SHUTDOWN]);
+
+        assert_eq!(event_bus.resume(), 2);
+        assert!(!event_bus.is_paused());
+        assert_eq!(*received.read(), vec![predefined::SERVER_SHUTDOWN, "chat", "chat"]);
+
+        event_bus.emit(Event::system("chat", serde_json::json!(3))).unwrap();
+        assert_eq!(received.read().len(), 4);
+        assert_eq!(event_bus.resume(), 0);
+    }
+
+    #[test]
+    fn test_lifecycle_listener() {
+        let event_bus = EventBus::new();
+        let mut receiver = event_bus.subscribe_lifecycle("operator", 2);
+        let load = |name: &str| Event::system(predefined::PLUGIN_LOAD, serde_json::json!(name));
+
+        // Only lifecycle events, and they skip the pause
+        event_bus.pause();
+        event_bus.emit(Event::system("chat", serde_json::json!(1))).unwrap();
+        event_bus.emit(load("a")).unwrap();
+        event_bus.emit(Event::plugin(predefined::PLUGIN_LOAD, serde_json::json!("x"), "fake")).unwrap();
+        assert_eq!(receiver.try_recv().unwrap().data, "a");
+        assert!(receiver.try_recv().is_err());
+        event_bus.resume();
+
+        for name in ["b", "c", "d"] {
+            event_bus.emit(load(name)).unwrap();
+        }
+        assert_eq!(event_bus.dropped_lifecycle_events("operator"), 1);
+        assert_eq!(event_bus.stats().dropped_lifecycle_events, 1);
+        assert_eq!(receiver.try_recv().unwrap().data, "b");
+        assert_eq!(receiver.try_recv().unwrap().data, "c");
+
+        drop(receiver);
+        event_bus.emit(load("e")).unwrap();
+        assert_eq!(event_bus.stats().lifecycle_listeners, 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_handlers() {
+        let event_bus = EventBus::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        for (name, delay) in [("slow", 30), ("fast", 10)] {
+            let order = Arc::clone(&order);
+            event_bus
+                .subscribe_async(
+                    "tick",
+                    Box::new(move |event| {
+                        let order = Arc::clone(&order);
+                        Box::pin(async move {
+                            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                            order.lock().push(format!("{} {}", name, event.data));
+                            Ok(())
+                        })
+                    }),
+                    name,
+                )
+                .unwrap();
+        }
+        let sync_order = Arc::clone(&order);
+        event_bus
+            .subscribe(
+                "tick",
+                Box::new(move |_event| {
+                    sync_order.lock().push("sync".to_string());
+                    Ok(())
+                }),
+                "sync",
+            )
+            .unwrap();
+        assert_eq!(event_bus.stats().total_subscriptions, 3);
+
+        // Async handlers are skipped by emit
+        event_bus.emit(Event::system("tick", serde_json::json!(0))).unwrap();
+        assert_eq!(*order.lock(), vec!["sync"]);
+
+        event_bus.emit_async(Event::system("tick", serde_json::json!(1))).await.unwrap();
+        assert_eq!(*order.lock(), vec!["sync", "sync", "fast 1", "slow 1"]);
+
+        event_bus.unsubscribe_all("slow").unwrap();
+        event_bus.unsubscribe_all("fast").unwrap();
+        assert_eq!(event_bus.get_subscribers("tick"), vec!["sync"]);
+        event_bus.unsubscribe("tick", "sync").unwrap();
+        assert!(event_bus.get_event_types().is_empty());
+    }
+
+    #[test]
+    fn test_cancellable_emit() {
+        let event_bus = EventBus::new();
+        event_bus
+            .subscribe_cancellable(
+                predefined::USER_JOIN_ROOM,
+                Box::new(|event| {
+                    Ok(match event.data["user_id"].as_u64() {
+                        Some(1) => HandlerResult::Cancel("banned".to_string()),
+                        Some(2) => HandlerResult::Modify(serde_json::json!({ "user_id": 3 })),
+                        _ => HandlerResult::Continue,
+                    })
+                }),
+                "moderation",
+                0,
+            )
+            .unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        event_bus
+            .subscribe_cancellable(
+                predefined::USER_JOIN_ROOM,
+                Box::new(move |event| {
+                    sink.lock().push(event.data["user_id"].clone());
+                    Ok(HandlerResult::Continue)
+                }),
+                "audit",
+                0,
+            )
+            .unwrap();
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&delivered);
+        event_bus
+            .subscribe(
+                predefined::USER_JOIN_ROOM,
+                Box::new(move |_event| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }),
+                "stats",
+            )
+            .unwrap();
+        let join = |user_id: u32| {
+            Event::system(predefined::USER_JOIN_ROOM, serde_json::json!({ "user_id": user_id }))
+        };
+
+        match event_bus.emit_cancellable(join(1)) {
+            EmitOutcome::Cancelled(reason) => assert_eq!(reason, "banned"),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(seen.lock().is_empty());
+        assert_eq!(delivered.load(Ordering::SeqCst), 0);
+
+        match event_bus.emit_cancellable(join(2)) {
+            EmitOutcome::Completed(event) => assert_eq!(event.data["user_id"], 3),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert_eq!(*seen.lock(), vec![serde_json::json!(3)]);
+        assert_eq!(delivered.load(Ordering::SeqCst), 1);
+
+        event_bus.unsubscribe_all("moderation").unwrap();
+        assert!(matches!(event_bus.emit_cancellable(join(1)), EmitOutcome::Completed(_)));
+        assert_eq!(event_bus.stats().total_subscriptions, 2);
+    }
+
+    #[test]
+    fn test_pattern_subscriptions() {
+        let event_bus = EventBus::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |label: &'static str| -> EventHandler {
+            let calls = Arc::clone(&calls);
+            Box::new(move |event| {
+                calls.lock().push(format!("{} {}", label, event.event_type));
+                Ok(())
+            })
+        };
+
+        event_bus.subscribe("room_*", record("prefix"), "logger").unwrap();
+        event_bus.subscribe(predefined::ROOM_CREATE, record("exact"), "logger").unwrap();
+        event_bus.subscribe("*", record("all"), "audit").unwrap();
+        event_bus.subscribe("*_room", record("suffix"), "audit").unwrap();
+        assert!(event_bus.has_subscribers(predefined::ROOM_DISBAND));
+        assert_eq!(event_bus.get_event_types(), vec![predefined::ROOM_CREATE]);
+
+        // Exact subscribers run first, then patterns in subscription order
+        event_bus.emit(Event::system(predefined::ROOM_CREATE, serde_json::json!({}))).unwrap();
+        event_bus.emit(Event::system(predefined::USER_JOIN_ROOM, serde_json::json!({}))).unwrap();
+        assert_eq!(
+            *calls.lock(),
+            vec![
+                "exact room_create",
+                "prefix room_create",
+                "all room_create",
+                "all user_join_room",
+                "suffix user_join_room",
+            ]
+        );
+
+        calls.lock().clear();
+        event_bus.unsubscribe("room_*", "logger").unwrap();
+        event_bus.unsubscribe_all("audit").unwrap();
+        event_bus.emit(Event::system(predefined::ROOM_CREATE, serde_json::json!({}))).unwrap();
+        event_bus.emit(Event::system(predefined::ROOM_DISBAND, serde_json::json!({}))).unwrap();
+        assert_eq!(*calls.lock(), vec!["exact room_create"]);
+        assert!(!event_bus.has_subscribers(predefined::ROOM_DISBAND));
+        assert_eq!(event_bus.stats().total_subscriptions, 1);
+
+        // Async and cancellable subscriptions would never match a pattern
+        assert!(event_bus
+            .subscribe_async("room_*", Box::new(|_event| Box::pin(async { Ok(()) })), "logger")
+            .is_err());
+        assert!(event_bus
+            .subscribe_cancellable("*", Box::new(|_event| Ok(HandlerResult::Continue)), "audit", 0)
+            .is_err());
+        assert_eq!(event_bus.stats().total_subscriptions, 1);
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(pattern_matches("*", "anything"));
+        assert!(pattern_matches("room_*", "room_"));
+        assert!(pattern_matches("room_*_mode", "room_switch_cycle_mode"));
+        assert!(!pattern_matches("room_*", "user_join_room"));
+        assert!(!pattern_matches("a*a", "a"));
+        assert!(pattern_matches("plugin_*", "plugin_hot_reload"));
+    }
+
+    #[test]
+    fn test_subscription_priority() {
+        let event_bus = EventBus::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |label: &'static str| -> EventHandler {
+            let calls = Arc::clone(&calls);
+            Box::new(move |_event| {
+                calls.lock().push(label);
+                Ok(())
+            })
+        };
+
+        event_bus.subscribe("chat", record("logger"), "logger").unwrap();
+        event_bus.subscribe_with_priority("chat", record("security"), "security", 100).unwrap();
+        event_bus.subscribe("chat", record("stats"), "stats").unwrap();
+        event_bus.subscribe_with_priority("*", record("audit"), "audit", 50).unwrap();
+        event_bus.subscribe_with_priority("chat", record("late"), "late", -10).unwrap();
+        event_bus.emit(Event::system("chat", serde_json::json!({}))).unwrap();
+        assert_eq!(*calls.lock(), vec!["security", "audit", "logger", "stats", "late"]);
+
+        // High-priority vetoes are evaluated first
+        let consulted = Arc::new(Mutex::new(Vec::new()));
+        for (name, priority) in [("filter", 0), ("security", 10)] {
+            let consulted = Arc::clone(&consulted);
+            event_bus
+                .subscribe_cancellable(
+                    "chat",
+                    Box::new(move |_event| {
+                        consulted.lock().push(name);
+                        Ok(HandlerResult::Cancel(name.to_string()))
+                    }),
+                    name,
+                    priority,
+                )
+                .unwrap();
+        }
+        match event_bus.emit_cancellable(Event::system("chat", serde_json::json!({}))) {
+            EmitOutcome::Cancelled(reason) => assert_eq!(reason, "security"),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert_eq!(*consulted.lock(), vec!["security"]);
+    }
+
+    #[test]
+    fn test_event_history_replay() {
+        let event_bus = EventBus::new();
+        event_bus.set_history_capacity(3);
+        let emit = |event_type: &str, timestamp: i64| {
+            let mut event = Event::system(event_type, serde_json::json!(timestamp));
+            event.timestamp = timestamp;
+            event_bus.emit(event).unwrap();
+        };
+        emit(predefined::SERVER_START, 1);
+        emit(predefined::ROOM_CREATE, 2);
+        emit(predefined::ROOM_CREATE, 3);
+        emit(predefined::ROOM_DISBAND, 4);
+
+        // The oldest event fell out of the buffer
+        assert_eq!(event_bus.replay_since(predefined::SERVER_START, 0, |_| Ok(())), 0);
+        let replayed = Mutex::new(Vec::new());
+        let count = event_bus.replay_since("room_*", 3, |event| {
+            replayed.lock().push(event.timestamp);
+            Ok(())
+        });
+        assert_eq!(count, 2);
+        assert_eq!(*replayed.lock(), vec![3, 4]);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let count = event_bus
+            .subscribe_with_replay(
+                predefined::ROOM_CREATE,
+                Box::new(move |event| {
+                    sink.lock().push(event.timestamp);
+                    Ok(())
+                }),
+                "late",
+                0,
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        emit(predefined::ROOM_CREATE, 5);
+        assert_eq!(*seen.lock(), vec![2, 3, 5]);
+
+        event_bus.set_history_capacity(0);
+        emit(predefined::ROOM_CREATE, 6);
+        assert_eq!(event_bus.replay_since("*", 0, |_| Ok(())), 0);
+    }
+}
@@ -108,16 +108,14 @@ pub struct HotReloadManager {
     config: HotReloadConfig,
     /// File watcher
     watcher: RwLock<Option<RecommendedWatcher>>,
-    /// Event receiver for file changes
-    event_rx: RwLock<Option<mpsc::UnboundedReceiver<notify::Result<Event>>>>,
-    /// Event sender for file changes
-    event_tx: mpsc::UnboundedSender<notify::Result<Event>>,
     /// Plugin restart attempts
     restart_attempts: RwLock<std::collections::HashMap<String, (u32, std::time::Instant)>>,
     /// Whether hot reload manager is running
     is_running: RwLock<bool>,
     /// Task handle for the hot reload loop
     task_handle: RwLock<Option<tokio::task::JoinHandle<()>>>,
+    /// Per-plugin hot reload state
+    plugin_states: RwLock<std::collections::HashMap<String, PluginHotReloadState>>,
 }
 
 impl HotReloadManager {
@@ -126,24 +124,57 @@ impl HotReloadManager {
         plugin_manager: Arc<crate::plugin_manager::PluginManager>,
         event_bus: Arc<crate::event_system::EventBus>,
         config: HotReloadConfig,
-    ) -> Result<Self> {
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
-        
-        Ok(Self {
+    ) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
             plugin_manager,
             event_bus,
             config,
             watcher: RwLock::new(None),
-            event_rx: RwLock::new(Some(event_rx)),
-            event_tx,
             restart_attempts: RwLock::new(std::collections::HashMap::new()),
             is_running: RwLock::new(false),
             task_handle: RwLock::new(None),
-        })
+            plugin_states: RwLock::new(std::collections::HashMap::new()),
+        }))
+    }
+
+    /// Enable or disable hot reload for a single plugin
+    pub fn set_plugin_enabled(&self, plugin_name: &str, enabled: bool) -> Result<()> {
+        if self.plugin_manager.get_plugin(plugin_name).is_none() {
+            return Err(Error::NotFound(plugin_name.to_string()));
+        }
+
+        let changed = {
+            let mut states = self.plugin_states.write();
+            let state = states
+                .entry(plugin_name.to_string())
+                .or_insert_with(|| PluginHotReloadState::new(true));
+            std::mem::replace(&mut state.enabled, enabled) != enabled
+        };
+        info!(
+            "Hot reload {} for plugin '{}'",
+            if enabled { "enabled" } else { "disabled" },
+            plugin_name
+        );
+        if changed && !enabled {
+            self.emit_hot_reload_event(HotReloadEvent::HotReloadDisabled {
+                plugin_name: plugin_name.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether hot reload is enabled for a plugin
+    pub fn is_plugin_enabled(&self, plugin_name: &str) -> bool {
+        self.plugin_states
+            .read()
+            .get(plugin_name)
+            .is_none_or(|state| state.enabled)
     }
 
     /// Start the hot reload manager
-    pub async fn start(&self) -> Result<()> {
+    ///
+    /// The watcher loop keeps the manager alive until [`Self::stop`] is called.
+    pub async fn start(self: &Arc<Self>) -> Result<()> {
         if *self.is_running.read() {
             return Err(Error::Runtime("Hot reload manager already running".to_string()));
         }
@@ -155,15 +186,14 @@ impl HotReloadManager {
 
         info!("Starting hot reload manager");
 
-        // Clone event sender for closure
-        let event_tx = self.event_tx.clone();
+        // Each run gets its own channel, so the manager can be restarted after stopping
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
 
         // Create file watcher
         let mut watcher = RecommendedWatcher::new(
             move |res: notify::Result<Event>| {
-                if let Ok(_) = event_tx.send(res) {
-                    // Event sent successfully
-                }
+                // The loop is gone once the manager stopped
+                let _ = event_tx.send(res);
             },
             notify::Config::default()
                 .with_poll_interval(Duration::from_secs(self.config.poll_interval_secs)),
@@ -183,14 +213,14 @@ impl HotReloadManager {
         // Store watcher
         *self.watcher.write() = Some(watcher);
         
-        // Start hot reload task
-        let this = self.clone_manager();
+        // Start hot reload task, marked running first so the loop does not exit at once
+        *self.is_running.write() = true;
+        let this = Arc::clone(self);
         let handle = tokio::spawn(async move {
-            this.hot_reload_loop().await;
+            this.hot_reload_loop(event_rx).await;
         });
         
         *self.task_handle.write() = Some(handle);
-        *self.is_running.write() = true;
         
         info!("Hot reload manager started successfully");
         Ok(())
@@ -221,8 +251,10 @@ impl HotReloadManager {
     }
 
     /// Hot reload loop
-    async fn hot_reload_loop(&self) {
-        let mut event_rx = self.event_rx.write().take().expect("Event receiver not available");
+    async fn hot_reload_loop(
+        &self,
+        mut event_rx: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    ) {
         let mut debounce_timer = time::interval(Duration::from_millis(self.config.debounce_delay_ms));
         let mut pending_changes = std::collections::HashMap::<String, Vec<PathBuf>>::new();
         
@@ -280,6 +312,11 @@ impl HotReloadManager {
             
             // Determine which plugin this file belongs to
             if let Some(plugin_name) = self.find_plugin_for_file(path) {
+                if !self.is_plugin_enabled(&plugin_name) {
+                    debug!("Hot reload disabled for plugin '{}', ignoring {:?}", plugin_name, path);
+                    continue;
+                }
+
                 // Add to pending changes for this plugin
                 pending_changes
                     .entry(plugin_name.clone())
@@ -306,6 +343,11 @@ impl HotReloadManager {
 
     /// Handle changes for a specific plugin
     async fn handle_plugin_changes(&self, plugin_name: &str, changed_files: Vec<PathBuf>) {
+        // Hot reload may have been disabled during the debounce period
+        if !self.is_plugin_enabled(plugin_name) {
+            return;
+        }
+
         // Check if plugin exists
         let _plugin = match self.plugin_manager.get_plugin(plugin_name) {
             Some(plugin) => plugin,
@@ -330,6 +372,50 @@ impl HotReloadManager {
             }
         }
         
+        // Only configuration changed: let the plugin pick it up without restarting, if it can
+        if has_config_change
+            && !has_wasm_change
+            && other_changes.is_empty()
+            && self.config.restart_on_config_change
+        {
+            match self.plugin_manager.reload_plugin_config(plugin_name).await {
+                Ok(()) => {
+                    self.plugin_states
+                        .write()
+                        .entry(plugin_name.to_string())
+                        .or_insert_with(|| PluginHotReloadState::new(true))
+                        .record_reload();
+                    let event = crate::event_system::Event::system(
+                        crate::event_system::predefined::CONFIG_RELOAD,
+                        json!({
+                            "plugin_name": plugin_name,
+                            "changed_files": changed_files
+                                .iter()
+                                .map(|p| p.to_string_lossy().to_string())
+                                .collect::<Vec<_>>(),
+                        }),
+                    );
+                    if let Err(e) = self.event_bus.emit(event) {
+                        error!("Failed to emit config reload event: {}", e);
+                    }
+                    return;
+                }
+                Err(Error::Config(e)) => {
+                    warn!(
+                        "Keeping the configuration of plugin '{}', the new one is invalid: {}",
+                        plugin_name, e
+                    );
+                    return;
+                }
+                Err(e) => {
+                    info!(
+                        "Restarting plugin '{}', its configuration cannot be reloaded in place: {}",
+                        plugin_name, e
+                    );
+                }
+            }
+        }
+
         // Decide whether to reload the plugin
         let should_reload = (has_wasm_change && self.config.restart_on_wasm_change) ||
                            (has_config_change && self.config.restart_on_config_change);
@@ -348,7 +434,7 @@ impl HotReloadManager {
                 plugin_name: plugin_name.to_string(),
                 reason: reason.to_string(),
                 changed_files: changed_files.clone(),
-            }).await;
+            });
             
             // Attempt to reload the plugin
             self.reload_plugin(plugin_name, changed_files).await;
@@ -397,7 +483,7 @@ impl HotReloadManager {
                     error: format!("Exceeded maximum restart attempts ({})", self.config.max_restart_attempts),
                     attempt: attempt_count,
                     max_attempts: self.config.max_restart_attempts,
-                }).await;
+                });
             } else {
                 warn!(
                     "Plugin '{}' reload attempted too soon, skipping (cooldown: {}s)",
@@ -410,7 +496,7 @@ impl HotReloadManager {
         // Emit reload started event
         self.emit_hot_reload_event(HotReloadEvent::PluginReloadStarted {
             plugin_name: plugin_name.to_string(),
-        }).await;
+        });
 
         // Measure reload duration
         let start_time = std::time::Instant::now();
@@ -426,23 +512,33 @@ impl HotReloadManager {
 
                 // Reset attempt count on successful reload
                 self.restart_attempts.write().remove(plugin_name);
+                self.plugin_states
+                    .write()
+                    .entry(plugin_name.to_string())
+                    .or_insert_with(|| PluginHotReloadState::new(true))
+                    .record_reload();
 
                 self.emit_hot_reload_event(HotReloadEvent::PluginReloadCompleted {
                     plugin_name: plugin_name.to_string(),
                     success: true,
                     error: None,
                     duration,
-                }).await;
+                });
             }
             Err(e) => {
                 error!("Failed to reload plugin '{}': {}", plugin_name, e);
+                self.plugin_states
+                    .write()
+                    .entry(plugin_name.to_string())
+                    .or_insert_with(|| PluginHotReloadState::new(true))
+                    .record_failed_reload(e.to_string());
 
                 self.emit_hot_reload_event(HotReloadEvent::PluginReloadCompleted {
                     plugin_name: plugin_name.to_string(),
                     success: false,
                     error: Some(e.to_string()),
                     duration,
-                }).await;
+                });
 
                 if attempt_count >= self.config.max_restart_attempts {
                     self.emit_hot_reload_event(HotReloadEvent::PluginReloadFailed {
@@ -450,7 +546,7 @@ impl HotReloadManager {
                         error: format!("Failed to reload after {} attempts", attempt_count),
                         attempt: attempt_count,
                         max_attempts: self.config.max_restart_attempts,
-                    }).await;
+                    });
                 }
             }
         }
@@ -510,7 +606,7 @@ impl HotReloadManager {
     }
 
     /// Emit a hot reload event
-    async fn emit_hot_reload_event(&self, event: HotReloadEvent) {
+    fn emit_hot_reload_event(&self, event: HotReloadEvent) {
         // Convert to JSON
         let json_event = match event {
             HotReloadEvent::FileChanged { path, event_kind } => {
@@ -562,24 +658,13 @@ impl HotReloadManager {
         
         // Emit to event bus
         if let Err(e) = self.event_bus.emit(crate::event_system::Event::system(
-            "plugin_hot_reload",
+            crate::event_system::predefined::PLUGIN_HOT_RELOAD,
             json_event,
         )) {
             error!("Failed to emit hot reload event: {}", e);
         }
     }
 
-    /// Clone the manager for async task
-    fn clone_manager(&self) -> Arc<Self> {
-        // This is a hack - we need to implement Clone or use Arc
-        // For now, we'll return self as Arc (assuming self is Arc)
-        // Actually self is &Self, so we need a different approach
-        // Let's store self as Arc in the struct
-        // For simplicity, we'll skip this and assume the caller handles it
-        // We need to refactor to store Arc<Self> in the struct
-        unimplemented!("Clone manager not implemented");
-    }
-
     /// Get hot reload manager statistics
     pub fn stats(&self) -> HotReloadManagerStats {
         let attempts = self.restart_attempts.read();
@@ -692,6 +777,101 @@ mod tests {
         assert_eq!(config.restart_cooldown_secs, 5);
     }
     
+    const MANIFEST: &str = r#"
+        name = "watched"
+        version = "1.0.0"
+        author = "test"
+        abi_version = "1.0.0"
+    "#;
+
+    #[tokio::test]
+    async fn test_disabled_plugin_ignores_changes() {
+        let dir = TempDir::new().unwrap();
+        let plugin_dir = dir.path().canonicalize().unwrap();
+        let (plugin_manager, host_api) =
+            crate::plugin_manager::create_plugin_system(&plugin_dir).unwrap();
+        let path = plugin_dir.join("watched.wasm");
+        std::fs::write(&path, MANIFEST).unwrap();
+        plugin_manager.load_plugin(&path).await.unwrap();
+
+        let manager = HotReloadManager::new(
+            Arc::clone(&plugin_manager),
+            Arc::new(crate::event_system::EventBus::new()),
+            HotReloadConfig::default(),
+        )
+        .unwrap();
+        host_api.set_hot_reload_manager(Arc::clone(&manager));
+        let event = Event::new(EventKind::Modify(notify::event::ModifyKind::Any))
+            .add_path(path.clone());
+
+        let mut pending = std::collections::HashMap::new();
+        manager.handle_file_event(&event, &mut pending).await;
+        assert_eq!(pending.get("watched"), Some(&vec![path.clone()]));
+
+        host_api.set_hot_reload_enabled("watched", false).unwrap();
+        assert!(!manager.is_plugin_enabled("watched"));
+        let mut pending = std::collections::HashMap::new();
+        manager.handle_file_event(&event, &mut pending).await;
+        assert!(pending.is_empty());
+
+        // Changes queued before disabling are dropped too
+        manager.handle_plugin_changes("watched", vec![path]).await;
+        assert_eq!(manager.stats().restart_attempts, 0);
+
+        assert!(host_api.set_hot_reload_enabled("missing", false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mass_reload_events_are_not_lost() {
+        use crate::event_system::{BROADCAST_CAPACITY, predefined};
+
+        const PLUGINS: usize = BROADCAST_CAPACITY;
+        let dir = TempDir::new().unwrap();
+        let plugin_dir = dir.path().canonicalize().unwrap();
+        let (plugin_manager, _host_api) =
+            crate::plugin_manager::create_plugin_system(&plugin_dir).unwrap();
+        let mut paths = Vec::new();
+        for i in 0..PLUGINS {
+            let path = plugin_dir.join(format!("plugin{i}.wasm"));
+            std::fs::write(&path, MANIFEST.replace("watched", &format!("plugin{i}"))).unwrap();
+            plugin_manager.load_plugin(&path).await.unwrap();
+            paths.push(path);
+        }
+
+        let event_bus = Arc::new(crate::event_system::EventBus::new());
+        let mut broadcast = event_bus.subscribe_broadcast();
+        let mut lifecycle = event_bus.subscribe_lifecycle("operator", PLUGINS * 4);
+        let manager = HotReloadManager::new(
+            Arc::clone(&plugin_manager),
+            Arc::clone(&event_bus),
+            HotReloadConfig {
+                restart_cooldown_secs: 0,
+                ..HotReloadConfig::default()
+            },
+        )
+        .unwrap();
+        for (i, path) in paths.into_iter().enumerate() {
+            manager.handle_plugin_changes(&format!("plugin{i}"), vec![path]).await;
+        }
+
+        // The broadcast channel overflowed...
+        assert!(matches!(
+            broadcast.try_recv(),
+            Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_))
+        ));
+        // ...but every reload outcome reached the lifecycle listener
+        let mut completed = 0;
+        while let Ok(event) = lifecycle.try_recv() {
+            assert_eq!(event.event_type, predefined::PLUGIN_HOT_RELOAD);
+            if event.data["type"] == "plugin_reload_completed" {
+                assert_eq!(event.data["success"], true);
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, PLUGINS);
+        assert_eq!(event_bus.dropped_lifecycle_events("operator"), 0);
+    }
+
     #[test]
     fn test_plugin_hot_reload_state() {
         let mut state = PluginHotReloadState::new(true);
@@ -709,4 +889,118 @@ mod tests {
         assert_eq!(state.reload_count, 2);
         assert_eq!(state.last_error, Some("test error".to_string()));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_start_watches_plugin_files() {
+        use crate::event_system::predefined;
+
+        let dir = TempDir::new().unwrap();
+        let plugin_dir = dir.path().canonicalize().unwrap();
+        let (plugin_manager, _host_api) =
+            crate::plugin_manager::create_plugin_system(&plugin_dir).unwrap();
+        let path = plugin_dir.join("watched.wasm");
+        std::fs::write(&path, MANIFEST).unwrap();
+        plugin_manager.load_plugin(&path).await.unwrap();
+
+        let event_bus = Arc::new(crate::event_system::EventBus::new());
+        let mut lifecycle = event_bus.subscribe_lifecycle("test", 16);
+        let manager = HotReloadManager::new(
+            Arc::clone(&plugin_manager),
+            Arc::clone(&event_bus),
+            HotReloadConfig {
+                debounce_delay_ms: 50,
+                restart_cooldown_secs: 0,
+                watch_directories: vec![plugin_dir.clone()],
+                ..HotReloadConfig::default()
+            },
+        )
+        .unwrap();
+        manager.start().await.unwrap();
+        assert!(manager.stats().is_running);
+        assert!(manager.start().await.is_err());
+
+        std::fs::write(&path, MANIFEST).unwrap();
+        let event = time::timeout(Duration::from_secs(10), async {
+            loop {
+                let event = lifecycle.recv().await.unwrap();
+                if event.data["type"] == "plugin_reload_required" {
+                    break event;
+                }
+            }
+        })
+        .await
+        .expect("no reload required after touching the plugin");
+        assert_eq!(event.event_type, predefined::PLUGIN_HOT_RELOAD);
+        assert_eq!(event.data["plugin_name"], "watched");
+
+        manager.stop().await.unwrap();
+        assert!(!manager.stats().is_running);
+        // A stopped manager can be started again
+        manager.start().await.unwrap();
+        manager.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_config_only_reload() {
+        use crate::event_system::predefined;
+
+        let dir = TempDir::new().unwrap();
+        let plugin_dir = dir.path().canonicalize().unwrap();
+        let (plugin_manager, _host_api) =
+            crate::plugin_manager::create_plugin_system(&plugin_dir).unwrap();
+        let path = plugin_dir.join("watched.wasm");
+        let manifest = format!(
+            "{MANIFEST}\nhooks = [\"on_config_reload\"]\n[config_schema]\nvolume = \"integer\"\n"
+        );
+        std::fs::write(&path, manifest).unwrap();
+        let config_path = plugin_manager.config_path("watched");
+        std::fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        std::fs::write(&config_path, "volume = 1").unwrap();
+        plugin_manager.load_plugin(&path).await.unwrap();
+
+        let event_bus = Arc::new(crate::event_system::EventBus::new());
+        let config_reloads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&config_reloads);
+        event_bus
+            .subscribe(
+                predefined::CONFIG_RELOAD,
+                Box::new(move |_| {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                }),
+                "test",
+            )
+            .unwrap();
+        let mut lifecycle = event_bus.subscribe_lifecycle("test", 16);
+        let manager = HotReloadManager::new(
+            Arc::clone(&plugin_manager),
+            Arc::clone(&event_bus),
+            HotReloadConfig {
+                restart_cooldown_secs: 0,
+                ..HotReloadConfig::default()
+            },
+        )
+        .unwrap();
+        let volume = || {
+            let plugin = plugin_manager.get_plugin("watched").unwrap();
+            plugin.read().config.get::<i64>("volume")
+        };
+
+        // Swapped in place, without a full reload
+        let plugin = plugin_manager.get_plugin("watched").unwrap();
+        std::fs::write(&config_path, "volume = 2").unwrap();
+        manager.handle_plugin_changes("watched", vec![config_path.clone()]).await;
+        assert_eq!(volume(), Some(2));
+        assert_eq!(config_reloads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(Arc::ptr_eq(&plugin, &plugin_manager.get_plugin("watched").unwrap()));
+        assert!(lifecycle.try_recv().is_err());
+
+        // A config failing the schema is rejected, keeping the current one
+        std::fs::write(&config_path, "volume = \"loud\"").unwrap();
+        manager.handle_plugin_changes("watched", vec![config_path.clone()]).await;
+        assert_eq!(config_reloads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(volume(), Some(2));
+        assert!(Arc::ptr_eq(&plugin, &plugin_manager.get_plugin("watched").unwrap()));
+        assert!(lifecycle.try_recv().is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{Error, Result};
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+use tracing::debug;
+
+/// HTTP route handler function signature, taking the JSON request body (`null` if empty)
+/// and returning the JSON response body
+pub type HttpRouteHandler = Box<dyn Fn(Value) -> Result<Value> + Send + Sync>;
+
+/// A registered route
+struct Route {
+    /// Plugin that registered this route
+    plugin: String,
+    /// Route handler
+    handler: Arc<HttpRouteHandler>,
+}
+
+/// Registry of HTTP routes served for plugins
+#[derive(Default)]
+pub struct HttpRoutes {
+    /// Routes by upper-case method and exact path
+    routes: RwLock<HashMap<(String, String), Route>>,
+}
+
+impl HttpRoutes {
+    /// Create a new route registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a route; a method and path can only be served by one plugin
+    pub fn register(
+        &self,
+        plugin: &str,
+        method: &str,
+        path: &str,
+        handler: HttpRouteHandler,
+    ) -> Result<()> {
+        if !path.starts_with('/') {
+            return Err(Error::Api(format!("HTTP route path must start with '/': {}", path)));
+        }
+        let key = (method.to_ascii_uppercase(), path.to_string());
+        let mut routes = self.routes.write();
+        if let Some(route) = routes.get(&key) {
+            return Err(Error::Api(format!(
+                "HTTP route {} {} already registered by plugin '{}'",
+                key.0, key.1, route.plugin
+            )));
+        }
+        debug!("Registering HTTP route {} {} from plugin '{}'", key.0, key.1, plugin);
+        routes.insert(
+            key,
+            Route {
+                plugin: plugin.to_string(),
+                handler: Arc::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    /// Unregister all routes of a plugin
+    pub fn unregister_all_from_plugin(&self, plugin: &str) {
+        self.routes.write().retain(|_, route| route.plugin != plugin);
+    }
+
+    /// Run the handler of a route, or `None` if no plugin serves it
+    pub fn handle(&self, method: &str, path: &str, body: Value) -> Option<Result<Value>> {
+        let handler = {
+            let routes = self.routes.read();
+            let route = routes.get(&(method.to_ascii_uppercase(), path.to_string()))?;
+            Arc::clone(&route.handler)
+        };
+        Some(handler(body))
+    }
+
+    /// Registered routes as `(method, path, plugin)`, sorted by path
+    pub fn list(&self) -> Vec<(String, String, String)> {
+        let mut routes: Vec<_> = self
+            .routes
+            .read()
+            .iter()
+            .map(|((method, path), route)| (method.clone(), path.clone(), route.plugin.clone()))
+            .collect();
+        routes.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_route_collisions_and_unregister() {
+        let routes = HttpRoutes::new();
+        routes
+            .register("stats", "get", "/stats/online", Box::new(|_| Ok(json!({"online": 3}))))
+            .unwrap();
+        routes
+            .register("stats", "POST", "/stats/online", Box::new(Ok))
+            .unwrap();
+        assert!(matches!(
+            routes.register("other", "GET", "/stats/online", Box::new(Ok)),
+            Err(Error::Api(_))
+        ));
+        assert!(routes.register("other", "GET", "stats", Box::new(Ok)).is_err());
+
+        let response = routes.handle("GET", "/stats/online", Value::Null).unwrap();
+        assert_eq!(response.unwrap(), json!({"online": 3}));
+        let echoed = routes.handle("post", "/stats/online", json!([1])).unwrap();
+        assert_eq!(echoed.unwrap(), json!([1]));
+        assert!(routes.handle("GET", "/stats", Value::Null).is_none());
+
+        routes.unregister_all_from_plugin("stats");
+        assert!(routes.list().is_empty());
+        assert!(routes.handle("GET", "/stats/online", Value::Null).is_none());
+    }
+}
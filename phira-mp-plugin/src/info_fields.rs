@@ -0,0 +1,147 @@
+use crate::{Error, Result};
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// JSON type a registered info field must hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoFieldType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl InfoFieldType {
+    /// Parse a type name as used by plugins, e.g. `"integer"`
+    pub fn parse(name: &str) -> Result<Self> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "string" => Self::String,
+            "number" | "float" => Self::Number,
+            "integer" | "int" => Self::Integer,
+            "boolean" | "bool" => Self::Boolean,
+            "array" => Self::Array,
+            "object" => Self::Object,
+            _ => return Err(Error::Api(format!("Unknown info field type: {}", name))),
+        })
+    }
+
+    /// Whether a value has this type
+    pub fn matches(self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Number => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Boolean => value.is_boolean(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+}
+
+/// A registered field
+struct InfoField {
+    /// Plugin that registered this field
+    plugin: String,
+    /// Type the value must have
+    field_type: InfoFieldType,
+}
+
+/// Registry of extra fields plugins add to serialized room or user info
+#[derive(Default)]
+pub struct InfoFields {
+    fields: RwLock<HashMap<String, InfoField>>,
+}
+
+impl InfoFields {
+    /// Create a new field registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a field; each name can only be registered by one plugin
+    pub fn register(&self, plugin: &str, name: &str, field_type: &str) -> Result<()> {
+        let field_type = InfoFieldType::parse(field_type)?;
+        let mut fields = self.fields.write();
+        if let Some(field) = fields.get(name)
+            && field.plugin != plugin
+        {
+            return Err(Error::Api(format!(
+                "Info field '{}' already registered by plugin '{}'",
+                name, field.plugin
+            )));
+        }
+        debug!("Registering info field {}: {:?} from plugin '{}'", name, field_type, plugin);
+        fields.insert(
+            name.to_string(),
+            InfoField {
+                plugin: plugin.to_string(),
+                field_type,
+            },
+        );
+        Ok(())
+    }
+
+    /// Unregister all fields of a plugin
+    pub fn unregister_all_from_plugin(&self, plugin: &str) {
+        self.fields.write().retain(|_, field| field.plugin != plugin);
+    }
+
+    /// Copy the registered fields present in `custom_data` into `output`
+    ///
+    /// Values of the wrong type are left out, and fields never overwrite keys `output`
+    /// already has.
+    pub fn merge_into(
+        &self,
+        output: &mut Map<String, Value>,
+        custom_data: &HashMap<String, Value>,
+    ) {
+        for (name, field) in self.fields.read().iter() {
+            let Some(value) = custom_data.get(name) else {
+                continue;
+            };
+            if !field.field_type.matches(value) {
+                warn!(
+                    "Omitting info field '{}' of plugin '{}': expected {:?}, got {}",
+                    name, field.plugin, field.field_type, value
+                );
+                continue;
+            }
+            output.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_registered_fields() {
+        let fields = InfoFields::new();
+        fields.register("ranked", "elo", "integer").unwrap();
+        fields.register("ranked", "id", "integer").unwrap();
+        fields.register("ranked", "division", "string").unwrap();
+        assert!(fields.register("other", "elo", "integer").is_err());
+        assert!(fields.register("other", "title", "decimal").is_err());
+
+        let custom_data = HashMap::from([
+            ("elo".to_string(), json!(1500)),
+            ("id".to_string(), json!(7)),
+            ("division".to_string(), json!(3)),
+            ("unregistered".to_string(), json!(true)),
+        ]);
+        let mut output = json!({ "id": 1 }).as_object().unwrap().clone();
+        fields.merge_into(&mut output, &custom_data);
+        assert_eq!(Value::Object(output), json!({ "id": 1, "elo": 1500 }));
+
+        fields.unregister_all_from_plugin("ranked");
+        let mut output = Map::new();
+        fields.merge_into(&mut output, &custom_data);
+        assert!(output.is_empty());
+    }
+}
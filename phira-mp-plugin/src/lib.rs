@@ -8,22 +8,36 @@ pub mod wasm_runtime;
 pub mod config;
 pub mod event_system;
 pub mod command_system;
+pub mod cooldown;
 pub mod api_host;
+pub mod chart_validator;
+pub mod matchmaking;
+pub mod player_input;
+pub mod rpc;
 pub mod metadata;
 pub mod dependency;
 pub mod sandbox;
 pub mod monitoring;
 pub mod hot_reload;
+pub mod http_routes;
+pub mod info_fields;
+pub mod ban_store;
+pub mod playtime;
 pub mod server_commands;
 // pub mod wit;
 // pub mod bindings;
 
+// Lets code generated by `#[plugin]` name this crate from inside it
+extern crate self as phira_mp_plugin;
+
 // Re-exports
-pub use plugin_manager::{PluginManager, create_plugin_system};
+pub use phira_mp_plugin_macros::plugin;
+pub use plugin_manager::{PluginManager, create_plugin_system, create_plugin_system_with};
 pub use metadata::PluginMetadata;
 pub use config::PluginConfig;
 pub use event_system::{Event, EventBus, EventHandler};
-pub use command_system::{Command, CommandRegistry};
+pub use command_system::{Command, CommandContext, CommandRegistry};
+pub use cooldown::CooldownTracker;
 pub use api_host::HostApi;
 pub use server_commands::ServerCommands;
 
@@ -49,8 +63,12 @@ pub enum Error {
     AlreadyLoaded(String),
     #[error("Plugin not found: {0}")]
     NotFound(String),
+    #[error("Plugin reload already in progress: {0}")]
+    ReloadInProgress(String),
     #[error("Invalid plugin manifest: {0}")]
     InvalidManifest(String),
+    #[error("Invalid WASM module {}: {1}", .0.display())]
+    InvalidModule(std::path::PathBuf, String),
     #[error("Unsupported plugin ABI version: {0}")]
     UnsupportedAbiVersion(String),
     #[error("Security violation: {0}")]
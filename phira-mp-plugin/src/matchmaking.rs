@@ -0,0 +1,131 @@
+use crate::Error;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use tracing::debug;
+
+/// A user waiting in a matchmaking queue
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueueEntry {
+    /// Queued user
+    pub user_id: u32,
+    /// Data attached by the plugin, e.g. rating or preferred mode
+    pub metadata: Value,
+    /// Enqueue time (milliseconds since epoch)
+    pub enqueued_at: i64,
+}
+
+/// Named first-in first-out matchmaking queues
+///
+/// Kept by the host so queued users survive plugin reloads. A user is in at most one queue.
+#[derive(Default)]
+pub struct MatchmakingQueues {
+    /// Queues by name, removed once empty
+    queues: Mutex<HashMap<String, VecDeque<QueueEntry>>>,
+}
+
+impl MatchmakingQueues {
+    /// Create empty queues
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a user to a queue, returning its 1-based position
+    pub fn push(&self, queue: &str, user_id: u32, metadata: Value) -> Result<usize, Error> {
+        let mut queues = self.queues.lock();
+        if let Some(current) = queues
+            .iter()
+            .find_map(|(name, entries)| entries.iter().any(|it| it.user_id == user_id).then_some(name))
+        {
+            return Err(Error::Api(format!(
+                "User {} is already queued in '{}'",
+                user_id, current
+            )));
+        }
+        let entries = queues.entry(queue.to_string()).or_default();
+        entries.push_back(QueueEntry {
+            user_id,
+            metadata,
+            enqueued_at: chrono::Utc::now().timestamp_millis(),
+        });
+        debug!("User {} joined queue '{}' at position {}", user_id, queue, entries.len());
+        Ok(entries.len())
+    }
+
+    /// Take the `n` longest-waiting users of a queue, or nobody if fewer are queued
+    pub fn pop_batch(&self, queue: &str, n: usize) -> Vec<QueueEntry> {
+        let mut queues = self.queues.lock();
+        let Some(entries) = queues.get_mut(queue) else {
+            return Vec::new();
+        };
+        if n == 0 || entries.len() < n {
+            return Vec::new();
+        }
+        let batch: Vec<_> = entries.drain(..n).collect();
+        if entries.is_empty() {
+            queues.remove(queue);
+        }
+        batch
+    }
+
+    /// Remove a user from whichever queue holds it, returning the queue name and entry
+    pub fn remove(&self, user_id: u32) -> Option<(String, QueueEntry)> {
+        let mut queues = self.queues.lock();
+        let (name, index) = queues.iter().find_map(|(name, entries)| {
+            entries
+                .iter()
+                .position(|it| it.user_id == user_id)
+                .map(|index| (name.clone(), index))
+        })?;
+        let entries = queues.get_mut(&name)?;
+        let entry = entries.remove(index)?;
+        if entries.is_empty() {
+            queues.remove(&name);
+        }
+        Some((name, entry))
+    }
+
+    /// Number of users in a queue
+    pub fn len(&self, queue: &str) -> usize {
+        self.queues.lock().get(queue).map_or(0, VecDeque::len)
+    }
+
+    /// Entries of a queue, longest-waiting first
+    pub fn entries(&self, queue: &str) -> Vec<QueueEntry> {
+        self.queues
+            .lock()
+            .get(queue)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_queue_order() {
+        let queues = MatchmakingQueues::new();
+        for user_id in 1..=5 {
+            assert_eq!(
+                queues.push("ranked", user_id, json!({ "rating": user_id * 100 })).unwrap(),
+                user_id as usize
+            );
+        }
+        assert!(queues.push("casual", 3, json!(null)).is_err());
+
+        assert!(queues.pop_batch("ranked", 6).is_empty());
+        let batch = queues.pop_batch("ranked", 2);
+        assert_eq!(batch.iter().map(|it| it.user_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(batch[1].metadata, json!({ "rating": 200 }));
+
+        assert_eq!(queues.remove(4).unwrap().0, "ranked");
+        assert!(queues.remove(4).is_none());
+        let batch = queues.pop_batch("ranked", 2);
+        assert_eq!(batch.iter().map(|it| it.user_id).collect::<Vec<_>>(), vec![3, 5]);
+        assert_eq!(queues.len("ranked"), 0);
+    }
+}
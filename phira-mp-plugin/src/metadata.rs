@@ -5,6 +5,37 @@ use std::{
     collections::HashMap,
 };
 use toml;
+use tracing::warn;
+
+/// Canonical permission identifiers a plugin may declare
+pub const KNOWN_PERMISSIONS: &[&str] = &[
+    "read_users",
+    "write_users",
+    "read_rooms",
+    "write_rooms",
+    "read_config",
+    "write_config",
+    "send_messages",
+    "broadcast",
+    "manage_bans",
+    "manage_plugins",
+    "register_commands",
+    "client_notifications",
+    "network",
+    "filesystem",
+    "admin",
+    "player_input",
+];
+
+/// How a plugin declaring unknown permissions is handled at load
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownPermissionPolicy {
+    /// Log a warning and load the plugin
+    #[default]
+    Warn,
+    /// Refuse to load the plugin
+    Reject,
+}
 
 /// Plugin metadata from manifest file
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,8 +66,12 @@ pub struct PluginMetadata {
     pub license: Option<String>,
     /// Minimum required host version (optional)
     pub min_host_version: Option<String>,
-    /// Plugin configuration schema (optional)
+    /// Plugin configuration schema (optional), either a JSON Schema (recognized by its
+    /// `$schema` key or `properties` table) or a table mapping keys to a type name or to a
+    /// table with `type` and `required`
     pub config_schema: Option<toml::Value>,
+    /// Optional hooks the plugin exports, such as `on_config_reload`
+    pub hooks: Option<Vec<String>>,
     /// Custom metadata fields (optional)
     #[serde(flatten)]
     pub custom: Option<HashMap<String, toml::Value>>,
@@ -138,6 +173,85 @@ impl PluginMetadata {
         self.permissions.as_ref().map(|perms| perms.contains(&permission.to_string())).unwrap_or(false)
     }
 
+    /// Get declared permissions that are not in [`KNOWN_PERMISSIONS`]
+    pub fn unknown_permissions(&self) -> Vec<&str> {
+        self.permissions
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|perm| !KNOWN_PERMISSIONS.contains(perm))
+            .collect()
+    }
+
+    /// Validate declared permissions against [`KNOWN_PERMISSIONS`]
+    pub fn validate_permissions(&self, policy: UnknownPermissionPolicy) -> Result<(), Error> {
+        let unknown = self.unknown_permissions();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        match policy {
+            UnknownPermissionPolicy::Warn => {
+                warn!("Plugin {} declares unknown permissions: {:?}", self.name, unknown);
+                Ok(())
+            }
+            UnknownPermissionPolicy::Reject => Err(Error::InvalidManifest(format!(
+                "Unknown permissions: {:?}",
+                unknown
+            ))),
+        }
+    }
+
+    /// Check if plugin exports an optional hook
+    pub fn has_hook(&self, hook: &str) -> bool {
+        self.hooks.as_ref().map(|hooks| hooks.iter().any(|it| it == hook)).unwrap_or(false)
+    }
+
+    /// Validate a configuration against the configuration schema, if any
+    ///
+    /// Keys missing from a key-to-type schema are accepted.
+    pub fn validate_config(&self, config: &crate::config::PluginConfig) -> Result<(), Error> {
+        let Some(schema) = self.config_schema.as_ref().and_then(toml::Value::as_table) else {
+            return Ok(());
+        };
+        let is_json_schema = schema.contains_key("$schema")
+            || schema.get("properties").is_some_and(toml::Value::is_table);
+        if is_json_schema {
+            let schema = serde_json::to_value(schema)
+                .map_err(|e| Error::InvalidManifest(format!("Invalid config schema: {}", e)))?;
+            return config.validate_against(&schema);
+        }
+        for (key, spec) in schema {
+            let (expected, required) = match spec {
+                toml::Value::String(expected) => (Some(expected.as_str()), false),
+                toml::Value::Table(spec) => (
+                    spec.get("type").and_then(toml::Value::as_str),
+                    spec.get("required").and_then(toml::Value::as_bool).unwrap_or(false),
+                ),
+                _ => {
+                    return Err(Error::InvalidManifest(format!(
+                        "Invalid config schema for key '{}'",
+                        key
+                    )))
+                }
+            };
+            match config.values.get(key) {
+                None if required => {
+                    return Err(Error::Config(format!("Missing required config key '{}'", key)))
+                }
+                Some(value) if expected.is_some_and(|expected| expected != value.type_str()) => {
+                    return Err(Error::Config(format!(
+                        "Config key '{}' must be {}, got {}",
+                        key,
+                        expected.unwrap_or_default(),
+                        value.type_str()
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     /// Check if plugin depends on another plugin
     pub fn depends_on(&self, plugin_name: &str) -> bool {
         self.dependencies.as_ref().map(|deps| deps.contains(&plugin_name.to_string())).unwrap_or(false)
@@ -174,6 +288,7 @@ impl Default for PluginMetadata {
             license: None,
             min_host_version: None,
             config_schema: None,
+            hooks: None,
             custom: None,
         }
     }
@@ -210,6 +325,25 @@ mod tests {
         assert!(metadata.has_tag("test"));
     }
     
+    #[test]
+    fn test_validate_permissions() {
+        let mut metadata = PluginMetadata {
+            name: "test-plugin".to_string(),
+            permissions: Some(vec!["read_users".to_string(), "write_config".to_string()]),
+            ..Default::default()
+        };
+        assert!(metadata.unknown_permissions().is_empty());
+        assert!(metadata.validate_permissions(UnknownPermissionPolicy::Reject).is_ok());
+
+        metadata.permissions.as_mut().unwrap().push("read_user".to_string());
+        assert_eq!(metadata.unknown_permissions(), vec!["read_user"]);
+        assert!(metadata.validate_permissions(UnknownPermissionPolicy::Warn).is_ok());
+        assert!(matches!(
+            metadata.validate_permissions(UnknownPermissionPolicy::Reject),
+            Err(Error::InvalidManifest(_))
+        ));
+    }
+
     #[test]
     fn test_invalid_metadata() {
         let toml_content = r#"
@@ -222,4 +356,47 @@ mod tests {
         let result = PluginMetadata::from_str(toml_content);
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_validate_config() {
+        let metadata = PluginMetadata::from_str(r#"
+            name = "test-plugin"
+            version = "1.0.0"
+            author = "Test Author"
+            abi_version = "1.0.0"
+            hooks = ["on_config_reload"]
+
+            [config_schema]
+            volume = "integer"
+            greeting = { type = "string", required = true }
+        "#).unwrap();
+        assert!(metadata.has_hook("on_config_reload"));
+        assert!(!metadata.has_hook("on_http_request"));
+
+        let mut config = crate::config::PluginConfig::new();
+        assert!(matches!(metadata.validate_config(&config), Err(Error::Config(_))));
+        config.set("greeting", "hi").unwrap();
+        config.set("extra", true).unwrap();
+        assert!(metadata.validate_config(&config).is_ok());
+        config.set("volume", "loud").unwrap();
+        assert!(matches!(metadata.validate_config(&config), Err(Error::Config(_))));
+        config.set("volume", 3).unwrap();
+        assert!(metadata.validate_config(&config).is_ok());
+
+        let metadata = PluginMetadata::from_str(r#"
+            name = "test-plugin"
+            version = "1.0.0"
+            author = "Test Author"
+            abi_version = "1.0.0"
+
+            [config_schema]
+            type = "object"
+            properties = { max_rooms = { type = "integer", minimum = 1 } }
+        "#).unwrap();
+        let mut config = crate::config::PluginConfig::new();
+        config.set("max_rooms", 0).unwrap();
+        assert!(matches!(metadata.validate_config(&config), Err(Error::Config(_))));
+        config.set("max_rooms", 4).unwrap();
+        assert!(metadata.validate_config(&config).is_ok());
+    }
+}
@@ -2,11 +2,16 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
     collections::{HashMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use serde_json::Value;
-use tracing::debug;
+use tracing::{debug, warn};
+use crate::Result;
 
 /// Plugin performance metrics
 #[derive(Debug, Clone)]
@@ -16,6 +21,10 @@ pub struct PluginMetrics {
     /// Memory usage in bytes
     pub memory_usage: u64,
     /// CPU usage percentage (0-100)
+    ///
+    /// Estimated by [`MetricsCollector::collect_metrics`] as the share of the time since the
+    /// previous collection that the plugin's tracked calls ran, see
+    /// [`MetricsCollector::track`].
     pub cpu_usage: f32,
     /// Number of active requests
     pub active_requests: u32,
@@ -153,12 +162,148 @@ impl Drop for RequestTracker {
     }
 }
 
+/// Settings for persisting metrics history across restarts
+#[derive(Debug, Clone)]
+pub struct MetricsPersistenceConfig {
+    /// Append-only time-series file, one JSON snapshot per line
+    pub path: PathBuf,
+    /// Snapshots older than this are discarded
+    pub retention: Duration,
+    /// Minimum interval between flushes to disk
+    pub flush_interval: Duration,
+}
+
+/// Metrics of a plugin as stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedMetrics {
+    memory_usage: u64,
+    cpu_usage: f32,
+    active_requests: u32,
+    total_requests: u64,
+    avg_latency_ms: f64,
+    error_rate: f64,
+    #[serde(default)]
+    custom_metrics: HashMap<String, Value>,
+}
+
+/// A history snapshot as stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedSnapshot {
+    /// Unix time in milliseconds
+    timestamp: i64,
+    metrics: HashMap<String, PersistedMetrics>,
+}
+
+impl PersistedSnapshot {
+    fn new(snapshot: &HashMap<String, PluginMetrics>) -> Self {
+        let metrics = snapshot
+            .iter()
+            .map(|(name, metrics)| {
+                (
+                    name.clone(),
+                    PersistedMetrics {
+                        memory_usage: metrics.memory_usage,
+                        cpu_usage: metrics.cpu_usage,
+                        active_requests: metrics.active_requests,
+                        total_requests: metrics.total_requests,
+                        avg_latency_ms: metrics.avg_latency_ms,
+                        error_rate: metrics.error_rate,
+                        custom_metrics: metrics.custom_metrics.clone(),
+                    },
+                )
+            })
+            .collect();
+        Self {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            metrics,
+        }
+    }
+
+    fn into_entry(self, now_ms: i64) -> HistoryEntry {
+        let age = Duration::from_millis(now_ms.saturating_sub(self.timestamp).max(0) as u64);
+        let timestamp = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+        let metrics = self
+            .metrics
+            .into_iter()
+            .map(|(name, metrics)| {
+                let restored = PluginMetrics {
+                    plugin_name: name.clone(),
+                    memory_usage: metrics.memory_usage,
+                    cpu_usage: metrics.cpu_usage,
+                    active_requests: metrics.active_requests,
+                    total_requests: metrics.total_requests,
+                    avg_latency_ms: metrics.avg_latency_ms,
+                    error_rate: metrics.error_rate,
+                    timestamp,
+                    custom_metrics: metrics.custom_metrics,
+                };
+                (name, restored)
+            })
+            .collect();
+        HistoryEntry {
+            captured_at: timestamp,
+            metrics,
+        }
+    }
+}
+
+/// A metrics history snapshot and when it was captured
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    captured_at: Instant,
+    metrics: HashMap<String, PluginMetrics>,
+}
+
+/// Persistence state of a metrics collector
+struct MetricsPersistence {
+    config: MetricsPersistenceConfig,
+    /// Snapshots not yet written to disk
+    pending: Vec<PersistedSnapshot>,
+    /// Last flush time
+    last_flush: Instant,
+    /// Timestamp of the oldest snapshot in the file
+    oldest_persisted: Option<i64>,
+}
+
+/// Read the snapshots in `config.path` within retention, rewriting the file to only contain them
+fn compact_metrics_file(config: &MetricsPersistenceConfig) -> Result<Vec<PersistedSnapshot>> {
+    let file = match fs::File::open(&config.path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let cutoff = chrono::Utc::now().timestamp_millis() - config.retention.as_millis() as i64;
+    let mut snapshots = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PersistedSnapshot>(&line) {
+            Ok(snapshot) if snapshot.timestamp >= cutoff => snapshots.push(snapshot),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping malformed metrics line in {:?}: {}", config.path, e),
+        }
+    }
+
+    let tmp_path = config.path.with_extension("tmp");
+    {
+        let mut tmp = fs::File::create(&tmp_path)?;
+        for snapshot in &snapshots {
+            writeln!(tmp, "{}", serde_json::to_string(snapshot)?)?;
+        }
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, &config.path)?;
+    Ok(snapshots)
+}
+
 /// Metrics collector for plugins
 pub struct MetricsCollector {
     /// Plugin metrics by plugin name
     metrics: RwLock<HashMap<String, Arc<RwLock<PluginMetrics>>>>,
-    /// Metrics history (ring buffer)
-    history: RwLock<VecDeque<HashMap<String, PluginMetrics>>>,
+    /// Metrics history (ring buffer), oldest first
+    history: RwLock<VecDeque<HistoryEntry>>,
     /// Maximum history size
     max_history_size: usize,
     /// Metrics aggregation interval
@@ -167,6 +312,10 @@ pub struct MetricsCollector {
     last_aggregation: RwLock<Instant>,
     /// Metrics subscribers
     subscribers: RwLock<Vec<mpsc::Sender<PluginMetrics>>>,
+    /// On-disk history, if enabled
+    persistence: Mutex<Option<MetricsPersistence>>,
+    /// Time spent in each plugin's tracked calls since the last collection
+    busy_time: Mutex<HashMap<String, Duration>>,
 }
 
 impl MetricsCollector {
@@ -179,7 +328,88 @@ impl MetricsCollector {
             aggregation_interval,
             last_aggregation: RwLock::new(Instant::now()),
             subscribers: RwLock::new(Vec::new()),
+            persistence: Mutex::new(None),
+            busy_time: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Persist history to a time-series file, restoring recent history from it
+    ///
+    /// Returns the number of restored snapshots.
+    pub fn enable_persistence(&self, config: MetricsPersistenceConfig) -> Result<usize> {
+        if let Some(parent) = config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let snapshots = compact_metrics_file(&config)?;
+        let oldest_persisted = snapshots.first().map(|snapshot| snapshot.timestamp);
+
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let skip = snapshots.len().saturating_sub(self.max_history_size);
+        let restored: Vec<_> = snapshots
+            .into_iter()
+            .skip(skip)
+            .map(|snapshot| snapshot.into_entry(now_ms))
+            .collect();
+        let count = restored.len();
+        {
+            let mut history = self.history.write();
+            for snapshot in restored.into_iter().rev() {
+                history.push_front(snapshot);
+            }
+            while history.len() > self.max_history_size {
+                history.pop_front();
+            }
         }
+
+        debug!("Restored {} metrics snapshots from {:?}", count, config.path);
+        *self.persistence.lock() = Some(MetricsPersistence {
+            config,
+            pending: Vec::new(),
+            last_flush: Instant::now(),
+            oldest_persisted,
+        });
+        Ok(count)
+    }
+
+    /// Append pending snapshots to the time-series file
+    pub fn flush_metrics(&self) -> Result<()> {
+        let mut persistence = self.persistence.lock();
+        let Some(persistence) = persistence.as_mut() else {
+            return Ok(());
+        };
+        persistence.last_flush = Instant::now();
+        if persistence.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&persistence.config.path)?;
+        let mut buf = String::new();
+        for snapshot in &persistence.pending {
+            buf.push_str(&serde_json::to_string(snapshot)?);
+            buf.push('\n');
+        }
+        file.write_all(buf.as_bytes())?;
+        file.flush()?;
+
+        if persistence.oldest_persisted.is_none() {
+            persistence.oldest_persisted = persistence.pending.first().map(|s| s.timestamp);
+        }
+        persistence.pending.clear();
+
+        // Compact once the file holds data well past retention
+        let retention_ms = persistence.config.retention.as_millis() as i64;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        if persistence
+            .oldest_persisted
+            .is_some_and(|oldest| now_ms - oldest > retention_ms.saturating_mul(2))
+        {
+            let snapshots = compact_metrics_file(&persistence.config)?;
+            persistence.oldest_persisted = snapshots.first().map(|s| s.timestamp);
+        }
+        Ok(())
     }
 
     /// Register a plugin for metrics collection
@@ -196,6 +426,7 @@ impl MetricsCollector {
     /// Unregister a plugin from metrics collection
     pub fn unregister_plugin(&self, plugin_name: &str) {
         self.metrics.write().remove(plugin_name);
+        self.busy_time.lock().remove(plugin_name);
         debug!("Unregistered plugin from metrics collection: {}", plugin_name);
     }
 
@@ -233,6 +464,34 @@ impl MetricsCollector {
         }
     }
 
+    /// End a request started with [`Self::start_request`], counting its duration as time
+    /// the plugin was busy
+    pub fn finish_request(&self, tracker: RequestTracker, success: bool) {
+        let elapsed = tracker.elapsed();
+        self.end_request(tracker.plugin_name(), success, elapsed);
+        *self
+            .busy_time
+            .lock()
+            .entry(tracker.plugin_name().to_string())
+            .or_default() += elapsed;
+    }
+
+    /// Run a call into a plugin, recording its latency, outcome and busy time
+    ///
+    /// Calls of plugins not registered are run untracked.
+    pub fn track<T, E>(
+        &self,
+        plugin_name: &str,
+        call: impl FnOnce() -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let tracker = self.start_request(plugin_name);
+        let result = call();
+        if let Some(tracker) = tracker {
+            self.finish_request(tracker, result.is_ok());
+        }
+        result
+    }
+
     /// Update plugin memory usage
     pub fn update_memory_usage(&self, plugin_name: &str, usage: u64) {
         if let Some(metrics) = self.metrics.read().get(plugin_name) {
@@ -255,51 +514,89 @@ impl MetricsCollector {
     }
 
     /// Collect and aggregate metrics
+    ///
+    /// Each plugin's CPU usage becomes the time its tracked calls ran since the previous
+    /// collection, as a percentage of that period. Calls run synchronously on host threads,
+    /// so their wall time stands in for CPU time; a plugin busy on several threads at once
+    /// is capped at 100%.
     pub fn collect_metrics(&self) {
         let now = Instant::now();
         let last_aggregation = *self.last_aggregation.read();
+        let period = now.duration_since(last_aggregation);
         
-        if now.duration_since(last_aggregation) < self.aggregation_interval {
+        if period < self.aggregation_interval {
             return;
         }
         
         // Update last aggregation time
         *self.last_aggregation.write() = now;
+
+        // Turn busy time into CPU usage over the period
+        let busy_time = std::mem::take(&mut *self.busy_time.lock());
+        if !period.is_zero() {
+            for (plugin_name, metrics) in self.metrics.read().iter() {
+                let busy = busy_time.get(plugin_name).copied().unwrap_or_default();
+                let usage = (busy.as_secs_f64() / period.as_secs_f64() * 100.0).min(100.0);
+                metrics.write().update_cpu_usage(usage as f32);
+            }
+        }
         
         // Get current metrics snapshot
         let snapshot = self.get_all_metrics();
         
         // Add to history
         let mut history = self.history.write();
-        history.push_back(snapshot);
+        history.push_back(HistoryEntry {
+            captured_at: now,
+            metrics: snapshot,
+        });
         
         // Trim history if it exceeds max size
         while history.len() > self.max_history_size {
             history.pop_front();
         }
         
+        // Queue for persistence
+        let flush_due = match self.persistence.lock().as_mut() {
+            Some(persistence) => {
+                if let Some(entry) = history.back() {
+                    persistence.pending.push(PersistedSnapshot::new(&entry.metrics));
+                }
+                persistence.last_flush.elapsed() >= persistence.config.flush_interval
+            }
+            None => false,
+        };
+        let history_size = history.len();
+        drop(history);
+
+        if flush_due && let Err(e) = self.flush_metrics() {
+            warn!("Failed to flush metrics: {}", e);
+        }
+
         // Notify subscribers
         self.notify_subscribers();
         
-        debug!("Collected metrics snapshot (history size: {})", history.len());
+        debug!("Collected metrics snapshot (history size: {})", history_size);
     }
 
     /// Get metrics history
     pub fn get_history(&self) -> Vec<HashMap<String, PluginMetrics>> {
-        self.history.read().iter().cloned().collect()
+        self.history.read().iter().map(|entry| entry.metrics.clone()).collect()
     }
 
-    /// Get aggregated metrics over time window
-    pub fn get_aggregated_metrics(&self, _window: Duration) -> HashMap<String, AggregatedMetrics> {
+    /// Get aggregated metrics over the snapshots captured within `window`
+    pub fn get_aggregated_metrics(&self, window: Duration) -> HashMap<String, AggregatedMetrics> {
         let history = self.history.read();
-        let _now = Instant::now();
+        let cutoff = Instant::now().checked_sub(window);
 
         let mut aggregated = HashMap::new();
 
-        for snapshot in history.iter().rev() {
-            // Check if snapshot is within time window
-            // Note: This is a simplification - real implementation would track timestamps
-            for (plugin_name, metrics) in snapshot {
+        let in_window = history
+            .iter()
+            .rev()
+            .take_while(|entry| cutoff.is_none_or(|cutoff| entry.captured_at >= cutoff));
+        for entry in in_window {
+            for (plugin_name, metrics) in &entry.metrics {
                 let entry = aggregated.entry(plugin_name.clone()).or_insert_with(|| {
                     AggregatedMetrics::new(plugin_name.clone())
                 });
@@ -491,6 +788,19 @@ impl HealthStatus {
         status
     }
     
+    /// The more severe of two statuses, where `Unknown` yields to any known status
+    pub fn worst(self, other: Self) -> Self {
+        fn severity(status: HealthStatus) -> u8 {
+            match status {
+                HealthStatus::Unknown => 0,
+                HealthStatus::Healthy => 1,
+                HealthStatus::Warning => 2,
+                HealthStatus::Critical => 3,
+            }
+        }
+        if severity(other) > severity(self) { other } else { self }
+    }
+
     /// Get status as string
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -538,12 +848,19 @@ impl Default for HealthThresholds {
     }
 }
 
+/// Plugin-provided health check, returning a status and a detail message
+pub type HealthProbe = Box<dyn Fn() -> (HealthStatus, String) + Send + Sync>;
+
 /// Health monitor for plugins
 pub struct HealthMonitor {
     thresholds: HealthThresholds,
     metrics_collector: Arc<MetricsCollector>,
     status_history: RwLock<VecDeque<HashMap<String, HealthStatus>>>,
     max_status_history: usize,
+    /// Health probes by plugin
+    probes: RwLock<HashMap<String, Vec<Arc<HealthProbe>>>>,
+    /// Details reported by the last run of each plugin's probes
+    probe_details: RwLock<HashMap<String, Vec<String>>>,
 }
 
 impl HealthMonitor {
@@ -558,10 +875,54 @@ impl HealthMonitor {
             metrics_collector,
             status_history: RwLock::new(VecDeque::with_capacity(max_status_history)),
             max_status_history,
+            probes: RwLock::new(HashMap::new()),
+            probe_details: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get the collector the monitor reads metrics from
+    pub fn metrics_collector(&self) -> &Arc<MetricsCollector> {
+        &self.metrics_collector
+    }
+
+    /// Register a health probe of a plugin, run on every health check
+    pub fn register_probe(&self, plugin_name: &str, probe: HealthProbe) {
+        debug!("Registering health probe from plugin '{}'", plugin_name);
+        self.probes
+            .write()
+            .entry(plugin_name.to_string())
+            .or_default()
+            .push(Arc::new(probe));
+    }
+
+    /// Unregister all health probes of a plugin
+    pub fn unregister_probes(&self, plugin_name: &str) {
+        self.probes.write().remove(plugin_name);
+        self.probe_details.write().remove(plugin_name);
+    }
+
+    /// Run the probes of a plugin, returning their worst status, if it has any
+    fn run_probes(&self, plugin_name: &str) -> Option<HealthStatus> {
+        let probes = self.probes.read().get(plugin_name)?.clone();
+        let mut status = HealthStatus::Unknown;
+        let mut details = Vec::with_capacity(probes.len());
+        for probe in probes {
+            let (probe_status, detail) = probe();
+            status = status.worst(probe_status);
+            details.push(detail);
         }
+        self.probe_details.write().insert(plugin_name.to_string(), details);
+        Some(status)
+    }
+
+    /// Details reported by the last run of a plugin's health probes
+    pub fn get_probe_details(&self, plugin_name: &str) -> Vec<String> {
+        self.probe_details.read().get(plugin_name).cloned().unwrap_or_default()
     }
 
     /// Check health of all plugins
+    ///
+    /// A plugin's status is the worst of its metrics-derived status and its probes.
     pub fn check_health(&self) -> HashMap<String, HealthStatus> {
         let metrics = self.metrics_collector.get_all_metrics();
         let mut statuses = HashMap::new();
@@ -570,6 +931,14 @@ impl HealthMonitor {
             let status = HealthStatus::from_metrics(&plugin_metrics, &self.thresholds);
             statuses.insert(plugin_name, status);
         }
+
+        let probed: Vec<String> = self.probes.read().keys().cloned().collect();
+        for plugin_name in probed {
+            if let Some(status) = self.run_probes(&plugin_name) {
+                let entry = statuses.entry(plugin_name).or_insert(HealthStatus::Unknown);
+                *entry = entry.worst(status);
+            }
+        }
         
         // Add to history
         let mut history = self.status_history.write();
@@ -585,13 +954,40 @@ impl HealthMonitor {
 
     /// Get health status for a specific plugin
     pub fn get_plugin_health(&self, plugin_name: &str) -> HealthStatus {
-        if let Some(metrics) = self.metrics_collector.get_plugin_metrics(plugin_name) {
+        let status = if let Some(metrics) = self.metrics_collector.get_plugin_metrics(plugin_name) {
             HealthStatus::from_metrics(&metrics, &self.thresholds)
         } else {
             HealthStatus::Unknown
+        };
+        match self.run_probes(plugin_name) {
+            Some(probed) => status.worst(probed),
+            None => status,
         }
     }
 
+    /// Collect metrics and check health on every `interval`, warning about critical plugins
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn spawn_checks(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                monitor.metrics_collector.collect_metrics();
+                for (plugin_name, status) in monitor.check_health() {
+                    if status == HealthStatus::Critical {
+                        warn!(
+                            "Plugin '{}' is critical: {:?}",
+                            plugin_name,
+                            monitor.get_probe_details(&plugin_name)
+                        );
+                    }
+                }
+            }
+        })
+    }
+
     /// Get health status history
     pub fn get_health_history(&self) -> Vec<HashMap<String, HealthStatus>> {
         self.status_history.read().iter().cloned().collect()
@@ -689,6 +1085,108 @@ mod tests {
         assert!(collector.get_plugin_metrics("test_plugin").is_none());
     }
     
+    #[test]
+    fn test_aggregation_window() {
+        let collector = MetricsCollector::new(10, Duration::ZERO);
+        let now = Instant::now();
+        for (age_secs, memory_usage) in [(300, 1), (120, 2), (30, 4), (0, 8)] {
+            let mut metrics = PluginMetrics::new("test_plugin".to_string());
+            metrics.update_memory_usage(memory_usage);
+            collector.history.write().push_back(HistoryEntry {
+                captured_at: now - Duration::from_secs(age_secs),
+                metrics: HashMap::from([("test_plugin".to_string(), metrics)]),
+            });
+        }
+
+        let aggregated = collector.get_aggregated_metrics(Duration::from_secs(60));
+        assert_eq!(aggregated["test_plugin"].samples, 2);
+        assert_eq!(aggregated["test_plugin"].min_memory, 4);
+        assert_eq!(aggregated["test_plugin"].max_memory, 8);
+
+        let aggregated = collector.get_aggregated_metrics(Duration::from_secs(3600));
+        assert_eq!(aggregated["test_plugin"].samples, 4);
+    }
+
+    #[test]
+    fn test_cpu_usage_from_tracked_calls() {
+        let collector = MetricsCollector::new(10, Duration::ZERO);
+        collector.register_plugin("test_plugin".to_string());
+        *collector.last_aggregation.write() = Instant::now() - Duration::from_millis(100);
+
+        let result: std::result::Result<(), ()> = collector.track("test_plugin", || {
+            thread::sleep(Duration::from_millis(30));
+            Ok(())
+        });
+        assert!(result.is_ok());
+        collector.collect_metrics();
+
+        let metrics = collector.get_plugin_metrics("test_plugin").unwrap();
+        assert_eq!(metrics.total_requests, 1);
+        assert!(metrics.cpu_usage > 0.0, "{}", metrics.cpu_usage);
+
+        // Nothing ran since, so the next period is idle
+        collector.collect_metrics();
+        assert_eq!(collector.get_plugin_metrics("test_plugin").unwrap().cpu_usage, 0.0);
+    }
+
+    #[test]
+    fn test_metrics_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MetricsPersistenceConfig {
+            path: dir.path().join("metrics.jsonl"),
+            retention: Duration::from_secs(3600),
+            flush_interval: Duration::from_secs(3600),
+        };
+
+        let collector = MetricsCollector::new(10, Duration::ZERO);
+        assert_eq!(collector.enable_persistence(config.clone()).unwrap(), 0);
+        collector.register_plugin("test_plugin".to_string());
+        for i in 1..=3 {
+            collector.update_memory_usage("test_plugin", i * 1024);
+            collector.end_request("test_plugin", i != 2, Duration::from_millis(10));
+            collector.collect_metrics();
+        }
+        collector.flush_metrics().unwrap();
+
+        let restored = MetricsCollector::new(2, Duration::ZERO);
+        assert_eq!(restored.enable_persistence(config).unwrap(), 2);
+        let history = restored.get_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0]["test_plugin"].memory_usage, 2 * 1024);
+        assert_eq!(history[1]["test_plugin"].memory_usage, 3 * 1024);
+        assert_eq!(history[1]["test_plugin"].total_requests, 3);
+        assert!(history[1]["test_plugin"].error_rate > 0.0);
+    }
+
+    #[test]
+    fn test_metrics_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MetricsPersistenceConfig {
+            path: dir.path().join("metrics.jsonl"),
+            retention: Duration::from_secs(60),
+            flush_interval: Duration::ZERO,
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        let line = |timestamp: i64| {
+            format!(
+                r#"{{"timestamp":{timestamp},"metrics":{{"p":{{"memory_usage":{timestamp},"cpu_usage":0.0,"active_requests":0,"total_requests":0,"avg_latency_ms":0.0,"error_rate":0.0}}}}}}"#
+            )
+        };
+        fs::write(
+            &config.path,
+            format!("{}\nnot json\n{}\n", line(now - 120_000), line(now - 1000)),
+        )
+        .unwrap();
+
+        let collector = MetricsCollector::new(10, Duration::ZERO);
+        assert_eq!(collector.enable_persistence(config.clone()).unwrap(), 1);
+        assert_eq!(collector.get_history()[0]["p"].memory_usage, (now - 1000) as u64);
+
+        // Expired and malformed lines are compacted away
+        let contents = fs::read_to_string(&config.path).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+    }
+
     #[test]
     fn test_health_status() {
         let thresholds = HealthThresholds::default();
@@ -0,0 +1,232 @@
+use crate::sandbox::Sandbox;
+use parking_lot::RwLock;
+use phira_mp_common::{JudgeEvent, TouchFrame};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Number of input batches buffered per subscription; further batches are dropped
+/// until the plugin catches up
+pub const PLAYER_INPUT_QUEUE_SIZE: usize = 64;
+
+/// Frames reported by a player in a live room
+#[derive(Debug, Clone)]
+pub enum PlayerInputFrames {
+    /// Touch frames
+    Touches(Arc<Vec<TouchFrame>>),
+    /// Judge events
+    Judges(Arc<Vec<JudgeEvent>>),
+}
+
+/// A batch of input frames from one player
+#[derive(Debug, Clone)]
+pub struct PlayerInput {
+    /// Room ID
+    pub room_id: String,
+    /// Player the frames come from
+    pub user_id: u32,
+    /// Reported frames
+    pub frames: PlayerInputFrames,
+}
+
+/// Player input handler function signature
+pub type PlayerInputHandler = Box<dyn Fn(&PlayerInput) + Send + Sync>;
+
+/// A registered subscription
+struct Subscription {
+    /// Plugin that subscribed
+    plugin: String,
+    /// Room whose input is forwarded
+    room_id: String,
+    /// Only every n-th batch is forwarded
+    sample_every: u32,
+    /// Batches seen so far, used for sampling
+    seen: AtomicU64,
+    /// Batches dropped because the queue was full
+    dropped: AtomicU64,
+    /// Queue drained by the delivery task
+    sender: mpsc::Sender<PlayerInput>,
+}
+
+/// Registry of player input subscriptions
+#[derive(Default)]
+pub struct PlayerInputSubscribers {
+    subscriptions: RwLock<Vec<Subscription>>,
+}
+
+impl PlayerInputSubscribers {
+    /// Create a new subscription registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe a plugin to the input of a room, forwarding every `sample_every`-th batch
+    ///
+    /// Batches are delivered on a separate task through a bounded queue. The handler's run
+    /// time is charged to `sandbox`; once the sandbox limits are exceeded the subscription
+    /// is dropped. Must be called within a Tokio runtime.
+    pub fn subscribe(
+        &self,
+        plugin: &str,
+        room_id: &str,
+        sample_every: u32,
+        sandbox: Option<Arc<Sandbox>>,
+        handler: PlayerInputHandler,
+    ) {
+        debug!(
+            "Plugin '{}' subscribed to player input of room {} (1/{})",
+            plugin, room_id, sample_every
+        );
+        let (sender, mut receiver) = mpsc::channel::<PlayerInput>(PLAYER_INPUT_QUEUE_SIZE);
+        let plugin_name = plugin.to_string();
+        tokio::spawn(async move {
+            while let Some(input) = receiver.recv().await {
+                let start = Instant::now();
+                handler(&input);
+                if let Some(sandbox) = &sandbox
+                    && let Err(e) = sandbox.record_cpu_time(start.elapsed())
+                {
+                    warn!(
+                        "Stopping player input delivery to plugin '{}': {}",
+                        plugin_name, e
+                    );
+                    break;
+                }
+            }
+        });
+        self.subscriptions.write().push(Subscription {
+            plugin: plugin.to_string(),
+            room_id: room_id.to_string(),
+            sample_every: sample_every.max(1),
+            seen: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            sender,
+        });
+    }
+
+    /// Unsubscribe a plugin from the input of a room
+    pub fn unsubscribe(&self, plugin: &str, room_id: &str) {
+        self.subscriptions
+            .write()
+            .retain(|it| it.plugin != plugin || it.room_id != room_id);
+    }
+
+    /// Unregister all subscriptions from a plugin
+    pub fn unregister_all_from_plugin(&self, plugin: &str) {
+        self.subscriptions.write().retain(|it| it.plugin != plugin);
+    }
+
+    /// Forward a batch to the subscribers of its room
+    pub fn dispatch(&self, input: &PlayerInput) {
+        let mut closed = false;
+        for subscription in self.subscriptions.read().iter() {
+            if subscription.room_id != input.room_id {
+                continue;
+            }
+            let seen = subscription.seen.fetch_add(1, Ordering::Relaxed);
+            if seen % u64::from(subscription.sample_every) != 0 {
+                continue;
+            }
+            match subscription.sender.try_send(input.clone()) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    subscription.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => closed = true,
+            }
+        }
+        if closed {
+            self.subscriptions.write().retain(|it| !it.sender.is_closed());
+        }
+    }
+
+    /// Whether any plugin is subscribed to the input of a room
+    pub fn has_subscribers(&self, room_id: &str) -> bool {
+        self.subscriptions
+            .read()
+            .iter()
+            .any(|it| it.room_id == room_id)
+    }
+
+    /// Number of batches dropped for a plugin because it could not keep up
+    pub fn dropped(&self, plugin: &str) -> u64 {
+        self.subscriptions
+            .read()
+            .iter()
+            .filter(|it| it.plugin == plugin)
+            .map(|it| it.dropped.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Number of subscriptions
+    pub fn len(&self) -> usize {
+        self.subscriptions.read().len()
+    }
+
+    /// Whether there are no subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.read().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn touches(room_id: &str, time: f32) -> PlayerInput {
+        PlayerInput {
+            room_id: room_id.to_string(),
+            user_id: 1,
+            frames: PlayerInputFrames::Touches(Arc::new(vec![TouchFrame {
+                time,
+                points: Vec::new(),
+            }])),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sampled_delivery() {
+        let subscribers = PlayerInputSubscribers::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        subscribers.subscribe(
+            "replay",
+            "room",
+            2,
+            None,
+            Box::new(move |input| {
+                if let PlayerInputFrames::Touches(frames) = &input.frames {
+                    sink.lock().unwrap().push(frames[0].time);
+                }
+            }),
+        );
+
+        for i in 0..6 {
+            subscribers.dispatch(&touches("room", i as f32));
+            subscribers.dispatch(&touches("other", 100.));
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(*received.lock().unwrap(), vec![0., 2., 4.]);
+
+        subscribers.unregister_all_from_plugin("replay");
+        assert!(!subscribers.has_subscribers("room"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_drops_when_full() {
+        let subscribers = PlayerInputSubscribers::new();
+        subscribers.subscribe("slow", "room", 1, None, Box::new(|_| {}));
+
+        // The delivery task cannot run before we yield, so the queue fills up
+        for _ in 0..PLAYER_INPUT_QUEUE_SIZE + 10 {
+            subscribers.dispatch(&touches("room", 0.));
+        }
+        assert_eq!(subscribers.dropped("slow"), 10);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::Result;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+use tracing::debug;
+
+/// Accumulated playtime of a user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaytimeRecord {
+    /// Last known name of the user
+    pub name: Option<String>,
+    /// Total playtime, in seconds
+    pub total: u64,
+    /// Playtime credited within the retention period, with when it was credited
+    pub log: Vec<(SystemTime, u64)>,
+}
+
+impl PlaytimeRecord {
+    /// Playtime credited within `window` of `now`
+    pub fn recent(&self, now: SystemTime, window: Duration) -> u64 {
+        self.log
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at).unwrap_or_default() <= window)
+            .map(|(_, seconds)| seconds)
+            .sum()
+    }
+}
+
+/// Playtime of all users, kept across restarts once opened with [`PlaytimeStore::open`]
+#[derive(Default)]
+pub struct PlaytimeStore {
+    records: RwLock<HashMap<u32, PlaytimeRecord>>,
+    /// File the records are flushed to
+    path: RwLock<Option<PathBuf>>,
+    /// Whether records changed since the last flush
+    dirty: AtomicBool,
+}
+
+impl PlaytimeStore {
+    /// Create an empty store that is never flushed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the records saved at `path`, if any, and flush to it from now on, returning the
+    /// number of users loaded
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let records: HashMap<u32, PlaytimeRecord> = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            HashMap::new()
+        };
+        let count = records.len();
+        *self.records.write() = records;
+        *self.path.write() = Some(path.to_path_buf());
+        self.dirty.store(false, Ordering::SeqCst);
+        Ok(count)
+    }
+
+    /// Credit `seconds` played at `at`, dropping log entries older than `retention`
+    pub fn record(
+        &self,
+        user_id: u32,
+        name: Option<&str>,
+        seconds: u64,
+        at: SystemTime,
+        retention: Duration,
+    ) {
+        let now = SystemTime::now();
+        let mut records = self.records.write();
+        let record = records.entry(user_id).or_default();
+        if let Some(name) = name {
+            record.name = Some(name.to_string());
+        }
+        record.total += seconds;
+        record.log.retain(|(at, _)| now.duration_since(*at).unwrap_or_default() <= retention);
+        record.log.push((at, seconds));
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Get the record of a user
+    pub fn get(&self, user_id: u32) -> Option<PlaytimeRecord> {
+        self.records.read().get(&user_id).cloned()
+    }
+
+    /// Get the records of all users
+    pub fn all(&self) -> Vec<(u32, PlaytimeRecord)> {
+        self.records
+            .read()
+            .iter()
+            .map(|(id, record)| (*id, record.clone()))
+            .collect()
+    }
+
+    /// Save the records if they changed since the last flush, returning whether they were
+    /// saved
+    pub fn flush(&self) -> Result<bool> {
+        let Some(path) = self.path.read().clone() else {
+            return Ok(false);
+        };
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let content = serde_json::to_string(&*self.records.read())?;
+        let temp = path.with_extension("tmp");
+        let result = std::fs::write(&temp, content).and_then(|_| std::fs::rename(&temp, &path));
+        if let Err(e) = result {
+            self.dirty.store(true, Ordering::SeqCst);
+            return Err(e.into());
+        }
+        debug!("Flushed playtime to {}", path.display());
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("playtime.json");
+        let retention = Duration::from_secs(3600);
+        let store = PlaytimeStore::new();
+        assert_eq!(store.open(&path).unwrap(), 0);
+        assert!(!store.flush().unwrap());
+
+        let now = SystemTime::now();
+        store.record(1, Some("alice"), 120, now, retention);
+        store.record(1, None, 30, now - retention * 2, retention);
+        store.record(2, None, 60, now, retention);
+        assert!(store.flush().unwrap());
+        assert!(!store.flush().unwrap());
+
+        let reopened = PlaytimeStore::new();
+        assert_eq!(reopened.open(&path).unwrap(), 2);
+        let record = reopened.get(1).unwrap();
+        assert_eq!(record.name.as_deref(), Some("alice"));
+        assert_eq!(record.total, 150);
+        assert_eq!(record.recent(now, retention), 120);
+        assert_eq!(reopened.get(2).unwrap().total, 60);
+        assert!(reopened.get(3).is_none());
+    }
+}
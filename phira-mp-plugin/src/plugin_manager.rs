@@ -1,20 +1,34 @@
 use crate::{
     Error, Result,
-    metadata::PluginMetadata,
+    metadata::{PluginMetadata, UnknownPermissionPolicy},
     config::PluginConfig,
-    wasm_runtime::{WasmRuntime, PluginInstance},
-    event_system::EventBus,
+    wasm_runtime::{WasmRuntime, PluginInstance, SandboxLimiter},
+    event_system::{Event, EventBus, predefined},
     command_system::CommandRegistry,
     api_host::HostApi,
     dependency::DependencyGraph,
+    sandbox::{ResourceLimits, SecurityPolicy},
 };
 use std::{
     path::{Path, PathBuf},
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
 };
-use parking_lot::RwLock;
-use tracing::{info, error};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, error, warn};
+
+/// File in the plugin directory recording which plugins are disabled
+pub const PLUGIN_STATES_FILE: &str = "plugins.toml";
+
+/// Persisted plugin flags
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PluginStates {
+    /// Plugins skipped when scanning the plugin directory
+    #[serde(default)]
+    disabled: BTreeSet<String>,
+}
 
 /// Plugin state
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -67,7 +81,7 @@ impl Plugin {
     }
 
     /// Initialize the plugin with runtime
-    pub fn initialize(&mut self, runtime: &WasmRuntime, _host_api: Arc<HostApi>) -> Result<()> {
+    pub fn initialize(&mut self, runtime: &WasmRuntime, host_api: Arc<HostApi>) -> Result<()> {
         if self.state != PluginState::Loaded {
             return Err(Error::Runtime(format!(
                 "Plugin {} is not in Loaded state",
@@ -77,8 +91,20 @@ impl Plugin {
 
         info!("Initializing plugin: {}", self.metadata.name);
 
-        // Create plugin instance
-        let instance = runtime.instantiate_plugin(&self.path)?;
+        // Create plugin instance, accounted to and bound by the limits of its sandbox
+        let sandbox_manager = host_api.sandbox_manager();
+        let sandbox = sandbox_manager.get_sandbox(&self.metadata.name).unwrap_or_else(|| {
+            sandbox_manager.create_sandbox(
+                self.metadata.name.clone(),
+                ResourceLimits::default(),
+                SecurityPolicy::default(),
+            )
+        });
+        let mut limiter = SandboxLimiter::new(sandbox).with_host_api(&host_api);
+        if let Some(metrics) = host_api.metrics_collector() {
+            limiter = limiter.with_metrics(metrics);
+        }
+        let instance = runtime.instantiate_plugin(&self.path, limiter)?;
         self.instance = Some(instance);
         self.state = PluginState::Initialized;
 
@@ -139,10 +165,8 @@ pub struct PluginManager {
     /// WASM runtime
     runtime: WasmRuntime,
     /// Event bus for plugin communication
-    #[allow(dead_code)]
     event_bus: Arc<EventBus>,
     /// Command registry
-    #[allow(dead_code)]
     command_registry: Arc<CommandRegistry>,
     /// Host API (weak reference to avoid circular dependency)
     host_api: std::sync::Weak<HostApi>,
@@ -150,14 +174,44 @@ pub struct PluginManager {
     dependency_graph: RwLock<DependencyGraph>,
     /// Plugin directory
     plugin_dir: PathBuf,
+    /// Handling of unknown permissions declared by plugins
+    unknown_permission_policy: RwLock<UnknownPermissionPolicy>,
+    /// Names of plugins currently being reloaded
+    reloading: Mutex<HashSet<String>>,
+}
+
+/// Marks a plugin as being reloaded until dropped
+struct ReloadGuard<'a> {
+    reloading: &'a Mutex<HashSet<String>>,
+    name: String,
+}
+
+impl Drop for ReloadGuard<'_> {
+    fn drop(&mut self) {
+        self.reloading.lock().remove(&self.name);
+    }
 }
 
 /// Create a plugin manager and host API pair (breaks circular dependency)
 pub fn create_plugin_system(
     plugin_dir: impl AsRef<Path>,
 ) -> Result<(Arc<PluginManager>, Arc<HostApi>)> {
-    use std::sync::Arc;
-    
+    create_plugin_system_with(
+        plugin_dir,
+        Arc::new(EventBus::new()),
+        Arc::new(CommandRegistry::new()),
+    )
+}
+
+/// Create a plugin manager and host API pair sharing an existing event bus and command
+/// registry
+///
+/// The manager holds the host API weakly and the host API points back at this manager.
+pub fn create_plugin_system_with(
+    plugin_dir: impl AsRef<Path>,
+    event_bus: Arc<EventBus>,
+    command_registry: Arc<CommandRegistry>,
+) -> Result<(Arc<PluginManager>, Arc<HostApi>)> {
     let plugin_dir = plugin_dir.as_ref().to_path_buf();
     
     // Ensure plugin directory exists
@@ -165,46 +219,34 @@ pub fn create_plugin_system(
         std::fs::create_dir_all(&plugin_dir)?;
     }
     
-    // Create core components
-    let event_bus = Arc::new(EventBus::new());
-    let command_registry = Arc::new(CommandRegistry::new());
     let runtime = WasmRuntime::new()?;
     
-    // Create a temporary weak reference placeholder
-    let temp_manager = Arc::new(PluginManager {
-        plugins: RwLock::new(HashMap::new()),
-        runtime,
-        event_bus: Arc::clone(&event_bus),
-        command_registry: Arc::clone(&command_registry),
-        host_api: std::sync::Weak::new(), // Will be updated later
-        dependency_graph: RwLock::new(DependencyGraph::new()),
-        plugin_dir: plugin_dir.clone(),
-    });
-    
-    // Create host API with weak reference to the temporary manager
-    let host_api = Arc::new(HostApi::new_with_weak(
-        Arc::clone(&event_bus),
-        Arc::clone(&command_registry),
-        Arc::downgrade(&temp_manager),
-    ));
-    
-    // Now create the real plugin manager with the actual host API
-    let runtime = WasmRuntime::new()?;
-    let plugin_manager = Arc::new(PluginManager {
-        plugins: RwLock::new(HashMap::new()),
-        runtime,
-        event_bus: Arc::clone(&event_bus),
-        command_registry: Arc::clone(&command_registry),
-        host_api: Arc::downgrade(&host_api),
-        dependency_graph: RwLock::new(DependencyGraph::new()),
-        plugin_dir,
+    // The host API holds a weak reference back to the manager that owns it
+    let mut host_api = None;
+    let plugin_manager = Arc::new_cyclic(|manager| {
+        let api = Arc::new(HostApi::new_with_weak(
+            Arc::clone(&event_bus),
+            Arc::clone(&command_registry),
+            manager.clone(),
+        ));
+        let weak_api = Arc::downgrade(&api);
+        host_api = Some(api);
+        PluginManager {
+            plugins: RwLock::new(HashMap::new()),
+            runtime,
+            event_bus: Arc::clone(&event_bus),
+            command_registry: Arc::clone(&command_registry),
+            host_api: weak_api,
+            dependency_graph: RwLock::new(DependencyGraph::new()),
+            plugin_dir,
+            unknown_permission_policy: RwLock::new(UnknownPermissionPolicy::default()),
+            reloading: Mutex::new(HashSet::new()),
+        }
     });
-    
-    // The host API currently points to temp_manager, but that's okay because
-    // temp_manager has the same structure (just without plugins loaded).
-    // For simplicity, we'll just return these two objects.
-    // The weak reference in host_api will still work for method calls that
-    // don't depend on plugin state.
+    let host_api = host_api.expect("host API is created with the plugin manager");
+    host_api.open_user_tags(plugin_manager.plugin_dir.join(crate::api_host::USER_TAGS_FILE))?;
+    host_api.open_playtime(plugin_manager.plugin_dir.join(crate::api_host::PLAYTIME_FILE))?;
+    host_api.open_bans(plugin_manager.plugin_dir.join(crate::api_host::BANS_FILE))?;
     
     Ok((plugin_manager, host_api))
 }
@@ -234,9 +276,16 @@ impl PluginManager {
             host_api: Arc::downgrade(&host_api),
             dependency_graph: RwLock::new(DependencyGraph::new()),
             plugin_dir,
+            unknown_permission_policy: RwLock::new(UnknownPermissionPolicy::default()),
+            reloading: Mutex::new(HashSet::new()),
         })
     }
 
+    /// Set how plugins declaring unknown permissions are handled
+    pub fn set_unknown_permission_policy(&self, policy: UnknownPermissionPolicy) {
+        *self.unknown_permission_policy.write() = policy;
+    }
+
     /// Get the host API as an Arc, returning an error if it has been dropped
     fn get_host_api(&self) -> Result<Arc<HostApi>> {
         self.host_api.upgrade().ok_or_else(|| Error::Runtime("Host API has been dropped".to_string()))
@@ -246,6 +295,7 @@ impl PluginManager {
     pub async fn load_plugin(&self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
         let metadata = PluginMetadata::from_file(path)?;
+        metadata.validate_permissions(*self.unknown_permission_policy.read())?;
         let plugin_name = metadata.name.clone();
         
         // Check if plugin is already loaded
@@ -257,12 +307,13 @@ impl PluginManager {
         }
 
         // Load configuration
-        let config_path = self.plugin_dir.join(&plugin_name).join("config.toml");
+        let config_path = self.config_path(&plugin_name);
         let config = if config_path.exists() {
             PluginConfig::from_file(&config_path)?
         } else {
             PluginConfig::default()
         };
+        metadata.validate_config(&config)?;
 
         // Create plugin instance
         let plugin = Plugin::new(metadata, config, path.to_path_buf());
@@ -276,6 +327,7 @@ impl PluginManager {
         // Check dependencies
         let missing_deps = self.dependency_graph.read().check_missing_dependencies(&plugin_name);
         if !missing_deps.is_empty() {
+            self.dependency_graph.write().remove_plugin(&plugin_name);
             return Err(Error::Dependency(format!(
                 "Missing dependencies for {}: {:?}",
                 plugin_name, missing_deps
@@ -289,28 +341,72 @@ impl PluginManager {
             plugins.insert(plugin_name.clone(), plugin_arc.clone());
         }
 
+        // Give the plugin a sandbox with default limits and track its calls
+        if let Ok(host_api) = self.get_host_api() {
+            host_api.sandbox_manager().create_sandbox(
+                plugin_name.clone(),
+                ResourceLimits::default(),
+                SecurityPolicy::default(),
+            );
+            if let Some(metrics) = host_api.metrics_collector() {
+                metrics.register_plugin(plugin_name.clone());
+            }
+        }
+
         // Initialize plugin - extract instance first to avoid holding lock across await
-        let (runtime_ref, host_api) = {
-            let mut plugin_guard = plugin_arc.write();
-            let host_api = self.get_host_api()?;
-            let _instance = plugin_guard.instance.take(); // Extract instance if any
-            
-            // For now, we'll just drop the lock and call initialize without instance
-            // The initialize method will create a new instance anyway
-            drop(plugin_guard);
-            (&self.runtime, host_api)
-        };
+        let result = (|| {
+            let (runtime_ref, host_api) = {
+                let mut plugin_guard = plugin_arc.write();
+                let host_api = self.get_host_api()?;
+                let _instance = plugin_guard.instance.take(); // Extract instance if any
 
-        // Re-acquire lock to call initialize
-        {
+                // For now, we'll just drop the lock and call initialize without instance
+                // The initialize method will create a new instance anyway
+                drop(plugin_guard);
+                (&self.runtime, host_api)
+            };
+
+            // Re-acquire lock to call initialize
             let mut plugin_guard = plugin_arc.write();
-            plugin_guard.initialize(runtime_ref, host_api)?;
+            plugin_guard.initialize(runtime_ref, host_api)
+        })();
+
+        if let Err(e) = result {
+            error!("Failed to initialize plugin {}: {}", plugin_name, e);
+            self.rollback_load(&plugin_name);
+            return Err(e);
         }
 
         info!("Plugin loaded successfully: {}", plugin_name);
         Ok(())
     }
 
+    /// Remove every trace of a plugin whose load failed
+    fn rollback_load(&self, name: &str) {
+        self.plugins.write().remove(name);
+        self.dependency_graph.write().remove_plugin(name);
+        self.event_bus.resume_subscriber(name);
+        self.command_registry.resume_plugin(name);
+        if let Err(e) = self.command_registry.unregister_all_from_plugin(name) {
+            error!("Failed to unregister commands of plugin {}: {}", name, e);
+        }
+        if let Err(e) = self.event_bus.unsubscribe_all(name) {
+            error!("Failed to unsubscribe events of plugin {}: {}", name, e);
+        }
+        if let Ok(host_api) = self.get_host_api() {
+            host_api.unregister_chart_validators(name);
+            host_api.unregister_player_input(name);
+            host_api.unregister_rpc_methods(name);
+            host_api.unregister_http_routes(name);
+            host_api.unregister_info_fields(name);
+            host_api.unregister_health_probes(name);
+            host_api.sandbox_manager().remove_sandbox(name);
+            if let Some(metrics) = host_api.metrics_collector() {
+                metrics.unregister_plugin(name);
+            }
+        }
+    }
+
     /// Initialize all loaded plugins (call their init functions)
     pub async fn initialize_all(&self) -> Result<()> {
         let plugins = self.plugins.read();
@@ -332,12 +428,11 @@ impl PluginManager {
 
     /// Start all initialized plugins
     pub async fn start_all(&self) -> Result<()> {
-        let plugins = self.plugins.read();
-        let plugin_names: Vec<String> = plugins.keys().cloned().collect();
-        drop(plugins);
+        let plugin_names: Vec<String> = self.plugins.read().keys().cloned().collect();
 
         for name in plugin_names {
-            if let Some(plugin) = self.plugins.read().get(&name) {
+            let plugin = self.plugins.read().get(&name).cloned();
+            if let Some(plugin) = plugin {
                 // Extract instance before await
                 let instance = {
                     let mut plugin_guard = plugin.write();
@@ -362,6 +457,49 @@ impl PluginManager {
         Ok(())
     }
 
+    /// Pause a running plugin, keeping it loaded
+    ///
+    /// Until [`Self::resume_plugin`], the event bus skips the plugin's handlers and its
+    /// commands are rejected.
+    pub fn pause_plugin(&self, name: &str) -> Result<()> {
+        self.set_paused(name, true)
+    }
+
+    /// Resume a paused plugin
+    pub fn resume_plugin(&self, name: &str) -> Result<()> {
+        self.set_paused(name, false)
+    }
+
+    fn set_paused(&self, name: &str, paused: bool) -> Result<()> {
+        let plugin = self.get_plugin(name).ok_or_else(|| Error::NotFound(name.to_string()))?;
+        let (from, to, event_type) = if paused {
+            (PluginState::Running, PluginState::Paused, predefined::PLUGIN_PAUSED)
+        } else {
+            (PluginState::Paused, PluginState::Running, predefined::PLUGIN_RESUMED)
+        };
+        {
+            let mut plugin = plugin.write();
+            if plugin.state != from {
+                return Err(Error::Runtime(format!(
+                    "Plugin {} is not in {:?} state",
+                    name, from
+                )));
+            }
+            plugin.state = to;
+        }
+
+        if paused {
+            self.event_bus.pause_subscriber(name);
+            self.command_registry.pause_plugin(name);
+        } else {
+            self.event_bus.resume_subscriber(name);
+            self.command_registry.resume_plugin(name);
+        }
+        info!("Plugin {}: {}", if paused { "paused" } else { "resumed" }, name);
+        self.event_bus.emit(Event::system(event_type, json!({ "plugin_name": name })))?;
+        Ok(())
+    }
+
     /// Unload a plugin by name
     pub async fn unload_plugin(&self, name: &str) -> Result<()> {
         // Get the plugin and remove it from the map first
@@ -373,7 +511,7 @@ impl PluginManager {
         // Extract instance and state before async operations to avoid holding locks
         let (should_stop, instance_opt) = {
             let mut plugin = plugin_arc.write();
-            let should_stop = plugin.state == PluginState::Running;
+            let should_stop = matches!(plugin.state, PluginState::Running | PluginState::Paused);
             let instance = plugin.instance.take();
             
             // Update state
@@ -398,11 +536,99 @@ impl PluginManager {
 
         // Remove from dependency graph
         self.dependency_graph.write().remove_plugin(name);
+        self.event_bus.resume_subscriber(name);
+        self.command_registry.resume_plugin(name);
+
+        // Stop enforcing the plugin's chart selection rules and drop its sandbox
+        if let Ok(host_api) = self.get_host_api() {
+            host_api.unregister_chart_validators(name);
+            host_api.unregister_player_input(name);
+            host_api.unregister_rpc_methods(name);
+            host_api.unregister_http_routes(name);
+            host_api.unregister_info_fields(name);
+            host_api.unregister_health_probes(name);
+            host_api.sandbox_manager().remove_sandbox(name);
+            if let Some(metrics) = host_api.metrics_collector() {
+                metrics.unregister_plugin(name);
+            }
+        }
 
         info!("Plugin unloaded: {}", name);
         Ok(())
     }
 
+    /// Names of loaded plugins in the order they are unloaded, dependents first
+    ///
+    /// Falls back to name order if the dependency graph has a cycle.
+    pub fn unload_order(&self) -> Vec<String> {
+        let mut remaining: HashSet<String> = self.plugins.read().keys().cloned().collect();
+        let mut order = match self.dependency_graph.read().get_unload_order() {
+            Ok(order) => order,
+            Err(e) => {
+                warn!("Failed to compute plugin unload order: {}", e);
+                Vec::new()
+            }
+        };
+        // Dependencies that were never loaded are part of the graph too
+        order.retain(|name| remaining.remove(name));
+        let mut rest: Vec<String> = remaining.into_iter().collect();
+        rest.sort();
+        order.extend(rest);
+        order
+    }
+
+    /// Unload all plugins in [`Self::unload_order`], continuing past failures
+    pub async fn unload_all(&self) {
+        for name in self.unload_order() {
+            if let Err(e) = self.unload_plugin(&name).await {
+                error!("Failed to unload plugin {}: {}", name, e);
+            }
+        }
+    }
+
+    /// Restart the plugin system: unload all plugins, drop their event subscriptions and
+    /// commands, then scan, initialize and start the plugin directory again
+    ///
+    /// Events emitted meanwhile are held back and delivered to the new plugins. Returns the
+    /// names of the plugins running afterwards.
+    pub async fn restart(&self) -> Result<Vec<String>> {
+        let names = self.unload_order();
+        let _guards = names
+            .iter()
+            .map(|name| self.begin_reload(name))
+            .collect::<Result<Vec<_>>>()?;
+        info!("Restarting plugin system ({} plugins loaded)", names.len());
+
+        let was_paused = self.event_bus.is_paused();
+        self.event_bus.pause();
+        self.unload_all().await;
+        for name in &names {
+            if let Err(e) = self.command_registry.unregister_all_from_plugin(name) {
+                error!("Failed to unregister commands of plugin {}: {}", name, e);
+            }
+            if let Err(e) = self.event_bus.unsubscribe_all(name) {
+                error!("Failed to unsubscribe events of plugin {}: {}", name, e);
+            }
+        }
+        *self.dependency_graph.write() = DependencyGraph::new();
+
+        let result = async {
+            self.scan_and_load().await?;
+            self.initialize_all().await?;
+            self.start_all().await
+        }
+        .await;
+        if !was_paused {
+            self.event_bus.resume();
+        }
+        result?;
+
+        let mut running: Vec<String> = self.plugins.read().keys().cloned().collect();
+        running.sort();
+        info!("Plugin system restarted with {} plugins", running.len());
+        Ok(running)
+    }
+
     /// Get a plugin by name
     pub fn get_plugin(&self, name: &str) -> Option<Arc<RwLock<Plugin>>> {
         self.plugins.read().get(name).cloned()
@@ -413,8 +639,22 @@ impl PluginManager {
         self.plugins.read().values().cloned().collect()
     }
 
+    /// Mark a plugin as being reloaded, failing if a reload is already in progress
+    fn begin_reload(&self, name: &str) -> Result<ReloadGuard<'_>> {
+        if !self.reloading.lock().insert(name.to_string()) {
+            return Err(Error::ReloadInProgress(name.to_string()));
+        }
+        Ok(ReloadGuard {
+            reloading: &self.reloading,
+            name: name.to_string(),
+        })
+    }
+
     /// Reload a plugin (unload and load again)
+    ///
+    /// Concurrent reloads of the same plugin are rejected with [`Error::ReloadInProgress`].
     pub async fn reload_plugin(&self, name: &str) -> Result<()> {
+        let _guard = self.begin_reload(name)?;
         let path = {
             let plugins = self.plugins.read();
             let plugin = plugins.get(name).ok_or_else(|| Error::NotFound(name.to_string()))?;
@@ -429,34 +669,204 @@ impl PluginManager {
         Ok(())
     }
 
-    /// Scan plugin directory and load all plugins
-    pub async fn scan_and_load(&self) -> Result<()> {
-        info!("Scanning plugin directory: {:?}", self.plugin_dir);
-        
-        let entries = std::fs::read_dir(&self.plugin_dir)?;
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            
+    /// Re-read a plugin's configuration and hand it to its `on_config_reload` hook, keeping
+    /// the plugin instance and its state
+    ///
+    /// Fails, keeping the current configuration, if the new configuration does not match
+    /// the plugin's schema ([`Error::Config`]) or the plugin does not declare the hook.
+    pub async fn reload_plugin_config(&self, name: &str) -> Result<()> {
+        let _guard = self.begin_reload(name)?;
+        let plugin = self.get_plugin(name).ok_or_else(|| Error::NotFound(name.to_string()))?;
+        let (config, instance) = {
+            let mut plugin = plugin.write();
+            let config = PluginConfig::from_file(self.config_path(name))?;
+            plugin.metadata.validate_config(&config)?;
+            if !plugin.metadata.has_hook("on_config_reload") {
+                return Err(Error::Runtime(format!(
+                    "Plugin {} has no on_config_reload hook",
+                    name
+                )));
+            }
+            (config, plugin.instance.take())
+        };
+
+        // Plugins not instantiated yet only need the new values
+        let result = match instance {
+            Some(mut instance) => {
+                let result = instance.on_config_reload(&config.to_json()?).await;
+                plugin.write().instance = Some(instance);
+                result
+            }
+            None => Ok(()),
+        };
+        result?;
+        plugin.write().config = config;
+
+        info!("Plugin configuration reloaded: {}", name);
+        Ok(())
+    }
+
+    /// Directory plugins are loaded from
+    pub fn plugin_dir(&self) -> &Path {
+        &self.plugin_dir
+    }
+
+    /// Event bus shared with the loaded plugins
+    pub fn event_bus(&self) -> Arc<EventBus> {
+        Arc::clone(&self.event_bus)
+    }
+
+    /// Path of a plugin's configuration file
+    pub fn config_path(&self, name: &str) -> PathBuf {
+        self.plugin_dir.join(name).join("config.toml")
+    }
+
+    /// Plugin files found in the plugin directory
+    fn plugin_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.plugin_dir)? {
+            let path = entry?.path();
+
             // Check if it's a WASM file or plugin directory
             if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("wasm") {
-                if let Err(e) = self.load_plugin(&path).await {
-                    error!("Failed to load plugin {:?}: {}", path, e);
-                }
+                paths.push(path);
             } else if path.is_dir() {
                 // Look for plugin.wasm in directory
                 let wasm_path = path.join("plugin.wasm");
                 if wasm_path.exists() {
-                    if let Err(e) = self.load_plugin(&wasm_path).await {
-                        error!("Failed to load plugin {:?}: {}", wasm_path, e);
-                    }
+                    paths.push(wasm_path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Scan plugin directory and load all enabled plugins, dependencies first
+    pub async fn scan_and_load(&self) -> Result<()> {
+        info!("Scanning plugin directory: {:?}", self.plugin_dir);
+
+        for path in self.ordered_plugin_paths()? {
+            if let Err(e) = self.load_plugin(&path).await {
+                error!("Failed to load plugin {:?}: {}", path, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the paths of all enabled plugins in dependency order, leaving out plugins
+    /// caught in a dependency cycle
+    ///
+    /// Files whose manifest cannot be read are kept at the end so that loading them
+    /// reports the error.
+    fn ordered_plugin_paths(&self) -> Result<Vec<PathBuf>> {
+        let disabled = self.load_states()?.disabled;
+        let mut manifests = Vec::new();
+        let mut unreadable = Vec::new();
+        for path in self.plugin_paths()? {
+            match PluginMetadata::from_file(&path) {
+                Ok(metadata) if disabled.contains(&metadata.name) => {
+                    info!("Skipping disabled plugin: {}", metadata.name);
                 }
+                Ok(metadata) => manifests.push((path, metadata)),
+                Err(_) => unreadable.push(path),
+            }
+        }
+
+        let build_graph = |manifests: &[(PathBuf, PluginMetadata)]| -> Result<DependencyGraph> {
+            let mut graph = DependencyGraph::new();
+            for (_, metadata) in manifests {
+                graph.add_plugin(
+                    metadata.name.clone(),
+                    metadata.dependencies.clone().unwrap_or_default(),
+                )?;
             }
+            Ok(graph)
+        };
+
+        let mut graph = build_graph(&manifests)?;
+        if let Err(e) = graph.check_circular_dependencies() {
+            warn!("Skipping plugins with circular dependencies: {}", e);
+            let cyclic: HashSet<String> = graph.circular_groups().into_iter().flatten().collect();
+            manifests.retain(|(_, metadata)| !cyclic.contains(&metadata.name));
+            graph = build_graph(&manifests)?;
+        }
+
+        let mut paths = Vec::new();
+        for name in graph.get_load_order()? {
+            paths.extend(
+                manifests
+                    .iter()
+                    .filter(|(_, metadata)| metadata.name == name)
+                    .map(|(path, _)| path.clone()),
+            );
+        }
+        paths.extend(unreadable);
+        Ok(paths)
+    }
+
+    fn load_states(&self) -> Result<PluginStates> {
+        let path = self.plugin_dir.join(PLUGIN_STATES_FILE);
+        if !path.exists() {
+            return Ok(PluginStates::default());
+        }
+        toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", PLUGIN_STATES_FILE, e)))
+    }
+
+    /// Whether a plugin is loaded by [`Self::scan_and_load`]
+    pub fn is_plugin_enabled(&self, name: &str) -> Result<bool> {
+        Ok(!self.load_states()?.disabled.contains(name))
+    }
+
+    /// Record whether a plugin is loaded on the next scan, without loading or unloading it
+    pub fn persist_plugin_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        if self.get_plugin(name).is_none() && self.find_plugin_path(name).is_none() {
+            return Err(Error::NotFound(name.to_string()));
+        }
+        let mut states = self.load_states()?;
+        let changed = if enabled {
+            states.disabled.remove(name)
+        } else {
+            states.disabled.insert(name.to_string())
+        };
+        if changed {
+            let content = toml::to_string(&states)
+                .map_err(|e| Error::Config(format!("Failed to serialize {}: {}", PLUGIN_STATES_FILE, e)))?;
+            std::fs::write(self.plugin_dir.join(PLUGIN_STATES_FILE), content)?;
         }
+        Ok(())
+    }
 
+    /// Load a plugin that was enabled, or unload one that was disabled
+    pub async fn apply_plugin_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let loaded = self.get_plugin(name).is_some();
+        if !enabled && loaded {
+            self.unload_plugin(name).await?;
+        } else if enabled
+            && !loaded
+            && let Some(path) = self.find_plugin_path(name)
+        {
+            self.load_plugin(path).await?;
+        }
         Ok(())
     }
 
+    /// Enable or disable a plugin, persisting the flag across restarts
+    ///
+    /// Disabling a loaded plugin unloads it; enabling loads it from the plugin directory.
+    pub async fn set_plugin_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.persist_plugin_enabled(name, enabled)?;
+        self.apply_plugin_enabled(name, enabled).await
+    }
+
+    /// Path of the plugin with the given name in the plugin directory
+    fn find_plugin_path(&self, name: &str) -> Option<PathBuf> {
+        self.plugin_paths().ok()?.into_iter().find(|path| {
+            PluginMetadata::from_file(path).is_ok_and(|metadata| metadata.name == name)
+        })
+    }
+
     /// Get plugin manager statistics
     pub fn stats(&self) -> PluginManagerStats {
         let plugins = self.plugins.read();
@@ -476,4 +886,332 @@ pub struct PluginManagerStats {
     pub loaded_plugins: usize,
     pub initialized_plugins: usize,
     pub running_plugins: usize,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_system::Command;
+
+    const MANIFEST: &str = r#"
+        name = "broken"
+        version = "1.0.0"
+        author = "test"
+        abi_version = "1.0.0"
+    "#;
+
+    #[tokio::test]
+    async fn test_create_plugin_system_links_host_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let event_bus = Arc::new(EventBus::new());
+        let command_registry = Arc::new(CommandRegistry::new());
+        let (manager, host_api) = create_plugin_system_with(
+            dir.path(),
+            Arc::clone(&event_bus),
+            Arc::clone(&command_registry),
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(&manager.get_host_api().unwrap(), &host_api));
+
+        let path = dir.path().join("listed.wasm");
+        std::fs::write(&path, MANIFEST.replace("broken", "listed")).unwrap();
+        manager.load_plugin(&path).await.unwrap();
+        let plugins = host_api.get_plugin_list().unwrap();
+        assert_eq!(plugins[0]["name"], "listed");
+        assert!(Arc::ptr_eq(&manager.event_bus, &event_bus));
+        assert!(Arc::ptr_eq(&manager.command_registry, &command_registry));
+    }
+
+    #[tokio::test]
+    async fn test_load_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("broken.wasm");
+        std::fs::write(&path, MANIFEST).unwrap();
+
+        manager.load_plugin(&path).await.unwrap();
+        let plugin = manager.get_plugin("broken").unwrap();
+        assert_eq!(plugin.read().state, PluginState::Initialized);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("broken.wasm");
+        std::fs::write(&path, format!("{MANIFEST}\npermissions = [\"read_user\"]")).unwrap();
+
+        manager.set_unknown_permission_policy(UnknownPermissionPolicy::Reject);
+        assert!(matches!(
+            manager.load_plugin(&path).await,
+            Err(Error::InvalidManifest(_))
+        ));
+        assert!(manager.get_plugin("broken").is_none());
+
+        manager.set_unknown_permission_policy(UnknownPermissionPolicy::Warn);
+        manager.load_plugin(&path).await.unwrap();
+        assert!(manager.get_plugin("broken").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_failed_load_leaves_no_residue() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("broken.wasm");
+        std::fs::write(&path, MANIFEST).unwrap();
+
+        // Registrations made by the plugin before its initialization failed
+        manager
+            .command_registry
+            .register(Command::new(
+                "broken_cmd",
+                "",
+                Box::new(|_, _| Ok(String::new())),
+                "broken",
+            ))
+            .unwrap();
+        manager
+            .event_bus
+            .subscribe("tick", Box::new(|_| Ok(())), "broken")
+            .unwrap();
+
+        // Initialization fails without a host API
+        drop(host_api);
+        assert!(manager.load_plugin(&path).await.is_err());
+
+        assert!(manager.get_plugin("broken").is_none());
+        assert_eq!(manager.dependency_graph.read().stats().total_plugins, 0);
+        assert!(manager.command_registry.get_command("broken_cmd").is_none());
+        assert!(!manager.event_bus.has_subscribers("tick"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        let path = dir.path().join("broken.wasm");
+        std::fs::write(&path, MANIFEST).unwrap();
+        manager.load_plugin(&path).await.unwrap();
+
+        // A reload in progress rejects the other one
+        let guard = manager.begin_reload("broken").unwrap();
+        assert!(matches!(
+            manager.reload_plugin("broken").await,
+            Err(Error::ReloadInProgress(_))
+        ));
+        drop(guard);
+
+        for _ in 0..20 {
+            let reloads: Vec<_> = (0..2)
+                .map(|_| {
+                    let manager = Arc::clone(&manager);
+                    tokio::spawn(async move { manager.reload_plugin("broken").await })
+                })
+                .collect();
+            let mut succeeded = 0;
+            for reload in reloads {
+                match reload.await.unwrap() {
+                    Ok(()) => succeeded += 1,
+                    Err(Error::ReloadInProgress(_)) => {}
+                    Err(e) => panic!("reload failed: {e}"),
+                }
+            }
+            assert!(succeeded >= 1);
+
+            let plugin = manager.get_plugin("broken").unwrap();
+            assert_eq!(plugin.read().state, PluginState::Initialized);
+            assert_eq!(manager.stats().total_plugins, 1);
+            assert_eq!(manager.dependency_graph.read().stats().total_plugins, 1);
+        }
+        assert!(manager.reloading.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unload_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        for (name, dependencies) in [("base", "[]"), ("lib", "[\"base\"]"), ("app", "[\"lib\"]")] {
+            let path = dir.path().join(format!("{name}.wasm"));
+            let manifest = MANIFEST.replace("broken", name);
+            std::fs::write(&path, format!("{manifest}\ndependencies = {dependencies}")).unwrap();
+            manager.load_plugin(&path).await.unwrap();
+        }
+
+        assert_eq!(manager.unload_order(), vec!["app", "lib", "base"]);
+        manager.unload_all().await;
+        assert_eq!(manager.stats().total_plugins, 0);
+        assert!(manager.unload_order().is_empty());
+        let graph = manager.dependency_graph.read();
+        assert_eq!(graph.stats().total_plugins, 0);
+        assert_eq!(graph.stats().total_dependencies, 0);
+        assert!(graph.get_load_order().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_loads_dependencies_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        let plugins = [
+            ("c", "[\"b\"]"),
+            ("b", "[\"a\"]"),
+            ("a", "[]"),
+            ("d", "[\"e\"]"),
+            ("e", "[\"d\"]"),
+        ];
+        for (name, dependencies) in plugins {
+            let path = dir.path().join(format!("{name}.wasm"));
+            let manifest = MANIFEST.replace("broken", name);
+            std::fs::write(&path, format!("{manifest}\ndependencies = {dependencies}")).unwrap();
+        }
+
+        // Plugins in a dependency cycle are left out
+        let order: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| dir.path().join(format!("{name}.wasm")))
+            .collect();
+        assert_eq!(manager.ordered_plugin_paths().unwrap(), order);
+
+        manager.scan_and_load().await.unwrap();
+        for name in ["a", "b", "c"] {
+            assert_eq!(manager.get_plugin(name).unwrap().read().state, PluginState::Initialized);
+        }
+        assert!(manager.get_plugin("d").is_none());
+        assert!(manager.get_plugin("e").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        std::fs::write(dir.path().join("broken.wasm"), MANIFEST).unwrap();
+        manager.scan_and_load().await.unwrap();
+        assert!(manager.pause_plugin("broken").is_err());
+        manager.start_all().await.unwrap();
+
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        manager
+            .event_bus
+            .subscribe(
+                "tick",
+                Box::new(move |_| {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                }),
+                "broken",
+            )
+            .unwrap();
+        manager
+            .command_registry
+            .register(Command::new(
+                "broken_cmd",
+                "",
+                Box::new(|_, _| Ok("ok".to_string())),
+                "broken",
+            ))
+            .unwrap();
+        let mut lifecycle = manager.event_bus.subscribe_lifecycle("test", 8);
+
+        manager.pause_plugin("broken").unwrap();
+        assert_eq!(manager.get_plugin("broken").unwrap().read().state, PluginState::Paused);
+        manager.event_bus.emit(Event::system("tick", json!(null))).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let err = manager.command_registry.execute("broken_cmd", None).unwrap_err();
+        assert!(err.to_string().contains("plugin paused"));
+        assert_eq!(lifecycle.try_recv().unwrap().event_type, predefined::PLUGIN_PAUSED);
+
+        manager.resume_plugin("broken").unwrap();
+        assert_eq!(manager.get_plugin("broken").unwrap().read().state, PluginState::Running);
+        manager.event_bus.emit(Event::system("tick", json!(null))).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(manager.command_registry.execute("broken_cmd", None).unwrap(), "ok");
+        assert_eq!(lifecycle.try_recv().unwrap().event_type, predefined::PLUGIN_RESUMED);
+        assert!(manager.resume_plugin("broken").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        std::fs::write(dir.path().join("broken.wasm"), MANIFEST).unwrap();
+        manager.scan_and_load().await.unwrap();
+        manager
+            .command_registry
+            .register(Command::new(
+                "broken_cmd",
+                "",
+                Box::new(|_, _| Ok(String::new())),
+                "broken",
+            ))
+            .unwrap();
+        manager
+            .event_bus
+            .subscribe("tick", Box::new(|_| Ok(())), "broken")
+            .unwrap();
+
+        // A plugin added since startup is picked up by the restart
+        let manifest = MANIFEST.replace("broken", "added");
+        std::fs::write(dir.path().join("added.wasm"), manifest).unwrap();
+        assert_eq!(manager.restart().await.unwrap(), vec!["added", "broken"]);
+
+        assert!(manager.get_plugin("broken").is_some());
+        assert!(manager.command_registry.get_command("broken_cmd").is_none());
+        assert!(!manager.event_bus.has_subscribers("tick"));
+        assert!(!manager.event_bus.is_paused());
+        assert!(host_api.sandbox_manager().get_sandbox("added").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_plugin_is_not_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        std::fs::write(dir.path().join("broken.wasm"), MANIFEST).unwrap();
+        manager.scan_and_load().await.unwrap();
+        assert!(manager.get_plugin("broken").is_some());
+
+        manager.set_plugin_enabled("broken", false).await.unwrap();
+        assert!(manager.get_plugin("broken").is_none());
+        assert!(!manager.is_plugin_enabled("broken").unwrap());
+        assert!(matches!(
+            manager.set_plugin_enabled("missing", false).await,
+            Err(Error::NotFound(_))
+        ));
+
+        // The flag survives a restart
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        manager.scan_and_load().await.unwrap();
+        assert!(manager.get_plugin("broken").is_none());
+
+        manager.set_plugin_enabled("broken", true).await.unwrap();
+        assert!(manager.get_plugin("broken").is_some());
+        let (manager, _host_api) = create_plugin_system(dir.path()).unwrap();
+        manager.scan_and_load().await.unwrap();
+        assert!(manager.get_plugin("broken").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_memory_is_reported() {
+        use crate::monitoring::{HealthMonitor, HealthThresholds, MetricsCollector};
+
+        // Module exporting one page of memory
+        const WASM: &[u8] = b"\0asm\x01\0\0\0\x05\x03\x01\0\x01\x07\x0a\x01\x06memory\x02\0";
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        let metrics = Arc::new(MetricsCollector::new(10, std::time::Duration::ZERO));
+        let plugin_metrics = metrics.register_plugin("memory".to_string());
+        host_api.set_health_monitor(Arc::new(HealthMonitor::new(
+            HealthThresholds::default(),
+            metrics,
+            10,
+        )));
+
+        let path = dir.path().join("memory.wasm");
+        std::fs::write(&path, WASM).unwrap();
+        let metadata = PluginMetadata::from_str(&MANIFEST.replace("broken", "memory")).unwrap();
+        let mut plugin = Plugin::new(metadata, PluginConfig::default(), path);
+        plugin.initialize(&manager.runtime, Arc::clone(&host_api)).unwrap();
+        assert_eq!(plugin_metrics.read().memory_usage, 64 * 1024);
+
+        plugin.unload().await.unwrap();
+        assert_eq!(plugin_metrics.read().memory_usage, 0);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Error, Result};
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::{cell::Cell, collections::HashMap, sync::Arc};
+use tracing::debug;
+
+/// Deepest chain of nested plugin calls allowed on one thread, so plugins calling each
+/// other back (A → B → A → ...) fail instead of overflowing the stack
+pub const MAX_RPC_DEPTH: usize = 8;
+
+/// RPC method handler function signature
+pub type RpcHandler = Box<dyn Fn(Value) -> Result<Value> + Send + Sync>;
+
+thread_local! {
+    /// Plugin calls in progress on this thread
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Holds one level of call depth until dropped
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Option<Self> {
+        CALL_DEPTH.with(|depth| {
+            if depth.get() >= MAX_RPC_DEPTH {
+                return None;
+            }
+            depth.set(depth.get() + 1);
+            Some(Self)
+        })
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Registry of methods plugins expose to each other
+#[derive(Default)]
+pub struct RpcRegistry {
+    /// Handlers by plugin, then method name
+    methods: RwLock<HashMap<String, HashMap<String, Arc<RpcHandler>>>>,
+}
+
+impl RpcRegistry {
+    /// Create a new RPC registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a method of a plugin; each method name can be registered once per plugin
+    pub fn register(&self, plugin: &str, method: &str, handler: RpcHandler) -> Result<()> {
+        let mut methods = self.methods.write();
+        let plugin_methods = methods.entry(plugin.to_string()).or_default();
+        if plugin_methods.contains_key(method) {
+            return Err(Error::Api(format!(
+                "RPC method '{}' already registered by plugin '{}'",
+                method, plugin
+            )));
+        }
+        debug!("Registering RPC method '{}' from plugin '{}'", method, plugin);
+        plugin_methods.insert(method.to_string(), Arc::new(handler));
+        Ok(())
+    }
+
+    /// Unregister all methods of a plugin
+    pub fn unregister_all_from_plugin(&self, plugin: &str) {
+        self.methods.write().remove(plugin);
+    }
+
+    /// Call a method of a plugin
+    ///
+    /// The handler runs on the calling thread, without the registry locked, so it may call
+    /// other plugins in turn up to [`MAX_RPC_DEPTH`] levels deep.
+    pub fn call(&self, target: &str, method: &str, args: Value) -> Result<Value> {
+        let handler = {
+            let methods = self.methods.read();
+            let plugin_methods = methods
+                .get(target)
+                .ok_or_else(|| Error::Api(format!("plugin '{}' exposes no RPC methods", target)))?;
+            let handler = plugin_methods.get(method).ok_or_else(|| {
+                Error::Api(format!("plugin '{}' has no RPC method '{}'", target, method))
+            })?;
+            Arc::clone(handler)
+        };
+        let _guard = DepthGuard::enter().ok_or_else(|| {
+            Error::Api(format!(
+                "RPC call to '{}.{}' exceeds the maximum depth of {}",
+                target, method, MAX_RPC_DEPTH
+            ))
+        })?;
+        handler(args)
+    }
+
+    /// Names of the methods registered by a plugin
+    pub fn methods_of(&self, plugin: &str) -> Vec<String> {
+        let mut methods: Vec<_> = self
+            .methods
+            .read()
+            .get(plugin)
+            .map(|methods| methods.keys().cloned().collect())
+            .unwrap_or_default();
+        methods.sort();
+        methods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_call_and_unknown_targets() {
+        let registry = RpcRegistry::new();
+        registry
+            .register(
+                "auth",
+                "get_role",
+                Box::new(|args| {
+                    Ok(json!({
+                        "role": if args["user_id"] == 1 { "admin" } else { "player" }
+                    }))
+                }),
+            )
+            .unwrap();
+
+        let result = registry.call("auth", "get_role", json!({"user_id": 1})).unwrap();
+        assert_eq!(result, json!({"role": "admin"}));
+        assert!(matches!(
+            registry.call("auth", "get_roles", Value::Null),
+            Err(Error::Api(_))
+        ));
+        assert!(matches!(
+            registry.call("stats", "get_role", Value::Null),
+            Err(Error::Api(_))
+        ));
+        assert!(registry.register("auth", "get_role", Box::new(Ok)).is_err());
+
+        registry.unregister_all_from_plugin("auth");
+        assert!(registry.methods_of("auth").is_empty());
+        assert!(registry.call("auth", "get_role", Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_recursion_is_bounded() {
+        let registry = Arc::new(RpcRegistry::new());
+        for (plugin, peer) in [("a", "b"), ("b", "a")] {
+            let weak = Arc::downgrade(&registry);
+            registry
+                .register(
+                    plugin,
+                    "ping",
+                    Box::new(move |args| weak.upgrade().unwrap().call(peer, "ping", args)),
+                )
+                .unwrap();
+        }
+
+        let err = registry.call("a", "ping", Value::Null).unwrap_err();
+        assert!(err.to_string().contains("maximum depth"), "{err}");
+        // The guard unwinds, so later calls start from depth zero again
+        CALL_DEPTH.with(|depth| assert_eq!(depth.get(), 0));
+    }
+}
@@ -283,8 +283,8 @@ impl SecurityPolicy {
 pub struct Sandbox {
     /// Plugin name
     plugin_name: String,
-    /// Resource limits
-    limits: ResourceLimits,
+    /// Resource limits (adjustable at runtime)
+    limits: RwLock<ResourceLimits>,
     /// Security policy
     policy: SecurityPolicy,
     /// Resource usage tracker
@@ -300,7 +300,7 @@ impl Sandbox {
     pub fn new(plugin_name: String, limits: ResourceLimits, policy: SecurityPolicy) -> Self {
         Self {
             plugin_name,
-            limits,
+            limits: RwLock::new(limits),
             policy,
             usage: RwLock::new(ResourceUsage::new()),
             operation_start_time: RwLock::new(None),
@@ -350,15 +350,16 @@ impl Sandbox {
     /// Check resource limits
     pub fn check_limits(&self) -> Result<(), Error> {
         let usage = self.usage.read();
-        usage.check_limits(&self.limits)
+        usage.check_limits(&self.limits.read())
     }
 
     /// Record memory allocation
     pub fn record_allocation(&self, size: usize) -> Result<(), Error> {
-        if size > self.limits.max_allocation_size {
+        let max_allocation_size = self.limits.read().max_allocation_size;
+        if size > max_allocation_size {
             return Err(Error::SecurityViolation(format!(
                 "Allocation size limit exceeded: {} > {} bytes",
-                size, self.limits.max_allocation_size
+                size, max_allocation_size
             )));
         }
         
@@ -366,7 +367,28 @@ impl Sandbox {
         usage.record_allocation(size);
         
         // Check limits after allocation
-        usage.check_limits(&self.limits)
+        usage.check_limits(&self.limits.read())
+    }
+
+    /// Account for linear memory growing by `delta` bytes
+    ///
+    /// Growth beyond the memory limit is refused, leaving usage unchanged, and recorded as
+    /// a security violation.
+    pub fn record_memory_growth(&self, delta: usize) -> Result<(), Error> {
+        let max_memory = self.limits.read().max_memory;
+        let mut usage = self.usage.write();
+        let memory_used = usage.memory_used.saturating_add(delta);
+        if memory_used > max_memory {
+            drop(usage);
+            self.record_security_violation();
+            return Err(Error::SecurityViolation(format!(
+                "Memory limit exceeded: {} > {} bytes",
+                memory_used, max_memory
+            )));
+        }
+        usage.memory_used = memory_used;
+        usage.peak_memory = usage.peak_memory.max(memory_used);
+        Ok(())
     }
 
     /// Record memory deallocation
@@ -381,7 +403,7 @@ impl Sandbox {
         usage.record_cpu_time(duration);
         
         // Check limits
-        usage.check_limits(&self.limits)
+        usage.check_limits(&self.limits.read())
     }
 
     /// Check filesystem access permission
@@ -482,8 +504,13 @@ impl Sandbox {
     }
 
     /// Get resource limits
-    pub fn get_resource_limits(&self) -> &ResourceLimits {
-        &self.limits
+    pub fn get_resource_limits(&self) -> ResourceLimits {
+        self.limits.read().clone()
+    }
+
+    /// Replace resource limits; subsequent checks use the new limits
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        *self.limits.write() = limits;
     }
 
     /// Reset resource usage statistics
@@ -644,6 +671,24 @@ mod tests {
         assert!(sandbox.end_operation().is_err()); // Not active
     }
     
+    #[test]
+    fn test_set_resource_limits() {
+        let sandbox = Sandbox::new(
+            "test_plugin".to_string(),
+            ResourceLimits::default(),
+            SecurityPolicy::default(),
+        );
+        assert!(sandbox.record_allocation(1024).is_ok());
+
+        sandbox.set_resource_limits(ResourceLimits {
+            max_memory: 1536,
+            ..ResourceLimits::default()
+        });
+        assert_eq!(sandbox.get_resource_limits().max_memory, 1536);
+        assert!(sandbox.record_allocation(256).is_ok());
+        assert!(sandbox.record_allocation(512).is_err());
+    }
+
     #[test]
     fn test_security_policy() {
         let policy = SecurityPolicy {
@@ -1,35 +1,36 @@
-use crate::{Error, Result, api_host::HostApi};
+use crate::{
+    Error, Result,
+    api_host::{DeliveryStatus, HostApi},
+    ban_store::BanEntry,
+    command_system::{ArgSpec, CommandContext, run_batch},
+};
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::sync::Arc;
+use serde_json::{Value, json};
+use std::time::Duration;
 use tracing::info;
 
-/// Server command implementations for all 45 commands
-pub struct ServerCommands {
-    host_api: Arc<HostApi>,
-}
-
-impl ServerCommands {
-    /// Create a new server commands instance
-    pub fn new(host_api: Arc<HostApi>) -> Self {
-        Self { host_api }
-    }
-
-    // ===== Command implementations =====
+/// Actor of commands typed at the server console
+pub const CONSOLE_ACTOR: &str = "console";
 
-    /// 帮助命令
-    pub fn help(&self, args: &[String]) -> Result<String> {
-        let help_text = r#"可用的服务器命令:
+/// 内置命令总览, /help 的输出, 也是 /helpjson 中内置命令条目的来源
+const HELP_OVERVIEW: &str = r#"可用的服务器命令:
 
 用户管理:
   /kick <用户ID>                    - 踢出用户
-  /banid <用户ID> <原因>            - 封禁用户(ID)
+  /banid <用户ID> <原因> [--for 时长] - 封禁用户(ID), 可限时
   /unbanid <用户ID>                 - 解封用户(ID)
-  /banip <IP地址> <原因>            - 封禁用户(IP)
+  /banip <IP地址> <原因> [--for 时长] - 封禁用户(IP), 可限时
   /unbanip <IP地址>                 - 解封用户(IP)
   /userinfo <用户ID>                - 获取用户完整信息
+  /sessioninfo <用户ID>             - 获取用户当前连接的会话信息
+  /trace <用户ID> <on/off>          - 开启或关闭用户连接的协议帧日志
   /username <用户ID>                - 获取用户名
   /userlang <用户ID>                - 获取用户语言
   /playtime <用户ID>                - 获取用户游玩时间
-  /playtop <数量>                   - 获取用户游玩时间总排行
+  /playtop <数量> [天数]            - 获取用户游玩时间总排行(可按近期天数排行)
+  /usertag <用户ID> [操作 标签]     - 查看或修改用户标签(操作: add/remove)
   /bannedids                        - 获取封禁用户列表(ID)
   /bannedips                        - 获取封禁用户列表(IP)
   /checkbanid <用户ID>              - 查询用户是否被封禁(ID)
@@ -43,8 +44,10 @@ impl ServerCommands {
   /checkroomban <用户ID> <房间ID>   - 查询用户是否被特定房间封禁
 
 房间管理:
-  /createroom <最大人数>            - 创建房间
+  /createroom <最大人数> [--practice] - 创建房间, 可设为练习房间
   /disbandroom <房间ID>             - 解散房间
+  /clearroom <房间ID>               - 清空房间内所有用户(保留房间)
+  /renameroom <房间ID> <名字>       - 重命名房间
   /joinroom <用户ID> <房间ID>       - 将用户加入至房间
   /kickroom <用户ID> <房间ID>       - 将用户踢出房间
   /roominfo <房间ID>                - 获取房间完整信息
@@ -59,19 +62,34 @@ impl ServerCommands {
   /normalmode <房间ID>              - 切换房间为普通模式
   /cyclemode <房间ID>               - 切换房间为循环模式
   /selectchart <房间ID> <谱面ID>    - 选择房间谱面ID
+  /saveroomtemplate <房间ID>        - 导出房间设置为JSON模板
+  /loadroomtemplate <模板JSON>      - 按模板创建新房间
 
 消息管理:
   /sendmsg <用户ID> <消息>          - 向指定用户发送消息
-  /broadcastall <消息>              - 向所有用户广播消息
+  /broadcastall <消息>              - 向所有用户广播消息(支持 --color/--bold)
   /broadcastroom <房间ID> <消息>    - 向指定房间广播消息
-  /broadcastrooms <消息>            - 向所有房间广播消息
+  /broadcastrooms [--force] <消息>  - 向所有房间广播消息
 
 服务器管理:
-  /shutdown                         - 关闭服务器
+  /shutdown [秒数]                  - 关闭服务器
   /restart                          - 重启服务器
   /reloadall                        - 重载所有插件
+  /restartplugins                   - 重启插件系统(不断开玩家连接)
   /reload <插件名>                  - 重载指定插件
+  /hotreload <插件名> <on/off>      - 开启或关闭插件热重载
+  /disableplugin <插件名>           - 禁用插件, 重启后仍不加载
+  /enableplugin <插件名>            - 启用被禁用的插件
+  /sandbox <插件名> [限制项 值]     - 查看或调整插件沙箱限制
+  /eventbus <pause/resume/stats>    - 暂停/恢复插件事件分发或查看丢弃统计
+  /selftest                         - 自检插件系统核心功能
   /plugins                          - 获取插件列表
+  /commands                         - 获取插件命令列表及所属插件
+  /help [命令名]                    - 获取命令列表或特定命令的详细用法
+  /helpjson                         - 以JSON格式导出命令目录
+  /whoowns <命令名>                 - 查询命令所属插件
+  /batch [--continue] <命令...>     - 按顺序批量执行命令
+  /undo                             - 撤销上一次可逆的管理操作
 
 查询统计:
   /playtotal                        - 获取用户游玩时间总排行榜
@@ -80,24 +98,141 @@ impl ServerCommands {
   /rooms                            - 获取房间列表
   /availableroomlist                - 获取可加入房间列表
   /onlineusers                      - 获取在线用户ID列表
+  /activecharts                     - 获取正在游玩的谱面列表
+  /serverstats [小时数]             - 获取服务器统计 (峰值在线, 房间创建, 完成对局)
 
 输入 /help <命令名> 获取特定命令的详细用法"#;
 
+/// 内置命令及其中文别名
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("undo", "撤销"),
+    ("help", "帮助"),
+    ("kick", "踢出"),
+    ("banid", "封禁id"),
+    ("unbanid", "解封id"),
+    ("banip", "封禁ip"),
+    ("unbanip", "解封ip"),
+    ("userinfo", "用户信息"),
+    ("sessioninfo", "会话信息"),
+    ("trace", "帧日志"),
+    ("username", "用户名"),
+    ("userlang", "用户语言"),
+    ("playtime", "游玩时间"),
+    ("playtop", "游玩排行"),
+    ("usertag", "用户标签"),
+    ("bannedids", "封禁列表id"),
+    ("bannedips", "封禁列表ip"),
+    ("checkbanid", "检查封禁id"),
+    ("checkbanip", "检查封禁ip"),
+    ("banroomid", "房间封禁id"),
+    ("unbanroomid", "房间解封id"),
+    ("banroomip", "房间封禁ip"),
+    ("unbanroomip", "房间解封ip"),
+    ("checkroomban", "检查房间封禁"),
+    ("createroom", "创建房间"),
+    ("disbandroom", "解散房间"),
+    ("clearroom", "清空房间"),
+    ("renameroom", "重命名房间"),
+    ("joinroom", "加入房间"),
+    ("kickroom", "踢出房间"),
+    ("roominfo", "房间信息"),
+    ("roomusers", "房间用户"),
+    ("roomuserids", "房间用户id"),
+    ("roomhost", "房间房主"),
+    ("setmaxusers", "设置最大用户"),
+    ("startprep", "开始准备"),
+    ("endprep", "结束准备"),
+    ("forcestart", "强制开始"),
+    ("setlock", "设置锁定"),
+    ("normalmode", "普通模式"),
+    ("cyclemode", "循环模式"),
+    ("selectchart", "选择谱面"),
+    ("saveroomtemplate", "保存房间模板"),
+    ("loadroomtemplate", "加载房间模板"),
+    ("sendmsg", "发送消息"),
+    ("broadcastall", "广播所有"),
+    ("broadcastroom", "广播房间"),
+    ("broadcastrooms", "广播所有房间"),
+    ("shutdown", "关闭"),
+    ("restart", "重启"),
+    ("reloadall", "重载所有"),
+    ("restartplugins", "重启插件"),
+    ("reload", "重载"),
+    ("hotreload", "热重载"),
+    ("disableplugin", "禁用插件"),
+    ("enableplugin", "启用插件"),
+    ("sandbox", "沙箱"),
+    ("eventbus", "事件总线"),
+    ("selftest", "自检"),
+    ("plugins", "插件列表"),
+    ("commands", "命令列表"),
+    ("helpjson", "命令目录"),
+    ("whoowns", "命令归属"),
+    ("batch", "批量"),
+    ("playtotal", "总游玩排行"),
+    ("onlinecount", "在线数量"),
+    ("availablerooms", "可用房间"),
+    ("rooms", "房间列表"),
+    ("availableroomlist", "可用房间列表"),
+    ("onlineusers", "在线用户"),
+    ("activecharts", "游玩谱面"),
+    ("serverstats", "服务器统计"),
+];
+
+/// Number of actions remembered per actor for /undo
+const UNDO_HISTORY_SIZE: usize = 50;
+
+/// An action remembered for /undo
+enum UndoEntry {
+    /// Reverted by running the inverse command
+    Reversible {
+        description: String,
+        command: &'static str,
+        args: Vec<String>,
+    },
+    /// Cannot be reverted, skipped by /undo
+    Irreversible { description: String },
+}
+
+/// Server command implementations for all 45 commands
+pub struct ServerCommands {
+    host_api: Arc<HostApi>,
+    /// Per-actor stack of performed actions, newest last
+    history: Mutex<HashMap<String, Vec<UndoEntry>>>,
+}
+
+impl ServerCommands {
+    /// Create a new server commands instance
+    pub fn new(host_api: Arc<HostApi>) -> Self {
+        Self {
+            host_api,
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // ===== Command implementations =====
+
+    /// 帮助命令
+    pub fn help(&self, args: &[String]) -> Result<String> {
+
         if args.is_empty() {
-            Ok(help_text.to_string())
+            Ok(HELP_OVERVIEW.to_string())
         } else {
-            let command = &args[0];
-            let detail = match command.as_str() {
+            let command = canonical_command(&args[0]);
+            let detail = match command {
                 "kick" => "踢出用户命令\n用法: /kick <用户ID>\n示例: /kick 123",
-                "banid" => "封禁用户(ID)\n用法: /banid <用户ID> <原因>\n示例: /banid 123 \"作弊\"",
+                "banid" => "封禁用户(ID), 加 --for 则为限时封禁, 时长如 30m, 1h, 7d, 1d12h, 到期自动解封\n用法: /banid <用户ID> <原因> [--for 时长]\n示例: /banid 123 \"作弊\" --for 1h",
                 "unbanid" => "解封用户(ID)\n用法: /unbanid <用户ID>\n示例: /unbanid 123",
-                "banip" => "封禁用户(IP)\n用法: /banip <IP地址> <原因>\n示例: /banip 192.168.1.1 \"滥用\"",
+                "banip" => "封禁用户(IP), 加 --for 则为限时封禁, 时长格式同 /banid\n用法: /banip <IP地址> <原因> [--for 时长]\n示例: /banip 192.168.1.1 \"滥用\" --for 7d",
                 "unbanip" => "解封用户(IP)\n用法: /unbanip <IP地址>\n示例: /unbanip 192.168.1.1",
                 "userinfo" => "获取用户完整信息\n用法: /userinfo <用户ID>\n示例: /userinfo 123",
+                "sessioninfo" => "获取用户当前连接的会话信息 (会话ID, 协议版本, 连接时间, 延迟, 观战标记, 所在房间), 用于排查连接问题\n用法: /sessioninfo <用户ID>\n示例: /sessioninfo 123",
+                "trace" => "开启或关闭用户当前连接的协议帧日志, 以 trace 级别记录收发的每个命令及其十六进制内容 (令牌等敏感内容会被隐藏), 用于排查客户端兼容问题, 默认关闭\n用法: /trace <用户ID> <on/off>\n示例: /trace 123 on",
                 "username" => "获取用户名\n用法: /username <用户ID>\n示例: /username 123",
                 "userlang" => "获取用户语言\n用法: /userlang <用户ID>\n示例: /userlang 123",
                 "playtime" => "获取用户游玩时间\n用法: /playtime <用户ID>\n示例: /playtime 123",
-                "playtop" => "获取用户游玩时间总排行\n用法: /playtop <数量>\n示例: /playtop 10",
+                "playtop" => "获取用户游玩时间总排行,指定天数时按该时间段内的游玩时间排行,时间相同按用户ID排序\n用法: /playtop <数量> [天数]\n示例: /playtop 10 30",
+                "usertag" => "查看或修改用户标签 (如 VIP, verified), 标签会持久保存, 可供插件和服务器配置用于权限控制\n用法: /usertag <用户ID> [add/remove <标签>]\n示例: /usertag 123 add VIP",
                 "bannedids" => "获取封禁用户列表(ID)\n用法: /bannedids",
                 "bannedips" => "获取封禁用户列表(IP)\n用法: /bannedips",
                 "checkbanid" => "查询用户是否被封禁(ID)\n用法: /checkbanid <用户ID>\n示例: /checkbanid 123",
@@ -107,8 +242,10 @@ impl ServerCommands {
                 "banroomip" => "封禁用户进入特定房间(IP)\n用法: /banroomip <IP地址> <房间ID>\n示例: /banroomip 192.168.1.1 1",
                 "unbanroomip" => "解封用户进入特定房间(IP)\n用法: /unbanroomip <IP地址> <房间ID>\n示例: /unbanroomip 192.168.1.1 1",
                 "checkroomban" => "查询用户是否被特定房间封禁\n用法: /checkroomban <用户ID> <房间ID>\n示例: /checkroomban 123 1",
-                "createroom" => "创建房间\n用法: /createroom <最大人数>\n示例: /createroom 4",
+                "createroom" => "创建房间, 加 --practice 则为练习房间, 房间内只有一名玩家时无需准备即可开始\n用法: /createroom <最大人数> [--practice]\n示例: /createroom 4 --practice",
                 "disbandroom" => "解散房间\n用法: /disbandroom <房间ID>\n示例: /disbandroom 1",
+                "clearroom" => "清空房间内所有用户, 保留房间及其设置\n用法: /clearroom <房间ID>\n示例: /clearroom event",
+                "renameroom" => "重命名房间, 名字最长32个字符, 控制字符会被移除\n用法: /renameroom <房间ID> <名字>\n示例: /renameroom 1 \"周末联赛\"",
                 "joinroom" => "将用户加入至房间\n用法: /joinroom <用户ID> <房间ID>\n示例: /joinroom 123 1",
                 "kickroom" => "将用户踢出房间\n用法: /kickroom <用户ID> <房间ID>\n示例: /kickroom 123 1",
                 "roominfo" => "获取房间完整信息\n用法: /roominfo <房间ID>\n示例: /roominfo 1",
@@ -123,21 +260,37 @@ impl ServerCommands {
                 "normalmode" => "切换房间为普通模式\n用法: /normalmode <房间ID>\n示例: /normalmode 1",
                 "cyclemode" => "切换房间为循环模式\n用法: /cyclemode <房间ID>\n示例: /cyclemode 1",
                 "selectchart" => "选择房间谱面ID\n用法: /selectchart <房间ID> <谱面ID>\n示例: /selectchart 1 100",
+                "saveroomtemplate" => "导出房间设置 (名字, 最大人数, 锁定, 循环模式, 谱面池) 为JSON模板, 可用 /loadroomtemplate 复用\n用法: /saveroomtemplate <房间ID>\n示例: /saveroomtemplate 1",
+                "loadroomtemplate" => "按 /saveroomtemplate 导出的模板创建新房间, 模板版本须与服务器一致\n用法: /loadroomtemplate <模板JSON>\n示例: /loadroomtemplate {\"version\":1,\"name\":\"周末联赛\",\"max_users\":8,\"locked\":false,\"cycle\":true,\"chart_pool\":[]}",
                 "sendmsg" => "向指定用户发送消息\n用法: /sendmsg <用户ID> <消息>\n示例: /sendmsg 123 \"你好\"",
-                "broadcastall" => "向所有用户广播消息\n用法: /broadcastall <消息>\n示例: /broadcastall \"服务器重启中...\"",
+                "broadcastall" => "向所有用户广播消息, 可指定颜色或加粗 (旧版客户端显示纯文本)\n用法: /broadcastall [--color <颜色>] [--bold] <消息>\n颜色: red, orange, yellow, green, blue, purple, gray\n示例: /broadcastall --color red --bold \"服务器重启中...\"",
                 "broadcastroom" => "向指定房间广播消息\n用法: /broadcastroom <房间ID> <消息>\n示例: /broadcastroom 1 \"准备开始游戏\"",
-                "broadcastrooms" => "向所有房间广播消息\n用法: /broadcastrooms <消息>\n示例: /broadcastrooms \"活动即将开始\"",
-                "shutdown" => "关闭服务器\n用法: /shutdown\n注意: 需要管理员权限",
+                "broadcastrooms" => "向所有房间广播消息, 默认跳过游戏中的房间\n用法: /broadcastrooms [--force] <消息>\n--force: 同时发送给游戏中的房间\n示例: /broadcastrooms \"活动即将开始\"",
+                "shutdown" => "关闭服务器, 先通知在线用户并等待指定秒数 (默认 5)\n用法: /shutdown [秒数]\n示例: /shutdown 30\n注意: 需要管理员权限",
                 "restart" => "重启服务器\n用法: /restart\n注意: 需要管理员权限",
                 "reloadall" => "重载所有插件\n用法: /reloadall",
+                "restartplugins" => "卸载全部插件并重新初始化插件系统,玩家连接不受影响\n用法: /restartplugins",
                 "reload" => "重载指定插件\n用法: /reload <插件名>\n示例: /reload test-plugin",
+                "hotreload" => "开启或关闭插件热重载\n用法: /hotreload <插件名> <on/off>\n示例: /hotreload test-plugin off",
+                "disableplugin" => "禁用插件: 卸载已加载的插件, 并在之后的扫描和重启中跳过它 (不删除文件)\n用法: /disableplugin <插件名>\n示例: /disableplugin test-plugin",
+                "enableplugin" => "启用被禁用的插件并立即加载\n用法: /enableplugin <插件名>\n示例: /enableplugin test-plugin",
+                "sandbox" => "查看或调整插件沙箱限制\n用法: /sandbox <插件名> [限制项 值]\n限制项: memory, cpu_time, execution_time, open_files, network_connections, allocation_size, total_allocation, stack_size\n示例: /sandbox test-plugin memory 536870912",
+                "eventbus" => "暂停或恢复插件事件分发, 暂停期间的事件会在恢复后送达 (关键系统事件不受影响); stats 显示各类丢弃事件计数\n用法: /eventbus <pause/resume/stats>\n示例: /eventbus pause",
+                "selftest" => "自检插件系统核心功能 (插件管理器, 事件, 命令, 键值存储), 用于部署后验证\n用法: /selftest",
                 "plugins" => "获取插件列表\n用法: /plugins",
+                "commands" => "获取插件命令列表及所属插件\n用法: /commands",
+                "helpjson" => "以JSON数组导出内置命令和所有已注册插件命令的目录 (命令名, 别名, 描述, 参数, 所需权限, 所属插件, 内置命令为 null), 供工具和文档使用\n用法: /helpjson",
+                "whoowns" => "查询命令所属插件\n用法: /whoowns <命令名>\n示例: /whoowns foo",
+                "batch" => "按顺序批量执行命令, 命令之间用换行或分号分隔, 默认在第一条失败的命令处停止, 加 --continue 则继续执行剩余命令\n用法: /batch [--continue] <命令1>; <命令2> ...\n示例: /batch --continue /kick 123; /banid 123 作弊",
+                "undo" => "撤销上一次可逆的管理操作 (封禁/解封, 房间封禁/解封(ID), 房间锁定), 每个操作者分别记录; 踢出, 解散房间, 关闭服务器等操作不可撤销, 会被跳过\n用法: /undo\n示例: /banid 123 作弊 后执行 /undo 即解封用户 123",
                 "playtotal" => "获取用户游玩时间总排行榜\n用法: /playtotal",
                 "onlinecount" => "获取在线用户数\n用法: /onlinecount",
                 "availablerooms" => "获取可加入房间数\n用法: /availablerooms",
                 "rooms" => "获取房间列表\n用法: /rooms",
                 "availableroomlist" => "获取可加入房间列表\n用法: /availableroomlist",
                 "onlineusers" => "获取在线用户ID列表\n用法: /onlineusers",
+                "activecharts" => "获取所有正在准备或游玩中的房间的谱面 (谱面ID, 房间ID, 玩家数), 按房间ID排序\n用法: /activecharts",
+                "serverstats" => "获取最近若干小时的服务器统计, 按小时分桶 (峰值在线用户, 创建房间数, 完成对局数, 认证限流次数), 默认24小时, 受保留时长限制\n用法: /serverstats [小时数]\n示例: /serverstats 6",
                 _ => return Err(Error::Command(format!("未知命令: {}", command))),
             };
             Ok(detail.to_string())
@@ -160,17 +313,19 @@ impl ServerCommands {
 
     /// 封禁用户(id)命令
     pub fn ban_user_by_id(&self, args: &[String]) -> Result<String> {
+        let (args, duration) = take_ban_duration(args)?;
         if args.len() < 2 {
-            return Err(Error::Command("用法: /banid <用户ID> <原因>".to_string()));
+            return Err(Error::Command("用法: /banid <用户ID> <原因> [--for 时长]".to_string()));
         }
 
         let user_id = args[0].parse::<u32>()
             .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
         let reason = args[1..].join(" ");
 
-        self.host_api.ban_user_by_id(user_id, &reason)?;
-        info!("用户 {} 已被封禁，原因: {}", user_id, reason);
-        Ok(format!("用户 {} 已被封禁，原因: {}", user_id, reason))
+        self.host_api.ban_user_by_id_for(user_id, &reason, duration.as_ref().map(|it| it.1))?;
+        let message = format!("用户 {} 已被封禁，原因: {}{}", user_id, reason, ban_term(&duration));
+        info!("{}", message);
+        Ok(message)
     }
 
     /// 解封用户(id)命令
@@ -189,8 +344,9 @@ impl ServerCommands {
 
     /// 封禁用户(ip)命令
     pub fn ban_user_by_ip(&self, args: &[String]) -> Result<String> {
+        let (args, duration) = take_ban_duration(args)?;
         if args.len() < 2 {
-            return Err(Error::Command("用法: /banip <IP地址> <原因>".to_string()));
+            return Err(Error::Command("用法: /banip <IP地址> <原因> [--for 时长]".to_string()));
         }
 
         let ip = &args[0];
@@ -201,9 +357,10 @@ impl ServerCommands {
             return Err(Error::Command("无效的IP地址".to_string()));
         }
 
-        self.host_api.ban_user_by_ip(ip, &reason)?;
-        info!("IP {} 已被封禁，原因: {}", ip, reason);
-        Ok(format!("IP {} 已被封禁，原因: {}", ip, reason))
+        self.host_api.ban_user_by_ip_for(ip, &reason, duration.as_ref().map(|it| it.1))?;
+        let message = format!("IP {} 已被封禁，原因: {}{}", ip, reason, ban_term(&duration));
+        info!("{}", message);
+        Ok(message)
     }
 
     /// 解封用户(ip)命令
@@ -237,6 +394,40 @@ impl ServerCommands {
             .map_err(|e| Error::Command(format!("序列化失败: {}", e)))?)
     }
 
+    /// 获取用户会话信息命令
+    pub fn get_session_info(&self, context: &CommandContext, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /sessioninfo <用户ID>".to_string()));
+        }
+
+        let user_id = args[0].parse::<u32>()
+            .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
+
+        let info = self.host_api.get_session_info(context, user_id)?;
+        serde_json::to_string_pretty(&info)
+            .map_err(|e| Error::Command(format!("序列化失败: {}", e)))
+    }
+
+    /// 开启或关闭协议帧日志命令
+    pub fn set_frame_trace(&self, args: &[String]) -> Result<String> {
+        if args.len() != 2 {
+            return Err(Error::Command("用法: /trace <用户ID> <on/off>".to_string()));
+        }
+
+        let user_id = args[0].parse::<u32>()
+            .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
+        let enabled = match args[1].to_lowercase().as_str() {
+            "on" | "开" | "true" | "1" => true,
+            "off" | "关" | "false" | "0" => false,
+            _ => return Err(Error::Command("日志状态必须是'on'或'off'".to_string())),
+        };
+
+        self.host_api.set_frame_trace(user_id, enabled)?;
+        let state = if enabled { "开启" } else { "关闭" };
+        info!("用户 {} 的协议帧日志已{}", user_id, state);
+        Ok(format!("用户 {} 的协议帧日志已{}", user_id, state))
+    }
+
     /// 获取用户名命令
     pub fn get_username(&self, args: &[String]) -> Result<String> {
         if args.len() != 1 {
@@ -280,16 +471,66 @@ impl ServerCommands {
                    user_id, hours, minutes, seconds))
     }
 
+    /// 查看或修改用户标签命令
+    pub fn user_tag(&self, args: &[String]) -> Result<String> {
+        let user_id = args
+            .first()
+            .ok_or_else(|| Error::Command("用法: /usertag <用户ID> [add/remove <标签>]".to_string()))?
+            .parse::<u32>()
+            .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
+
+        match args[1..] {
+            [] => {
+                let tags = self.host_api.get_user_tags(user_id);
+                if tags.is_empty() {
+                    Ok(format!("用户 {} 没有标签", user_id))
+                } else {
+                    Ok(format!("用户 {} 的标签: {}", user_id, tags.join(", ")))
+                }
+            }
+            [ref action, ref tag] if action == "add" => {
+                if self.host_api.add_user_tag(user_id, tag)? {
+                    info!("用户 {} 已添加标签 {}", user_id, tag);
+                    Ok(format!("用户 {} 已添加标签 {}", user_id, tag))
+                } else {
+                    Ok(format!("用户 {} 已有标签 {}", user_id, tag))
+                }
+            }
+            [ref action, ref tag] if action == "remove" => {
+                if self.host_api.remove_user_tag(user_id, tag)? {
+                    info!("用户 {} 已移除标签 {}", user_id, tag);
+                    Ok(format!("用户 {} 已移除标签 {}", user_id, tag))
+                } else {
+                    Ok(format!("用户 {} 没有标签 {}", user_id, tag))
+                }
+            }
+            _ => Err(Error::Command("用法: /usertag <用户ID> [add/remove <标签>]".to_string())),
+        }
+    }
+
     /// 获取用户游玩时间总排行（插件实现）命令
     pub fn get_playtime_leaderboard(&self, args: &[String]) -> Result<String> {
+        if args.len() > 2 {
+            return Err(Error::Command("用法: /playtop <数量> [天数]".to_string()));
+        }
         let limit = if args.is_empty() {
             10
         } else {
             args[0].parse::<u32>()
                 .map_err(|_| Error::Command("无效的数量".to_string()))?
         };
+        let window = match args.get(1) {
+            Some(days) => {
+                let days = days.parse::<u64>()
+                    .ok()
+                    .filter(|days| *days > 0)
+                    .ok_or_else(|| Error::Command("无效的天数".to_string()))?;
+                Some(std::time::Duration::from_secs(days * 24 * 60 * 60))
+            }
+            None => None,
+        };
 
-        let leaderboard = self.host_api.get_playtime_leaderboard(limit)?;
+        let leaderboard = self.host_api.get_playtime_leaderboard(limit, window)?;
         Ok(serde_json::to_string_pretty(&leaderboard)
             .map_err(|e| Error::Command(format!("序列化失败: {}", e)))?)
     }
@@ -353,10 +594,9 @@ impl ServerCommands {
 
         let user_id = args[0].parse::<u32>()
             .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
-        let room_id = args[1].parse::<u32>()
-            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let room_id = parse_room_id(&args[1])?;
 
-        self.host_api.ban_user_from_room_by_id(user_id, room_id)?;
+        self.host_api.ban_user_from_room_by_id(user_id, &room_id)?;
         info!("用户 {} 已被封禁进入房间 {}", user_id, room_id);
         Ok(format!("用户 {} 已被封禁进入房间 {}", user_id, room_id))
     }
@@ -369,10 +609,9 @@ impl ServerCommands {
 
         let user_id = args[0].parse::<u32>()
             .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
-        let room_id = args[1].parse::<u32>()
-            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let room_id = parse_room_id(&args[1])?;
 
-        self.host_api.unban_user_from_room_by_id(user_id, room_id)?;
+        self.host_api.unban_user_from_room_by_id(user_id, &room_id)?;
         info!("用户 {} 已解封进入房间 {}", user_id, room_id);
         Ok(format!("用户 {} 已解封进入房间 {}", user_id, room_id))
     }
@@ -384,14 +623,13 @@ impl ServerCommands {
         }
 
         let ip = &args[0];
-        let room_id = args[1].parse::<u32>()
-            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let room_id = parse_room_id(&args[1])?;
 
         if !is_valid_ip(ip) {
             return Err(Error::Command("无效的IP地址".to_string()));
         }
 
-        self.host_api.ban_user_from_room_by_ip(ip, room_id)?;
+        self.host_api.ban_user_from_room_by_ip(ip, &room_id)?;
         info!("IP {} 已被封禁进入房间 {}", ip, room_id);
         Ok(format!("IP {} 已被封禁进入房间 {}", ip, room_id))
     }
@@ -403,14 +641,13 @@ impl ServerCommands {
         }
 
         let ip = &args[0];
-        let room_id = args[1].parse::<u32>()
-            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let room_id = parse_room_id(&args[1])?;
 
         if !is_valid_ip(ip) {
             return Err(Error::Command("无效的IP地址".to_string()));
         }
 
-        self.host_api.unban_user_from_room_by_ip(ip, room_id)?;
+        self.host_api.unban_user_from_room_by_ip(ip, &room_id)?;
         info!("IP {} 已解封进入房间 {}", ip, room_id);
         Ok(format!("IP {} 已解封进入房间 {}", ip, room_id))
     }
@@ -423,10 +660,9 @@ impl ServerCommands {
 
         let user_id = args[0].parse::<u32>()
             .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
-        let room_id = args[1].parse::<u32>()
-            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let room_id = parse_room_id(&args[1])?;
 
-        let banned = self.host_api.is_user_banned_from_room(user_id, room_id)?;
+        let banned = self.host_api.is_user_banned_from_room(user_id, &room_id)?;
         if banned {
             Ok(format!("用户 {} 在房间 {} 中被封禁", user_id, room_id))
         } else {
@@ -436,8 +672,9 @@ impl ServerCommands {
 
     /// 创建房间命令
     pub fn create_room(&self, args: &[String]) -> Result<String> {
-        if args.len() != 1 {
-            return Err(Error::Command("用法: /createroom <最大人数>".to_string()));
+        let practice = args.get(1).is_some_and(|it| it == "--practice");
+        if args.is_empty() || args.len() > 2 || args.len() == 2 && !practice {
+            return Err(Error::Command("用法: /createroom <最大人数> [--practice]".to_string()));
         }
 
         let max_users = args[0].parse::<u32>()
@@ -447,7 +684,11 @@ impl ServerCommands {
             return Err(Error::Command("最大人数必须在1-100之间".to_string()));
         }
 
-        let room_id = self.host_api.create_room(max_users)?;
+        let room_id = if practice {
+            self.host_api.create_practice_room(max_users)?
+        } else {
+            self.host_api.create_room(max_users)?
+        };
         info!("创建房间 {}，最大人数: {}", room_id, max_users);
         Ok(format!("创建房间 {}，最大人数: {}", room_id, max_users))
     }
@@ -466,6 +707,31 @@ impl ServerCommands {
         Ok(format!("房间 {} 已解散", room_id))
     }
 
+    /// 清空房间命令
+    pub fn clear_room(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /clearroom <房间ID>".to_string()));
+        }
+
+        let room_id = &args[0];
+        self.host_api.clear_room(room_id)?;
+        info!("清空房间 {}", room_id);
+        Ok(format!("房间 {} 已清空", room_id))
+    }
+
+    /// 重命名房间命令
+    pub fn rename_room(&self, args: &[String]) -> Result<String> {
+        if args.len() < 2 {
+            return Err(Error::Command("用法: /renameroom <房间ID> <名字>".to_string()));
+        }
+
+        let room_id = args[0].parse::<u32>()
+            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+        let name = self.host_api.set_room_name(room_id, &args[1..].join(" "))?;
+        info!("重命名房间 {} 为 {}", room_id, name);
+        Ok(format!("房间 {} 已重命名为 {}", room_id, name))
+    }
+
     /// 将用户加入至房间命令
     pub fn add_user_to_room(&self, args: &[String]) -> Result<String> {
         if args.len() != 2 {
@@ -498,6 +764,32 @@ impl ServerCommands {
         Ok(format!("用户 {} 已被踢出房间 {}", user_id, room_id))
     }
 
+    /// 导出房间模板命令
+    pub fn export_room_template(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /saveroomtemplate <房间ID>".to_string()));
+        }
+
+        let room_id = args[0].parse::<u32>()
+            .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
+
+        let template = self.host_api.export_room_template(room_id)?;
+        serde_json::to_string(&template)
+            .map_err(|e| Error::Command(format!("序列化失败: {}", e)))
+    }
+
+    /// 按模板创建房间命令
+    pub fn create_room_from_template(&self, args: &[String]) -> Result<String> {
+        if args.is_empty() {
+            return Err(Error::Command("用法: /loadroomtemplate <模板JSON>".to_string()));
+        }
+
+        let template: serde_json::Value = serde_json::from_str(&args.join(" "))
+            .map_err(|e| Error::Command(format!("无效的模板: {}", e)))?;
+        let room_id = self.host_api.create_room_from_template(&template)?;
+        Ok(format!("已按模板创建房间 {}", room_id))
+    }
+
     /// 获取房间完整信息命令
     pub fn get_room_info(&self, args: &[String]) -> Result<String> {
         if args.len() != 1 {
@@ -630,7 +922,7 @@ impl ServerCommands {
             _ => return Err(Error::Command("锁定状态必须是'是'或'否'".to_string())),
         };
 
-        self.host_api.set_room_lock(room_id, locked)?;
+        self.host_api.set_room_lock(room_id, locked, None)?;
         info!("设置房间 {} 锁定状态为 {}", room_id, if locked { "锁定" } else { "未锁定" });
         Ok(format!("房间 {} 锁定状态设置为 {}", room_id, if locked { "锁定" } else { "未锁定" }))
     }
@@ -671,7 +963,7 @@ impl ServerCommands {
 
         let room_id = args[0].parse::<u32>()
             .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
-        let chart_id = args[1].parse::<u32>()
+        let chart_id = args[1].parse::<phira_mp_common::ChartId>()
             .map_err(|_| Error::Command("无效的谱面ID".to_string()))?;
 
         self.host_api.select_room_chart(room_id, chart_id)?;
@@ -689,19 +981,51 @@ impl ServerCommands {
             .map_err(|_| Error::Command("无效的用户ID".to_string()))?;
         let message = args[1..].join(" ");
 
-        self.host_api.send_message_to_user(user_id, &message)?;
-        info!("向用户 {} 发送消息: {}", user_id, message);
-        Ok(format!("消息已发送给用户 {}", user_id))
+        match self.host_api.send_message_to_user(user_id, &message)? {
+            DeliveryStatus::Delivered => {
+                info!("向用户 {} 发送消息: {}", user_id, message);
+                Ok(format!("消息已发送给用户 {}", user_id))
+            }
+            DeliveryStatus::UserOffline => Ok(format!("用户 {} 不在线, 消息未送达", user_id)),
+        }
     }
 
     /// 向所有用户广播消息命令
     pub fn broadcast_message_to_all(&self, args: &[String]) -> Result<String> {
-        if args.is_empty() {
-            return Err(Error::Command("用法: /broadcastall <消息>".to_string()));
+        let mut style = phira_mp_common::AnnouncementStyle::default();
+        let mut words = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--bold" => style.bold = true,
+                "--color" => {
+                    let color = iter
+                        .next()
+                        .ok_or_else(|| Error::Command("--color 需要指定颜色".to_string()))?;
+                    style.color = Some(color.parse().map_err(|_| {
+                        Error::Command(format!(
+                            "未知颜色: {} (可用: red, orange, yellow, green, blue, purple, gray)",
+                            color
+                        ))
+                    })?);
+                }
+                _ => words.push(arg.as_str()),
+            }
+        }
+        if words.is_empty() {
+            return Err(Error::Command(
+                "用法: /broadcastall [--color <颜色>] [--bold] <消息>".to_string(),
+            ));
         }
 
-        let message = args.join(" ");
-        self.host_api.broadcast_message_to_all(&message)?;
+        let message = words.join(" ");
+        if style != phira_mp_common::AnnouncementStyle::default() {
+            self.host_api.broadcast_announcement(&message, style)?;
+        } else {
+            let reached = self.host_api.broadcast_message_to_all(&message)?;
+            info!("向所有用户广播消息: {}", message);
+            return Ok(format!("消息已广播给 {} 个用户", reached));
+        }
         info!("向所有用户广播消息: {}", message);
         Ok("消息已广播给所有用户".to_string())
     }
@@ -716,28 +1040,38 @@ impl ServerCommands {
             .map_err(|_| Error::Command("无效的房间ID".to_string()))?;
         let message = args[1..].join(" ");
 
-        self.host_api.broadcast_message_to_room(room_id, &message)?;
+        let reached = self.host_api.broadcast_message_to_room(room_id, &message)?;
         info!("向房间 {} 广播消息: {}", room_id, message);
-        Ok(format!("消息已广播给房间 {}", room_id))
+        Ok(format!("消息已广播给房间 {} 的 {} 个用户", room_id, reached))
     }
 
     /// 向所有房间广播消息命令
     pub fn broadcast_message_to_all_rooms(&self, args: &[String]) -> Result<String> {
+        let force = args.first().is_some_and(|arg| arg == "--force");
+        let args = if force { &args[1..] } else { args };
         if args.is_empty() {
-            return Err(Error::Command("用法: /broadcastrooms <消息>".to_string()));
+            return Err(Error::Command("用法: /broadcastrooms [--force] <消息>".to_string()));
         }
 
         let message = args.join(" ");
-        self.host_api.broadcast_message_to_all_rooms(&message)?;
+        let reached = self.host_api.broadcast_message_to_all_rooms(&message, force)?;
         info!("向所有房间广播消息: {}", message);
-        Ok("消息已广播给所有房间".to_string())
+        Ok(format!("消息已广播给所有房间的 {} 个用户", reached))
     }
 
     /// 关闭服务器命令
-    pub fn shutdown_server(&self, _args: &[String]) -> Result<String> {
-        self.host_api.shutdown_server()?;
+    pub fn shutdown_server(&self, args: &[String]) -> Result<String> {
+        let grace = match args {
+            [] => crate::api_host::DEFAULT_SHUTDOWN_GRACE,
+            [secs] => std::time::Duration::from_secs(
+                secs.parse::<u64>()
+                    .map_err(|_| Error::Command("无效的秒数".to_string()))?,
+            ),
+            _ => return Err(Error::Command("用法: /shutdown [秒数]".to_string())),
+        };
+        self.host_api.shutdown_server(grace)?;
         info!("服务器关闭请求已发送");
-        Ok("服务器将在5秒后关闭".to_string())
+        Ok(format!("服务器将在{}秒后关闭", grace.as_secs()))
     }
 
     /// 重启服务器命令
@@ -754,6 +1088,12 @@ impl ServerCommands {
         Ok("所有插件正在重载".to_string())
     }
 
+    /// 重启插件系统命令
+    pub fn restart_plugin_system(&self, _args: &[String]) -> Result<String> {
+        self.host_api.restart_plugin_system()?;
+        Ok("插件系统正在重启".to_string())
+    }
+
     /// 重载指定插件命令
     pub fn reload_plugin(&self, args: &[String]) -> Result<String> {
         if args.len() != 1 {
@@ -766,6 +1106,130 @@ impl ServerCommands {
         Ok(format!("插件 {} 正在重载", plugin_name))
     }
 
+    /// 开启或关闭插件热重载命令
+    pub fn set_plugin_hot_reload(&self, args: &[String]) -> Result<String> {
+        if args.len() != 2 {
+            return Err(Error::Command("用法: /hotreload <插件名> <on/off>".to_string()));
+        }
+
+        let plugin_name = &args[0];
+        let enabled = match args[1].to_lowercase().as_str() {
+            "on" | "开" | "true" | "1" => true,
+            "off" | "关" | "false" | "0" => false,
+            _ => return Err(Error::Command("热重载状态必须是'on'或'off'".to_string())),
+        };
+
+        self.host_api.set_hot_reload_enabled(plugin_name, enabled)?;
+        let state = if enabled { "开启" } else { "关闭" };
+        info!("插件 {} 的热重载已{}", plugin_name, state);
+        Ok(format!("插件 {} 的热重载已{}", plugin_name, state))
+    }
+
+    /// 禁用插件命令
+    pub fn disable_plugin(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /disableplugin <插件名>".to_string()));
+        }
+
+        let plugin_name = &args[0];
+        self.host_api.set_plugin_enabled(plugin_name, false)?;
+        Ok(format!("插件 {} 已禁用", plugin_name))
+    }
+
+    /// 启用插件命令
+    pub fn enable_plugin(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /enableplugin <插件名>".to_string()));
+        }
+
+        let plugin_name = &args[0];
+        self.host_api.set_plugin_enabled(plugin_name, true)?;
+        Ok(format!("插件 {} 已启用", plugin_name))
+    }
+
+    /// 查看或调整插件沙箱限制命令
+    pub fn plugin_sandbox(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 && args.len() != 3 {
+            return Err(Error::Command("用法: /sandbox <插件名> [限制项 值]".to_string()));
+        }
+
+        let plugin_name = &args[0];
+        if args.len() == 3 {
+            let mut limits = self.host_api.get_sandbox_limits(plugin_name)?;
+            let value = args[2].parse::<u64>()
+                .map_err(|_| Error::Command("无效的限制值".to_string()))?;
+            let size = usize::try_from(value)
+                .map_err(|_| Error::Command("无效的限制值".to_string()))?;
+            match args[1].as_str() {
+                "memory" => limits.max_memory = size,
+                "cpu_time" => limits.max_cpu_time_ms = value,
+                "execution_time" => limits.max_execution_time_ms = value,
+                "open_files" => limits.max_open_files = size,
+                "network_connections" => limits.max_network_connections = size,
+                "allocation_size" => limits.max_allocation_size = size,
+                "total_allocation" => limits.max_total_allocation = size,
+                "stack_size" => limits.max_stack_size = size,
+                item => return Err(Error::Command(format!("未知的限制项: {}", item))),
+            }
+            self.host_api.set_sandbox_limits(plugin_name, limits)?;
+            info!("插件 {} 的沙箱限制 {} 已设置为 {}", plugin_name, args[1], value);
+        }
+
+        let limits = self.host_api.get_sandbox_limits(plugin_name)?;
+        let usage = self.host_api.get_sandbox_usage(plugin_name)?;
+        Ok(format!(
+            "插件 {} 的沙箱 (已用 / 上限):\n  内存: {} / {} 字节 (峰值 {})\n  CPU 时间: {} / {} 毫秒\n  执行时间: {} / {} 毫秒\n  打开文件: {} / {}\n  网络连接: {} / {}\n  单次分配上限: {} 字节\n  总分配: {} / {} 字节\n  栈大小上限: {} 字节\n  安全违规: {}",
+            plugin_name,
+            usage.memory_used, limits.max_memory, usage.peak_memory,
+            usage.cpu_time_used_ms, limits.max_cpu_time_ms,
+            usage.execution_time_used_ms, limits.max_execution_time_ms,
+            usage.open_files, limits.max_open_files,
+            usage.network_connections, limits.max_network_connections,
+            limits.max_allocation_size,
+            usage.total_allocated, limits.max_total_allocation,
+            limits.max_stack_size,
+            usage.security_violations,
+        ))
+    }
+
+    /// 暂停或恢复事件分发命令
+    pub fn control_event_bus(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /eventbus <pause/resume/stats>".to_string()));
+        }
+
+        match args[0].to_lowercase().as_str() {
+            "pause" | "暂停" => {
+                self.host_api.pause_events();
+                info!("暂停插件事件分发");
+                Ok("插件事件分发已暂停".to_string())
+            }
+            "resume" | "恢复" => {
+                let count = self.host_api.resume_events();
+                info!("恢复插件事件分发");
+                Ok(format!("插件事件分发已恢复, 送达 {} 个暂存事件", count))
+            }
+            "stats" | "统计" => serde_json::to_string_pretty(&self.host_api.get_event_bus_stats())
+                .map_err(|e| Error::Command(format!("序列化失败: {}", e))),
+            _ => Err(Error::Command("操作必须是 pause, resume 或 stats".to_string())),
+        }
+    }
+
+    /// 自检命令
+    pub fn self_test(&self, _args: &[String]) -> Result<String> {
+        let results = self.host_api.self_test();
+        let passed = results.iter().filter(|it| it.passed).count();
+        let mut output = format!("自检完成: {}/{} 项通过", passed, results.len());
+        for result in results {
+            if result.passed {
+                output.push_str(&format!("\n  {}: 通过", result.subsystem));
+            } else {
+                output.push_str(&format!("\n  {}: 失败 ({})", result.subsystem, result.detail));
+            }
+        }
+        Ok(output)
+    }
+
     /// 获取插件列表命令
     pub fn get_plugin_list(&self, _args: &[String]) -> Result<String> {
         let plugins = self.host_api.get_plugin_list()?;
@@ -773,6 +1237,91 @@ impl ServerCommands {
             .map_err(|e| Error::Command(format!("序列化失败: {}", e)))?)
     }
 
+    /// 获取插件命令列表命令
+    pub fn list_commands(&self, _args: &[String]) -> Result<String> {
+        let commands = self.host_api.get_registered_commands();
+        if commands.is_empty() {
+            return Ok("暂无已注册的插件命令".to_string());
+        }
+        let mut output = String::from("已注册的插件命令:");
+        for command in commands {
+            output.push_str(&format!(
+                "\n  /{} (插件: {}) - {}",
+                command.name, command.plugin, command.description
+            ));
+            for line in command.subcommand_tree() {
+                output.push_str(&format!("\n  {}", line));
+            }
+        }
+        Ok(output)
+    }
+
+    /// 导出命令目录命令, 内置命令在前, 插件命令在后
+    pub fn help_json(&self, _args: &[String]) -> Result<String> {
+        let mut catalog = builtin_catalog();
+        if let Value::Array(commands) = self.host_api.get_command_catalog() {
+            catalog.extend(commands);
+        }
+        serde_json::to_string_pretty(&catalog)
+            .map_err(|e| Error::Command(format!("序列化失败: {}", e)))
+    }
+
+    /// 查询命令所属插件命令
+    pub fn who_owns_command(&self, args: &[String]) -> Result<String> {
+        if args.len() != 1 {
+            return Err(Error::Command("用法: /whoowns <命令名>".to_string()));
+        }
+
+        let name = args[0].trim_start_matches('/');
+        match self.host_api.get_command_owner(name) {
+            Some(plugin) => Ok(format!("命令 /{} 由插件 {} 提供", name, plugin)),
+            None => Err(Error::Command(format!("命令 /{} 未注册", name))),
+        }
+    }
+
+    /// 批量执行命令
+    pub fn batch(&self, context: &CommandContext, args: &[String]) -> Result<String> {
+        let (stop_on_error, args) = match args.first().map(String::as_str) {
+            Some("--continue") => (false, &args[1..]),
+            _ => (true, args),
+        };
+        let lines: Vec<String> = args
+            .join(" ")
+            .split(['\n', ';'])
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        if lines.is_empty() {
+            return Err(Error::Command("用法: /batch [--continue] <命令1>; <命令2> ...".to_string()));
+        }
+
+        let results = run_batch(&lines, stop_on_error, |line| self.execute_line(context, line));
+        let mut output = format!("已执行 {}/{} 条命令:", results.len(), lines.len());
+        for (index, (line, result)) in lines.iter().zip(&results).enumerate() {
+            match result {
+                Ok(result) => output.push_str(&format!("\n[{}] {} - 成功: {}", index + 1, line, result)),
+                Err(e) => output.push_str(&format!("\n[{}] {} - 失败: {}", index + 1, line, e)),
+            }
+        }
+        if results.len() < lines.len() {
+            output.push_str("\n遇到错误, 已停止执行剩余命令");
+        }
+        Ok(output)
+    }
+
+    /// 执行一行命令, 未知命令交给插件命令
+    fn execute_line(&self, context: &CommandContext, line: &str) -> Result<String> {
+        let line = line.trim_start_matches('/');
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default().to_lowercase();
+        let args: Vec<String> = parts.map(str::to_string).collect();
+        match self.execute_as(context, &command, &args) {
+            Err(Error::Command(e)) if e.starts_with("未知命令") => self.host_api.execute_command(line, context),
+            result => result,
+        }
+    }
+
     /// 获取用户游玩时间总排行榜命令
     pub fn get_playtime_total_leaderboard(&self, _args: &[String]) -> Result<String> {
         let leaderboard = self.host_api.get_playtime_total_leaderboard()?;
@@ -813,65 +1362,365 @@ impl ServerCommands {
             .map_err(|e| Error::Command(format!("序列化失败: {}", e)))?)
     }
 
-    /// 执行命令的通用入口点
+    /// 获取正在游玩的谱面列表命令
+    pub fn get_active_charts(&self, _args: &[String]) -> Result<String> {
+        let charts = self.host_api.get_active_charts()?;
+        serde_json::to_string_pretty(&charts)
+            .map_err(|e| Error::Command(format!("序列化失败: {}", e)))
+    }
+
+    /// 获取服务器统计命令
+    pub fn get_server_stats(&self, args: &[String]) -> Result<String> {
+        let hours = if args.is_empty() {
+            24
+        } else {
+            args[0].parse::<u64>()
+                .ok()
+                .filter(|hours| *hours > 0)
+                .ok_or_else(|| Error::Command("无效的小时数".to_string()))?
+        };
+
+        let stats = self
+            .host_api
+            .get_server_stats(std::time::Duration::from_secs(hours * 3600))?;
+        serde_json::to_string_pretty(&stats)
+            .map_err(|e| Error::Command(format!("序列化失败: {}", e)))
+    }
+
+    /// 撤销上一次可逆的管理操作
+    pub fn undo(&self, context: &CommandContext, _args: &[String]) -> Result<String> {
+        let actor = actor_of(context);
+        let entry = self.history.lock().get_mut(&actor).and_then(Vec::pop);
+        match entry {
+            None => Err(Error::Command("没有可撤销的操作".to_string())),
+            Some(UndoEntry::Irreversible { description }) => {
+                Ok(format!("{} 不可撤销, 已跳过", description))
+            }
+            Some(UndoEntry::Reversible { description, command, args }) => {
+                self.dispatch(context, command, &args)?;
+                info!("{} 撤销了操作: {}", actor, description);
+                Ok(format!("已撤销: {}", description))
+            }
+        }
+    }
+
+    /// 在执行命令前生成其撤销记录, 未改变状态的操作不记录
+    fn undo_entry(&self, command: &str, args: &[String]) -> Option<UndoEntry> {
+        let reversible = |description: String, command: &'static str, args: Vec<String>| {
+            Some(UndoEntry::Reversible { description, command, args })
+        };
+        let irreversible = |description: String| Some(UndoEntry::Irreversible { description });
+        let user_id = || args.first()?.parse::<u32>().ok();
+        let room_id = |index: usize| args.get(index)?.parse::<u32>().ok();
+
+        match command {
+            "banid" => {
+                let user_id = user_id()?;
+                if args.len() < 2 {
+                    return None;
+                }
+                // 重复封禁会替换原封禁, 撤销时恢复原封禁
+                match self.host_api.get_ban_by_id(user_id) {
+                    Some(ban) => reversible(
+                        format!("更新用户 {} 的封禁", user_id),
+                        "banid",
+                        reban_args(user_id.to_string(), &ban),
+                    ),
+                    None => reversible(format!("封禁用户 {}", user_id), "unbanid", vec![user_id.to_string()]),
+                }
+            }
+            "unbanid" => {
+                let user_id = user_id()?;
+                let ban = self.host_api.get_ban_by_id(user_id)?;
+                reversible(
+                    format!("解封用户 {}", user_id),
+                    "banid",
+                    reban_args(user_id.to_string(), &ban),
+                )
+            }
+            "banip" => {
+                let ip = args.first()?;
+                if args.len() < 2 {
+                    return None;
+                }
+                match self.host_api.get_ban_by_ip(ip) {
+                    Some(ban) => reversible(format!("更新IP {} 的封禁", ip), "banip", reban_args(ip.clone(), &ban)),
+                    None => reversible(format!("封禁IP {}", ip), "unbanip", vec![ip.clone()]),
+                }
+            }
+            "unbanip" => {
+                let ip = args.first()?;
+                let ban = self.host_api.get_ban_by_ip(ip)?;
+                reversible(format!("解封IP {}", ip), "banip", reban_args(ip.clone(), &ban))
+            }
+            "banroomid" => {
+                let (user_id, room_id) = (user_id()?, parse_room_id(args.get(1)?).ok()?);
+                let banned = self.host_api.is_user_banned_from_room(user_id, &room_id).ok()?;
+                let ban = command == "banroomid";
+                if banned == ban {
+                    return None;
+                }
+                reversible(
+                    format!("{}用户 {} 进入房间 {}", if ban { "封禁" } else { "解封" }, user_id, room_id),
+                    if ban { "unbanroomid" } else { "banroomid" },
+                    args.to_vec(),
+                )
+            }
+            "setlock" => {
+                let room_id = room_id(0)?;
+                let locked = self.host_api.get_room_info(room_id).ok()?["locked"].as_bool()?;
+                let requested = matches!(args.get(1)?.to_lowercase().as_str(), "是" | "true" | "1" | "yes");
+                if requested == locked {
+                    return None;
+                }
+                reversible(
+                    format!("设置房间 {} 锁定状态", room_id),
+                    "setlock",
+                    vec![room_id.to_string(), if locked { "是" } else { "否" }.to_string()],
+                )
+            }
+            "kick" => irreversible(format!("踢出用户 {}", args.first()?)),
+            "kickroom" => irreversible(format!("将用户 {} 踢出房间", args.first()?)),
+            "disbandroom" => irreversible(format!("解散房间 {}", args.first()?)),
+            "clearroom" => irreversible(format!("清空房间 {}", args.first()?)),
+            "shutdown" => irreversible("关闭服务器".to_string()),
+            "restart" => irreversible("重启服务器".to_string()),
+            _ => None,
+        }
+    }
+
+    /// 执行命令的通用入口点, 以控制台身份执行
     pub fn execute(&self, command: &str, args: &[String]) -> Result<String> {
+        self.execute_as(&CommandContext::console(), command, args)
+    }
+
+    /// 以指定调用者身份执行命令, 调用者需要 admin 权限, 并按调用者记录供 /undo 撤销的操作
+    pub fn execute_as(&self, context: &CommandContext, command: &str, args: &[String]) -> Result<String> {
+        if !context.has_permission("admin") {
+            return Err(Error::Command(format!("{} 缺少执行服务器命令所需的 admin 权限", actor_of(context))));
+        }
+        let command = canonical_command(command);
+        if command == "undo" {
+            return self.undo(context, args);
+        }
+        let entry = self.undo_entry(command, args);
+        let result = self.dispatch(context, command, args)?;
+        if let Some(entry) = entry {
+            let mut history = self.history.lock();
+            let actions = history.entry(actor_of(context)).or_default();
+            if actions.len() >= UNDO_HISTORY_SIZE {
+                actions.remove(0);
+            }
+            actions.push(entry);
+        }
+        Ok(result)
+    }
+
+    /// 分发命令到对应实现
+    fn dispatch(&self, context: &CommandContext, command: &str, args: &[String]) -> Result<String> {
         match command {
-            "help" | "帮助" => self.help(args),
-            "kick" | "踢出" => self.kick_user(args),
-            "banid" | "封禁id" => self.ban_user_by_id(args),
-            "unbanid" | "解封id" => self.unban_user_by_id(args),
-            "banip" | "封禁ip" => self.ban_user_by_ip(args),
-            "unbanip" | "解封ip" => self.unban_user_by_ip(args),
-            "userinfo" | "用户信息" => self.get_user_info(args),
-            "username" | "用户名" => self.get_username(args),
-            "userlang" | "用户语言" => self.get_user_language(args),
-            "playtime" | "游玩时间" => self.get_user_playtime(args),
-            "playtop" | "游玩排行" => self.get_playtime_leaderboard(args),
-            "bannedids" | "封禁列表id" => self.get_banned_users_by_id(args),
-            "bannedips" | "封禁列表ip" => self.get_banned_users_by_ip(args),
-            "checkbanid" | "检查封禁id" => self.is_user_banned_by_id(args),
-            "checkbanip" | "检查封禁ip" => self.is_user_banned_by_ip(args),
-            "banroomid" | "房间封禁id" => self.ban_user_from_room_by_id(args),
-            "unbanroomid" | "房间解封id" => self.unban_user_from_room_by_id(args),
-            "banroomip" | "房间封禁ip" => self.ban_user_from_room_by_ip(args),
-            "unbanroomip" | "房间解封ip" => self.unban_user_from_room_by_ip(args),
-            "checkroomban" | "检查房间封禁" => self.is_user_banned_from_room(args),
-            "createroom" | "创建房间" => self.create_room(args),
-            "disbandroom" | "解散房间" => self.disband_room(args),
-            "joinroom" | "加入房间" => self.add_user_to_room(args),
-            "kickroom" | "踢出房间" => self.kick_user_from_room(args),
-            "roominfo" | "房间信息" => self.get_room_info(args),
-            "roomusers" | "房间用户" => self.get_room_user_count(args),
-            "roomuserids" | "房间用户id" => self.get_room_user_ids(args),
-            "roomhost" | "房间房主" => self.get_room_host_id(args),
-            "setmaxusers" | "设置最大用户" => self.set_room_max_users(args),
-            "startprep" | "开始准备" => self.start_room_preparation(args),
-            "endprep" | "结束准备" => self.end_room_preparation(args),
-            "forcestart" | "强制开始" => self.force_start_room_game(args),
-            "setlock" | "设置锁定" => self.set_room_lock(args),
-            "normalmode" | "普通模式" => self.switch_room_to_normal_mode(args),
-            "cyclemode" | "循环模式" => self.switch_room_to_cycle_mode(args),
-            "selectchart" | "选择谱面" => self.select_room_chart(args),
-            "sendmsg" | "发送消息" => self.send_message_to_user(args),
-            "broadcastall" | "广播所有" => self.broadcast_message_to_all(args),
-            "broadcastroom" | "广播房间" => self.broadcast_message_to_room(args),
-            "broadcastrooms" | "广播所有房间" => self.broadcast_message_to_all_rooms(args),
-            "shutdown" | "关闭" => self.shutdown_server(args),
-            "restart" | "重启" => self.restart_server(args),
-            "reloadall" | "重载所有" => self.reload_all_plugins(args),
-            "reload" | "重载" => self.reload_plugin(args),
-            "plugins" | "插件列表" => self.get_plugin_list(args),
-            "playtotal" | "总游玩排行" => self.get_playtime_total_leaderboard(args),
-            "onlinecount" | "在线数量" => self.get_online_user_count(args),
-            "availablerooms" | "可用房间" => self.get_available_room_count(args),
-            "rooms" | "房间列表" => self.get_room_list(args),
-            "availableroomlist" | "可用房间列表" => self.get_available_room_list(args),
-            "onlineusers" | "在线用户" => self.get_online_user_ids(args),
+            "help" => self.help(args),
+            "kick" => self.kick_user(args),
+            "banid" => self.ban_user_by_id(args),
+            "unbanid" => self.unban_user_by_id(args),
+            "banip" => self.ban_user_by_ip(args),
+            "unbanip" => self.unban_user_by_ip(args),
+            "userinfo" => self.get_user_info(args),
+            "sessioninfo" => self.get_session_info(context, args),
+            "trace" => self.set_frame_trace(args),
+            "username" => self.get_username(args),
+            "userlang" => self.get_user_language(args),
+            "playtime" => self.get_user_playtime(args),
+            "playtop" => self.get_playtime_leaderboard(args),
+            "usertag" => self.user_tag(args),
+            "bannedids" => self.get_banned_users_by_id(args),
+            "bannedips" => self.get_banned_users_by_ip(args),
+            "checkbanid" => self.is_user_banned_by_id(args),
+            "checkbanip" => self.is_user_banned_by_ip(args),
+            "banroomid" => self.ban_user_from_room_by_id(args),
+            "unbanroomid" => self.unban_user_from_room_by_id(args),
+            "banroomip" => self.ban_user_from_room_by_ip(args),
+            "unbanroomip" => self.unban_user_from_room_by_ip(args),
+            "checkroomban" => self.is_user_banned_from_room(args),
+            "createroom" => self.create_room(args),
+            "disbandroom" => self.disband_room(args),
+            "clearroom" => self.clear_room(args),
+            "renameroom" => self.rename_room(args),
+            "joinroom" => self.add_user_to_room(args),
+            "kickroom" => self.kick_user_from_room(args),
+            "roominfo" => self.get_room_info(args),
+            "roomusers" => self.get_room_user_count(args),
+            "roomuserids" => self.get_room_user_ids(args),
+            "roomhost" => self.get_room_host_id(args),
+            "setmaxusers" => self.set_room_max_users(args),
+            "startprep" => self.start_room_preparation(args),
+            "endprep" => self.end_room_preparation(args),
+            "forcestart" => self.force_start_room_game(args),
+            "setlock" => self.set_room_lock(args),
+            "normalmode" => self.switch_room_to_normal_mode(args),
+            "cyclemode" => self.switch_room_to_cycle_mode(args),
+            "selectchart" => self.select_room_chart(args),
+            "saveroomtemplate" => self.export_room_template(args),
+            "loadroomtemplate" => self.create_room_from_template(args),
+            "sendmsg" => self.send_message_to_user(args),
+            "broadcastall" => self.broadcast_message_to_all(args),
+            "broadcastroom" => self.broadcast_message_to_room(args),
+            "broadcastrooms" => self.broadcast_message_to_all_rooms(args),
+            "shutdown" => self.shutdown_server(args),
+            "restart" => self.restart_server(args),
+            "reloadall" => self.reload_all_plugins(args),
+            "restartplugins" => self.restart_plugin_system(args),
+            "reload" => self.reload_plugin(args),
+            "hotreload" => self.set_plugin_hot_reload(args),
+            "disableplugin" => self.disable_plugin(args),
+            "enableplugin" => self.enable_plugin(args),
+            "sandbox" => self.plugin_sandbox(args),
+            "eventbus" => self.control_event_bus(args),
+            "selftest" => self.self_test(args),
+            "plugins" => self.get_plugin_list(args),
+            "commands" => self.list_commands(args),
+            "helpjson" => self.help_json(args),
+            "whoowns" => self.who_owns_command(args),
+            "batch" => self.batch(context, args),
+            "playtotal" => self.get_playtime_total_leaderboard(args),
+            "onlinecount" => self.get_online_user_count(args),
+            "availablerooms" => self.get_available_room_count(args),
+            "rooms" => self.get_room_list(args),
+            "availableroomlist" => self.get_available_room_list(args),
+            "onlineusers" => self.get_online_user_ids(args),
+            "activecharts" => self.get_active_charts(args),
+            "serverstats" => self.get_server_stats(args),
             _ => Err(Error::Command(format!("未知命令: {}", command))),
         }
     }
 }
 
+/// `--for` 选项给出的封禁时长, 原文和解析结果
+type BanTerm = (String, Duration);
+
+/// 从参数中取出 `--for <时长>` 选项, 返回其余参数及时长
+fn take_ban_duration(args: &[String]) -> Result<(Vec<String>, Option<BanTerm>)> {
+    let Some(index) = args.iter().position(|it| it == "--for") else {
+        return Ok((args.to_vec(), None));
+    };
+    let text = args.get(index + 1)
+        .ok_or_else(|| Error::Command("--for 后缺少时长".to_string()))?;
+    let duration = parse_duration(text)
+        .ok_or_else(|| Error::Command(format!("无效的时长: {}", text)))?;
+    let mut rest = args[..index].to_vec();
+    rest.extend_from_slice(&args[index + 2..]);
+    Ok((rest, Some((text.clone(), duration))))
+}
+
+/// 解析 `30m`, `1h`, `1d12h` 形式的时长, 单位为 s/m/h/d/w
+fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(number.parse::<u64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// 封禁结果中的期限说明
+fn ban_term(duration: &Option<BanTerm>) -> String {
+    match duration {
+        Some((text, _)) => format!("，期限: {}", text),
+        None => String::new(),
+    }
+}
+
+/// 将内置命令的中文别名换成命令名, 其他命令原样返回
+fn canonical_command(command: &str) -> &str {
+    BUILTIN_ALIASES
+        .iter()
+        .find(|(_, alias)| *alias == command)
+        .map_or(command, |(name, _)| name)
+}
+
+/// 由 [`HELP_OVERVIEW`] 生成内置命令的目录条目, 格式与插件命令相同, 所属插件为空
+fn builtin_catalog() -> Vec<Value> {
+    HELP_OVERVIEW
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('/')?.split_once(" - "))
+        .map(|(usage, description)| {
+            let usage = usage.trim();
+            let (name, args) = usage.split_once(' ').unwrap_or((usage, ""));
+            let aliases: Vec<&str> = BUILTIN_ALIASES
+                .iter()
+                .filter(|(command, _)| *command == name)
+                .map(|(_, alias)| *alias)
+                .collect();
+            json!({
+                "name": name,
+                "aliases": aliases,
+                "description": description.trim(),
+                "args": usage_args(args),
+                "permissions": ["admin"],
+                "plugin": null,
+            })
+        })
+        .collect()
+}
+
+/// 解析用法中的参数, `<参数>` 为必填, `[参数]` 为可选
+fn usage_args(usage: &str) -> Vec<ArgSpec> {
+    let mut args = Vec::new();
+    let mut rest = usage;
+    while let Some(start) = rest.find(['<', '[']) {
+        let (close, required) = if rest[start..].starts_with('<') { ('>', true) } else { (']', false) };
+        let Some(len) = rest[start..].find(close) else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        args.push(if required { ArgSpec::required(name, "") } else { ArgSpec::optional(name, "") });
+        rest = &rest[start + len + 1..];
+    }
+    args
+}
+
+/// 撤销解封时恢复原封禁的参数, 限时封禁只恢复剩余时长
+fn reban_args(target: String, ban: &BanEntry) -> Vec<String> {
+    let mut args = vec![target, ban.reason.clone()];
+    if let Some(remaining) = ban.remaining(std::time::SystemTime::now()) {
+        args.push("--for".to_string());
+        args.push(format!("{}s", remaining.as_secs().max(1)));
+    }
+    args
+}
+
+/// 调用者在 /undo 历史中的名字
+fn actor_of(context: &CommandContext) -> String {
+    match &context.plugin {
+        _ if context.console => CONSOLE_ACTOR.to_string(),
+        Some(plugin) => format!("plugin:{}", plugin),
+        None => format!("user:{}", context.user_id),
+    }
+}
+
+/// 校验房间ID, 规则与服务器创建房间时相同
+fn parse_room_id(arg: &str) -> Result<String> {
+    phira_mp_common::RoomId::try_from(arg.to_string())
+        .map(|id| id.to_string())
+        .map_err(|_| Error::Command("无效的房间ID".to_string()))
+}
+
 /// 简单的IP地址验证
 fn is_valid_ip(ip: &str) -> bool {
     // 简单的IPv4验证
@@ -894,12 +1743,8 @@ fn is_valid_ip(ip: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        plugin_manager::PluginManager,
-        event_system::EventBus,
-        command_system::CommandRegistry,
-    };
-    use std::sync::Arc;
+    use crate::plugin_manager::create_plugin_system;
+    use serde_json::{Value, json};
 
     #[test]
     fn test_is_valid_ip() {
@@ -913,55 +1758,245 @@ mod tests {
 
     #[test]
     fn test_server_commands_creation() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            create_plugin_system(dir.path()).expect("Failed to create plugin system");
+
+        let commands = ServerCommands::new(host_api);
+        assert!(commands.help(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_command_owners() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        for (name, plugin) in [("foo", "plugin_a"), ("bar", "plugin_b"), ("baz", "plugin_a")] {
+            host_api
+                .register_command(name, "test", Box::new(|_, _| Ok(String::new())), plugin)
+                .unwrap();
+        }
+
+        let owners = host_api.get_command_owners();
+        assert_eq!(owners.len(), 3);
+        assert_eq!(owners["foo"], "plugin_a");
+        assert_eq!(owners["bar"], "plugin_b");
+        assert_eq!(owners["baz"], "plugin_a");
+
+        let commands = ServerCommands::new(host_api);
+        let listing = commands.execute("commands", &[]).unwrap();
+        assert!(listing.contains("/bar (插件: plugin_b)"));
+        assert!(listing.find("/bar").unwrap() < listing.find("/foo").unwrap());
+        assert_eq!(
+            commands.execute("whoowns", &["/bar".to_string()]).unwrap(),
+            "命令 /bar 由插件 plugin_b 提供"
+        );
+        assert!(commands.execute("whoowns", &["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_help_json() {
+        use crate::command_system::{ArgSpec, Command};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        host_api
+            .register_command("bar", "test", Box::new(|_, _| Ok(String::new())), "plugin_b")
+            .unwrap();
+        host_api
+            .register_command_spec(
+                Command::new("warn", "Warn a user", Box::new(|_, _| Ok(String::new())), "plugin_a")
+                    .with_aliases(vec!["w".to_string()])
+                    .with_permissions(vec!["moderate".to_string()])
+                    .with_args(vec![
+                        ArgSpec::required("user", "User ID"),
+                        ArgSpec::optional("reason", "Shown to the user"),
+                    ]),
+            )
+            .unwrap();
+
+        let commands = ServerCommands::new(host_api);
+        let catalog: Value =
+            serde_json::from_str(&commands.execute("helpjson", &[]).unwrap()).unwrap();
+        let catalog = catalog.as_array().unwrap();
+        let find = |name: &str| catalog.iter().find(|it| it["name"] == name).unwrap();
+        // Built-in commands come first
+        assert_eq!(
+            catalog[0],
+            json!({
+                "name": "kick",
+                "aliases": ["踢出"],
+                "description": "踢出用户",
+                "args": [{ "name": "用户ID", "description": "", "required": true }],
+                "permissions": ["admin"],
+                "plugin": null,
+            })
+        );
+        assert_eq!(
+            find("banid")["args"],
+            json!([
+                { "name": "用户ID", "description": "", "required": true },
+                { "name": "原因", "description": "", "required": true },
+                { "name": "--for 时长", "description": "", "required": false },
+            ])
+        );
+        assert!(find("undo")["args"].as_array().unwrap().is_empty());
+        assert_eq!(catalog.len(), BUILTIN_ALIASES.len() + 2);
+        assert_eq!(find("bar")["args"], json!([]));
+        // Aliases run the same command
+        assert_eq!(commands.execute("帮助", &[]).unwrap(), commands.execute("help", &[]).unwrap());
+        assert_eq!(
+            *find("warn"),
+            json!({
+                "name": "warn",
+                "aliases": ["w"],
+                "description": "Warn a user",
+                "args": [
+                    { "name": "user", "description": "User ID", "required": true },
+                    { "name": "reason", "description": "Shown to the user", "required": false },
+                ],
+                "permissions": ["moderate"],
+                "plugin": "plugin_a",
+            })
+        );
+    }
+
+    #[test]
+    fn test_undo() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        let commands = ServerCommands::new(Arc::clone(&host_api));
+        let args = |args: &[&str]| args.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+
+        commands.execute("banid", &args(&["123", "作弊"])).unwrap();
+        assert!(host_api.is_user_banned_by_id(123).unwrap());
+        // Another actor has nothing to undo
+        let admin = CommandContext::new(1, ["admin"]);
+        assert!(commands.execute_as(&admin, "undo", &[]).is_err());
+        // Server commands need the admin permission
+        let user = CommandContext::new(2, Vec::<String>::new());
+        assert!(commands.execute_as(&user, "undo", &[]).is_err());
+        assert!(commands.execute_as(&user, "banid", &args(&["5", "作弊"])).is_err());
+        assert!(!host_api.is_user_banned_by_id(5).unwrap());
+        assert_eq!(commands.execute("undo", &[]).unwrap(), "已撤销: 封禁用户 123");
+        assert!(!host_api.is_user_banned_by_id(123).unwrap());
+        assert!(commands.execute("undo", &[]).is_err());
+
+        // Banning a banned user again replaces the ban, undone by restoring the old one
+        commands.execute("banid", &args(&["123", "作弊"])).unwrap();
+        commands.execute("banid", &args(&["123", "刷屏", "--for", "1h"])).unwrap();
+        assert_eq!(commands.execute("undo", &[]).unwrap(), "已撤销: 更新用户 123 的封禁");
+        let ban = host_api.get_ban_by_id(123).unwrap();
+        assert_eq!(ban.reason, "作弊");
+        assert_eq!(ban.remaining(std::time::SystemTime::now()), None);
+        commands.execute("undo", &[]).unwrap();
+        assert!(!host_api.is_user_banned_by_id(123).unwrap());
+
+        // Failed commands are not recorded
+        assert!(commands.execute("kick", &args(&["123"])).is_err());
+        assert!(commands.execute("undo", &[]).is_err());
+    }
+
+    #[test]
+    fn test_temporary_ban() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        let commands = ServerCommands::new(Arc::clone(&host_api));
+        let args = |args: &[&str]| args.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(parse_duration("1d12h"), Some(Duration::from_secs(36 * 60 * 60)));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert!(commands.execute("banid", &args(&["123", "作弊", "--for", "1x"])).is_err());
+        assert!(commands.execute("banid", &args(&["123", "--for", "1h"])).is_err());
+
+        assert_eq!(
+            commands.execute("banid", &args(&["123", "作弊", "--for", "1h"])).unwrap(),
+            "用户 123 已被封禁，原因: 作弊，期限: 1h"
+        );
+        commands.execute("banid", &args(&["7", "刷屏"])).unwrap();
+        let banned: Value =
+            serde_json::from_str(&commands.execute("bannedids", &[]).unwrap()).unwrap();
+        assert_eq!(banned[0]["id"], 7);
+        assert_eq!(banned[0]["remaining_secs"], Value::Null);
+        assert_eq!(banned[1]["id"], 123);
+        assert_eq!(banned[1]["reason"], "作弊");
+        let remaining = banned[1]["remaining_secs"].as_u64().unwrap();
+        assert!(remaining > 3590 && remaining <= 3600);
+
+        // Undoing an unban restores the reason and what was left of the term
+        commands.execute("unbanid", &args(&["123"])).unwrap();
+        assert!(!host_api.is_user_banned_by_id(123).unwrap());
+        commands.execute("undo", &[]).unwrap();
+        let ban = host_api.get_ban_by_id(123).unwrap();
+        assert_eq!(ban.reason, "作弊");
+        assert!(ban.remaining(std::time::SystemTime::now()).unwrap() <= Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) = create_plugin_system(dir.path()).unwrap();
+        let sandbox = host_api.sandbox_manager().create_sandbox(
+            "heavy".to_string(),
+            crate::sandbox::ResourceLimits::default(),
+            crate::sandbox::SecurityPolicy::default(),
+        );
+        let commands = ServerCommands::new(Arc::clone(&host_api));
+
+        let args = |args: &[&str]| args.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+        assert!(commands.plugin_sandbox(&args(&["heavy"])).is_ok());
+        assert!(commands.plugin_sandbox(&args(&["missing"])).is_err());
+        assert!(commands.plugin_sandbox(&args(&["heavy", "bogus", "1"])).is_err());
+
+        let output = commands
+            .plugin_sandbox(&args(&["heavy", "memory", "2048"]))
+            .unwrap();
+        assert!(output.contains("内存: 0 / 2048 字节"));
+        assert_eq!(host_api.get_sandbox_limits("heavy").unwrap().max_memory, 2048);
+
+        assert!(sandbox.record_allocation(2048).is_ok());
+        assert!(sandbox.record_allocation(1).is_err());
+    }
+
+    #[test]
+    fn test_self_test_passes() {
+        use crate::{
+            command_system::CommandRegistry, event_system::EventBus,
+            plugin_manager::PluginManager,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
         let event_bus = Arc::new(EventBus::new());
         let command_registry = Arc::new(CommandRegistry::new());
-        
-        // 创建一个临时的插件管理器
-        let plugin_manager = Arc::new(PluginManager::new(
-            "/tmp",
-            Arc::clone(&event_bus),
-            Arc::clone(&command_registry),
-            Arc::new(HostApi::new(
+        let mut host_api = None;
+        let _plugin_manager = Arc::new_cyclic(|manager| {
+            let api = Arc::new(HostApi::new_with_weak(
                 Arc::clone(&event_bus),
                 Arc::clone(&command_registry),
-                Arc::new(PluginManager::new(
-                    "/tmp",
-                    Arc::clone(&event_bus),
-                    Arc::clone(&command_registry),
-                    Arc::new(HostApi::new(
-                        Arc::clone(&event_bus),
-                        Arc::clone(&command_registry),
-                        Arc::new(PluginManager::new(
-                            "/tmp",
-                            Arc::clone(&event_bus),
-                            Arc::clone(&command_registry),
-                            Arc::new(HostApi::new(
-                                Arc::clone(&event_bus),
-                                Arc::clone(&command_registry),
-                                Arc::new(PluginManager::new(
-                                    "/tmp",
-                                    Arc::clone(&event_bus),
-                                    Arc::clone(&command_registry),
-                                    Arc::new(HostApi::new(
-                                        Arc::clone(&event_bus),
-                                        Arc::clone(&command_registry),
-                                        Arc::new(()),
-                                    )?),
-                                )?),
-                            )?),
-                        )?),
-                    )?),
-                )?),
-            )?),
-        ).expect("Failed to create plugin manager"));
-
-        let host_api = Arc::new(HostApi::new(
-            event_bus,
-            command_registry,
-            plugin_manager,
-        ));
-        
-        let commands = ServerCommands::new(host_api);
-        assert!(commands.help(&[]).is_ok());
+                manager.clone(),
+            ));
+            host_api = Some(Arc::clone(&api));
+            PluginManager::new(dir.path(), event_bus, command_registry, api).unwrap()
+        });
+        let host_api = host_api.unwrap();
+
+        let results = host_api.self_test();
+        assert_eq!(
+            results.iter().map(|it| it.subsystem).collect::<Vec<_>>(),
+            ["plugin_manager", "events", "commands", "kv"]
+        );
+        assert!(results.iter().all(|it| it.passed), "{:?}", results);
+        assert!(host_api.get_command_owner("__selftest").is_none());
+
+        let commands = ServerCommands::new(Arc::clone(&host_api));
+        assert!(commands
+            .execute("selftest", &[])
+            .unwrap()
+            .starts_with("自检完成: 4/4 项通过"));
+
+        host_api.pause_events();
+        let output = commands.execute("selftest", &[]).unwrap();
+        assert!(output.contains("events: 失败"));
+        host_api.resume_events();
     }
-}
\ No newline at end of file
+}
@@ -1,54 +1,923 @@
-use crate::Result;
-use std::path::Path;
+use crate::{
+    Error, Result,
+    api_host::{HostApi, HttpResponse},
+    metadata::PluginMetadata,
+    monitoring::MetricsCollector,
+    sandbox::{ResourceLimits, Sandbox},
+};
+use std::{
+    future::Future,
+    path::Path,
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
 
-/// WASM runtime environment (stub implementation)
-pub struct WasmRuntime;
+/// Interval at which the engine epoch advances, the granularity of call deadlines
+pub const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// WASM runtime environment
+pub struct WasmRuntime {
+    /// Engine modules are validated against
+    engine: wasmtime::Engine,
+    /// Host functions plugins can import, see [`HOST_MODULE`]
+    linker: wasmtime::Linker<SandboxLimiter>,
+    /// Set on drop to stop the epoch ticker thread
+    ticker_stop: Arc<AtomicBool>,
+}
 
 impl WasmRuntime {
     /// Create a new WASM runtime
+    ///
+    /// The engine interrupts calls at their epoch deadline; a background thread advances
+    /// the epoch every [`EPOCH_TICK`] until the runtime is dropped.
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        let mut config = wasmtime::Config::new();
+        config.epoch_interruption(true);
+        let engine = wasmtime::Engine::new(&config)?;
+
+        let ticker_stop = Arc::new(AtomicBool::new(false));
+        let ticker_engine = engine.clone();
+        let stop = Arc::clone(&ticker_stop);
+        std::thread::Builder::new()
+            .name("wasm-epoch-ticker".to_string())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(EPOCH_TICK);
+                    ticker_engine.increment_epoch();
+                }
+            })?;
+
+        let mut linker = wasmtime::Linker::new(&engine);
+        link_host(&mut linker)?;
+
+        Ok(Self {
+            engine,
+            linker,
+            ticker_stop,
+        })
+    }
+
+    /// Instantiate `module` in `store`, resolving its imports against the host functions
+    ///
+    /// Imports the host does not provide yet trap when called.
+    fn instantiate(
+        &self,
+        store: &mut wasmtime::Store<SandboxLimiter>,
+        module: &wasmtime::Module,
+    ) -> Result<wasmtime::Instance> {
+        let mut linker = self.linker.clone();
+        linker.define_unknown_imports_as_traps(module)?;
+        Ok(linker.instantiate(store, module)?)
+    }
+
+    /// Get the number of epoch ticks a call may run under `limits`
+    ///
+    /// Epochs measure wall-clock time, so the stricter of the CPU and execution time
+    /// limits applies. Always at least one tick.
+    pub fn epoch_deadline(limits: &ResourceLimits) -> u64 {
+        let limit_ms = limits.max_cpu_time_ms.min(limits.max_execution_time_ms);
+        limit_ms.div_ceil(EPOCH_TICK.as_millis() as u64).max(1)
+    }
+
+    /// Instantiate a core module and call one of its parameterless exports in a sandbox
+    ///
+    /// The store accounts its linear memory through `limiter` and the call runs under the
+    /// deadline of the sandbox's limits; a call running past it is interrupted with
+    /// [`Error::SecurityViolation`].
+    pub fn call_in_sandbox(&self, wasm: &[u8], name: &str, limiter: SandboxLimiter) -> Result<()> {
+        let limits = limiter.sandbox.get_resource_limits();
+        let module = wasmtime::Module::new(&self.engine, wasm)?;
+        let mut store = wasmtime::Store::new(&self.engine, limiter);
+        store.limiter(|limiter| limiter);
+        store.set_epoch_deadline(Self::epoch_deadline(&limits));
+        let instance = self.instantiate(&mut store, &module)?;
+        let func = instance.get_typed_func::<(), ()>(&mut store, name)?;
+        func.call(&mut store, ()).map_err(|e| call_error(e, name, &limits))
     }
 
     /// Load a plugin module from a file
-    pub fn load_module(&self, _path: impl AsRef<Path>) -> Result<()> {
-        // Stub implementation
+    ///
+    /// Fails with [`Error::InvalidModule`] naming the file if it is not a valid WASM module.
+    pub fn load_module(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        wasmtime::Module::validate(&self.engine, &bytes)
+            .map_err(|e| Error::InvalidModule(path.to_path_buf(), format!("{:#}", e)))
+    }
+
+    /// Instantiate a plugin whose memory is accounted by `limiter` and whose calls are
+    /// bound by the limits of its sandbox
+    ///
+    /// Bare manifests get an instance without a module. Fails with
+    /// [`Error::InvalidModule`] naming the file if it is neither a valid WASM module nor a
+    /// manifest.
+    pub fn instantiate_plugin(
+        &self,
+        module_path: impl AsRef<Path>,
+        limiter: SandboxLimiter,
+    ) -> Result<PluginInstance> {
+        let module_path = module_path.as_ref();
+        let bytes = std::fs::read(module_path)?;
+        if bytes.starts_with(WASM_MAGIC) {
+            return self.instantiate_module(module_path, &bytes, limiter);
+        }
+        let manifest = std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|content| PluginMetadata::from_str(content).map_err(|e| e.to_string()));
+        if let Err(e) = manifest {
+            return Err(Error::InvalidModule(
+                module_path.to_path_buf(),
+                format!("neither a WASM module nor a plugin manifest: {}", e),
+            ));
+        }
+        Ok(PluginInstance {
+            limits: limiter.sandbox.get_resource_limits(),
+            memory: None,
+        })
+    }
+
+    /// Instantiate a plugin module, in binary or text format, whose memory is accounted
+    /// by `limiter` and whose calls are bound by the limits of its sandbox
+    ///
+    /// Modules exporting their linear memory are instantiated, so the host can pass data
+    /// through [`GuestMemory`]; other modules are not run yet. Fails with
+    /// [`Error::InvalidModule`] naming `path` if the module does not compile.
+    pub fn instantiate_module(
+        &self,
+        path: &Path,
+        wasm: &[u8],
+        limiter: SandboxLimiter,
+    ) -> Result<PluginInstance> {
+        let limits = limiter.sandbox.get_resource_limits();
+        let module = wasmtime::Module::new(&self.engine, wasm)
+            .map_err(|e| Error::InvalidModule(path.to_path_buf(), format!("{:#}", e)))?;
+        let memory = if module.get_export(MEMORY_EXPORT).is_some() {
+            Some(GuestMemory::instantiate(self, &module, limiter)?)
+        } else {
+            None
+        };
+        Ok(PluginInstance { limits, memory })
+    }
+}
+
+/// Map the failure of a guest call, reporting calls interrupted at their deadline as
+/// [`Error::SecurityViolation`]
+fn call_error(e: wasmtime::Error, name: &str, limits: &ResourceLimits) -> Error {
+    if e.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::Interrupt) {
+        Error::SecurityViolation(format!(
+            "call to '{}' exceeded its time limit of {}ms",
+            name,
+            limits.max_cpu_time_ms.min(limits.max_execution_time_ms)
+        ))
+    } else {
+        Error::Wasmtime(e)
+    }
+}
+
+/// Module plugins import host functions from, named after the `host` interface of the
+/// WIT world
+pub const HOST_MODULE: &str = "host";
+
+/// A logging host function, by import name, taking the plugin name and message
+type HostLog = (&'static str, fn(&str, &str));
+
+/// Define the host functions in `linker`
+///
+/// Strings are passed as a pointer and length into the plugin's exported memory. Results
+/// are JSON, `{"Ok": ...}` or `{"Err": "..."}`, written to memory taken from the plugin's
+/// [`ALLOC_EXPORT`] and returned packed like the results of [`PluginInstance::call`].
+fn link_host(linker: &mut wasmtime::Linker<SandboxLimiter>) -> Result<()> {
+    let logs: [HostLog; 4] = [
+        ("log-debug", |plugin, message| debug!("[plugin {}] {}", plugin, message)),
+        ("log-info", |plugin, message| info!("[plugin {}] {}", plugin, message)),
+        ("log-warn", |plugin, message| warn!("[plugin {}] {}", plugin, message)),
+        ("log-error", |plugin, message| error!("[plugin {}] {}", plugin, message)),
+    ];
+    for (name, log) in logs {
+        linker.func_wrap(
+            HOST_MODULE,
+            name,
+            move |mut caller: wasmtime::Caller<'_, SandboxLimiter>, ptr: u32, len: u32| {
+                let message = read_guest_string(&mut caller, ptr, len)?;
+                log(caller.data().sandbox.plugin_name(), &message);
+                Ok(())
+            },
+        )?;
+    }
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "http-get",
+        |mut caller: wasmtime::Caller<'_, SandboxLimiter>, url_ptr: u32, url_len: u32| {
+            let url = read_guest_string(&mut caller, url_ptr, url_len)?;
+            let result = http_request(&caller, move |host_api, plugin| async move {
+                host_api.http_get(&plugin, &url).await
+            });
+            write_guest_result(&mut caller, result)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "http-post",
+        |mut caller: wasmtime::Caller<'_, SandboxLimiter>,
+         url_ptr: u32,
+         url_len: u32,
+         content_type_ptr: u32,
+         content_type_len: u32,
+         body_ptr: u32,
+         body_len: u32| {
+            let url = read_guest_string(&mut caller, url_ptr, url_len)?;
+            let content_type = read_guest_string(&mut caller, content_type_ptr, content_type_len)?;
+            let body = read_guest_string(&mut caller, body_ptr, body_len)?;
+            let result = http_request(&caller, move |host_api, plugin| async move {
+                host_api.http_post(&plugin, &url, &content_type, body).await
+            });
+            write_guest_result(&mut caller, result)
+        },
+    )?;
+    Ok(())
+}
+
+/// Send an HTTP request on behalf of the calling plugin and wait for the response
+///
+/// Host functions are synchronous, so the request runs to completion on a runtime of its
+/// own, off the thread of the guest call.
+fn http_request<F>(
+    caller: &wasmtime::Caller<'_, SandboxLimiter>,
+    request: impl FnOnce(Arc<HostApi>, String) -> F + Send,
+) -> Result<HttpResponse>
+where
+    F: Future<Output = Result<HttpResponse>>,
+{
+    let limiter = caller.data();
+    let host_api = limiter
+        .host_api
+        .upgrade()
+        .ok_or_else(|| Error::Runtime("Host API has been dropped".to_string()))?;
+    let plugin = limiter.sandbox.plugin_name().to_string();
+    std::thread::scope(|scope| {
+        scope
+            .spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?
+                    .block_on(request(host_api, plugin))
+            })
+            .join()
+            .unwrap_or_else(|_| Err(Error::Runtime("HTTP request panicked".to_string())))
+    })
+}
+
+/// Write the JSON of a host function result into the calling plugin's memory, returning
+/// its pointer and length packed
+fn write_guest_result<T: serde::Serialize>(
+    caller: &mut wasmtime::Caller<'_, SandboxLimiter>,
+    result: Result<T>,
+) -> wasmtime::Result<u64> {
+    let json = serde_json::to_vec(&result.map_err(|e| e.to_string()))?;
+    let len = u32::try_from(json.len())?;
+    let alloc = caller
+        .get_export(ALLOC_EXPORT)
+        .and_then(wasmtime::Extern::into_func)
+        .ok_or_else(|| wasmtime::Error::msg(format!("plugin exports no '{}'", ALLOC_EXPORT)))?
+        .typed::<u32, u32>(&caller)?;
+    let ptr = match alloc.call(&mut *caller, len)? {
+        0 => return Err(wasmtime::Error::msg(format!("guest failed to allocate {} bytes", len))),
+        ptr => ptr,
+    };
+    let memory = caller
+        .get_export(MEMORY_EXPORT)
+        .and_then(wasmtime::Extern::into_memory)
+        .ok_or_else(|| wasmtime::Error::msg(format!("plugin exports no '{}'", MEMORY_EXPORT)))?;
+    memory.write(&mut *caller, ptr as usize, &json)?;
+    Ok((u64::from(ptr) << 32) | u64::from(len))
+}
+
+/// Read a UTF-8 string, replacing invalid sequences, from the calling plugin's memory
+fn read_guest_string(
+    caller: &mut wasmtime::Caller<'_, SandboxLimiter>,
+    ptr: u32,
+    len: u32,
+) -> wasmtime::Result<String> {
+    let memory = caller
+        .get_export(MEMORY_EXPORT)
+        .and_then(wasmtime::Extern::into_memory)
+        .ok_or_else(|| wasmtime::Error::msg(format!("plugin exports no '{}'", MEMORY_EXPORT)))?;
+    let start = ptr as usize;
+    let bytes = memory
+        .data(&caller)
+        .get(start..start + len as usize)
+        .ok_or_else(|| {
+            wasmtime::Error::msg(format!("string of {} bytes at {:#x} is out of bounds", len, ptr))
+        })?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+impl Drop for WasmRuntime {
+    fn drop(&mut self) {
+        self.ticker_stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Accounts the linear memory of a plugin's store to its [`Sandbox`]
+///
+/// Growth beyond [`ResourceLimits::max_memory`] is denied. The memory is released from
+/// the sandbox when the store is dropped.
+pub struct SandboxLimiter {
+    sandbox: Arc<Sandbox>,
+    /// Collector the plugin's memory usage is reported to
+    metrics: Option<Arc<MetricsCollector>>,
+    /// Host API the plugin's host function calls are served by
+    host_api: Weak<HostApi>,
+    /// Bytes of linear memory granted to the store
+    granted: usize,
+}
+
+impl SandboxLimiter {
+    /// Create a limiter accounting to a sandbox
+    pub fn new(sandbox: Arc<Sandbox>) -> Self {
+        Self {
+            sandbox,
+            metrics: None,
+            host_api: Weak::new(),
+            granted: 0,
+        }
+    }
+
+    /// Serve the plugin's host function calls, such as `http-get`, with a host API
+    pub fn with_host_api(mut self, host_api: &Arc<HostApi>) -> Self {
+        self.host_api = Arc::downgrade(host_api);
+        self
+    }
+
+    /// Also report the plugin's memory usage to a metrics collector
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn report_memory(&self) {
+        if let Some(metrics) = &self.metrics {
+            let memory_used = self.sandbox.get_resource_usage().memory_used;
+            metrics.update_memory_usage(self.sandbox.plugin_name(), memory_used as u64);
+        }
+    }
+}
+
+impl wasmtime::ResourceLimiter for SandboxLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        let delta = desired.saturating_sub(current);
+        if let Err(e) = self.sandbox.record_memory_growth(delta) {
+            warn!("Denying memory growth of plugin '{}': {}", self.sandbox.plugin_name(), e);
+            return Ok(false);
+        }
+        self.granted += delta;
+        self.report_memory();
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl Drop for SandboxLimiter {
+    fn drop(&mut self) {
+        self.sandbox.record_deallocation(self.granted);
+        self.report_memory();
+    }
+}
+
+/// Leading bytes of every WASM binary
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Name of the linear memory a plugin exports
+pub const MEMORY_EXPORT: &str = "memory";
+/// Name of the guest allocator, `alloc(size: i32) -> i32`, returning 0 on failure
+pub const ALLOC_EXPORT: &str = "alloc";
+/// Name of the guest deallocator, `dealloc(ptr: i32, size: i32)`
+pub const DEALLOC_EXPORT: &str = "dealloc";
+/// Name of the guest start hook, `start() -> i32`, returning 0 on success
+pub const START_EXPORT: &str = "start";
+/// Name of the guest stop hook, `stop() -> i32`, returning 0 on success
+pub const STOP_EXPORT: &str = "stop";
+
+/// The linear memory of an instantiated plugin and the guest functions managing it
+///
+/// Every access is bounds-checked against the current memory size; out-of-range pointers
+/// fail with [`Error::Runtime`].
+pub struct GuestMemory {
+    store: wasmtime::Store<SandboxLimiter>,
+    instance: wasmtime::Instance,
+    memory: wasmtime::Memory,
+    alloc: Option<wasmtime::TypedFunc<u32, u32>>,
+    dealloc: Option<wasmtime::TypedFunc<(u32, u32), ()>>,
+    /// Epoch ticks each guest call may run, see [`WasmRuntime::epoch_deadline`]
+    deadline: u64,
+}
+
+impl GuestMemory {
+    /// Instantiate `module` in a store whose linear memory is accounted by `limiter`
+    fn instantiate(
+        runtime: &WasmRuntime,
+        module: &wasmtime::Module,
+        limiter: SandboxLimiter,
+    ) -> Result<Self> {
+        let deadline = WasmRuntime::epoch_deadline(&limiter.sandbox.get_resource_limits());
+        let mut store = wasmtime::Store::new(&runtime.engine, limiter);
+        store.limiter(|limiter| limiter);
+        store.set_epoch_deadline(deadline);
+        let instance = runtime.instantiate(&mut store, module)?;
+        let memory = instance
+            .get_memory(&mut store, MEMORY_EXPORT)
+            .ok_or_else(|| Error::Runtime(format!("export '{}' is not a memory", MEMORY_EXPORT)))?;
+        let alloc = instance.get_typed_func(&mut store, ALLOC_EXPORT).ok();
+        let dealloc = instance.get_typed_func(&mut store, DEALLOC_EXPORT).ok();
+        Ok(Self {
+            store,
+            instance,
+            memory,
+            alloc,
+            dealloc,
+            deadline,
+        })
+    }
+
+    /// Allocate `size` bytes with the guest allocator, returning the pointer
+    pub fn allocate(&mut self, size: u32) -> Result<u32> {
+        let alloc = self
+            .alloc
+            .clone()
+            .ok_or_else(|| Error::Runtime(format!("plugin exports no '{}'", ALLOC_EXPORT)))?;
+        self.store.set_epoch_deadline(self.deadline);
+        match alloc.call(&mut self.store, size)? {
+            0 => Err(Error::Runtime(format!("guest failed to allocate {} bytes", size))),
+            ptr => Ok(ptr),
+        }
+    }
+
+    /// Free `size` bytes at `ptr` with the guest deallocator
+    pub fn free(&mut self, ptr: u32, size: u32) -> Result<()> {
+        let dealloc = self
+            .dealloc
+            .clone()
+            .ok_or_else(|| Error::Runtime(format!("plugin exports no '{}'", DEALLOC_EXPORT)))?;
+        self.range(ptr, size)?;
+        self.store.set_epoch_deadline(self.deadline);
+        dealloc.call(&mut self.store, (ptr, size))?;
+        Ok(())
+    }
+
+    /// Call the lifecycle hook `name`, `() -> i32`, if the plugin exports it
+    ///
+    /// A nonzero result fails with [`Error::Runtime`].
+    fn call_hook(&mut self, name: &str) -> Result<()> {
+        let Ok(hook) = self.instance.get_typed_func::<(), i32>(&mut self.store, name) else {
+            return Ok(());
+        };
+        self.store.set_epoch_deadline(self.deadline);
+        match hook.call(&mut self.store, ()).map_err(|e| self.call_error(e, name))? {
+            0 => Ok(()),
+            code => Err(Error::Runtime(format!("plugin hook '{}' failed with {}", name, code))),
+        }
+    }
+
+    /// Call `name(ptr: i32, len: i32) -> i64` with `args` copied into guest memory
+    ///
+    /// The result packs the pointer of the returned bytes in its upper and their length in
+    /// its lower 32 bits. Arguments and result are freed afterwards if the plugin exports
+    /// a deallocator.
+    fn call(&mut self, name: &str, args: &[u8]) -> Result<Vec<u8>> {
+        let func = self
+            .instance
+            .get_typed_func::<(u32, u32), u64>(&mut self.store, name)
+            .map_err(|_| Error::Runtime(format!("plugin exports no '{}'", name)))?;
+        let len = args.len() as u32;
+        let ptr = if args.is_empty() { 0 } else { self.allocate(len)? };
+        self.write(ptr, args)?;
+        self.store.set_epoch_deadline(self.deadline);
+        let packed = func
+            .call(&mut self.store, (ptr, len))
+            .map_err(|e| self.call_error(e, name))?;
+        let (result_ptr, result_len) = ((packed >> 32) as u32, packed as u32);
+        let result = self.read(result_ptr, result_len)?;
+        if self.dealloc.is_some() {
+            if len > 0 {
+                self.free(ptr, len)?;
+            }
+            if result_len > 0 {
+                self.free(result_ptr, result_len)?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn call_error(&self, e: wasmtime::Error, name: &str) -> Error {
+        call_error(e, name, &self.store.data().sandbox.get_resource_limits())
+    }
+
+    /// Read `size` bytes at `ptr`
+    pub fn read(&self, ptr: u32, size: u32) -> Result<Vec<u8>> {
+        let range = self.range(ptr, size)?;
+        Ok(self.memory.data(&self.store)[range].to_vec())
+    }
+
+    /// Write `data` at `ptr`
+    pub fn write(&mut self, ptr: u32, data: &[u8]) -> Result<()> {
+        let size = u32::try_from(data.len())
+            .map_err(|_| Error::Runtime(format!("{} bytes do not fit guest memory", data.len())))?;
+        let range = self.range(ptr, size)?;
+        self.memory.data_mut(&mut self.store)[range].copy_from_slice(data);
         Ok(())
     }
 
-    /// Instantiate a plugin
-    pub fn instantiate_plugin(&self, _module_path: impl AsRef<Path>) -> Result<PluginInstance> {
-        // Stub implementation
-        Ok(PluginInstance)
+    /// Current size of the memory in bytes
+    pub fn size(&self) -> usize {
+        self.memory.data_size(&self.store)
+    }
+
+    fn range(&self, ptr: u32, size: u32) -> Result<std::ops::Range<usize>> {
+        let start = ptr as usize;
+        let end = start + size as usize;
+        if end > self.size() {
+            return Err(Error::Runtime(format!(
+                "guest memory access of {} bytes at {:#x} is out of bounds ({} bytes)",
+                size,
+                ptr,
+                self.size()
+            )));
+        }
+        Ok(start..end)
     }
 }
 
-/// Plugin instance (stub)
-pub struct PluginInstance;
+/// Plugin instance
+pub struct PluginInstance {
+    /// Limits the plugin's calls run under, see [`WasmRuntime::epoch_deadline`]
+    limits: ResourceLimits,
+    /// Linear memory of the instantiated module, if it exports one
+    memory: Option<GuestMemory>,
+}
 
 impl PluginInstance {
+    /// Get the limits the plugin's calls run under
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    /// Get the plugin's linear memory
+    pub fn memory(&mut self) -> Result<&mut GuestMemory> {
+        self.memory
+            .as_mut()
+            .ok_or_else(|| Error::Runtime("plugin exports no linear memory".to_string()))
+    }
+
     /// Initialize the plugin
     pub async fn initialize(&mut self) -> Result<()> {
         Ok(())
     }
 
-    /// Start the plugin
+    /// Start the plugin by calling its [`START_EXPORT`] hook, if it has one
+    ///
+    /// Like every guest call, the hook runs under the plugin's deadline.
     pub async fn start(&mut self) -> Result<()> {
-        Ok(())
+        match &mut self.memory {
+            Some(memory) => memory.call_hook(START_EXPORT),
+            None => Ok(()),
+        }
     }
 
-    /// Stop the plugin
+    /// Stop the plugin by calling its [`STOP_EXPORT`] hook, if it has one
     pub async fn stop(&mut self) -> Result<()> {
-        Ok(())
+        match &mut self.memory {
+            Some(memory) => memory.call_hook(STOP_EXPORT),
+            None => Ok(()),
+        }
+    }
+
+    /// Call a plugin function with `args`, returning the bytes it returned
+    ///
+    /// Instances without a module have nothing to run and return no bytes. A call running
+    /// past the plugin's deadline fails with [`Error::SecurityViolation`].
+    pub async fn call(&mut self, name: &str, args: &[u8]) -> Result<Vec<u8>> {
+        match &mut self.memory {
+            Some(memory) => memory.call(name, args),
+            None => Ok(Vec::new()),
+        }
     }
 
-    /// Call a plugin function
-    pub async fn call(&mut self, _name: &str, _args: &[u8]) -> Result<Vec<u8>> {
-        Ok(Vec::new())
+    /// Hand a reloaded configuration, as JSON, to the plugin's `on_config_reload` hook
+    pub async fn on_config_reload(&mut self, config: &str) -> Result<()> {
+        self.call("on_config_reload", config.as_bytes()).await?;
+        Ok(())
     }
 
     /// Clean up plugin resources
     pub async fn cleanup(&mut self) -> Result<()> {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::SecurityPolicy;
+
+    fn limiter(limits: ResourceLimits) -> SandboxLimiter {
+        SandboxLimiter::new(Arc::new(Sandbox::new(
+            "test".to_string(),
+            limits,
+            SecurityPolicy::default(),
+        )))
+    }
+
+    #[test]
+    fn test_invalid_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = WasmRuntime::new().unwrap();
+
+        let path = dir.path().join("corrupt.wasm");
+        std::fs::write(&path, b"\x7fELF garbage").unwrap();
+        let err = runtime.load_module(&path).unwrap_err();
+        assert!(matches!(&err, Error::InvalidModule(p, _) if *p == path));
+        let message = err.to_string();
+        assert!(message.contains("corrupt.wasm"), "{}", message);
+        assert!(message.starts_with("Invalid WASM module"), "{}", message);
+
+        // Smallest valid module: magic number and version
+        let path = dir.path().join("empty.wasm");
+        std::fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+        runtime.load_module(&path).unwrap();
+
+        // Instantiating a plugin reports garbage and corrupt modules alike
+        for (name, bytes) in [
+            ("garbage.wasm", &b"\x7fELF garbage"[..]),
+            ("truncated.wasm", &b"\0asm\x01\0\0\0\x01\x05"[..]),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            let Err(err) = runtime.instantiate_plugin(&path, limiter(ResourceLimits::default()))
+            else {
+                panic!("{} was instantiated", name);
+            };
+            assert!(matches!(&err, Error::InvalidModule(p, _) if *p == path), "{}", err);
+            assert!(err.to_string().contains(name), "{}", err);
+        }
+
+        // Bare manifests still get an instance without a module
+        let path = dir.path().join("manifest.wasm");
+        let manifest = "name = \"manifest\"\nversion = \"1.0.0\"\nauthor = \"test\"\nabi_version = \"1.0.0\"\n";
+        std::fs::write(&path, manifest).unwrap();
+        runtime.instantiate_plugin(&path, limiter(ResourceLimits::default())).unwrap();
+    }
+
+    #[test]
+    fn test_guest_memory() {
+        let runtime = WasmRuntime::new().unwrap();
+        // Bump allocator handing out memory from offset 1024
+        let wasm = r#"
+            (module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "alloc") (param $size i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get $size))))
+                (func (export "dealloc") (param i32 i32)))
+        "#;
+        let mut instance = runtime
+            .instantiate_module(Path::new("guest.wat"), wasm.as_bytes(), limiter(ResourceLimits::default()))
+            .unwrap();
+        let memory = instance.memory().unwrap();
+
+        let json = br#"{"user_id":1}"#;
+        let ptr = memory.allocate(json.len() as u32).unwrap();
+        assert_eq!(ptr, 1024);
+        memory.write(ptr, json).unwrap();
+        assert_eq!(memory.read(ptr, json.len() as u32).unwrap(), json);
+        memory.free(ptr, json.len() as u32).unwrap();
+
+        let size = memory.size() as u32;
+        assert!(matches!(memory.read(size - 1, 2), Err(Error::Runtime(_))));
+        assert!(matches!(memory.write(u32::MAX, b"x"), Err(Error::Runtime(_))));
+        assert_eq!(memory.read(size, 0).unwrap(), b"");
+
+        // Modules without memory have none to bridge
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.wasm");
+        std::fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+        let mut instance =
+            runtime.instantiate_plugin(&path, limiter(ResourceLimits::default())).unwrap();
+        assert!(matches!(instance.memory(), Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn test_host_imports() {
+        const PAGE: usize = 64 * 1024;
+        let runtime = WasmRuntime::new().unwrap();
+        let wasm = r#"
+            (module
+                (import "host" "log-info" (func $log (param i32 i32)))
+                (import "host" "kick-user" (func $kick (param i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello")
+                (func (export "greet") (call $log (i32.const 16) (i32.const 5)))
+                (func (export "kick") (drop (call $kick (i32.const 1)))))
+        "#;
+        let sandbox = Arc::new(Sandbox::new(
+            "test".to_string(),
+            ResourceLimits::default(),
+            SecurityPolicy::default(),
+        ));
+        let mut instance = runtime
+            .instantiate_module(Path::new("guest.wat"), wasm.as_bytes(), SandboxLimiter::new(Arc::clone(&sandbox)))
+            .unwrap();
+        instance.memory().unwrap();
+        // The plugin's store accounts its memory to the sandbox until dropped
+        assert_eq!(sandbox.get_resource_usage().memory_used, PAGE);
+        drop(instance);
+        assert_eq!(sandbox.get_resource_usage().memory_used, 0);
+
+        runtime
+            .call_in_sandbox(wasm.as_bytes(), "greet", SandboxLimiter::new(Arc::clone(&sandbox)))
+            .unwrap();
+        // Host functions not provided yet trap
+        assert!(runtime
+            .call_in_sandbox(wasm.as_bytes(), "kick", SandboxLimiter::new(sandbox))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_imports() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+                .unwrap();
+        });
+
+        let runtime = WasmRuntime::new().unwrap();
+        // `fetch` hands its argument to `http-get` and returns the result
+        let wasm = r#"
+            (module
+                (import "host" "http-get" (func $get (param i32 i32) (result i64)))
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "alloc") (param $size i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get $size))))
+                (func (export "dealloc") (param i32 i32))
+                (func (export "fetch") (param $ptr i32) (param $len i32) (result i64)
+                    (call $get (local.get $ptr) (local.get $len))))
+        "#;
+        let host_api = Arc::new(HostApi::new_with_weak(
+            Arc::new(crate::event_system::EventBus::new()),
+            Arc::new(crate::command_system::CommandRegistry::new()),
+            Weak::new(),
+        ));
+        let sandbox = host_api.sandbox_manager().create_sandbox(
+            "fetcher".to_string(),
+            ResourceLimits::default(),
+            SecurityPolicy {
+                allow_network: true,
+                allowed_network_hosts: vec!["127.0.0.1".to_string()],
+                ..Default::default()
+            },
+        );
+        let limiter = SandboxLimiter::new(Arc::clone(&sandbox)).with_host_api(&host_api);
+        let mut instance =
+            runtime.instantiate_module(Path::new("fetcher.wat"), wasm.as_bytes(), limiter).unwrap();
+
+        let url = format!("http://{}/", addr);
+        let response: serde_json::Value =
+            serde_json::from_slice(&instance.call("fetch", url.as_bytes()).await.unwrap()).unwrap();
+        assert_eq!(response, serde_json::json!({ "Ok": { "status": 200, "body": "ok" } }));
+
+        // Hosts outside the plugin's policy are refused
+        let response: serde_json::Value = serde_json::from_slice(
+            &instance.call("fetch", b"http://example.com/").await.unwrap(),
+        )
+        .unwrap();
+        let error = response["Err"].as_str().unwrap();
+        assert!(error.starts_with("Security violation"), "{}", error);
+        assert_eq!(sandbox.security_violations(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_instance_calls() {
+        let runtime = WasmRuntime::new().unwrap();
+        // `echo` returns its argument, `start` spins and `stop` fails
+        let wasm = r#"
+            (module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "alloc") (param $size i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get $size))))
+                (func (export "dealloc") (param i32 i32))
+                (func (export "start") (result i32) (loop (br 0)) (i32.const 0))
+                (func (export "stop") (result i32) (i32.const 2))
+                (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+                    (i64.or
+                        (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                        (i64.extend_i32_u (local.get $len)))))
+        "#;
+        let limits = ResourceLimits {
+            max_cpu_time_ms: 50,
+            ..ResourceLimits::default()
+        };
+        let mut instance = runtime.instantiate_module(Path::new("guest.wat"), wasm.as_bytes(), limiter(limits)).unwrap();
+
+        assert_eq!(instance.call("echo", b"ping").await.unwrap(), b"ping");
+        assert_eq!(instance.call("echo", b"").await.unwrap(), b"");
+        assert!(matches!(instance.call("missing", b"").await, Err(Error::Runtime(_))));
+
+        let started = std::time::Instant::now();
+        let err = instance.start().await.unwrap_err();
+        assert!(matches!(err, Error::SecurityViolation(_)), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // Each call gets a fresh deadline
+        assert_eq!(instance.call("echo", b"pong").await.unwrap(), b"pong");
+        assert!(matches!(instance.stop().await, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn test_busy_loop_is_interrupted() {
+        let runtime = WasmRuntime::new().unwrap();
+        let wasm = r#"
+            (module
+                (func (export "spin") (loop (br 0)))
+                (func (export "noop")))
+        "#;
+        let limits = ResourceLimits {
+            max_cpu_time_ms: 50,
+            ..ResourceLimits::default()
+        };
+        assert_eq!(WasmRuntime::epoch_deadline(&limits), 5);
+
+        let sandbox = Arc::new(Sandbox::new("test".to_string(), limits, SecurityPolicy::default()));
+
+        let started = std::time::Instant::now();
+        let err = runtime
+            .call_in_sandbox(wasm.as_bytes(), "spin", SandboxLimiter::new(Arc::clone(&sandbox)))
+            .unwrap_err();
+        assert!(matches!(err, Error::SecurityViolation(_)), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        runtime
+            .call_in_sandbox(wasm.as_bytes(), "noop", SandboxLimiter::new(sandbox))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_memory_growth_is_accounted() {
+        const PAGE: usize = 64 * 1024;
+        let runtime = WasmRuntime::new().unwrap();
+        let wasm = r#"
+            (module
+                (memory 1)
+                (func (export "grow") (drop (memory.grow (i32.const 1))))
+                (func (export "grow_past_limit")
+                    (if (i32.ne (memory.grow (i32.const 4)) (i32.const -1))
+                        (then unreachable))))
+        "#;
+        let limits = ResourceLimits {
+            max_memory: 3 * PAGE,
+            ..ResourceLimits::default()
+        };
+        let sandbox = Arc::new(Sandbox::new("test".to_string(), limits, SecurityPolicy::default()));
+        let metrics = Arc::new(MetricsCollector::new(1, Duration::ZERO));
+        let plugin_metrics = metrics.register_plugin("test".to_string());
+
+        let limiter = SandboxLimiter::new(Arc::clone(&sandbox)).with_metrics(Arc::clone(&metrics));
+        runtime.call_in_sandbox(wasm.as_bytes(), "grow", limiter).unwrap();
+        let usage = sandbox.get_resource_usage();
+        assert_eq!(usage.peak_memory, 2 * PAGE);
+        // The store is gone, and its memory with it
+        assert_eq!(usage.memory_used, 0);
+        assert_eq!(plugin_metrics.read().memory_usage, 0);
+
+        let limiter = SandboxLimiter::new(Arc::clone(&sandbox));
+        runtime.call_in_sandbox(wasm.as_bytes(), "grow_past_limit", limiter).unwrap();
+        assert_eq!(sandbox.security_violations(), 1);
+        assert_eq!(sandbox.get_resource_usage().peak_memory, 2 * PAGE);
+    }
+}
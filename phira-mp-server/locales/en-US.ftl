@@ -1,9 +1,20 @@
 
 create-id-occupied = Room ID is occupied
+create-room-limit = The server cannot host more rooms
+create-tag-required = You don't have permission to create rooms
 
 join-game-ongoing = Game is ongoing
 join-room-full = Room is full
 join-room-locked = Room is locked
+join-room-locked-by = Room was locked by { $name } ({ $id }) { $minutes } min ago
 join-cant-monitor = Permission denied. You can't monitor this room.
+join-banned = You are banned from this room
+
+select-chart-not-in-pool = The chart is not in the room's chart pool
 
 start-no-chart-selected = No chart selected
+
+auth-client-outdated = Client is outdated (protocol { $version }, at least { $min } required), please update Phira
+auth-banned = You are banned from this server
+
+shutdown-warning = Server shutting down in { $seconds } s
@@ -1,9 +1,20 @@
 
 create-id-occupied = 房间 ID 已被占用
+create-room-limit = 服务器房间数量已达上限
+create-tag-required = 你没有创建房间的权限
 
 join-game-ongoing = 游戏正在进行中
 join-room-full = 房间已满
 join-room-locked = 房间已锁定
+join-room-locked-by = 房间已被 { $name } ({ $id }) 于 { $minutes } 分钟前锁定
 join-cant-monitor = 权限不足，不能旁观房间
+join-banned = 你已被此房间封禁
+
+select-chart-not-in-pool = 该谱面不在房间的谱面池中
 
 start-no-chart-selected = 还没有选择谱面
+
+auth-client-outdated = 客户端版本过旧（协议 { $version }，至少需要 { $min }），请更新 Phira
+auth-banned = 你已被此服务器封禁
+
+shutdown-warning = 服务器将在 { $seconds } 秒后关闭
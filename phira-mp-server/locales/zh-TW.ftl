@@ -1,9 +1,20 @@
 
 create-id-occupied = 房間 ID 已被佔用
+create-room-limit = 伺服器房間數量已達上限
+create-tag-required = 你沒有建立房間的權限
 
 join-game-ongoing = 遊戲正在進行中
 join-room-full = 房間已滿
 join-room-locked = 房間已鎖定
+join-room-locked-by = 房間已被 { $name } ({ $id }) 於 { $minutes } 分鐘前鎖定
 join-cant-monitor = 權限不足，不能旁觀房間
+join-banned = 你已被此房間封鎖
+
+select-chart-not-in-pool = 該譜面不在房間的譜面池中
 
 start-no-chart-selected = 還沒有選擇譜面
+
+auth-client-outdated = 用戶端版本過舊（協議 { $version }，至少需要 { $min }），請更新 Phira
+auth-banned = 你已被此伺服器封鎖
+
+shutdown-warning = 伺服器將在 { $seconds } 秒後關閉
@@ -0,0 +1,206 @@
+//! Verification of client tokens.
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// Default Phira API server, used for charts, records and authentication.
+pub const DEFAULT_API_HOST: &str = "https://phira.5wyxi.com";
+
+/// How long authentication backs off after a rate limit without `Retry-After`.
+pub const AUTH_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest backoff taken from `Retry-After`.
+pub const MAX_AUTH_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// Account a token belongs to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthInfo {
+    pub id: i32,
+    pub name: String,
+    pub language: String,
+}
+
+/// The auth server refused to answer because of rate limiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    /// How long to wait before asking again, if known.
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(retry_after) => write!(
+                f,
+                "authentication is rate limited, retry in {}s",
+                retry_after.as_secs().max(1)
+            ),
+            None => write!(f, "authentication is rate limited"),
+        }
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Resolves the token a client authenticates with to its account.
+pub trait AuthProvider: Send + Sync {
+    fn authenticate<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<AuthInfo>> + Send + 'a>>;
+}
+
+/// Asks the `/me` endpoint of a Phira API server.
+pub struct HttpAuthProvider {
+    host: String,
+    client: reqwest::Client,
+}
+
+impl HttpAuthProvider {
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl AuthProvider for HttpAuthProvider {
+    fn authenticate<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<AuthInfo>> + Send + 'a>> {
+        Box::pin(async move {
+            let resp = self
+                .client
+                .get(format!("{}/me", self.host))
+                .header(reqwest::header::AUTHORIZATION, format!("Bearer {token}"))
+                .send()
+                .await?;
+            if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                // Only the delay-seconds form of `Retry-After` is understood
+                let retry_after = resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|it| it.to_str().ok())
+                    .and_then(|it| it.trim().parse().ok())
+                    .map(Duration::from_secs);
+                return Err(RateLimited { retry_after }.into());
+            }
+            Ok(resp.error_for_status()?.json().await?)
+        })
+    }
+}
+
+/// Accepts a fixed set of tokens, for local testing.
+pub struct StaticAuthProvider {
+    users: HashMap<String, AuthInfo>,
+}
+
+impl StaticAuthProvider {
+    pub fn new(users: HashMap<String, AuthInfo>) -> Self {
+        Self { users }
+    }
+}
+
+impl AuthProvider for StaticAuthProvider {
+    fn authenticate<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<AuthInfo>> + Send + 'a>> {
+        let res = self
+            .users
+            .get(token)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown token"));
+        Box::pin(async move { res })
+    }
+}
+
+/// Recent authentications, answering for their tokens while the auth server rate limits,
+/// and the backoff during which it is not asked.
+pub struct AuthCache {
+    ttl: Duration,
+    entries: parking_lot::Mutex<HashMap<String, (AuthInfo, Instant)>>,
+    backoff_until: parking_lot::Mutex<Option<Instant>>,
+}
+
+impl AuthCache {
+    /// Creates a cache keeping authentications for `ttl`, none if zero.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: parking_lot::Mutex::default(),
+            backoff_until: parking_lot::Mutex::default(),
+        }
+    }
+
+    /// Remembers a successful authentication, evicting expired ones.
+    pub fn insert(&self, token: &str, info: &AuthInfo) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock();
+        entries.retain(|_, (_, at)| now.duration_since(*at) < self.ttl);
+        entries.insert(token.to_owned(), (info.clone(), now));
+    }
+
+    /// The account `token` authenticated as within the TTL.
+    pub fn get(&self, token: &str) -> Option<AuthInfo> {
+        self.entries
+            .lock()
+            .get(token)
+            .filter(|(_, at)| at.elapsed() < self.ttl)
+            .map(|(info, _)| info.clone())
+    }
+
+    /// Stops asking the auth server for `backoff`.
+    pub fn back_off(&self, backoff: Duration) {
+        *self.backoff_until.lock() = Some(Instant::now() + backoff);
+    }
+
+    /// Time left until the auth server may be asked again, if backing off.
+    pub fn backoff_remaining(&self) -> Option<Duration> {
+        let mut guard = self.backoff_until.lock();
+        let remaining = (*guard)?
+            .checked_duration_since(Instant::now())
+            .filter(|it| !it.is_zero());
+        if remaining.is_none() {
+            *guard = None;
+        }
+        remaining
+    }
+}
+
+/// How client tokens are verified.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuthConfig {
+    /// Ask a Phira API server, `api_host` if no host is given.
+    Http {
+        #[serde(default)]
+        host: Option<String>,
+    },
+    /// Accept only the listed tokens.
+    Static { users: HashMap<String, AuthInfo> },
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self::Http { host: None }
+    }
+}
+
+impl AuthConfig {
+    pub fn provider(&self, api_host: &str) -> Arc<dyn AuthProvider> {
+        match self {
+            Self::Http { host } => {
+                Arc::new(HttpAuthProvider::new(host.as_deref().unwrap_or(api_host)))
+            }
+            Self::Static { users } => Arc::new(StaticAuthProvider::new(users.clone())),
+        }
+    }
+}
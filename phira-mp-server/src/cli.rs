@@ -3,12 +3,12 @@ use anyhow::{Result, anyhow};
 use phira_mp_plugin::{
     PluginManager,
     event_system::EventBus,
-    command_system::CommandRegistry,
+    command_system::{CommandContext, CommandRegistry},
     api_host::HostApi,
     server_commands::ServerCommands,
     create_plugin_system,
 };
-use tracing::{info, error};
+use tracing::info;
 
 /// CLI command handler for server administration
 pub struct CliHandler {
@@ -70,14 +70,15 @@ impl CliHandler {
         let args: Vec<String> = parts[1..].iter().map(|&s| s.to_string()).collect();
 
         // Execute via server commands
-        let result = self.server_commands.execute(&command, &args);
+        let console = CommandContext::console();
+        let result = self.server_commands.execute_as(&console, &command, &args);
 
         // If command not found in server commands, try command registry
         match result {
             Ok(output) => Ok(output),
             Err(e) if e.to_string().contains("未知命令") => {
                 // Try command registry
-                self.command_registry.execute(command_line)
+                self.command_registry.execute_async(command_line, Some(&console)).await
                     .map_err(|e| anyhow!("Command error: {}", e))
             }
             Err(e) => Err(anyhow!("Command error: {}", e)),
@@ -97,13 +98,7 @@ impl CliHandler {
     /// Shutdown plugin system
     pub async fn shutdown_plugins(&self) -> anyhow::Result<()> {
         info!("Shutting down plugins from CLI handler");
-        let plugins = self.plugin_manager.get_all_plugins();
-        for plugin_arc in plugins {
-            let plugin = plugin_arc.read();
-            if let Err(e) = self.plugin_manager.unload_plugin(&plugin.metadata.name).await {
-                error!("Failed to unload plugin {}: {}", plugin.metadata.name, e);
-            }
-        }
+        self.plugin_manager.unload_all().await;
         info!("Plugins shutdown complete");
         Ok(())
     }
@@ -0,0 +1,84 @@
+//! Serves the HTTP routes plugins register through the host API.
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    http::{Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use phira_mp_plugin::HostApi;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::warn;
+
+/// Builds the router. Every request falls through to the plugin route registry, so routes
+/// registered or unregistered while serving take effect immediately.
+pub fn router(host_api: Arc<HostApi>) -> Router {
+    Router::new().fallback(move |method: Method, uri: Uri, body: Bytes| {
+        let host_api = Arc::clone(&host_api);
+        async move { dispatch(&host_api, method, uri, body) }
+    })
+}
+
+/// Answers requests on `listener` until the task is dropped.
+pub async fn serve(listener: TcpListener, host_api: Arc<HostApi>) {
+    if let Err(err) = axum::serve(listener, router(host_api)).await {
+        warn!("plugin HTTP server stopped: {err:?}");
+    }
+}
+
+fn dispatch(host_api: &HostApi, method: Method, uri: Uri, body: Bytes) -> Response {
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(body) => body,
+            Err(err) => return error(StatusCode::BAD_REQUEST, err),
+        }
+    };
+    match host_api.handle_http_request(method.as_str(), uri.path(), body) {
+        Some(Ok(response)) => Json(response).into_response(),
+        Some(Err(err)) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+        None => error(StatusCode::NOT_FOUND, "no such route"),
+    }
+}
+
+fn error(status: StatusCode, err: impl ToString) -> Response {
+    (status, Json(json!({ "error": err.to_string() }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_plugin_route() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api
+            .register_http_route(
+                "stats",
+                "GET",
+                "/stats/online",
+                Box::new(|_| Ok(json!({ "online": 3 }))),
+            )
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::clone(&host_api)));
+        let url = format!("http://{addr}/stats/online");
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.json::<Value>().await.unwrap(),
+            json!({ "online": 3 })
+        );
+
+        host_api.unregister_http_routes("stats");
+        assert_eq!(reqwest::get(&url).await.unwrap().status(), 404);
+    }
+}
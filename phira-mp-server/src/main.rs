@@ -1,5 +1,16 @@
+mod auth;
+pub use auth::*;
+
 mod cli;
+mod http;
 mod l10n;
+mod metrics;
+
+mod otlp;
+pub use otlp::*;
+
+mod plugin_bridge;
+pub use plugin_bridge::*;
 
 mod room;
 pub use room::*;
@@ -10,8 +21,15 @@ pub use server::*;
 mod session;
 pub use session::*;
 
+mod stats;
+pub use stats::*;
+
 use anyhow::Result;
 use clap::Parser;
+use phira_mp_plugin::{
+    hot_reload::{HotReloadConfig, HotReloadManager},
+    monitoring::{HealthMonitor, HealthThresholds, MetricsCollector},
+};
 use std::{
     collections::{
         HashMap,
@@ -19,6 +37,8 @@ use std::{
     },
     net::{Ipv6Addr, SocketAddr},
     path::Path,
+    sync::Arc,
+    time::Duration,
 };
 use tokio::{net::TcpListener, sync::RwLock};
 use tracing::{info, warn};
@@ -40,7 +60,10 @@ fn vacant_entry<V>(map: &mut HashMap<Uuid, V>) -> VacantEntry<'_, Uuid, V> {
     }
 }
 
-pub fn init_log(file: &str) -> Result<WorkerGuard> {
+pub fn init_log(
+    file: &str,
+    otlp: Option<&OtlpConfig>,
+) -> Result<(WorkerGuard, Option<OtlpGuard>)> {
     use tracing::{Level, metadata::LevelFilter};
     use tracing_log::LogTracer;
     use tracing_subscriber::{EnvFilter, filter, fmt, prelude::*};
@@ -59,11 +82,27 @@ pub fn init_log(file: &str) -> Result<WorkerGuard> {
     let (non_blocking, guard) =
         tracing_appender::non_blocking(tracing_appender::rolling::hourly(log_dir, file));
 
+    let (otlp, otlp_guard) = otlp.map(otlp_layer).transpose()?.unzip();
+    let otlp = otlp.map(|layer| {
+        // Spans of the exporter's own requests are not exported
+        layer.with_filter(
+            filter::Targets::new()
+                .with_target("hyper", LevelFilter::OFF)
+                .with_target("reqwest", LevelFilter::OFF)
+                .with_target("h2", LevelFilter::OFF)
+                .with_default(Level::DEBUG),
+        )
+    });
+
     let subscriber = tracing_subscriber::registry()
+        .with(otlp)
         .with(
-            fmt::layer()
-                .with_writer(non_blocking)
-                .with_filter(LevelFilter::DEBUG),
+            fmt::layer().with_writer(non_blocking).with_filter(
+                // Frame traces are only emitted for sessions traced with `/trace`
+                filter::Targets::new()
+                    .with_target(phira_mp_common::FRAME_TRACE_TARGET, Level::TRACE)
+                    .with_default(Level::DEBUG),
+            ),
         )
         .with(
             fmt::layer()
@@ -79,7 +118,7 @@ pub fn init_log(file: &str) -> Result<WorkerGuard> {
         );
 
     tracing::subscriber::set_global_default(subscriber).expect("unable to set global subscriber");
-    Ok(guard)
+    Ok((guard, otlp_guard))
 }
 
 /// Command line arguments
@@ -123,7 +162,7 @@ struct Args {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _guard = init_log("phira-mp")?;
+    let _guard = init_log("phira-mp", ServerConfig::load().otlp.as_ref())?;
 
     let args = Args::parse();
 
@@ -147,7 +186,6 @@ async fn run_cli_mode(args: Args) -> Result<()> {
         Ok(handler) => handler,
         Err(e) => {
             eprintln!("Failed to initialize CLI handler: {}", e);
-            eprintln!("Note: This may be due to circular dependencies in the current implementation.");
             eprintln!("Trying to continue with limited functionality...");
             return Ok(());
         }
@@ -159,7 +197,7 @@ async fn run_cli_mode(args: Args) -> Result<()> {
     }
 
     // Execute command if provided
-    if let Some(command) = args.command {
+    let res = if let Some(command) = args.command {
         let all_args: Vec<String> = std::iter::once(command)
             .chain(args.command_args.into_iter())
             .collect();
@@ -177,7 +215,13 @@ async fn run_cli_mode(args: Args) -> Result<()> {
     } else {
         // Interactive mode
         cli_handler.start_interactive().await
+    };
+
+    // Unload plugins, dependents first
+    if let Err(e) = cli_handler.shutdown_plugins().await {
+        eprintln!("Warning: Failed to shut down plugins: {}", e);
     }
+    res
 }
 
 /// Run in server mode
@@ -191,10 +235,118 @@ async fn run_server_mode(args: Args) -> Result<()> {
     }
 
     let listener: Server = TcpListener::bind(addrs).await?.into();
+    let _plugins = start_plugins(&args.plugin_dir, listener.state()).await;
 
+    let state = Arc::clone(listener.state());
     loop {
-        if let Err(err) = listener.accept().await {
-            warn!("failed to accept: {err:?}");
+        tokio::select! {
+            res = listener.accept() => {
+                if let Err(err) = res {
+                    warn!("failed to accept: {err:?}");
+                }
+            }
+            _ = state.shutting_down.wait() => break,
         }
     }
+    state.stopped.wait().await;
+    info!("server stopped");
+    Ok(())
+}
+
+/// Interval between plugin health checks, including plugin health probes.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How often accumulated playtime is saved.
+const PLAYTIME_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of plugin health snapshots kept.
+const HEALTH_HISTORY_SIZE: usize = 100;
+
+/// Load plugins and connect them to the server. The returned handles must be kept alive.
+async fn start_plugins(
+    plugin_dir: &str,
+    state: &Arc<ServerState>,
+) -> Option<(
+    Arc<phira_mp_plugin::PluginManager>,
+    Arc<phira_mp_plugin::HostApi>,
+)> {
+    let (plugin_manager, host_api) = match phira_mp_plugin::create_plugin_system(plugin_dir) {
+        Ok(system) => system,
+        Err(err) => {
+            warn!("failed to create plugin system: {err:?}");
+            return None;
+        }
+    };
+    host_api.set_server_bridge(Arc::new(PluginBridge::new(state)));
+    plugin_manager
+        .event_bus()
+        .set_handler_concurrency(state.config.event_handlers);
+    let metrics_collector =
+        Arc::new(MetricsCollector::new(HEALTH_HISTORY_SIZE, HEALTH_CHECK_INTERVAL));
+    let health_monitor = Arc::new(HealthMonitor::new(
+        HealthThresholds::default(),
+        Arc::clone(&metrics_collector),
+        HEALTH_HISTORY_SIZE,
+    ));
+    health_monitor.spawn_checks(HEALTH_CHECK_INTERVAL);
+    if let Some(addr) = state.config.metrics_addr {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!("serving plugin metrics on {addr}");
+                tokio::spawn(metrics::serve(
+                    listener,
+                    metrics_collector,
+                    Arc::clone(&health_monitor),
+                ));
+            }
+            Err(err) => warn!("failed to bind metrics address {addr}: {err:?}"),
+        }
+    }
+    if let Some(addr) = state.config.http_addr {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!("serving plugin HTTP routes on {addr}");
+                tokio::spawn(http::serve(listener, Arc::clone(&host_api)));
+            }
+            Err(err) => warn!("failed to bind HTTP address {addr}: {err:?}"),
+        }
+    }
+    host_api.set_health_monitor(health_monitor);
+    tokio::spawn({
+        let host_api = Arc::clone(&host_api);
+        async move {
+            let mut interval = tokio::time::interval(PLAYTIME_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = host_api.flush_playtime() {
+                    warn!("failed to flush playtime: {err:?}");
+                }
+            }
+        }
+    });
+    let _ = state.host_api.set(Arc::clone(&host_api));
+    if let Err(err) = async {
+        plugin_manager.scan_and_load().await?;
+        plugin_manager.initialize_all().await?;
+        plugin_manager.start_all().await
+    }
+    .await
+    {
+        warn!("failed to start plugins: {err:?}");
+    }
+    let hot_reload = HotReloadManager::new(
+        Arc::clone(&plugin_manager),
+        plugin_manager.event_bus(),
+        HotReloadConfig {
+            watch_directories: vec![plugin_manager.plugin_dir().to_path_buf()],
+            ..HotReloadConfig::default()
+        },
+    );
+    match hot_reload {
+        Ok(manager) => match manager.start().await {
+            Ok(()) => host_api.set_hot_reload_manager(manager),
+            Err(err) => warn!("failed to start hot reload: {err:?}"),
+        },
+        Err(err) => warn!("failed to create hot reload manager: {err:?}"),
+    }
+    Some((plugin_manager, host_api))
 }
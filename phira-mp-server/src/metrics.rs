@@ -0,0 +1,202 @@
+//! Serves plugin metrics at `/metrics` in the Prometheus text exposition format.
+
+use phira_mp_plugin::monitoring::{HealthMonitor, HealthStatus, MetricsCollector, PluginMetrics};
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, warn};
+
+/// Largest request head read before answering.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Name, help text and value of a per-plugin gauge.
+type Gauge = (&'static str, &'static str, fn(&PluginMetrics) -> f64);
+
+const GAUGES: [Gauge; 4] = [
+    (
+        "plugin_memory_bytes",
+        "Memory used by the plugin in bytes.",
+        |it| it.memory_usage as f64,
+    ),
+    (
+        "plugin_cpu_percent",
+        "CPU usage of the plugin in percent.",
+        |it| it.cpu_usage as f64,
+    ),
+    (
+        "plugin_error_rate",
+        "Fraction of the plugin's requests that failed.",
+        |it| it.error_rate,
+    ),
+    (
+        "plugin_avg_latency_ms",
+        "Average latency of the plugin's requests in milliseconds.",
+        |it| it.avg_latency_ms,
+    ),
+];
+
+const HEALTH_STATUSES: [(HealthStatus, &str); 4] = [
+    (HealthStatus::Healthy, "healthy"),
+    (HealthStatus::Warning, "warning"),
+    (HealthStatus::Critical, "critical"),
+    (HealthStatus::Unknown, "unknown"),
+];
+
+/// Renders plugin metrics and health, every series labelled with its plugin.
+pub fn render(
+    metrics: &HashMap<String, PluginMetrics>,
+    health: &HashMap<String, HealthStatus>,
+) -> String {
+    let mut plugins: Vec<_> = metrics.iter().collect();
+    plugins.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut out = String::new();
+    for (name, help, value) in GAUGES {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+        for (plugin, metrics) in &plugins {
+            let _ = writeln!(
+                out,
+                "{name}{{plugin=\"{}\"}} {}",
+                escape(plugin),
+                value(metrics)
+            );
+        }
+    }
+
+    let mut statuses: Vec<_> = health.iter().collect();
+    statuses.sort_by(|a, b| a.0.cmp(b.0));
+    out.push_str(
+        "# HELP plugin_health_status Health status of the plugin, 1 for the current one.\n",
+    );
+    out.push_str("# TYPE plugin_health_status gauge\n");
+    for (plugin, status) in statuses {
+        for (candidate, label) in HEALTH_STATUSES {
+            let _ = writeln!(
+                out,
+                "plugin_health_status{{plugin=\"{}\",status=\"{label}\"}} {}",
+                escape(plugin),
+                u8::from(candidate == *status)
+            );
+        }
+    }
+    out
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers scrapes on `listener` until the task is dropped.
+pub async fn serve(
+    listener: TcpListener,
+    collector: Arc<MetricsCollector>,
+    monitor: Arc<HealthMonitor>,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!("failed to accept metrics connection: {err:?}");
+                continue;
+            }
+        };
+        let collector = Arc::clone(&collector);
+        let monitor = Arc::clone(&monitor);
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream, &collector, &monitor).await {
+                debug!("failed to answer metrics request: {err:?}");
+            }
+        });
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    collector: &MetricsCollector,
+    monitor: &HealthMonitor,
+) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|it| it == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            render(&collector.get_all_metrics(), &monitor.check_health()),
+        ),
+        _ => ("404 Not Found", String::new()),
+    };
+    let head = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: text/plain; version=0.0.4\r\n\
+         content-length: {}\r\nconnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phira_mp_plugin::monitoring::HealthThresholds;
+    use std::{collections::HashSet, time::Duration};
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let collector = Arc::new(MetricsCollector::new(1, Duration::ZERO));
+        collector.register_plugin("chat \"bot\"".to_owned());
+        collector.update_memory_usage("chat \"bot\"", 2048);
+        let monitor = Arc::new(HealthMonitor::new(
+            HealthThresholds::default(),
+            Arc::clone(&collector),
+            1,
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, collector, monitor));
+        let response = reqwest::get(format!("http://{addr}/metrics"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let text = response.text().await.unwrap();
+
+        let mut names = HashSet::new();
+        for line in text.lines().filter(|it| !it.starts_with('#')) {
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            value.parse::<f64>().unwrap();
+            let (name, labels) = series.split_once('{').unwrap();
+            assert!(labels.starts_with(r#"plugin="chat \"bot\"""#), "{line}");
+            names.insert(name);
+        }
+        for name in [
+            "plugin_memory_bytes",
+            "plugin_cpu_percent",
+            "plugin_error_rate",
+            "plugin_avg_latency_ms",
+            "plugin_health_status",
+        ] {
+            assert!(names.contains(name), "{name} missing from {text}");
+        }
+        assert!(text.contains(r#"plugin_memory_bytes{plugin="chat \"bot\""} 2048"#));
+        let healthy = r#"plugin_health_status{plugin="chat \"bot\"",status="healthy"} 1"#;
+        assert!(text.contains(healthy));
+
+        let response = reqwest::get(format!("http://{addr}/")).await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,134 @@
+//! Exports `tracing` spans to an OpenTelemetry collector using OTLP over HTTP with JSON
+//! encoding.
+
+use anyhow::{Result, bail};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    trace::{Sampler, SdkTracer, SdkTracerProvider},
+};
+use serde::Deserialize;
+use tracing::{Subscriber, warn};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+fn default_sampling_ratio() -> f64 {
+    1.
+}
+
+fn default_service_name() -> String {
+    "phira-mp-server".to_owned()
+}
+
+/// OpenTelemetry span export, disabled unless configured.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OtlpConfig {
+    /// Base URL of the collector's OTLP/HTTP receiver, e.g. `http://localhost:4318`.
+    pub endpoint: String,
+    /// Fraction of traces exported, decided at each root span.
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+impl OtlpConfig {
+    /// The traces path appended to the endpoint, keeping any base path it already has.
+    fn traces_url(&self) -> Result<reqwest::Url> {
+        let url = reqwest::Url::parse(&format!(
+            "{}/v1/traces",
+            self.endpoint.trim_end_matches('/')
+        ))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("unsupported OTLP endpoint scheme: {}", url.scheme());
+        }
+        Ok(url)
+    }
+
+    fn provider(&self) -> Result<SdkTracerProvider> {
+        if !(0. ..=1.).contains(&self.sampling_ratio) {
+            bail!("OTLP sampling ratio must be between 0 and 1");
+        }
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpJson)
+            .with_endpoint(self.traces_url()?.as_str())
+            .build()?;
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                self.sampling_ratio,
+            ))))
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(self.service_name.clone())
+                    .build(),
+            )
+            .build())
+    }
+}
+
+/// Flushes spans still queued for export when dropped.
+pub struct OtlpGuard(SdkTracerProvider);
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.shutdown() {
+            warn!("failed to flush spans: {err:?}");
+        }
+    }
+}
+
+/// Creates the layer exporting closed spans to the collector, along with the guard flushing
+/// them on shutdown.
+pub fn otlp_layer<S>(config: &OtlpConfig) -> Result<(OpenTelemetryLayer<S, SdkTracer>, OtlpGuard)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let provider = config.provider()?;
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        OtlpGuard(provider),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(endpoint: &str, sampling_ratio: f64) -> OtlpConfig {
+        OtlpConfig {
+            endpoint: endpoint.to_owned(),
+            sampling_ratio,
+            service_name: default_service_name(),
+        }
+    }
+
+    #[test]
+    fn test_otlp_config() {
+        assert!(config("not a url", 1.).traces_url().is_err());
+        assert!(config("ftp://localhost", 1.).traces_url().is_err());
+        assert!(config("http://localhost:4318", 1.5).provider().is_err());
+
+        assert_eq!(
+            config("http://localhost:4318", 1.).traces_url().unwrap().as_str(),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            config("https://collector.example/otlp/", 1.)
+                .traces_url()
+                .unwrap()
+                .as_str(),
+            "https://collector.example/otlp/v1/traces"
+        );
+        assert_eq!(
+            config("https://collector.example/otlp", 1.)
+                .traces_url()
+                .unwrap()
+                .as_str(),
+            "https://collector.example/otlp/v1/traces"
+        );
+    }
+}
@@ -0,0 +1,1158 @@
+use crate::{
+    HostLeavePolicy, InternalRoomState, ROOM_MAX_USERS, Room, ServerConfig, ServerState, User,
+};
+use phira_mp_common::{
+    AnnouncementStyle, Capabilities, ChartId, JudgeEvent, Message, PROTOCOL_VERSION, RoomId,
+    SCHEDULED_ANNOUNCEMENT_CHANNEL, ScheduledAnnouncement, ServerCommand,
+};
+use phira_mp_plugin::{
+    Error,
+    api_host::{DeliveryStatus, LiveRoom, NotificationTarget, RoomLock, RoomState, ServerBridge},
+};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Weak, atomic::Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::runtime::Handle;
+use tracing::{debug, info, warn};
+
+/// Connects the plugin host API to the running server.
+pub struct PluginBridge {
+    server: Weak<ServerState>,
+    handle: Handle,
+}
+
+impl PluginBridge {
+    /// Must be called within a tokio runtime.
+    pub fn new(server: &Arc<ServerState>) -> Self {
+        Self {
+            server: Arc::downgrade(server),
+            handle: Handle::current(),
+        }
+    }
+
+    fn server(&self) -> phira_mp_plugin::Result<Arc<ServerState>> {
+        self.server
+            .upgrade()
+            .ok_or_else(|| Error::Api("Server is shut down".to_owned()))
+    }
+
+    /// Waits for `future` from synchronous plugin code, for lookups that must not be skipped
+    /// while a lock is busy.
+    ///
+    /// Must not be called from a current-thread runtime.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        if Handle::try_current().is_ok() {
+            tokio::task::block_in_place(|| self.handle.block_on(future))
+        } else {
+            self.handle.block_on(future)
+        }
+    }
+
+    /// Sends a chat message from the configured sender to the connected ones of `users`,
+    /// returning how many there are.
+    fn send_chat(&self, server: &ServerState, users: Vec<Arc<User>>, content: &str) -> usize {
+        let users: Vec<_> = self.block_on(async {
+            let mut connected = Vec::with_capacity(users.len());
+            for user in users {
+                if is_connected(&user).await {
+                    connected.push(user);
+                }
+            }
+            connected
+        });
+        let reached = users.len();
+        let cmd = ServerCommand::Message(Message::Chat {
+            user: server.config.message_sender,
+            content: content.to_owned(),
+        });
+        self.handle.spawn(async move {
+            for user in users {
+                user.try_send(cmd.clone()).await;
+            }
+        });
+        reached
+    }
+}
+
+impl ServerBridge for PluginBridge {
+    fn push_client_notification(&self, target: &NotificationTarget, channel: &str, data: &str) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let target = target.clone();
+        let cmd = ServerCommand::PluginNotification {
+            channel: channel.to_owned(),
+            data: data.to_owned(),
+        };
+        self.handle.spawn(async move {
+            for user in notification_recipients(&server, &target).await {
+                if user
+                    .capabilities()
+                    .await
+                    .contains(Capabilities::PLUGIN_NOTIFICATION)
+                {
+                    user.try_send(cmd.clone()).await;
+                } else {
+                    debug!(user = user.id, "skipping plugin notification");
+                }
+            }
+        });
+    }
+
+    fn broadcast_announcement(&self, content: &str, style: &AnnouncementStyle) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let content = content.to_owned();
+        let style = style.clone();
+        self.handle.spawn(async move {
+            let reached = server.announce(&content, &style).await;
+            debug!(reached, "sent announcement");
+        });
+    }
+
+    fn set_judge_recording(&self, room_id: &str, enabled: bool) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let Ok(id) = RoomId::try_from(room_id.to_owned()) else {
+            return;
+        };
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room {
+                room.set_judge_recording(enabled).await;
+            }
+        });
+    }
+
+    fn get_game_judges(&self, room_id: &str, round: u32) -> Option<HashMap<u32, Vec<JudgeEvent>>> {
+        let server = self.server.upgrade()?;
+        let id = RoomId::try_from(room_id.to_owned()).ok()?;
+        let judges = server.game_judges(&id, round)?;
+        Some(
+            judges
+                .players
+                .iter()
+                .filter_map(|(player, judges)| Some((u32::try_from(*player).ok()?, judges.clone())))
+                .collect(),
+        )
+    }
+
+    fn set_chat_channel_member(&self, room_id: &str, channel: &str, user_id: u32, joined: bool) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let (Ok(id), Ok(user)) = (RoomId::try_from(room_id.to_owned()), i32::try_from(user_id))
+        else {
+            return;
+        };
+        let channel = channel.to_owned();
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room {
+                let res = if joined {
+                    room.join_channel(user, &channel).await
+                } else {
+                    room.leave_channel(user, &channel).await
+                };
+                if let Err(err) = res {
+                    warn!(
+                        user,
+                        channel, "failed to update channel membership: {err:?}"
+                    );
+                }
+            }
+        });
+    }
+
+    fn clear_room(&self, room_id: &str) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let Ok(id) = RoomId::try_from(room_id.to_owned()) else {
+            return;
+        };
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            match room {
+                Some(room) => room.clear().await,
+                None => warn!(room = id.to_string(), "clearing missing room"),
+            }
+        });
+    }
+
+    fn set_room_persistent(&self, room_id: &str, persistent: bool) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let Ok(id) = RoomId::try_from(room_id.to_owned()) else {
+            return;
+        };
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room {
+                room.persistent.store(persistent, Ordering::SeqCst);
+            }
+        });
+    }
+
+    fn set_room_lobby(&self, room_id: &str, lobby: bool) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let Ok(id) = RoomId::try_from(room_id.to_owned()) else {
+            return;
+        };
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room {
+                room.lobby.store(lobby, Ordering::SeqCst);
+            }
+        });
+    }
+
+    fn set_room_name(&self, room_id: &str, name: &str) -> phira_mp_plugin::Result<()> {
+        let server = self.server()?;
+        let busy = || Error::Api("Room is busy, try again".to_owned());
+        let not_found = || Error::Api(format!("Room {room_id} not found"));
+        let id = RoomId::try_from(room_id.to_owned()).map_err(|_| not_found())?;
+        // Refused rather than blocking while the room is being updated
+        let room = server
+            .rooms
+            .try_read()
+            .map_err(|_| busy())?
+            .get(&id)
+            .map(Arc::clone)
+            .ok_or_else(not_found)?;
+        *room.name.try_write().map_err(|_| busy())? = name.to_owned();
+        let cmd = room.rename_command(name.to_owned());
+        self.handle.spawn(async move {
+            let mut users = room.users().await;
+            users.extend(room.monitors().await);
+            for user in users {
+                if user
+                    .capabilities()
+                    .await
+                    .contains(Capabilities::PLUGIN_NOTIFICATION)
+                {
+                    user.try_send(cmd.clone()).await;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn set_room_lock(
+        &self,
+        room_id: &str,
+        locked: bool,
+        locked_by: Option<u32>,
+    ) -> phira_mp_plugin::Result<()> {
+        let server = self.server()?;
+        let busy = || Error::Api("Server is busy, try again".to_owned());
+        let not_found = || Error::Api(format!("Room {room_id} not found"));
+        let id = RoomId::try_from(room_id.to_owned()).map_err(|_| not_found())?;
+        // Refused rather than blocking while the lists are being updated
+        let room = server
+            .rooms
+            .try_read()
+            .map_err(|_| busy())?
+            .get(&id)
+            .map(Arc::clone)
+            .ok_or_else(not_found)?;
+        let user = match locked_by {
+            Some(user_id) => {
+                let missing = || Error::Api(format!("User {user_id} not found"));
+                let user_id = i32::try_from(user_id).map_err(|_| missing())?;
+                let user = server
+                    .users
+                    .try_read()
+                    .map_err(|_| busy())?
+                    .get(&user_id)
+                    .map(Arc::clone);
+                Some(user.ok_or_else(missing)?)
+            }
+            None => None,
+        };
+        info!(room = room_id, locked, "plugin lock room");
+        room.set_locked(locked, user.as_deref());
+        self.handle.spawn(async move {
+            room.send(Message::LockRoom { lock: locked }).await;
+        });
+        Ok(())
+    }
+
+    fn set_room_cycle(&self, room_id: &str, cycle: bool) -> phira_mp_plugin::Result<()> {
+        let server = self.server()?;
+        let not_found = || Error::Api(format!("Room {room_id} not found"));
+        let id = RoomId::try_from(room_id.to_owned()).map_err(|_| not_found())?;
+        // Refused rather than blocking while the room list is being updated
+        let room = server
+            .rooms
+            .try_read()
+            .map_err(|_| Error::Api("Room list is busy, try again".to_owned()))?
+            .get(&id)
+            .map(Arc::clone)
+            .ok_or_else(not_found)?;
+        info!(room = room_id, cycle, "plugin cycle room");
+        room.cycle.store(cycle, Ordering::SeqCst);
+        self.handle.spawn(async move {
+            room.send(Message::CycleRoom { cycle }).await;
+        });
+        Ok(())
+    }
+
+    fn set_chart_pool(
+        &self,
+        room_id: &str,
+        pool: &[(ChartId, u32)],
+    ) -> phira_mp_plugin::Result<()> {
+        let server = self.server()?;
+        let not_found = || Error::Api(format!("Room {room_id} not found"));
+        let id = RoomId::try_from(room_id.to_owned()).map_err(|_| not_found())?;
+        // Refused rather than blocking while the room list is being updated
+        let room = server
+            .rooms
+            .try_read()
+            .map_err(|_| Error::Api("Room list is busy, try again".to_owned()))?
+            .get(&id)
+            .map(Arc::clone)
+            .ok_or_else(not_found)?;
+        room.set_chart_pool(pool.to_vec());
+        Ok(())
+    }
+
+    fn set_room_cohost(&self, room_id: &str, user_id: u32, cohost: bool) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let (Ok(id), Ok(user)) = (RoomId::try_from(room_id.to_owned()), i32::try_from(user_id))
+        else {
+            return;
+        };
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room
+                && let Err(err) = room.set_cohost(user, cohost).await
+            {
+                warn!(user, cohost, "failed to update co-host: {err:?}");
+            }
+        });
+    }
+
+    fn send_channel_message(&self, room_id: &str, channel: &str, user_id: u32, content: &str) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let (Ok(id), Ok(user)) = (RoomId::try_from(room_id.to_owned()), i32::try_from(user_id))
+        else {
+            return;
+        };
+        let channel = channel.to_owned();
+        let content = content.to_owned();
+        self.handle.spawn(async move {
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            if let Some(room) = room {
+                room.send_to_channel(user, &channel, content).await;
+            }
+        });
+    }
+
+    fn get_server_setting(&self, key: &str) -> Option<Value> {
+        server_setting(&self.server.upgrade()?.config, key)
+    }
+
+    fn get_user_latency(&self, user_id: u32) -> Option<Duration> {
+        let server = self.server.upgrade()?;
+        let id = i32::try_from(user_id).ok()?;
+        // Skipped rather than blocking while the user list is being updated
+        let user = server.users.try_read().ok()?.get(&id).map(Arc::clone)?;
+        user.latency.rtt()
+    }
+
+    fn get_server_stats(&self, window: Duration) -> Vec<Value> {
+        let Some(server) = self.server.upgrade() else {
+            return Vec::new();
+        };
+        server
+            .stats
+            .buckets(window)
+            .into_iter()
+            .filter_map(|it| serde_json::to_value(it).ok())
+            .collect()
+    }
+
+    fn get_session_info(&self, user_id: u32) -> Option<Value> {
+        let server = self.server.upgrade()?;
+        let id = i32::try_from(user_id).ok()?;
+        // Skipped rather than blocking while the user list is being updated
+        let user = server.users.try_read().ok()?.get(&id).map(Arc::clone)?;
+        let session = user.session.try_read().ok()?.as_ref()?.upgrade()?;
+        Some(session_info(
+            &session.id.to_string(),
+            session.version(),
+            session.connected_at,
+            &user,
+        ))
+    }
+
+    fn set_frame_trace(&self, user_id: u32, enabled: bool) -> bool {
+        let Some(server) = self.server.upgrade() else {
+            return false;
+        };
+        let Ok(id) = i32::try_from(user_id) else {
+            return false;
+        };
+        // Skipped rather than blocking while the user list is being updated
+        let Some(user) = server
+            .users
+            .try_read()
+            .ok()
+            .and_then(|it| it.get(&id).map(Arc::clone))
+        else {
+            return false;
+        };
+        let Some(session) = user
+            .session
+            .try_read()
+            .ok()
+            .and_then(|it| it.as_ref()?.upgrade())
+        else {
+            return false;
+        };
+        session.stream.set_trace(enabled);
+        true
+    }
+
+    fn kick_user(&self, user_id: u32) -> bool {
+        let Some(server) = self.server.upgrade() else {
+            return false;
+        };
+        let Ok(id) = i32::try_from(user_id) else {
+            return false;
+        };
+        let Some(user) =
+            self.block_on(async { server.users.read().await.get(&id).map(Arc::clone) })
+        else {
+            return false;
+        };
+        // Spawned, since the caller may hold locks the kick takes
+        self.handle.spawn(async move { user.kick().await });
+        true
+    }
+
+    fn create_room(
+        &self,
+        first_id: u32,
+        max_users: u32,
+        practice: Option<bool>,
+    ) -> phira_mp_plugin::Result<u32> {
+        let server = self.server()?;
+        // Refused rather than blocking while the room list is being updated
+        let mut rooms = server
+            .rooms
+            .try_write()
+            .map_err(|_| Error::Api("Room list is busy, try again".to_owned()))?;
+        if server.at_room_limit(rooms.len()) {
+            return Err(Error::Api("Server is at its room limit".to_owned()));
+        }
+        let (room_id, id) = (first_id..)
+            .map_while(|room_id| Some((room_id, RoomId::try_from(room_id.to_string()).ok()?)))
+            .find(|(_, id)| !rooms.contains_key(id))
+            .ok_or_else(|| Error::Api("No free room ID".to_owned()))?;
+        let room = Arc::new(Room::new(id.clone(), Weak::new()));
+        room.max_users.store(max_users as usize, Ordering::SeqCst);
+        let practice = practice.unwrap_or(server.config.solo_practice);
+        room.practice.store(practice, Ordering::SeqCst);
+        rooms.insert(id.clone(), room);
+        drop(rooms);
+        server.stats.room_created();
+        info!(
+            room = id.to_string(),
+            max_users, practice, "plugin create room"
+        );
+        Ok(room_id)
+    }
+
+    fn get_rooms(&self) -> phira_mp_plugin::Result<Vec<LiveRoom>> {
+        let server = self.server()?;
+        let busy = || Error::Api("Room list is busy, try again".to_owned());
+        // Refused rather than blocking while the rooms are being updated
+        let rooms: Vec<_> = server
+            .rooms
+            .try_read()
+            .map_err(|_| busy())?
+            .values()
+            .cloned()
+            .collect();
+        rooms
+            .iter()
+            .map(|room| live_room(room).ok_or_else(busy))
+            .collect()
+    }
+
+    fn send_message_to_user(&self, user_id: u32, content: &str) -> Option<DeliveryStatus> {
+        let server = self.server.upgrade()?;
+        let id = i32::try_from(user_id).ok()?;
+        let cmd = ServerCommand::Message(Message::Chat {
+            user: server.config.message_sender,
+            content: content.to_owned(),
+        });
+        let (user, connected) = self.block_on(async {
+            let user = server.users.read().await.get(&id).map(Arc::clone)?;
+            let connected = is_connected(&user).await;
+            Some((user, connected))
+        })?;
+        if !connected {
+            return Some(DeliveryStatus::UserOffline);
+        }
+        self.handle.spawn(async move { user.try_send(cmd).await });
+        Some(DeliveryStatus::Delivered)
+    }
+
+    fn broadcast_message(
+        &self,
+        room_id: Option<&str>,
+        content: &str,
+    ) -> phira_mp_plugin::Result<usize> {
+        let server = self.server()?;
+        let busy = || Error::Api("Server is busy, try again".to_owned());
+        // Refused rather than blocking while the lists are being updated
+        let users = match room_id {
+            None => server
+                .users
+                .try_read()
+                .map_err(|_| busy())?
+                .values()
+                .cloned()
+                .collect(),
+            Some(room_id) => {
+                let not_found = || Error::Api(format!("Room {room_id} not found"));
+                let id = RoomId::try_from(room_id.to_owned()).map_err(|_| not_found())?;
+                let room = server
+                    .rooms
+                    .try_read()
+                    .map_err(|_| busy())?
+                    .get(&id)
+                    .map(Arc::clone)
+                    .ok_or_else(not_found)?;
+                room.try_members().ok_or_else(busy)?
+            }
+        };
+        Ok(self.send_chat(&server, users, content))
+    }
+
+    fn broadcast_message_to_rooms(
+        &self,
+        content: &str,
+        include_playing: bool,
+    ) -> phira_mp_plugin::Result<usize> {
+        let server = self.server()?;
+        let busy = || Error::Api("Server is busy, try again".to_owned());
+        let rooms: Vec<_> = server
+            .rooms
+            .try_read()
+            .map_err(|_| busy())?
+            .values()
+            .cloned()
+            .collect();
+        let mut users = Vec::new();
+        for room in rooms {
+            // A room whose state is being updated may be starting a game
+            let playing = room
+                .state
+                .try_read()
+                .map_or(true, |it| matches!(*it, InternalRoomState::Playing { .. }));
+            if playing && !include_playing {
+                continue;
+            }
+            users.extend(room.try_members().ok_or_else(busy)?);
+        }
+        Ok(self.send_chat(&server, users, content))
+    }
+
+    fn shutdown(&self, grace: Duration) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        self.handle
+            .spawn(async move { server.shutdown(grace).await });
+    }
+
+    fn set_scheduled_announcement(&self, announcement: Option<&ScheduledAnnouncement>) {
+        let Some(server) = self.server.upgrade() else {
+            return;
+        };
+        let data = server.set_scheduled_announcement(announcement.cloned());
+        self.push_client_notification(
+            &NotificationTarget::All,
+            SCHEDULED_ANNOUNCEMENT_CHANNEL,
+            &data,
+        );
+    }
+}
+
+/// Technical details of a user's connection exposed to plugins and the console.
+pub fn session_info(session_id: &str, version: u8, connected_at: SystemTime, user: &User) -> Value {
+    let room = user
+        .room
+        .try_read()
+        .ok()
+        .and_then(|it| it.as_ref().map(|room| room.id.to_string()));
+    json!({
+        "user_id": user.id,
+        "session_id": session_id,
+        "protocol_version": version,
+        "connected_at": connected_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        "latency_ms": user.latency.rtt().map(|it| it.as_millis() as u64),
+        "monitor": user.monitor.load(Ordering::SeqCst),
+        "room": room,
+    })
+}
+
+/// A room as seen by plugins, `None` while any part of it is being updated.
+fn live_room(room: &Room) -> Option<LiveRoom> {
+    let user_ids: Vec<u32> = room
+        .try_users()?
+        .iter()
+        .filter_map(|it| u32::try_from(it.id).ok())
+        .collect();
+    let state = match &*room.state.try_read().ok()? {
+        InternalRoomState::SelectChart => RoomState::SelectingChart,
+        InternalRoomState::WaitForReady { .. } => RoomState::WaitingForReady,
+        InternalRoomState::Playing { .. } => RoomState::Playing,
+    };
+    Some(LiveRoom {
+        id: room.id.to_string(),
+        name: room.name.try_read().ok()?.clone(),
+        host_id: room
+            .host
+            .try_read()
+            .ok()?
+            .upgrade()
+            .and_then(|it| u32::try_from(it.id).ok())
+            .unwrap_or(0),
+        playing_user_ids: if state == RoomState::Playing {
+            user_ids.clone()
+        } else {
+            Vec::new()
+        },
+        user_ids,
+        max_users: room.max_users.load(Ordering::SeqCst) as u32,
+        locked: room.is_locked(),
+        lock: room.lock_info().map(|lock| RoomLock {
+            user_id: lock.user.and_then(|(id, _)| u32::try_from(id).ok()),
+            locked_at: lock.at,
+        }),
+        cycle: room.is_cycle(),
+        chart_id: room.chart.try_read().ok()?.as_ref().map(|it| it.id),
+        chart_pool: room.chart_pool(),
+        state,
+    })
+}
+
+/// Whether a user has a live session.
+async fn is_connected(user: &User) -> bool {
+    user.session
+        .read()
+        .await
+        .as_ref()
+        .is_some_and(|it| it.strong_count() > 0)
+}
+
+/// Value of a server setting exposed to plugins.
+pub fn server_setting(config: &ServerConfig, key: &str) -> Option<Value> {
+    Some(match key {
+        "max_room_users" => json!(ROOM_MAX_USERS),
+        "monitors" => json!(config.monitors),
+        "host_leave_policy" => json!(match config.host_leave_policy {
+            HostLeavePolicy::PromoteHost => "promote_host",
+            HostLeavePolicy::EndGame => "end_game",
+        }),
+        "protocol_version" => json!(PROTOCOL_VERSION),
+        "otlp_enabled" => json!(config.otlp.is_some()),
+        "max_rooms" => json!(config.max_rooms),
+        _ => return None,
+    })
+}
+
+/// Online users addressed by `target`, including room monitors.
+pub async fn notification_recipients(
+    server: &ServerState,
+    target: &NotificationTarget,
+) -> Vec<Arc<User>> {
+    match target {
+        NotificationTarget::All => server.users.read().await.values().cloned().collect(),
+        NotificationTarget::Room(id) => {
+            let Ok(id) = RoomId::try_from(id.clone()) else {
+                return Vec::new();
+            };
+            let room = server.rooms.read().await.get(&id).map(Arc::clone);
+            match room {
+                Some(room) => {
+                    let mut users = room.users().await;
+                    users.extend(room.monitors().await);
+                    users
+                }
+                None => Vec::new(),
+            }
+        }
+        NotificationTarget::User(id) => {
+            let Ok(id) = i32::try_from(*id) else {
+                return Vec::new();
+            };
+            server
+                .users
+                .read()
+                .await
+                .get(&id)
+                .cloned()
+                .into_iter()
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Room, ServerConfig, l10n::Language};
+    use phira_mp_common::{ROOM_RENAMED_CHANNEL, RoomName};
+    use phira_mp_plugin::api_host::SERVER_SETTINGS;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_session_info() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let user = Arc::new(User::new(
+            7,
+            "user".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        // Known but without a live session
+        server.users.write().await.insert(7, Arc::clone(&user));
+        assert!(PluginBridge::new(&server).get_session_info(7).is_none());
+
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        *user.room.write().await = Some(Arc::new(Room::new(id, Arc::downgrade(&user))));
+        user.monitor.store(true, Ordering::SeqCst);
+        user.latency.ping_sent();
+        user.latency.pong_received();
+
+        let connected_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let info = session_info("session", PROTOCOL_VERSION, connected_at, &user);
+        assert_eq!(info["user_id"], 7);
+        assert_eq!(info["session_id"], "session");
+        assert_eq!(info["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(info["connected_at"], 1_700_000_000);
+        assert!(info["latency_ms"].is_u64());
+        assert_eq!(info["monitor"], true);
+        assert_eq!(info["room"], "room");
+    }
+
+    #[tokio::test]
+    async fn test_notification_recipients() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let users: Vec<_> = (1..=3)
+            .map(|id| {
+                Arc::new(User::new(
+                    id,
+                    format!("user{id}"),
+                    Language::default(),
+                    Arc::clone(&server),
+                ))
+            })
+            .collect();
+        for user in &users {
+            server.users.write().await.insert(user.id, Arc::clone(user));
+        }
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&users[0])));
+        assert!(room.add_user(Arc::downgrade(&users[1]), false).await);
+        server.rooms.write().await.insert(id, room);
+
+        let ids = |users: Vec<Arc<User>>| {
+            let mut ids: Vec<i32> = users.iter().map(|it| it.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(
+            ids(notification_recipients(&server, &NotificationTarget::All).await),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            ids(
+                notification_recipients(&server, &NotificationTarget::Room("room".to_owned()))
+                    .await
+            ),
+            vec![1, 2]
+        );
+        assert_eq!(
+            ids(notification_recipients(&server, &NotificationTarget::User(3)).await),
+            vec![3]
+        );
+        assert!(
+            notification_recipients(&server, &NotificationTarget::Room("missing".to_owned()))
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_announcement_on_connect() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let caps = Capabilities::from_version(PROTOCOL_VERSION);
+        assert!(server.scheduled_announcement_command(caps).is_none());
+
+        let announcement = ScheduledAnnouncement {
+            title: "Weekly cup".to_owned(),
+            starts_at: 1_700_000_000,
+        };
+        let bridge = PluginBridge::new(&server);
+        bridge.set_scheduled_announcement(Some(&announcement));
+        let Some(ServerCommand::PluginNotification { channel, data }) =
+            server.scheduled_announcement_command(caps)
+        else {
+            panic!("announcement not sent on connect");
+        };
+        assert_eq!(channel, SCHEDULED_ANNOUNCEMENT_CHANNEL);
+        assert_eq!(
+            ScheduledAnnouncement::from_notification(&data).unwrap(),
+            Some(announcement)
+        );
+        // Too old to decode plugin notifications
+        assert!(
+            server
+                .scheduled_announcement_command(Capabilities::from_version(2))
+                .is_none()
+        );
+
+        bridge.set_scheduled_announcement(None);
+        assert!(server.scheduled_announcement_command(caps).is_none());
+    }
+
+    #[test]
+    fn test_server_settings() {
+        let config = ServerConfig::default();
+        for key in SERVER_SETTINGS {
+            assert!(
+                server_setting(&config, key).is_some(),
+                "{key} is not exposed"
+            );
+        }
+        assert_eq!(server_setting(&config, "monitors"), Some(json!([2])));
+        assert_eq!(server_setting(&config, "otlp_enabled"), Some(json!(false)));
+        assert_eq!(server_setting(&config, "otlp"), None);
+    }
+
+    #[tokio::test]
+    async fn test_create_room() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig {
+                max_rooms: Some(3),
+                ..ServerConfig::default()
+            },
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+
+        // A player already took the first numeric ID
+        let user = Arc::new(User::new(
+            1,
+            "user".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let taken: RoomId = "1".to_owned().try_into().unwrap();
+        server.rooms.write().await.insert(
+            taken.clone(),
+            Arc::new(Room::new(taken, Arc::downgrade(&user))),
+        );
+
+        let room_id = host_api.create_room(1).unwrap();
+        assert_eq!(room_id, 2);
+        assert_eq!(host_api.get_room_info(room_id).unwrap()["max_users"], 1);
+        let id: RoomId = "2".to_owned().try_into().unwrap();
+        let room = server.rooms.read().await.get(&id).map(Arc::clone).unwrap();
+        assert!(room.users().await.is_empty());
+        assert_eq!(room.max_users.load(Ordering::SeqCst), 1);
+        assert!(!room.is_practice());
+
+        // The first player to join becomes host, and fills the room
+        assert!(room.add_user(Arc::downgrade(&user), false).await);
+        assert!(room.claim_host(&user).await);
+        assert!(room.check_host(&user).await.is_ok());
+        let other = Arc::new(User::new(
+            2,
+            "other".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        assert!(!room.add_user(Arc::downgrade(&other), false).await);
+        assert!(!room.claim_host(&other).await);
+
+        assert_eq!(host_api.create_practice_room(1).unwrap(), 3);
+        let id: RoomId = "3".to_owned().try_into().unwrap();
+        assert!(server.rooms.read().await[&id].is_practice());
+
+        assert!(host_api.create_room(4).is_err());
+        assert_eq!(server.rooms.read().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_room_template() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+        let user = Arc::new(User::new(
+            7,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "3".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        *room.name.write().await = "Weekend cup".to_owned();
+        room.max_users.store(6, Ordering::SeqCst);
+        room.set_locked(true, Some(&user));
+        room.cycle.store(true, Ordering::SeqCst);
+        room.set_chart_pool(vec![(ChartId::try_from(1).unwrap(), 2)]);
+        server.rooms.write().await.insert(id, room);
+
+        let template = host_api.export_room_template(3).unwrap();
+        let room_id = host_api.create_room_from_template(&template).unwrap();
+        let id: RoomId = room_id.to_string().try_into().unwrap();
+        let created = server.rooms.read().await.get(&id).map(Arc::clone).unwrap();
+        assert_eq!(*created.name.read().await, "Weekend cup");
+        assert_eq!(created.max_users.load(Ordering::SeqCst), 6);
+        assert!(created.is_locked() && created.is_cycle());
+        assert_eq!(
+            created.chart_pool(),
+            vec![(ChartId::try_from(1).unwrap(), 2)]
+        );
+        assert!(created.users().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rooms_are_read_from_server() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+
+        let user = Arc::new(User::new(
+            7,
+            "user".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "arena".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        *room.chart.write().await = Some(crate::Chart {
+            id: phira_mp_common::ChartId::try_from(42i64).unwrap(),
+            name: "chart".to_owned(),
+        });
+        *room.state.write().await = InternalRoomState::Playing {
+            results: HashMap::new(),
+            aborted: Default::default(),
+        };
+        server.rooms.write().await.insert(id, room);
+
+        assert_eq!(
+            host_api.get_active_charts().unwrap(),
+            json!([{ "chart_id": 42, "room_id": "arena", "player_count": 1 }])
+        );
+        let rooms = host_api.get_room_list().unwrap();
+        assert_eq!(rooms[0]["host_id"], 7);
+        assert_eq!(rooms[0]["state"], "PLAYING");
+    }
+
+    #[tokio::test]
+    async fn test_rename_room() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+        let id: RoomId = "3".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Weak::new()));
+        server.rooms.write().await.insert(id, Arc::clone(&room));
+        let caps = Capabilities::from_version(PROTOCOL_VERSION);
+        assert!(room.name_command(caps).await.is_none());
+
+        host_api.set_room_name(3, "Weekly cup").unwrap();
+        assert_eq!(*room.name.read().await, "Weekly cup");
+        assert_eq!(host_api.get_room_info(3).unwrap()["name"], "Weekly cup");
+        // Users joining later are told the name
+        let Some(ServerCommand::PluginNotification { channel, data }) =
+            room.name_command(caps).await
+        else {
+            panic!("name not sent on join");
+        };
+        assert_eq!(channel, ROOM_RENAMED_CHANNEL);
+        assert_eq!(
+            RoomName::from_notification(&data).unwrap(),
+            RoomName {
+                room_id: "3".to_owned(),
+                name: "Weekly cup".to_owned(),
+            }
+        );
+        assert!(
+            room.name_command(Capabilities::from_version(2))
+                .await
+                .is_none()
+        );
+        assert!(host_api.set_room_name(4, "missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chart_pool() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+        let user = Arc::new(User::new(
+            7,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "3".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        server.rooms.write().await.insert(id, Arc::clone(&room));
+        let chart = |id: i32| ChartId::try_from(id).unwrap();
+        let validate = |id| {
+            crate::l10n::LANGUAGE.sync_scope(Arc::new(Language::default()), || {
+                room.validate_chart(&user, chart(id))
+            })
+        };
+        assert!(room.pick_pool_chart().is_none());
+        validate(5).unwrap();
+
+        host_api
+            .replace_chart_pool(3, vec![(chart(1), 1), (chart(2), 0)])
+            .unwrap_err();
+        host_api.replace_chart_pool(3, vec![(chart(1), 2)]).unwrap();
+        assert_eq!(room.chart_pool(), vec![(chart(1), 2)]);
+        assert_eq!(
+            host_api.get_room_info(3).unwrap()["chart_pool"],
+            json!([{ "chart_id": 1, "weight": 2 }])
+        );
+        // Only pool charts are selected, by hand or after a round
+        assert_eq!(room.pick_pool_chart(), Some(chart(1)));
+        validate(1).unwrap();
+        assert_eq!(
+            validate(5).unwrap_err().to_string(),
+            "The chart is not in the room's chart pool"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lock_room() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+        let user = Arc::new(User::new(
+            7,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        server.users.write().await.insert(7, Arc::clone(&user));
+        let id: RoomId = "3".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        server.rooms.write().await.insert(id, Arc::clone(&room));
+
+        // Locked by the console, seen by both the server and plugins
+        host_api.set_room_lock(3, true, None).unwrap();
+        assert!(room.is_locked());
+        assert!(room.lock_info().unwrap().user.is_none());
+        assert_eq!(host_api.get_room_info(3).unwrap()["locked"], true);
+
+        host_api.set_room_lock(3, true, Some(7)).unwrap();
+        assert_eq!(room.lock_info().unwrap().user, Some((7, "host".to_owned())));
+        assert_eq!(host_api.get_room_info(3).unwrap()["locked_by"], 7);
+        assert!(host_api.set_room_lock(3, true, Some(8)).is_err());
+
+        host_api.set_room_lock(3, false, None).unwrap();
+        assert!(!room.is_locked());
+        assert_eq!(host_api.get_room_info(3).unwrap()["locked"], false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(PluginBridge::new(&server)));
+        let notified = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counter = Arc::clone(&notified);
+        host_api
+            .subscribe_event(
+                phira_mp_plugin::event_system::predefined::SERVER_SHUTDOWN,
+                Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }),
+                "test",
+            )
+            .unwrap();
+        assert!(server.host_api.set(Arc::clone(&host_api)).is_ok());
+
+        host_api.shutdown_server(Duration::from_secs(5)).unwrap();
+        server.shutting_down.wait().await;
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        assert!(!server.stopped.initialized());
+        assert_eq!(notified.load(Ordering::SeqCst), 0);
+
+        // Shutting down again does not restart the grace period
+        host_api.shutdown_server(Duration::from_secs(60)).unwrap();
+        tokio::time::timeout(Duration::from_secs(2), server.stopped.wait())
+            .await
+            .unwrap();
+        assert_eq!(notified.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,19 +1,53 @@
-use crate::{Chart, Record, User};
+use crate::{Chart, HostLeavePolicy, LobbyConfig, Record, SYSTEM_SENDER, ServerState, User, tl};
 use anyhow::{Result, bail};
-use phira_mp_common::{ClientRoomState, Message, RoomId, RoomState, ServerCommand};
+use phira_mp_common::{
+    Capabilities, ChartId, ClientRoomState, DEFAULT_CHAT_CHANNEL, JudgeEvent, Message,
+    ROOM_RENAMED_CHANNEL, ResultRow, RoomId, RoomName, RoomState, RoomStatePatch, ServerCommand,
+};
+use phira_mp_plugin::{
+    chart_validator::ChartSelectionDecision,
+    player_input::{PlayerInput, PlayerInputFrames},
+};
 use rand::seq::IndexedRandom;
 use std::{
     collections::{HashMap, HashSet},
-    ops::Deref,
+    ops::{Deref, DerefMut},
     sync::{
         Arc, Weak,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
     },
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tracing::{debug, info, trace, warn};
 
-const ROOM_MAX_USERS: usize = 8;
+pub const ROOM_MAX_USERS: usize = 8;
+/// Maximum named chat channels in a room.
+const MAX_CHAT_CHANNELS: usize = 16;
+/// Maximum judge events buffered for a single round.
+const MAX_BUFFERED_JUDGES: usize = 200_000;
+
+/// Judge timeline of a finished round, by player.
+#[derive(Debug)]
+pub struct GameJudges {
+    pub room: RoomId,
+    pub round: u32,
+    pub players: HashMap<i32, Vec<JudgeEvent>>,
+}
+
+#[derive(Default)]
+struct JudgeBuffer {
+    players: HashMap<i32, Vec<JudgeEvent>>,
+    len: usize,
+    truncated: bool,
+}
+
+/// Outcome of the last finished round, kept for late record submissions.
+struct FinishedRound {
+    ended: Instant,
+    results: HashMap<i32, Record>,
+    aborted: HashSet<i32>,
+}
 
 #[derive(Default, Debug)]
 pub enum InternalRoomState {
@@ -29,7 +63,7 @@ pub enum InternalRoomState {
 }
 
 impl InternalRoomState {
-    pub fn to_client(&self, chart: Option<i32>) -> RoomState {
+    pub fn to_client(&self, chart: Option<ChartId>) -> RoomState {
         match self {
             Self::SelectChart => RoomState::SelectChart(chart),
             Self::WaitForReady { .. } => RoomState::WaitingForReady,
@@ -38,34 +72,98 @@ impl InternalRoomState {
     }
 }
 
+/// Who locked a room and when.
+#[derive(Debug, Clone)]
+pub struct RoomLock {
+    /// ID and name of the user who locked the room, `None` for the console or a plugin.
+    pub user: Option<(i32, String)>,
+    pub at: SystemTime,
+}
+
 pub struct Room {
     pub id: RoomId,
+    /// Display name, the ID unless renamed.
+    pub name: RwLock<String>,
     pub host: RwLock<Weak<User>>,
+    /// Users granted chart selection, locking and starting alongside the host.
+    cohosts: RwLock<HashSet<i32>>,
     pub state: RwLock<InternalRoomState>,
 
     pub live: AtomicBool,
     pub locked: AtomicBool,
+    /// Set by [`Self::set_locked`] while locked.
+    lock: parking_lot::Mutex<Option<RoomLock>>,
     pub cycle: AtomicBool,
+    /// Whether the room is kept when its last user leaves.
+    pub persistent: AtomicBool,
+    /// Whether the room is a lobby, kept and reset to the configured [`LobbyConfig`] when its
+    /// last user leaves.
+    pub lobby: AtomicBool,
+    /// Whether a lone player starts playing without the ready handshake.
+    pub practice: AtomicBool,
+    /// Most players admitted at once, monitors aside.
+    pub max_users: AtomicUsize,
 
     users: RwLock<Vec<Weak<User>>>,
     monitors: RwLock<Vec<Weak<User>>>,
     pub chart: RwLock<Option<Chart>>,
+    /// Weighted charts the room is limited to, a new one drawn after each round; empty for any.
+    chart_pool: parking_lot::Mutex<Vec<(ChartId, u32)>>,
+
+    /// Room-wide state as of the last [`Self::on_state_change`], base of the next patch
+    last_snapshot: RwLock<ClientRoomState>,
+
+    /// Whether judge timelines are kept after each round (opt-in due to size).
+    pub record_judges: AtomicBool,
+    judges: RwLock<JudgeBuffer>,
+    /// Number of finished rounds.
+    rounds: AtomicU32,
+    last_round: RwLock<Option<FinishedRound>>,
+
+    /// Members of named chat channels other than [`DEFAULT_CHAT_CHANNEL`].
+    channels: RwLock<HashMap<String, HashSet<i32>>>,
 }
 
 impl Room {
     pub fn new(id: RoomId, host: Weak<User>) -> Self {
         Self {
-            id,
+            id: id.clone(),
+            name: RwLock::new(id.to_string()),
             host: host.clone().into(),
+            cohosts: RwLock::default(),
             state: RwLock::default(),
 
             live: AtomicBool::new(false),
             locked: AtomicBool::new(false),
+            lock: parking_lot::Mutex::default(),
             cycle: AtomicBool::new(false),
+            persistent: AtomicBool::new(false),
+            lobby: AtomicBool::new(false),
+            practice: AtomicBool::new(false),
+            max_users: AtomicUsize::new(ROOM_MAX_USERS),
 
             users: vec![host].into(),
             monitors: Vec::new().into(),
             chart: RwLock::default(),
+            chart_pool: parking_lot::Mutex::default(),
+
+            last_snapshot: RwLock::new(ClientRoomState {
+                id: id.clone(),
+                state: RoomState::default(),
+                live: false,
+                locked: false,
+                cycle: false,
+                is_host: false,
+                is_ready: false,
+                users: HashMap::new(),
+            }),
+
+            record_judges: AtomicBool::new(false),
+            judges: RwLock::default(),
+            rounds: AtomicU32::new(0),
+            last_round: RwLock::default(),
+
+            channels: RwLock::default(),
         }
     }
 
@@ -77,10 +175,99 @@ impl Room {
         self.locked.load(Ordering::SeqCst)
     }
 
+    /// Locks or unlocks the room, recording `user` as the one who locked it.
+    pub fn set_locked(&self, lock: bool, user: Option<&User>) {
+        self.locked.store(lock, Ordering::SeqCst);
+        *self.lock.lock() = lock.then(|| RoomLock {
+            user: user.map(|user| (user.id, user.name.clone())),
+            at: SystemTime::now(),
+        });
+    }
+
+    pub fn lock_info(&self) -> Option<RoomLock> {
+        self.lock.lock().clone()
+    }
+
+    /// Reason shown to users refused by the lock.
+    pub fn locked_reason(&self) -> String {
+        match self.lock_info() {
+            Some(RoomLock {
+                user: Some((id, name)),
+                at,
+            }) => {
+                let minutes = at.elapsed().unwrap_or_default().as_secs() / 60;
+                tl!("join-room-locked-by", "name" => name, "id" => id, "minutes" => minutes)
+            }
+            _ => tl!("join-room-locked").into_owned(),
+        }
+    }
+
     pub fn is_cycle(&self) -> bool {
         self.cycle.load(Ordering::SeqCst)
     }
 
+    pub fn is_persistent(&self) -> bool {
+        self.persistent.load(Ordering::SeqCst)
+    }
+
+    pub fn is_lobby(&self) -> bool {
+        self.lobby.load(Ordering::SeqCst)
+    }
+
+    pub fn is_practice(&self) -> bool {
+        self.practice.load(Ordering::SeqCst)
+    }
+
+    /// Number of finished rounds, the last of which is the current round number.
+    pub fn rounds(&self) -> u32 {
+        self.rounds.load(Ordering::SeqCst)
+    }
+
+    /// Enables judge recording, making the room live so that players stream judges.
+    pub async fn set_judge_recording(&self, enabled: bool) {
+        self.record_judges.store(enabled, Ordering::SeqCst);
+        if enabled && !self.live.fetch_or(true, Ordering::SeqCst) {
+            info!(room = self.id.to_string(), "room goes live");
+            self.on_state_change().await;
+        }
+    }
+
+    /// Buffers judge events of a player for the current round, if recording.
+    pub async fn buffer_judges(&self, player: i32, judges: &[JudgeEvent]) {
+        if !self.record_judges.load(Ordering::SeqCst)
+            || !matches!(*self.state.read().await, InternalRoomState::Playing { .. })
+        {
+            return;
+        }
+        let mut buffer = self.judges.write().await;
+        let take = judges.len().min(MAX_BUFFERED_JUDGES - buffer.len);
+        if take < judges.len() && !buffer.truncated {
+            warn!(room = self.id.to_string(), "judge buffer full, truncating");
+            buffer.truncated = true;
+        }
+        if take > 0 {
+            buffer
+                .players
+                .entry(player)
+                .or_default()
+                .extend_from_slice(&judges[..take]);
+            buffer.len += take;
+        }
+    }
+
+    /// Ends the current round, returning its judge timeline if recorded.
+    async fn finish_round(&self) -> Option<GameJudges> {
+        let round = self.rounds.fetch_add(1, Ordering::SeqCst) + 1;
+        let buffer = std::mem::take(&mut *self.judges.write().await);
+        self.record_judges
+            .load(Ordering::SeqCst)
+            .then(|| GameJudges {
+                room: self.id.clone(),
+                round,
+                players: buffer.players,
+            })
+    }
+
     pub async fn client_room_state(&self) -> RoomState {
         self.state
             .read()
@@ -89,14 +276,43 @@ impl Room {
     }
 
     pub async fn client_state(&self, user: &User) -> ClientRoomState {
+        ClientRoomState {
+            is_host: self.check_host(user).await.is_ok(),
+            is_ready: matches!(&*self.state.read().await, InternalRoomState::WaitForReady { started } if started.contains(&user.id)),
+            ..self.snapshot().await
+        }
+    }
+
+    /// Notification of the display name for a client joining the room, if renamed.
+    pub async fn name_command(&self, caps: Capabilities) -> Option<ServerCommand> {
+        if !caps.contains(Capabilities::PLUGIN_NOTIFICATION) {
+            return None;
+        }
+        let name = self.name.read().await.clone();
+        (name != self.id.to_string()).then(|| self.rename_command(name))
+    }
+
+    pub fn rename_command(&self, name: String) -> ServerCommand {
+        ServerCommand::PluginNotification {
+            channel: ROOM_RENAMED_CHANNEL.to_owned(),
+            data: RoomName {
+                room_id: self.id.to_string(),
+                name,
+            }
+            .to_notification(),
+        }
+    }
+
+    /// Client room state without the per-client fields.
+    async fn snapshot(&self) -> ClientRoomState {
         ClientRoomState {
             id: self.id.clone(),
             state: self.client_room_state().await,
             live: self.is_live(),
             locked: self.is_locked(),
             cycle: self.is_cycle(),
-            is_host: self.check_host(user).await.is_ok(),
-            is_ready: matches!(&*self.state.read().await, InternalRoomState::WaitForReady { started } if started.contains(&user.id)),
+            is_host: false,
+            is_ready: false,
             users: self
                 .users
                 .read()
@@ -108,9 +324,34 @@ impl Room {
         }
     }
 
+    /// Patch from the last broadcast snapshot to the current state, which becomes the new
+    /// snapshot.
+    async fn take_state_patch(&self) -> RoomStatePatch {
+        let snapshot = self.snapshot().await;
+        let mut guard = self.last_snapshot.write().await;
+        let patch = RoomStatePatch::diff(&guard, &snapshot);
+        *guard = snapshot;
+        patch
+    }
+
     pub async fn on_state_change(&self) {
-        self.broadcast(ServerCommand::ChangeState(self.client_room_state().await))
-            .await;
+        let patch = self.take_state_patch().await;
+        let state = self.client_room_state().await;
+        for session in self.users().await.into_iter().chain(self.monitors().await) {
+            if session
+                .capabilities()
+                .await
+                .contains(Capabilities::ROOM_STATE_PATCH)
+            {
+                if !patch.is_empty() {
+                    session
+                        .try_send(ServerCommand::RoomStatePatch(patch.clone()))
+                        .await;
+                }
+            } else {
+                session.try_send(ServerCommand::ChangeState(state)).await;
+            }
+        }
     }
 
     pub async fn add_user(&self, user: Weak<User>, monitor: bool) -> bool {
@@ -122,7 +363,7 @@ impl Room {
         } else {
             let mut guard = self.users.write().await;
             guard.retain(|it| it.strong_count() > 0);
-            if guard.len() >= ROOM_MAX_USERS {
+            if guard.len() >= self.max_users.load(Ordering::SeqCst) {
                 false
             } else {
                 guard.push(user);
@@ -149,6 +390,25 @@ impl Room {
             .collect()
     }
 
+    /// Users, monitors aside, `None` while the list is being updated.
+    pub fn try_users(&self) -> Option<Vec<Arc<User>>> {
+        let users = self.users.try_read().ok()?;
+        Some(users.iter().filter_map(Weak::upgrade).collect())
+    }
+
+    /// Users and monitors, `None` while either list is being updated.
+    pub fn try_members(&self) -> Option<Vec<Arc<User>>> {
+        let users = self.users.try_read().ok()?;
+        let monitors = self.monitors.try_read().ok()?;
+        Some(
+            users
+                .iter()
+                .chain(monitors.iter())
+                .filter_map(Weak::upgrade)
+                .collect(),
+        )
+    }
+
     pub async fn check_host(&self, user: &User) -> Result<()> {
         if self.host.read().await.upgrade().map(|it| it.id) != Some(user.id) {
             bail!("only host can do this");
@@ -156,6 +416,124 @@ impl Room {
         Ok(())
     }
 
+    /// Makes `user` the host if the room has none, as when created by the server or left by a
+    /// disconnected host. Returns whether the user became host.
+    pub async fn claim_host(&self, user: &Arc<User>) -> bool {
+        let mut guard = self.host.write().await;
+        if guard.strong_count() > 0 {
+            return false;
+        }
+        *guard = Arc::downgrade(user);
+        drop(guard);
+        debug!(
+            room = self.id.to_string(),
+            user = user.id,
+            "user claims host"
+        );
+        self.send(Message::NewHost { user: user.id }).await;
+        true
+    }
+
+    /// Like [`Self::check_host`], but also accepts co-hosts.
+    pub async fn check_host_or_cohost(&self, user: &User) -> Result<()> {
+        if self.check_host(user).await.is_err() && !self.cohosts.read().await.contains(&user.id) {
+            bail!("only host or co-host can do this");
+        }
+        Ok(())
+    }
+
+    /// Grants or revokes co-host permissions of a member.
+    pub async fn set_cohost(&self, user: i32, cohost: bool) -> Result<()> {
+        if cohost {
+            if !self.users().await.iter().any(|it| it.id == user) {
+                bail!("user is not in the room");
+            }
+            self.cohosts.write().await.insert(user);
+        } else {
+            self.cohosts.write().await.remove(&user);
+        }
+        Ok(())
+    }
+
+    pub fn chart_pool(&self) -> Vec<(ChartId, u32)> {
+        self.chart_pool.lock().clone()
+    }
+
+    pub fn set_chart_pool(&self, pool: Vec<(ChartId, u32)>) {
+        *self.chart_pool.lock() = pool;
+    }
+
+    /// Draws a chart from the pool by weight, if any.
+    pub fn pick_pool_chart(&self) -> Option<ChartId> {
+        self.chart_pool
+            .lock()
+            .choose_weighted(&mut rand::rng(), |(_, weight)| *weight)
+            .ok()
+            .map(|(id, _)| *id)
+    }
+
+    /// Fetches chart `id` and selects it for the room on behalf of `user`.
+    pub async fn select_chart(&self, server: &ServerState, user: i32, id: ChartId) -> Result<()> {
+        trace!("fetch");
+        let res: Chart = reqwest::get(format!("{}/chart/{id}", server.config.api_host))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        debug!("chart is {res:?}");
+        self.send(Message::SelectChart {
+            user,
+            name: res.name.clone(),
+            id: res.id,
+        })
+        .await;
+        *self.chart.write().await = Some(res);
+        self.on_state_change().await;
+        Ok(())
+    }
+
+    /// Asks plugin chart validators whether `user` may select chart `id`, which must be in
+    /// the chart pool if the room has one.
+    pub fn validate_chart(&self, user: &User, id: ChartId) -> Result<()> {
+        {
+            let pool = self.chart_pool.lock();
+            if !pool.is_empty() && !pool.iter().any(|(chart, _)| *chart == id) {
+                bail!(tl!("select-chart-not-in-pool"));
+            }
+        }
+        let Some(host_api) = user.server.host_api.get() else {
+            return Ok(());
+        };
+        let Ok(user_id) = u32::try_from(user.id) else {
+            bail!("invalid user");
+        };
+        if let ChartSelectionDecision::Deny(reason) =
+            host_api.validate_chart_selection(&self.id.to_string(), user_id, id)
+        {
+            bail!(reason);
+        }
+        Ok(())
+    }
+
+    /// Forward input frames of a live player to subscribed plugins
+    pub fn forward_input(&self, user: &User, frames: PlayerInputFrames) {
+        let Some(host_api) = user.server.host_api.get() else {
+            return;
+        };
+        let Ok(user_id) = u32::try_from(user.id) else {
+            return;
+        };
+        let room_id = self.id.to_string();
+        if !host_api.has_player_input_subscribers(&room_id) {
+            return;
+        }
+        host_api.dispatch_player_input(&PlayerInput {
+            room_id,
+            user_id,
+            frames,
+        });
+    }
+
     #[inline]
     pub async fn send(&self, msg: Message) {
         self.broadcast(ServerCommand::Message(msg)).await;
@@ -173,6 +551,15 @@ impl Room {
         }
     }
 
+    /// Broadcast to users and monitors whose client has all of `caps`.
+    pub async fn broadcast_capable(&self, caps: Capabilities, cmd: ServerCommand) {
+        for session in self.users().await.into_iter().chain(self.monitors().await) {
+            if session.capabilities().await.contains(caps) {
+                session.try_send(cmd.clone()).await;
+            }
+        }
+    }
+
     pub async fn broadcast_monitors(&self, cmd: ServerCommand) {
         for session in self.monitors().await {
             session.try_send(cmd.clone()).await;
@@ -188,9 +575,90 @@ impl Room {
         .await;
     }
 
+    pub async fn join_channel(&self, user: i32, channel: &str) -> Result<()> {
+        if channel == DEFAULT_CHAT_CHANNEL {
+            return Ok(());
+        }
+        if channel.is_empty() {
+            bail!("invalid channel");
+        }
+        let mut channels = self.channels.write().await;
+        if !channels.contains_key(channel) && channels.len() >= MAX_CHAT_CHANNELS {
+            bail!("too many channels");
+        }
+        channels.entry(channel.to_owned()).or_default().insert(user);
+        Ok(())
+    }
+
+    pub async fn leave_channel(&self, user: i32, channel: &str) -> Result<()> {
+        if channel == DEFAULT_CHAT_CHANNEL {
+            bail!("cannot leave the default channel");
+        }
+        let mut channels = self.channels.write().await;
+        if let Some(members) = channels.get_mut(channel) {
+            members.remove(&user);
+            if members.is_empty() {
+                channels.remove(channel);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn is_in_channel(&self, user: i32, channel: &str) -> bool {
+        channel == DEFAULT_CHAT_CHANNEL
+            || self
+                .channels
+                .read()
+                .await
+                .get(channel)
+                .is_some_and(|it| it.contains(&user))
+    }
+
+    /// Users and monitors receiving messages sent to `channel`.
+    pub async fn channel_recipients(&self, channel: &str) -> Vec<Arc<User>> {
+        let all = self.users().await.into_iter().chain(self.monitors().await);
+        if channel == DEFAULT_CHAT_CHANNEL {
+            return all.collect();
+        }
+        let channels = self.channels.read().await;
+        let Some(members) = channels.get(channel) else {
+            return Vec::new();
+        };
+        all.filter(|it| members.contains(&it.id)).collect()
+    }
+
+    /// Sends a chat message to members of `channel`. Clients unaware of channels receive a
+    /// plain chat message tagged with the channel name.
+    pub async fn send_to_channel(&self, user: i32, channel: &str, content: String) {
+        if channel == DEFAULT_CHAT_CHANNEL {
+            self.send(Message::Chat { user, content }).await;
+            return;
+        }
+        for session in self.channel_recipients(channel).await {
+            let msg = if session
+                .capabilities()
+                .await
+                .contains(Capabilities::CHAT_CHANNELS)
+            {
+                Message::ChannelChat {
+                    user,
+                    channel: channel.to_owned(),
+                    content: content.clone(),
+                }
+            } else {
+                Message::Chat {
+                    user,
+                    content: format!("[{channel}] {content}"),
+                }
+            };
+            session.try_send(ServerCommand::Message(msg)).await;
+        }
+    }
+
     /// Return: should the room be dropped
     #[must_use]
     pub async fn on_user_leave(&self, user: &User) -> bool {
+        user.credit_playtime();
         self.send(Message::LeaveRoom {
             user: user.id,
             name: user.name.clone(),
@@ -205,29 +673,185 @@ impl Room {
         .write()
         .await
         .retain(|it| it.upgrade().is_some_and(|it| it.id != user.id));
+        self.channels.write().await.retain(|_, members| {
+            members.remove(&user.id);
+            !members.is_empty()
+        });
+        self.cohosts.write().await.remove(&user.id);
         if self.check_host(user).await.is_ok() {
             info!("host disconnected!");
             let users = self.users().await;
             if users.is_empty() {
+                if self.is_lobby() {
+                    info!("room users all disconnected, resetting lobby");
+                    self.reset_lobby(&user.server.config.lobby).await;
+                    return false;
+                }
+                if self.is_persistent() {
+                    info!("room users all disconnected, keeping persistent room");
+                    self.reset_state().await;
+                    return false;
+                }
                 info!("room users all disconnected, dropping room");
                 return true;
             } else {
-                let user = users.choose(&mut rand::rng()).unwrap();
-                debug!("selected {} as host", user.id);
-                *self.host.write().await = Arc::downgrade(user);
-                self.send(Message::NewHost { user: user.id }).await;
-                user.try_send(ServerCommand::ChangeHost(true)).await;
+                let new_host = users.choose(&mut rand::rng()).unwrap();
+                debug!("selected {} as host", new_host.id);
+                *self.host.write().await = Arc::downgrade(new_host);
+                self.send(Message::NewHost { user: new_host.id }).await;
+                new_host.try_send(ServerCommand::ChangeHost(true)).await;
+                if user.server.config.host_leave_policy == HostLeavePolicy::EndGame {
+                    self.abort_playing().await;
+                }
             }
         }
         self.check_all_ready().await;
         false
     }
 
+    /// Removes every user and monitor, keeping the room, its settings and its host, who
+    /// regains the role when rejoining.
+    pub async fn clear(&self) {
+        let users: Vec<_> = self
+            .users()
+            .await
+            .into_iter()
+            .chain(self.monitors().await)
+            .collect();
+        info!(
+            room = self.id.to_string(),
+            users = users.len(),
+            "clearing room"
+        );
+        for user in &users {
+            self.send(Message::LeaveRoom {
+                user: user.id,
+                name: user.name.clone(),
+            })
+            .await;
+        }
+        self.users.write().await.clear();
+        self.monitors.write().await.clear();
+        self.channels.write().await.clear();
+        self.cohosts.write().await.clear();
+        for user in &users {
+            *user.room.write().await = None;
+        }
+        self.reset_state().await;
+    }
+
+    /// Returns an emptied room to chart selection, discarding any unfinished game.
+    async fn reset_state(&self) {
+        *self.state.write().await = InternalRoomState::SelectChart;
+        *self.judges.write().await = JudgeBuffer::default();
+        self.on_state_change().await;
+    }
+
+    /// Returns an emptied lobby to its defaults: unlocked, named after its ID, without a
+    /// chart or co-hosts, and in the configured mode.
+    async fn reset_lobby(&self, config: &LobbyConfig) {
+        self.locked.store(false, Ordering::SeqCst);
+        *self.lock.lock() = None;
+        self.cycle.store(config.cycle, Ordering::SeqCst);
+        *self.name.write().await = self.id.to_string();
+        *self.chart.write().await = None;
+        self.cohosts.write().await.clear();
+        self.reset_state().await;
+    }
+
+    /// Mark every player still playing as aborted, so that the game ends on the next
+    /// [`Self::check_all_ready`].
+    async fn abort_playing(&self) {
+        let users = self.users().await;
+        let mut guard = self.state.write().await;
+        let InternalRoomState::Playing { results, aborted } = guard.deref_mut() else {
+            return;
+        };
+        info!(room = self.id.to_string(), "host left, ending game");
+        let newly_aborted: Vec<i32> = users
+            .into_iter()
+            .map(|it| it.id)
+            .filter(|id| !results.contains_key(id) && aborted.insert(*id))
+            .collect();
+        drop(guard);
+        for user in newly_aborted {
+            self.send(Message::Abort { user }).await;
+        }
+    }
+
+    /// Credits the playtime of the last game not yet credited, and resets the game time.
     pub async fn reset_game_time(&self) {
         for user in self.users().await {
-            user.game_time
-                .store(f32::NEG_INFINITY.to_bits(), Ordering::SeqCst);
+            user.credit_playtime();
+        }
+    }
+
+    /// Merges a record arriving after its game ended into the round results, if within
+    /// `grace` of the end and the player had not finished, then resends the results.
+    pub async fn submit_late_record(&self, record: Record, grace: Duration) -> Result<()> {
+        let mut guard = self.last_round.write().await;
+        let Some(round) = guard.as_mut() else {
+            bail!("no game to submit to");
+        };
+        if round.ended.elapsed() > grace {
+            bail!("record submitted too late, the game has ended");
         }
+        let player = record.player;
+        if round.results.contains_key(&player) {
+            bail!("already uploaded");
+        }
+        if !round.aborted.remove(&player) {
+            bail!("not in the last game");
+        }
+        round.results.insert(player, record);
+        let rows = result_rows(&round.results, &round.aborted);
+        drop(guard);
+        info!(
+            room = self.id.to_string(),
+            user = player,
+            "accepted late record"
+        );
+        self.broadcast_capable(
+            Capabilities::GAME_RESULTS,
+            ServerCommand::GameResults {
+                room: self.id.clone(),
+                rows,
+            },
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Starts a game requested by `user`, waiting for the others to be ready.
+    ///
+    /// A lone player of a practice room starts playing right away.
+    pub async fn request_start(&self, user: i32) {
+        self.reset_game_time().await;
+        self.send(Message::GameStart { user }).await;
+        if self.is_practice() && self.users().await.len() == 1 && self.monitors().await.is_empty() {
+            debug!(room = self.id.to_string(), "solo practice, skip ready");
+            self.start_playing().await;
+            return;
+        }
+        debug!(room = self.id.to_string(), "room wait for ready");
+        *self.state.write().await = InternalRoomState::WaitForReady {
+            started: std::iter::once(user).collect(),
+        };
+        self.on_state_change().await;
+        self.check_all_ready().await;
+    }
+
+    async fn start_playing(&self) {
+        info!(room = self.id.to_string(), "game start");
+        self.send(Message::StartPlaying).await;
+        self.reset_game_time().await;
+        *self.judges.write().await = JudgeBuffer::default();
+        *self.last_round.write().await = None;
+        *self.state.write().await = InternalRoomState::Playing {
+            results: HashMap::new(),
+            aborted: HashSet::new(),
+        };
+        self.on_state_change().await;
     }
 
     pub async fn check_all_ready(&self) {
@@ -242,14 +866,7 @@ impl Room {
                     .all(|it| started.contains(&it.id))
                 {
                     drop(guard);
-                    info!(room = self.id.to_string(), "game start");
-                    self.send(Message::StartPlaying).await;
-                    self.reset_game_time().await;
-                    *self.state.write().await = InternalRoomState::Playing {
-                        results: HashMap::new(),
-                        aborted: HashSet::new(),
-                    };
-                    self.on_state_change().await;
+                    self.start_playing().await;
                 }
             }
             InternalRoomState::Playing { results, aborted } => {
@@ -259,8 +876,29 @@ impl Room {
                     .into_iter()
                     .all(|it| results.contains_key(&it.id) || aborted.contains(&it.id))
                 {
+                    let rows = result_rows(results, aborted);
+                    let finished = FinishedRound {
+                        ended: Instant::now(),
+                        results: results.clone(),
+                        aborted: aborted.clone(),
+                    };
                     drop(guard);
-                    // TODO print results
+                    *self.last_round.write().await = Some(finished);
+                    let judges = self.finish_round().await;
+                    if let Some(user) = self.users().await.first() {
+                        user.server.stats.game_completed();
+                        if let Some(judges) = judges {
+                            user.server.archive_judges(judges);
+                        }
+                    }
+                    self.broadcast_capable(
+                        Capabilities::GAME_RESULTS,
+                        ServerCommand::GameResults {
+                            room: self.id.clone(),
+                            rows,
+                        },
+                    )
+                    .await;
                     self.send(Message::GameEnd).await;
                     // dbg!(2);
                     *self.state.write().await = InternalRoomState::SelectChart;
@@ -285,9 +923,718 @@ impl Room {
                         new_host.try_send(ServerCommand::ChangeHost(true)).await;
                     }
                     self.on_state_change().await;
+                    if let Some(id) = self.pick_pool_chart()
+                        && let Some(user) = self.users().await.first().cloned()
+                    {
+                        debug!(
+                            room = self.id.to_string(),
+                            chart = id.get(),
+                            "rotating chart"
+                        );
+                        // Fetched apart, so that a slow chart server does not hold up leaving,
+                        // readying and playing
+                        let server = Arc::clone(&user.server);
+                        let room_id = self.id.clone();
+                        tokio::spawn(async move {
+                            let room = server.rooms.read().await.get(&room_id).map(Arc::clone);
+                            let Some(room) = room else {
+                                return;
+                            };
+                            if let Err(err) = room.select_chart(&server, SYSTEM_SENDER, id).await {
+                                warn!(
+                                    room = room_id.to_string(),
+                                    "failed to rotate chart: {err:?}"
+                                );
+                            }
+                        });
+                    }
                 }
             }
             _ => {}
         }
     }
 }
+
+/// Placements of a finished round: finishers by score then accuracy (ties share a rank),
+/// followed by aborted players.
+pub fn result_rows(results: &HashMap<i32, Record>, aborted: &HashSet<i32>) -> Vec<ResultRow> {
+    let mut records: Vec<&Record> = results.values().collect();
+    records.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.accuracy.total_cmp(&a.accuracy))
+            .then(a.player.cmp(&b.player))
+    });
+    let mut rows: Vec<ResultRow> = Vec::with_capacity(records.len() + aborted.len());
+    for (index, record) in records.into_iter().enumerate() {
+        let rank = match rows.last() {
+            Some(last) if last.score == record.score && last.accuracy == record.accuracy => {
+                last.rank
+            }
+            _ => index as u32 + 1,
+        };
+        rows.push(ResultRow {
+            rank,
+            player: record.player,
+            score: record.score,
+            accuracy: record.accuracy,
+            full_combo: record.full_combo,
+            aborted: false,
+        });
+    }
+    let mut aborted: Vec<i32> = aborted.iter().copied().collect();
+    aborted.sort_unstable();
+    let rank = rows.len() as u32 + 1;
+    rows.extend(aborted.into_iter().map(|player| ResultRow {
+        rank,
+        player,
+        score: 0,
+        accuracy: 0.,
+        full_combo: false,
+        aborted: true,
+    }));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ServerConfig, ServerState, l10n::Language};
+    use tokio::sync::mpsc;
+
+    fn server(config: ServerConfig) -> Arc<ServerState> {
+        Arc::new(ServerState::new(config, mpsc::channel(1).0))
+    }
+
+    fn record(player: i32, score: i32, accuracy: f32, full_combo: bool) -> Record {
+        Record {
+            id: player * 10,
+            player,
+            score,
+            perfect: 0,
+            good: 0,
+            bad: 0,
+            miss: 0,
+            max_combo: 0,
+            accuracy,
+            full_combo,
+            std: 0.,
+            std_score: 0.,
+        }
+    }
+
+    #[test]
+    fn test_result_rows_placements() {
+        let results = [
+            record(1, 900_000, 0.95, false),
+            record(2, 1_000_000, 1.0, true),
+            record(3, 900_000, 0.95, false),
+            record(4, 900_000, 0.97, false),
+        ]
+        .into_iter()
+        .map(|it| (it.player, it))
+        .collect();
+        let aborted = [5].into_iter().collect();
+
+        let rows = result_rows(&results, &aborted);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|it| (it.rank, it.player, it.aborted))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 2, false),
+                (2, 4, false),
+                (3, 1, false),
+                (3, 3, false),
+                (5, 5, true)
+            ]
+        );
+        assert!(rows[0].full_combo);
+    }
+
+    async fn host_leaves_mid_game(policy: HostLeavePolicy) -> (Room, Arc<User>) {
+        let server = server(ServerConfig {
+            host_leave_policy: policy,
+            ..ServerConfig::default()
+        });
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let guest = Arc::new(User::new(
+            2,
+            "guest".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        assert!(room.add_user(Arc::downgrade(&guest), false).await);
+        *room.state.write().await = InternalRoomState::Playing {
+            results: HashMap::new(),
+            aborted: HashSet::new(),
+        };
+
+        assert!(!room.on_user_leave(&host).await);
+        assert_eq!(room.users().await.len(), 1);
+        assert!(room.check_host(&guest).await.is_ok());
+        (room, guest)
+    }
+
+    #[tokio::test]
+    async fn test_host_leave_promotes_host() {
+        let (room, _guest) = host_leaves_mid_game(HostLeavePolicy::PromoteHost).await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::Playing { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_host_leave_ends_game() {
+        let (room, _guest) = host_leaves_mid_game(HostLeavePolicy::EndGame).await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::SelectChart
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_state_patch_contains_changed_fields() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        let mut client = room.client_state(&host).await;
+        room.take_state_patch().await;
+
+        room.locked.store(true, Ordering::SeqCst);
+        let patch = room.take_state_patch().await;
+        assert_eq!(
+            patch,
+            RoomStatePatch {
+                locked: Some(true),
+                ..Default::default()
+            }
+        );
+        assert!(room.take_state_patch().await.is_empty());
+
+        *room.state.write().await = InternalRoomState::WaitForReady {
+            started: HashSet::new(),
+        };
+        let guest = Arc::new(User::new(
+            2,
+            "guest".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        assert!(room.add_user(Arc::downgrade(&guest), false).await);
+        let patch = room.take_state_patch().await;
+        assert_eq!(patch.state, Some(RoomState::WaitingForReady));
+        assert_eq!(patch.users, vec![(2, Some(guest.to_info()))]);
+        assert_eq!(patch.locked, None);
+
+        let expected = room.client_state(&host).await;
+        RoomStatePatch::diff(&client, &expected).apply(&mut client);
+        assert_eq!(client.users, expected.users);
+        assert_eq!(client.state, expected.state);
+        assert!(client.locked);
+    }
+
+    #[tokio::test]
+    async fn test_chart_validator_denies_odd_charts() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        let chart = |id: i32| ChartId::try_from(id).unwrap();
+        assert!(room.validate_chart(&host, chart(3)).is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.register_chart_validator(
+            "even_only",
+            0,
+            Box::new(|selection| {
+                if selection.chart_id.get() % 2 == 1 {
+                    ChartSelectionDecision::Deny("odd charts are banned".to_owned())
+                } else {
+                    ChartSelectionDecision::Allow
+                }
+            }),
+        );
+        assert!(server.host_api.set(host_api).is_ok());
+
+        assert!(room.validate_chart(&host, chart(2)).is_ok());
+        let err = room.validate_chart(&host, chart(3)).unwrap_err();
+        assert_eq!(err.to_string(), "odd charts are banned");
+        assert!(ChartId::try_from(-1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_playtime_credited_once_and_persisted() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        assert!(server.host_api.set(Arc::clone(&host_api)).is_ok());
+
+        host.game_time.store(95.5f32.to_bits(), Ordering::SeqCst);
+        host.credit_playtime();
+        room.reset_game_time().await;
+        assert_eq!(host_api.get_user_playtime(1).unwrap(), 95);
+        assert!(host_api.flush_playtime().unwrap());
+
+        let (_plugin_manager, reopened) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        assert_eq!(reopened.get_user_playtime(1).unwrap(), 95);
+    }
+
+    #[tokio::test]
+    async fn test_judges_recorded_per_round() {
+        let server = server(ServerConfig::default());
+        let users: Vec<_> = (1..=2)
+            .map(|id| {
+                Arc::new(User::new(
+                    id,
+                    format!("user{id}"),
+                    Language::default(),
+                    Arc::clone(&server),
+                ))
+            })
+            .collect();
+        let room = Room::new(
+            "room".to_owned().try_into().unwrap(),
+            Arc::downgrade(&users[0]),
+        );
+        assert!(room.add_user(Arc::downgrade(&users[1]), false).await);
+        room.set_judge_recording(true).await;
+        assert!(room.is_live());
+
+        let judge = |time: f32, note_id: u32| JudgeEvent {
+            time,
+            line_id: 0,
+            note_id,
+            judgement: phira_mp_common::Judgement::Perfect,
+        };
+        // Judges outside of a game are not buffered
+        room.buffer_judges(1, &[judge(0., 0)]).await;
+
+        *room.state.write().await = InternalRoomState::WaitForReady {
+            started: [1, 2].into_iter().collect(),
+        };
+        room.check_all_ready().await;
+        room.buffer_judges(1, &[judge(1., 1), judge(2., 2)]).await;
+        room.buffer_judges(2, &[judge(1.5, 1)]).await;
+        room.buffer_judges(1, &[judge(3., 3)]).await;
+        if let InternalRoomState::Playing { results, .. } = &mut *room.state.write().await {
+            results.insert(1, record(1, 1_000_000, 1.0, true));
+            results.insert(2, record(2, 900_000, 0.9, false));
+        }
+        room.check_all_ready().await;
+
+        assert_eq!(room.rounds(), 1);
+        let id = room.id.clone();
+        let judges = server.game_judges(&id, 1).unwrap();
+        let notes = |player: i32| -> Vec<u32> {
+            judges.players[&player]
+                .iter()
+                .map(|it| it.note_id)
+                .collect()
+        };
+        assert_eq!(notes(1), vec![1, 2, 3]);
+        assert_eq!(notes(2), vec![1]);
+        assert!(server.game_judges(&id, 2).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_game_end_sends_results() {
+        use crate::session::tests::{MockAuth, authenticate_alice, connect};
+
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let (session, mut resp_rx, client) = connect(&server, authenticate_alice()).await;
+        let session = session.unwrap();
+        assert!(session.capabilities().contains(Capabilities::GAME_RESULTS));
+        // The session is attached to the user once authenticated
+        while !matches!(resp_rx.recv().await, Some(ServerCommand::Authenticate(_))) {}
+        let host = Arc::clone(&session.user);
+        let guest = Arc::new(User::new(
+            2,
+            "guest".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        assert!(room.add_user(Arc::downgrade(&guest), false).await);
+
+        *room.state.write().await = InternalRoomState::Playing {
+            results: std::iter::once((7, record(7, 1_000_000, 1.0, true))).collect(),
+            aborted: HashSet::new(),
+        };
+        room.check_all_ready().await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::Playing { .. }
+        ));
+
+        if let InternalRoomState::Playing { aborted, .. } = &mut *room.state.write().await {
+            aborted.insert(2);
+        }
+        room.check_all_ready().await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::SelectChart
+        ));
+
+        let rows = loop {
+            match resp_rx.recv().await.unwrap() {
+                ServerCommand::GameResults { room: id, rows } => {
+                    assert_eq!(id, room.id);
+                    break rows;
+                }
+                _ => continue,
+            }
+        };
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|it| (it.rank, it.player, it.score, it.full_combo, it.aborted))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(1, 7, 1_000_000, true, false), (2, 2, 0, false, true)]
+        );
+        assert_eq!(rows[0].accuracy, 1.0);
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_channel_chat_reaches_members_only() {
+        let server = server(ServerConfig::default());
+        let users: Vec<_> = (1..=4)
+            .map(|id| {
+                Arc::new(User::new(
+                    id,
+                    format!("user{id}"),
+                    Language::default(),
+                    Arc::clone(&server),
+                ))
+            })
+            .collect();
+        let room = Room::new(
+            "room".to_owned().try_into().unwrap(),
+            Arc::downgrade(&users[0]),
+        );
+        for user in &users[1..] {
+            assert!(room.add_user(Arc::downgrade(user), false).await);
+        }
+        room.join_channel(1, "team1").await.unwrap();
+        room.join_channel(3, "team1").await.unwrap();
+        room.join_channel(2, "team2").await.unwrap();
+
+        let ids = |users: Vec<Arc<User>>| {
+            let mut ids: Vec<i32> = users.iter().map(|it| it.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(ids(room.channel_recipients("team1").await), vec![1, 3]);
+        assert_eq!(ids(room.channel_recipients("team2").await), vec![2]);
+        assert_eq!(
+            ids(room.channel_recipients(DEFAULT_CHAT_CHANNEL).await),
+            vec![1, 2, 3, 4]
+        );
+        assert!(room.channel_recipients("team3").await.is_empty());
+        assert!(room.is_in_channel(4, DEFAULT_CHAT_CHANNEL).await);
+        assert!(!room.is_in_channel(4, "team1").await);
+
+        room.leave_channel(3, "team1").await.unwrap();
+        assert_eq!(ids(room.channel_recipients("team1").await), vec![1]);
+        assert!(room.leave_channel(1, DEFAULT_CHAT_CHANNEL).await.is_err());
+
+        assert!(!room.on_user_leave(&users[1]).await);
+        assert!(room.channel_recipients("team2").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_solo_practice_skips_ready() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let guest = Arc::new(User::new(
+            2,
+            "guest".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        room.practice.store(true, Ordering::SeqCst);
+
+        room.request_start(host.id).await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::Playing { .. }
+        ));
+
+        // A second player brings back the ready handshake
+        *room.state.write().await = InternalRoomState::SelectChart;
+        assert!(room.add_user(Arc::downgrade(&guest), false).await);
+        room.request_start(host.id).await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::WaitForReady { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clear_keeps_room() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let guest = Arc::new(User::new(
+            2,
+            "guest".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&host)));
+        assert!(room.add_user(Arc::downgrade(&guest), false).await);
+        for user in [&host, &guest] {
+            *user.room.write().await = Some(Arc::clone(&room));
+        }
+        room.locked.store(true, Ordering::SeqCst);
+        *room.state.write().await = InternalRoomState::WaitForReady {
+            started: HashSet::new(),
+        };
+        server
+            .rooms
+            .write()
+            .await
+            .insert(id.clone(), Arc::clone(&room));
+
+        room.clear().await;
+        assert!(server.rooms.read().await.contains_key(&id));
+        assert!(room.users().await.is_empty());
+        assert!(host.room.read().await.is_none());
+        assert!(guest.room.read().await.is_none());
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::SelectChart
+        ));
+        assert!(room.is_locked());
+
+        // The host keeps the role when rejoining
+        assert!(room.add_user(Arc::downgrade(&host), false).await);
+        assert!(room.check_host(&host).await.is_ok());
+
+        // A persistent room survives its last user leaving
+        room.persistent.store(true, Ordering::SeqCst);
+        assert!(!room.on_user_leave(&host).await);
+        room.persistent.store(false, Ordering::SeqCst);
+        assert!(room.add_user(Arc::downgrade(&host), false).await);
+        assert!(room.on_user_leave(&host).await);
+    }
+
+    #[tokio::test]
+    async fn test_lobby_resets_when_emptied() {
+        let server = server(ServerConfig {
+            lobby: LobbyConfig { cycle: true },
+            ..ServerConfig::default()
+        });
+        let host = Arc::new(User::new(
+            1,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "lobby".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&host)));
+        *host.room.write().await = Some(Arc::clone(&room));
+        room.lobby.store(true, Ordering::SeqCst);
+        room.set_locked(true, Some(&host));
+        *room.name.write().await = "Friday night".to_owned();
+        *room.chart.write().await = Some(Chart {
+            id: 1.try_into().unwrap(),
+            name: "chart".to_owned(),
+        });
+        *room.state.write().await = InternalRoomState::WaitForReady {
+            started: HashSet::new(),
+        };
+
+        assert!(!room.on_user_leave(&host).await);
+        assert!(!room.is_locked());
+        assert!(room.lock_info().is_none());
+        assert!(room.is_cycle());
+        assert_eq!(*room.name.read().await, "lobby");
+        assert!(room.chart.read().await.is_none());
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::SelectChart
+        ));
+
+        // The lobby stays open for the next player
+        assert!(room.add_user(Arc::downgrade(&host), false).await);
+        assert_eq!(room.users().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cohost_permissions() {
+        let server = server(ServerConfig::default());
+        let users: Vec<_> = (1..=3)
+            .map(|id| {
+                Arc::new(User::new(
+                    id,
+                    format!("user{id}"),
+                    Language::default(),
+                    Arc::clone(&server),
+                ))
+            })
+            .collect();
+        let room = Room::new(
+            "room".to_owned().try_into().unwrap(),
+            Arc::downgrade(&users[0]),
+        );
+        assert!(room.add_user(Arc::downgrade(&users[1]), false).await);
+        assert!(room.set_cohost(3, true).await.is_err());
+
+        room.set_cohost(2, true).await.unwrap();
+        // Co-hosts may select charts, but disbanding stays with the host
+        assert!(room.check_host_or_cohost(&users[1]).await.is_ok());
+        assert!(room.check_host(&users[1]).await.is_err());
+        assert!(room.check_host_or_cohost(&users[0]).await.is_ok());
+
+        room.set_cohost(2, false).await.unwrap();
+        assert!(room.check_host_or_cohost(&users[1]).await.is_err());
+
+        room.set_cohost(2, true).await.unwrap();
+        assert!(!room.on_user_leave(&users[1]).await);
+        assert!(room.add_user(Arc::downgrade(&users[1]), false).await);
+        assert!(room.check_host_or_cohost(&users[1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lock_metadata() {
+        let server = server(ServerConfig::default());
+        let host = Arc::new(User::new(
+            7,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let room = Room::new("room".to_owned().try_into().unwrap(), Arc::downgrade(&host));
+        let reason = || {
+            crate::l10n::LANGUAGE.sync_scope(Arc::new(Language::default()), || room.locked_reason())
+        };
+
+        room.set_locked(true, Some(&host));
+        assert!(room.is_locked());
+        let lock = room.lock_info().unwrap();
+        assert_eq!(lock.user, Some((7, "host".to_owned())));
+        assert!(lock.at.elapsed().unwrap() < Duration::from_secs(60));
+        assert_eq!(reason(), "Room was locked by host (7) 0 min ago");
+
+        room.set_locked(false, Some(&host));
+        assert!(!room.is_locked());
+        assert!(room.lock_info().is_none());
+        assert_eq!(reason(), "Room is locked");
+
+        // Locked by the console
+        room.set_locked(true, None);
+        assert!(room.is_locked());
+        assert!(room.lock_info().unwrap().user.is_none());
+        assert_eq!(reason(), "Room is locked");
+    }
+
+    #[tokio::test]
+    async fn test_late_record_grace_window() {
+        let server = server(ServerConfig::default());
+        let users: Vec<_> = (1..=3)
+            .map(|id| {
+                Arc::new(User::new(
+                    id,
+                    format!("user{id}"),
+                    Language::default(),
+                    Arc::clone(&server),
+                ))
+            })
+            .collect();
+        let room = Room::new(
+            "room".to_owned().try_into().unwrap(),
+            Arc::downgrade(&users[0]),
+        );
+        for user in &users[1..] {
+            assert!(room.add_user(Arc::downgrade(user), false).await);
+        }
+        let grace = Duration::from_secs(1);
+        assert!(
+            room.submit_late_record(record(2, 1, 1.0, true), grace)
+                .await
+                .is_err()
+        );
+
+        // Players 2 and 3 were still uploading when the game was ended
+        *room.state.write().await = InternalRoomState::Playing {
+            results: std::iter::once((1, record(1, 1_000_000, 1.0, true))).collect(),
+            aborted: [2, 3].into_iter().collect(),
+        };
+        room.check_all_ready().await;
+        assert!(matches!(
+            *room.state.read().await,
+            InternalRoomState::SelectChart
+        ));
+
+        room.submit_late_record(record(2, 900_000, 0.9, false), grace)
+            .await
+            .unwrap();
+        assert!(
+            room.submit_late_record(record(1, 1_000_000, 1.0, true), grace)
+                .await
+                .is_err()
+        );
+        {
+            let guard = room.last_round.read().await;
+            let round = guard.as_ref().unwrap();
+            assert!(round.results.contains_key(&2));
+            assert!(!round.aborted.contains(&2));
+        }
+
+        if let Some(round) = room.last_round.write().await.as_mut() {
+            round.ended -= Duration::from_secs(2);
+        }
+        let err = room
+            .submit_late_record(record(3, 800_000, 0.8, false), grace)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too late"));
+    }
+}
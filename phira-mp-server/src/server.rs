@@ -1,29 +1,187 @@
-use crate::{IdMap, Room, SafeMap, Session, User, vacant_entry};
+use crate::{
+    AUTH_RATE_LIMIT_BACKOFF, AuthCache, AuthConfig, AuthInfo, AuthProvider, DEFAULT_API_HOST,
+    GameJudges, IdMap, MAX_AUTH_RATE_LIMIT_BACKOFF, OtlpConfig, RateLimited, Room, SafeMap,
+    ServerStats, Session, User, vacant_entry,
+};
 use anyhow::Result;
-use phira_mp_common::RoomId;
+use phira_mp_common::{
+    AnnouncementStyle, Capabilities, ChartId, Message, RoomId, SCHEDULED_ANNOUNCEMENT_CHANNEL,
+    ScheduledAnnouncement, ServerCommand,
+};
+use phira_mp_plugin::event_system::HandlerConcurrency;
 use serde::Deserialize;
-use std::{fs::File, sync::Arc};
-use tokio::{net::TcpListener, sync::mpsc, task::JoinHandle};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    sync::{Arc, OnceLock, Weak},
+    time::Duration,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{SetOnce, mpsc},
+    task::JoinHandle,
+    time,
+};
 use tracing::{info, warn};
 use uuid::Uuid;
 
+/// Number of recorded rounds kept in memory.
+const JUDGE_ARCHIVE_SIZE: usize = 32;
+
+/// Chat sender of messages from the server itself, such as announcements shown to clients
+/// without announcement support. No account has this ID.
+pub const SYSTEM_SENDER: i32 = 0;
+
 #[derive(Debug, Deserialize)]
 pub struct Chart {
-    pub id: i32,
+    pub id: ChartId,
     pub name: String,
 }
 
+/// What happens to a running game when its host leaves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostLeavePolicy {
+    /// Promote another player to host and keep playing.
+    #[default]
+    PromoteHost,
+    /// Promote another player to host and end the game, counting unfinished players as aborted.
+    EndGame,
+}
+
+/// What happens when an account connects while its previous session is still connected.
+///
+/// The previous session is disconnected either way, so only one session acts for a user.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateSessionPolicy {
+    /// The new session takes over the user, staying in its room.
+    #[default]
+    Inherit,
+    /// The user leaves its room and the new session starts fresh.
+    Fresh,
+}
+
+/// State a lobby room returns to when its last user leaves, see [`Room::lobby`].
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct LobbyConfig {
+    /// Whether lobbies reset to cycle mode.
+    #[serde(default)]
+    pub cycle: bool,
+}
+
+fn default_played_grace_secs() -> u64 {
+    10
+}
+
+fn default_stats_retention_hours() -> usize {
+    24
+}
+
+fn default_auth_cache_secs() -> u64 {
+    60 * 60
+}
+
+fn default_message_sender() -> i32 {
+    SYSTEM_SENDER
+}
+
+fn default_api_host() -> String {
+    DEFAULT_API_HOST.to_owned()
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     pub monitors: Vec<i32>,
+    #[serde(default)]
+    pub host_leave_policy: HostLeavePolicy,
+    #[serde(default)]
+    pub duplicate_session_policy: DuplicateSessionPolicy,
+    /// Seconds after a game ends during which late records are still accepted.
+    #[serde(default = "default_played_grace_secs")]
+    pub played_grace_secs: u64,
+    /// Span export to an OpenTelemetry collector, off if unset.
+    #[serde(default)]
+    pub otlp: Option<OtlpConfig>,
+    /// Hours of aggregate statistics kept in memory.
+    #[serde(default = "default_stats_retention_hours")]
+    pub stats_retention_hours: usize,
+    /// Oldest protocol version allowed to authenticate, any if unset.
+    #[serde(default)]
+    pub min_client_version: Option<u8>,
+    /// Whether created rooms let a lone player start without the ready handshake by
+    /// default; rooms created as practice rooms always do.
+    #[serde(default)]
+    pub solo_practice: bool,
+    /// Phira API server charts and records are fetched from.
+    #[serde(default = "default_api_host")]
+    pub api_host: String,
+    /// How clients' tokens are verified.
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Seconds a successful authentication still answers for its token while the auth
+    /// server rate limits, 0 to refuse instead.
+    #[serde(default = "default_auth_cache_secs")]
+    pub auth_cache_secs: u64,
+    /// State emptied lobby rooms are reset to.
+    #[serde(default)]
+    pub lobby: LobbyConfig,
+    /// User tag required to create rooms, anyone may if unset.
+    #[serde(default)]
+    pub create_room_tag: Option<String>,
+    /// Most rooms open at once, unlimited if unset.
+    #[serde(default)]
+    pub max_rooms: Option<usize>,
+    /// User ID shown as the sender of chat messages plugins send to users, the server's own
+    /// [`SYSTEM_SENDER`] by default.
+    #[serde(default = "default_message_sender")]
+    pub message_sender: i32,
+    /// Address serving plugin metrics to Prometheus at `/metrics`, off if unset.
+    #[serde(default)]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+    /// Address serving the HTTP routes plugins register, off if unset.
+    #[serde(default)]
+    pub http_addr: Option<std::net::SocketAddr>,
+    /// Limit of concurrent plugin handler calls for high-frequency events, such as events
+    /// plugins emit, and what happens beyond it.
+    #[serde(default)]
+    pub event_handlers: HandlerConcurrency,
 }
 impl Default for ServerConfig {
     fn default() -> Self {
-        Self { monitors: vec![2] }
+        Self {
+            monitors: vec![2],
+            host_leave_policy: HostLeavePolicy::default(),
+            duplicate_session_policy: DuplicateSessionPolicy::default(),
+            played_grace_secs: default_played_grace_secs(),
+            otlp: None,
+            stats_retention_hours: default_stats_retention_hours(),
+            min_client_version: None,
+            solo_practice: false,
+            api_host: default_api_host(),
+            auth: AuthConfig::default(),
+            auth_cache_secs: default_auth_cache_secs(),
+            lobby: LobbyConfig::default(),
+            create_room_tag: None,
+            max_rooms: None,
+            message_sender: default_message_sender(),
+            metrics_addr: None,
+            http_addr: None,
+            event_handlers: HandlerConcurrency::default(),
+        }
+    }
+}
+impl ServerConfig {
+    /// Reads `server_config.yml`, falling back to defaults.
+    pub fn load() -> Self {
+        File::open("server_config.yml")
+            .ok()
+            .and_then(|f| serde_yaml::from_reader(f).ok())
+            .unwrap_or_default()
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Record {
     pub id: i32,
     pub player: i32,
@@ -42,11 +200,228 @@ pub struct Record {
 pub struct ServerState {
     pub config: ServerConfig,
     pub sessions: IdMap<Arc<Session>>,
+    /// Authenticated users, kept after disconnecting until the reconnect window ends.
     pub users: SafeMap<i32, Arc<User>>,
 
     pub rooms: SafeMap<RoomId, Arc<Room>>,
 
     pub lost_con_tx: mpsc::Sender<Uuid>,
+
+    /// Verifies the tokens clients authenticate with.
+    pub auth: Arc<dyn AuthProvider>,
+    /// Answers for recently authenticated tokens while the auth server rate limits.
+    pub auth_cache: AuthCache,
+
+    /// Plugin host API, set once plugins are started.
+    pub host_api: OnceLock<Arc<phira_mp_plugin::HostApi>>,
+
+    /// Judge timelines of recently finished recorded rounds, oldest first.
+    judge_archive: parking_lot::Mutex<VecDeque<Arc<GameJudges>>>,
+
+    /// Hourly counters of users, rooms and games.
+    pub stats: ServerStats,
+
+    /// Upcoming event announced by plugins, sent to clients when they authenticate.
+    scheduled_announcement: parking_lot::Mutex<Option<ScheduledAnnouncement>>,
+
+    /// Set when a shutdown begins, after which no connections are accepted.
+    pub shutting_down: SetOnce<()>,
+    /// Set when the shutdown grace period has passed and the server should exit.
+    pub stopped: SetOnce<()>,
+}
+
+impl ServerState {
+    pub fn new(config: ServerConfig, lost_con_tx: mpsc::Sender<Uuid>) -> Self {
+        let auth = config.auth.provider(&config.api_host);
+        Self::with_auth(config, lost_con_tx, auth)
+    }
+
+    /// Creates the state with a custom authentication backend instead of `config.auth`.
+    pub fn with_auth(
+        config: ServerConfig,
+        lost_con_tx: mpsc::Sender<Uuid>,
+        auth: Arc<dyn AuthProvider>,
+    ) -> Self {
+        Self {
+            stats: ServerStats::new(config.stats_retention_hours),
+            auth_cache: AuthCache::new(Duration::from_secs(config.auth_cache_secs)),
+            config,
+            sessions: IdMap::default(),
+            users: SafeMap::default(),
+
+            rooms: SafeMap::default(),
+
+            lost_con_tx,
+
+            auth,
+
+            host_api: OnceLock::new(),
+
+            judge_archive: parking_lot::Mutex::default(),
+
+            scheduled_announcement: parking_lot::Mutex::default(),
+
+            shutting_down: SetOnce::new(),
+            stopped: SetOnce::new(),
+        }
+    }
+
+    /// Verifies a token with [`Self::auth`].
+    ///
+    /// When the auth server rate limits, it is not asked again until the backoff passes, and
+    /// meanwhile tokens are answered from [`Self::auth_cache`] or refused with
+    /// [`RateLimited`].
+    pub async fn authenticate(&self, token: &str) -> Result<AuthInfo> {
+        if let Some(remaining) = self.auth_cache.backoff_remaining() {
+            return self.auth_cache.get(token).ok_or_else(|| {
+                RateLimited {
+                    retry_after: Some(remaining),
+                }
+                .into()
+            });
+        }
+        match self.auth.authenticate(token).await {
+            Ok(info) => {
+                self.auth_cache.insert(token, &info);
+                Ok(info)
+            }
+            Err(err) => {
+                let Some(limited) = err.downcast_ref::<RateLimited>() else {
+                    return Err(err);
+                };
+                let backoff = limited
+                    .retry_after
+                    .unwrap_or(AUTH_RATE_LIMIT_BACKOFF)
+                    .min(MAX_AUTH_RATE_LIMIT_BACKOFF);
+                warn!(
+                    backoff_secs = backoff.as_secs(),
+                    "auth server rate limited, backing off"
+                );
+                self.auth_cache.back_off(backoff);
+                self.stats.auth_rate_limited();
+                if let Some(host_api) = self.host_api.get() {
+                    host_api.on_auth_rate_limited(backoff);
+                }
+                self.auth_cache.get(token).ok_or_else(|| {
+                    RateLimited {
+                        retry_after: Some(backoff),
+                    }
+                    .into()
+                })
+            }
+        }
+    }
+
+    /// Whether `rooms` open rooms leave no room for another under `max_rooms`.
+    pub fn at_room_limit(&self, rooms: usize) -> bool {
+        self.config.max_rooms.is_some_and(|max| rooms >= max)
+    }
+
+    /// Drops an emptied room and tells plugins it was disbanded.
+    pub async fn remove_room(&self, id: &RoomId) {
+        if self.rooms.write().await.remove(id).is_none() {
+            return;
+        }
+        if let Some(host_api) = self.host_api.get() {
+            host_api.on_room_disband(&id.to_string());
+        }
+    }
+
+    /// Keeps the judge timeline of a finished round, evicting the oldest beyond capacity.
+    pub fn archive_judges(&self, judges: GameJudges) {
+        let mut archive = self.judge_archive.lock();
+        archive.push_back(Arc::new(judges));
+        while archive.len() > JUDGE_ARCHIVE_SIZE {
+            archive.pop_front();
+        }
+    }
+
+    /// Stops accepting connections and warns connected users, then after `grace` notifies
+    /// plugins and sets [`Self::stopped`]. Ignored if already shutting down.
+    pub async fn shutdown(&self, grace: Duration) {
+        if self.shutting_down.set(()).is_err() {
+            return;
+        }
+        info!(grace_secs = grace.as_secs(), "shutting down");
+        let users: Vec<_> = self.users.read().await.values().cloned().collect();
+        for user in users {
+            let Some(session) = user.session.read().await.as_ref().and_then(Weak::upgrade) else {
+                continue;
+            };
+            let args = fluent::fluent_args!["seconds" => grace.as_secs()];
+            let content = user.lang.format("shutdown-warning", Some(&args));
+            session
+                .try_send(ServerCommand::Message(Message::Chat {
+                    user: self.config.message_sender,
+                    content: content.into_owned(),
+                }))
+                .await;
+        }
+        time::sleep(grace).await;
+        if let Some(host_api) = self.host_api.get() {
+            host_api.on_server_shutdown().await;
+        }
+        let _ = self.stopped.set(());
+    }
+
+    /// Sends an announcement to every connected user, returning how many were reached.
+    ///
+    /// Clients without [`Capabilities::ANNOUNCEMENTS`] get the plain text as a chat message.
+    pub async fn announce(&self, content: &str, style: &AnnouncementStyle) -> usize {
+        let users: Vec<_> = self.users.read().await.values().cloned().collect();
+        let mut reached = 0;
+        for user in users {
+            let Some(session) = user.session.read().await.as_ref().and_then(Weak::upgrade) else {
+                continue;
+            };
+            let msg = if session.capabilities().contains(Capabilities::ANNOUNCEMENTS) {
+                Message::Announcement {
+                    content: content.to_owned(),
+                    style: style.clone(),
+                }
+            } else {
+                Message::Chat {
+                    user: SYSTEM_SENDER,
+                    content: content.to_owned(),
+                }
+            };
+            session.try_send(ServerCommand::Message(msg)).await;
+            reached += 1;
+        }
+        reached
+    }
+
+    /// Replaces the announced upcoming event, returning its notification data.
+    pub fn set_scheduled_announcement(
+        &self,
+        announcement: Option<ScheduledAnnouncement>,
+    ) -> String {
+        let data = ScheduledAnnouncement::to_notification(announcement.as_ref());
+        *self.scheduled_announcement.lock() = announcement;
+        data
+    }
+
+    /// Notification of the announced upcoming event for a newly connected client, if any.
+    pub fn scheduled_announcement_command(&self, caps: Capabilities) -> Option<ServerCommand> {
+        if !caps.contains(Capabilities::PLUGIN_NOTIFICATION) {
+            return None;
+        }
+        let guard = self.scheduled_announcement.lock();
+        let announcement = guard.as_ref()?;
+        Some(ServerCommand::PluginNotification {
+            channel: SCHEDULED_ANNOUNCEMENT_CHANNEL.to_owned(),
+            data: ScheduledAnnouncement::to_notification(Some(announcement)),
+        })
+    }
+
+    /// Judge timeline of a recorded round, if still archived.
+    pub fn game_judges(&self, room: &RoomId, round: u32) -> Option<Arc<GameJudges>> {
+        self.judge_archive
+            .lock()
+            .iter()
+            .find(|it| it.room == *room && it.round == round)
+            .cloned()
+    }
 }
 
 pub struct Server {
@@ -58,19 +433,8 @@ pub struct Server {
 impl From<TcpListener> for Server {
     fn from(listener: TcpListener) -> Self {
         let (lost_con_tx, mut lost_con_rx) = mpsc::channel(16);
-        let config: ServerConfig = File::open("server_config.yml")
-            .ok()
-            .and_then(|f| serde_yaml::from_reader(f).ok())
-            .unwrap_or_default();
-        let state = Arc::new(ServerState {
-            config,
-            sessions: IdMap::default(),
-            users: SafeMap::default(),
-
-            rooms: SafeMap::default(),
-
-            lost_con_tx,
-        });
+        let config = ServerConfig::load();
+        let state = Arc::new(ServerState::new(config, lost_con_tx));
         let lost_con_handle = tokio::spawn({
             let state = Arc::clone(&state);
             async move {
@@ -101,6 +465,10 @@ impl From<TcpListener> for Server {
 }
 
 impl Server {
+    pub fn state(&self) -> &Arc<ServerState> {
+        &self.state
+    }
+
     pub async fn accept(&self) -> Result<()> {
         let (stream, addr) = self.listener.accept().await?;
         let mut guard = self.state.sessions.write().await;
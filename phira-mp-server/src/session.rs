@@ -1,33 +1,105 @@
 use crate::{
-    Chart, InternalRoomState, Record, Room, ServerState,
+    DuplicateSessionPolicy, InternalRoomState, RateLimited, Record, Room, ServerConfig,
+    ServerState,
     l10n::{LANGUAGE, Language},
     tl,
 };
 use anyhow::{Result, anyhow, bail};
 use phira_mp_common::{
-    ClientCommand, HEARTBEAT_DISCONNECT_TIMEOUT, JoinRoomResponse, Message, ServerCommand, Stream,
-    UserInfo,
+    Capabilities, ClientCommand, HEARTBEAT_DISCONNECT_TIMEOUT, HEARTBEAT_INTERVAL,
+    JoinRoomResponse, Message, QueueOverflow, RoomId, ServerCommand, Stream, UserInfo,
 };
-use serde::Deserialize;
+use phira_mp_plugin::player_input::PlayerInputFrames;
 use std::{
-    collections::{HashSet, hash_map::Entry},
+    collections::{HashMap, hash_map::Entry},
+    net::IpAddr,
     ops::DerefMut,
     sync::{
         Arc, Weak,
-        atomic::{AtomicBool, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     net::TcpStream,
-    sync::{Mutex, Notify, OnceCell, RwLock, oneshot},
+    sync::{Mutex, Notify, OnceCell, RwLock, SetOnce, oneshot},
     task::JoinHandle,
     time,
 };
 use tracing::{Instrument, debug, debug_span, error, info, trace, warn};
 use uuid::Uuid;
 
-const HOST: &str = "https://phira.5wyxi.com";
+/// How long a disconnected user is kept for reconnecting.
+const DANGLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Rejects clients older than the configured minimum protocol version.
+pub fn check_client_version(config: &ServerConfig, version: u8) -> Result<()> {
+    if let Some(min) = config.min_client_version
+        && version < min
+    {
+        bail!(tl!("auth-client-outdated", "version" => version, "min" => min));
+    }
+    Ok(())
+}
+
+/// Rejects users banned by ID, or connecting from a banned IP.
+fn check_banned(server: &ServerState, id: i32, ip: IpAddr) -> Result<()> {
+    let Some(host_api) = server.host_api.get() else {
+        return Ok(());
+    };
+    let banned = host_api.is_user_banned_by_id(id as u32).unwrap_or(false)
+        || host_api
+            .is_user_banned_by_ip(&ip.to_string())
+            .unwrap_or(false);
+    if banned {
+        warn!(user = id, %ip, "rejecting banned user");
+        bail!(tl!("auth-banned"));
+    }
+    Ok(())
+}
+
+/// Round-trip time of a connection, measured with server-initiated pings.
+#[derive(Default)]
+pub struct Latency {
+    sent: std::sync::Mutex<Option<Instant>>,
+    /// Smoothed RTT in microseconds, 0 until the first pong.
+    rtt: AtomicU64,
+}
+
+impl Latency {
+    pub fn ping_sent(&self) {
+        *self.sent.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Records the answer to the last ping, returning the measured RTT.
+    pub fn pong_received(&self) -> Option<Duration> {
+        let sample = self.sent.lock().unwrap().take()?.elapsed();
+        let micros = u64::try_from(sample.as_micros()).unwrap_or(u64::MAX).max(1);
+        let _ = self
+            .rtt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |rtt| {
+                Some(if rtt == 0 {
+                    micros
+                } else {
+                    (rtt.saturating_mul(7) / 8).saturating_add(micros / 8)
+                })
+            });
+        Some(sample)
+    }
+
+    /// Smoothed RTT, if measured.
+    pub fn rtt(&self) -> Option<Duration> {
+        match self.rtt.load(Ordering::SeqCst) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    pub fn reset(&self) {
+        *self.sent.lock().unwrap() = None;
+        self.rtt.store(0, Ordering::SeqCst);
+    }
+}
 
 pub struct User {
     pub id: i32,
@@ -40,6 +112,8 @@ pub struct User {
 
     pub monitor: AtomicBool,
     pub game_time: AtomicU32,
+    /// Latency of the current session, if the client answers pings.
+    pub latency: Latency,
 
     pub dangle_mark: Mutex<Option<Arc<()>>>,
 }
@@ -57,6 +131,7 @@ impl User {
 
             monitor: AtomicBool::default(),
             game_time: AtomicU32::default(),
+            latency: Latency::default(),
 
             dangle_mark: Mutex::default(),
         }
@@ -74,9 +149,120 @@ impl User {
         self.server.config.monitors.contains(&self.id)
     }
 
+    /// Whether the user has the tag [`ServerConfig::create_room_tag`] requires, if any.
+    pub fn can_create_room(&self) -> bool {
+        let Some(tag) = &self.server.config.create_room_tag else {
+            return true;
+        };
+        self.server
+            .host_api
+            .get()
+            .is_some_and(|api| api.user_has_tag(self.id as u32, tag))
+    }
+
     pub async fn set_session(&self, session: Weak<Session>) {
         *self.session.write().await = Some(session);
         *self.dangle_mark.lock().await = None;
+        self.latency.reset();
+    }
+
+    /// Attaches the session of a reconnecting client, disconnecting the previous session if
+    /// it is still connected and applying [`DuplicateSessionPolicy`].
+    pub async fn replace_session(&self, session: Weak<Session>) {
+        let old = self.session.read().await.as_ref().and_then(Weak::upgrade);
+        self.set_session(session).await;
+        let Some(old) = old else {
+            return;
+        };
+        warn!(
+            user = self.id,
+            "account connected again, disconnecting session {}", old.id
+        );
+        if let Err(err) = self.server.lost_con_tx.send(old.id).await {
+            error!("failed to mark lost connection ({}): {err:?}", old.id);
+        }
+        old.stream.close();
+        self.on_duplicate_session().await;
+    }
+
+    /// Applies [`DuplicateSessionPolicy`] to the room of a user whose session was replaced.
+    pub async fn on_duplicate_session(&self) {
+        if self.server.config.duplicate_session_policy != DuplicateSessionPolicy::Fresh {
+            return;
+        }
+        let room = self.room.read().await.as_ref().map(Arc::clone);
+        if let Some(room) = room {
+            info!(
+                user = self.id,
+                room = room.id.to_string(),
+                "session replaced, leaving room"
+            );
+            if room.on_user_leave(self).await {
+                self.server.remove_room(&room.id).await;
+            }
+        }
+    }
+
+    /// Credits the time played in the current game to the user's playtime, once.
+    pub fn credit_playtime(&self) {
+        let time = f32::from_bits(
+            self.game_time
+                .swap(f32::NEG_INFINITY.to_bits(), Ordering::SeqCst),
+        );
+        if !time.is_finite() || time < 1. {
+            return;
+        }
+        if let Some(host_api) = self.server.host_api.get() {
+            host_api.on_user_playtime(self.id as u32, &self.name, time as u64);
+        }
+    }
+
+    /// Whether `session` is the one currently acting for this user.
+    pub async fn is_current_session(&self, session: &Arc<Session>) -> bool {
+        self.session
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|it| it.ptr_eq(&Arc::downgrade(session)))
+    }
+
+    /// IP address of the current session, if connected.
+    pub async fn ip(&self) -> Option<IpAddr> {
+        self.session
+            .read()
+            .await
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map(|it| it.ip)
+    }
+
+    /// Whether the user or the IP of its session is banned from the room.
+    pub async fn is_banned_from_room(&self, id: &RoomId) -> bool {
+        let Some(host_api) = self.server.host_api.get() else {
+            return false;
+        };
+        let room_id = id.to_string();
+        if host_api
+            .is_user_banned_from_room(self.id as u32, &room_id)
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        self.ip().await.is_some_and(|ip| {
+            host_api
+                .is_ip_banned_from_room(&ip.to_string(), &room_id)
+                .unwrap_or(false)
+        })
+    }
+
+    pub async fn capabilities(&self) -> Capabilities {
+        self.session
+            .read()
+            .await
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map(|it| it.capabilities())
+            .unwrap_or_default()
     }
 
     pub async fn try_send(&self, cmd: ServerCommand) {
@@ -87,8 +273,51 @@ impl User {
         }
     }
 
+    /// Takes the user out of its room, dropping the room if it empties.
+    ///
+    /// The room is taken from the user first, so that concurrent leaves, such as a kick and a
+    /// dangle timeout, leave only once.
+    pub async fn leave_room(&self) {
+        let room = self.room.write().await.take();
+        if let Some(room) = room
+            && room.on_user_leave(self).await
+        {
+            self.server.remove_room(&room.id).await;
+        }
+    }
+
+    /// Disconnects the user without waiting for a reconnect: closes its session, forgets the
+    /// user and leaves its room, cancelling the timeout of a dangling user.
+    pub async fn kick(self: &Arc<Self>) {
+        info!(user = self.id, "kicking user");
+        *self.dangle_mark.lock().await = None;
+        {
+            let mut users = self.server.users.write().await;
+            if users.get(&self.id).is_some_and(|it| Arc::ptr_eq(it, self)) {
+                users.remove(&self.id);
+            }
+        }
+        let session = self
+            .session
+            .write()
+            .await
+            .take()
+            .and_then(|it| it.upgrade());
+        if let Some(session) = session {
+            self.server.sessions.write().await.remove(&session.id);
+            session.stream.close();
+        }
+        self.leave_room().await;
+        if let Some(host_api) = self.server.host_api.get() {
+            host_api.on_user_disconnect(self.id as u32);
+        }
+    }
+
     pub async fn dangle(self: Arc<Self>) {
         warn!(user = self.id, "user dangling");
+        if let Some(host_api) = self.server.host_api.get() {
+            host_api.on_user_disconnect(self.id as u32);
+        }
         let guard = self.room.read().await;
         let room = guard.as_ref().map(Arc::clone);
         drop(guard);
@@ -98,26 +327,18 @@ impl User {
                 warn!(user = self.id, "lost connection on playing, aborting");
                 self.server.users.write().await.remove(&self.id);
                 drop(guard);
-                if room.on_user_leave(&self).await {
-                    self.server.rooms.write().await.remove(&room.id);
-                }
+                self.leave_room().await;
                 return;
             }
         }
         let dangle_mark = Arc::new(());
         *self.dangle_mark.lock().await = Some(Arc::clone(&dangle_mark));
         tokio::spawn(async move {
-            time::sleep(Duration::from_secs(10)).await;
+            time::sleep(DANGLE_TIMEOUT).await;
             if Arc::strong_count(&dangle_mark) > 1 {
-                let guard = self.room.read().await;
-                let room = guard.as_ref().map(Arc::clone);
-                drop(guard);
-                if let Some(room) = room {
-                    self.server.users.write().await.remove(&self.id);
-                    if room.on_user_leave(&self).await {
-                        self.server.rooms.write().await.remove(&room.id);
-                    }
-                }
+                // Users outside rooms are dropped too, or they would be kept forever
+                self.server.users.write().await.remove(&self.id);
+                self.leave_room().await;
             }
         });
     }
@@ -127,17 +348,24 @@ pub struct Session {
     pub id: Uuid,
     pub stream: Stream<ServerCommand, ClientCommand>,
     pub user: Arc<User>,
+    pub connected_at: SystemTime,
+    /// Peer IP address, with IPv4-mapped addresses converted to IPv4.
+    pub ip: IpAddr,
 
     monitor_task_handle: JoinHandle<()>,
+    probe_task_handle: JoinHandle<()>,
 }
 
 impl Session {
     pub async fn new(id: Uuid, stream: TcpStream, server: Arc<ServerState>) -> Result<Arc<Self>> {
         stream.set_nodelay(true)?;
+        let connected_at = SystemTime::now();
+        let ip = stream.peer_addr()?.ip().to_canonical();
         let this = Arc::new(OnceCell::<Arc<Session>>::new());
         let this_inited = Arc::new(Notify::new());
         let (tx, rx) = oneshot::channel::<Arc<User>>();
         let last_recv: Arc<Mutex<Instant>> = Arc::new(Mutex::new(Instant::now()));
+        let version = Arc::new(SetOnce::<u8>::new());
         let stream = Stream::<ServerCommand, ClientCommand>::new(
             None,
             stream,
@@ -147,6 +375,7 @@ impl Session {
                 let mut tx = Some(tx);
                 let server = Arc::clone(&server);
                 let last_recv = Arc::clone(&last_recv);
+                let version = Arc::clone(&version);
                 let waiting_for_authenticate = Arc::new(AtomicBool::new(true));
                 let panicked = Arc::new(AtomicBool::new(false));
                 move |send_tx, cmd| {
@@ -155,6 +384,7 @@ impl Session {
                     let tx = tx.take();
                     let server = Arc::clone(&server);
                     let last_recv = Arc::clone(&last_recv);
+                    let version = Arc::clone(&version);
                     let waiting_for_authenticate = Arc::clone(&waiting_for_authenticate);
                     let panicked = Arc::clone(&panicked);
                     async move {
@@ -163,7 +393,15 @@ impl Session {
                             return;
                         }
                         if matches!(cmd, ClientCommand::Ping) {
-                            let _ = send_tx.send(ServerCommand::Pong).await;
+                            let _ = send_tx.send(ServerCommand::Pong);
+                            return;
+                        }
+                        if matches!(cmd, ClientCommand::Pong) {
+                            if let Some(rtt) =
+                                this.get().and_then(|it| it.user.latency.pong_received())
+                            {
+                                trace!("session {id}: rtt {rtt:?}");
+                            }
                             return;
                         }
                         if waiting_for_authenticate.load(Ordering::SeqCst) {
@@ -178,49 +416,46 @@ impl Session {
                                             bail!("invalid token");
                                         }
                                         debug!("session {id}: authenticate {token}");
-                                        #[derive(Debug, Deserialize)]
-                                        struct UserInfo {
-                                            id: i32,
-                                            name: String,
-                                            language: String,
-                                        }
-                                        let resp: Result<UserInfo> = async {
-                                            Ok(reqwest::Client::new()
-                                                .get(format!("{HOST}/me"))
-                                                .header(
-                                                    reqwest::header::AUTHORIZATION,
-                                                    format!("Bearer {token}"),
-                                                )
-                                                .send()
-                                                .await?
-                                                .error_for_status()?
-                                                .json()
-                                                .await?)
-                                        }
-                                        .await;
-                                        let resp = match resp {
+                                        let resp = match server.authenticate(&token).await {
                                             Ok(resp) => resp,
                                             Err(err) => {
                                                 warn!("failed to fetch info: {err:?}");
+                                                if let Some(limited) =
+                                                    err.downcast_ref::<RateLimited>()
+                                                {
+                                                    bail!("{limited}");
+                                                }
                                                 bail!("failed to fetch info");
                                             }
                                         };
                                         debug!("session {id} <- {resp:?}");
+                                        let lang =
+                                            resp.language.parse().map(Language).unwrap_or_default();
+                                        let version = *version.wait().await;
+                                        LANGUAGE.sync_scope(Arc::new(lang.clone()), || {
+                                            check_client_version(&server.config, version)?;
+                                            check_banned(&server, resp.id, ip)
+                                        })?;
                                         let mut users_guard = server.users.write().await;
-                                        if let Some(user) = users_guard.get(&resp.id) {
+                                        if let Some(user) =
+                                            users_guard.get(&resp.id).map(Arc::clone)
+                                        {
+                                            // Released first: the old session is disconnected
+                                            // through `lost_con_tx`, whose consumer takes
+                                            // `users` to dangle it
+                                            drop(users_guard);
                                             info!("reconnect");
-                                            let _ = tx.send(Arc::clone(user));
+                                            let _ = tx.send(Arc::clone(&user));
                                             this_inited.notified().await;
-                                            user.set_session(Arc::downgrade(this.get().unwrap()))
-                                                .await;
+                                            user.replace_session(Arc::downgrade(
+                                                this.get().unwrap(),
+                                            ))
+                                            .await;
                                         } else {
                                             let user = Arc::new(User::new(
                                                 resp.id,
                                                 resp.name,
-                                                resp.language
-                                                    .parse()
-                                                    .map(Language)
-                                                    .unwrap_or_default(),
+                                                lang,
                                                 Arc::clone(&server),
                                             ));
                                             let _ = tx.send(Arc::clone(&user));
@@ -228,6 +463,7 @@ impl Session {
                                             user.set_session(Arc::downgrade(this.get().unwrap()))
                                                 .await;
                                             users_guard.insert(resp.id, user);
+                                            server.stats.record_users(users_guard.len());
                                         }
                                         Ok(())
                                     }
@@ -236,24 +472,46 @@ impl Session {
                                 if let Err(err) = res {
                                     warn!("failed to authenticate: {err:?}");
                                     let _ = send_tx
-                                        .send(ServerCommand::Authenticate(Err(err.to_string())))
-                                        .await;
+                                        .send(ServerCommand::Authenticate(Err(err.to_string())));
                                     panicked.store(true, Ordering::SeqCst);
                                     if let Err(err) = server.lost_con_tx.send(id).await {
                                         error!("failed to mark lost connection ({id}): {err:?}");
                                     }
                                 } else {
-                                    let user = &this.get().unwrap().user;
-                                    let room_state = match user.room.read().await.as_ref() {
+                                    let session = this.get().unwrap();
+                                    let user = &session.user;
+                                    let room = user.room.read().await.clone();
+                                    let room_state = match &room {
                                         Some(room) => Some(room.client_state(user).await),
                                         None => None,
                                     };
-                                    let _ = send_tx
-                                        .send(ServerCommand::Authenticate(Ok((
-                                            user.to_info(),
-                                            room_state,
-                                        ))))
-                                        .await;
+                                    let _ = send_tx.send(ServerCommand::Authenticate(Ok((
+                                        user.to_info(),
+                                        room_state,
+                                    ))));
+                                    if let Some(cmd) = server
+                                        .scheduled_announcement_command(session.capabilities())
+                                    {
+                                        let _ = send_tx.send(cmd);
+                                    }
+                                    if let Some(host_api) = server.host_api.get() {
+                                        host_api.on_user_connect(
+                                            phira_mp_plugin::api_host::UserInfo {
+                                                id: user.id as u32,
+                                                name: user.name.clone(),
+                                                language: user.lang.0.to_string(),
+                                                session_id: session.id,
+                                                ip: Some(session.ip.to_string()),
+                                                custom_data: HashMap::new(),
+                                            },
+                                        );
+                                    }
+                                    if let Some(room) = room
+                                        && let Some(cmd) =
+                                            room.name_command(session.capabilities()).await
+                                    {
+                                        let _ = send_tx.send(cmd);
+                                    }
                                     waiting_for_authenticate.store(false, Ordering::SeqCst);
                                 }
                                 return;
@@ -262,11 +520,18 @@ impl Session {
                                 return;
                             }
                         }
-                        let user = this.get().map(|it| Arc::clone(&it.user)).unwrap();
+                        let session = Arc::clone(this.get().unwrap());
+                        let user = Arc::clone(&session.user);
+                        if !user.is_current_session(&session).await {
+                            warn!("session {id} was replaced, ignoring: {cmd:?}");
+                            return;
+                        }
+                        let span = debug_span!("session", session = %id, user = user.id);
                         if let Some(resp) = LANGUAGE
                             .scope(Arc::new(user.lang.clone()), process(user, cmd))
+                            .instrument(span)
                             .await
-                            && let Err(err) = send_tx.send(resp).await
+                            && let Err(err) = send_tx.send(resp)
                         {
                             error!("failed to handle message, aborting connection {id}: {err:?}",);
                             panicked.store(true, Ordering::SeqCst);
@@ -279,6 +544,7 @@ impl Session {
             }),
         )
         .await?;
+        let _ = version.set(stream.version());
         let monitor_task_handle = tokio::spawn({
             let last_recv = Arc::clone(&last_recv);
             async move {
@@ -298,14 +564,18 @@ impl Session {
             }
         });
 
+        // Without a successful authentication no user exists, so the connection is dropped
         let user = rx.await?;
 
-        let res = Arc::new(Self {
+        let res = Arc::new_cyclic(|this: &Weak<Self>| Self {
             id,
             stream,
             user,
+            connected_at,
+            ip,
 
             monitor_task_handle,
+            probe_task_handle: tokio::spawn(probe_latency(this.clone())),
         });
         let _ = this.set(Arc::clone(&res));
         this_inited.notify_one();
@@ -316,6 +586,10 @@ impl Session {
         self.stream.version()
     }
 
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::from_version(self.version())
+    }
+
     pub fn name(&self) -> &str {
         &self.user.name
     }
@@ -323,6 +597,16 @@ impl Session {
     pub async fn try_send(&self, cmd: ServerCommand) {
         if let Err(err) = self.stream.send(cmd).await {
             error!("failed to deliver command to {}: {err:?}", self.id);
+            if err.is::<QueueOverflow>() {
+                // Spawned, since the caller may hold locks the disconnect takes
+                let server = Arc::clone(&self.user.server);
+                let id = self.id;
+                tokio::spawn(async move {
+                    if let Err(err) = server.lost_con_tx.send(id).await {
+                        error!("failed to mark lost connection ({id}): {err:?}");
+                    }
+                });
+            }
         }
     }
 }
@@ -330,7 +614,62 @@ impl Session {
 impl Drop for Session {
     fn drop(&mut self) {
         self.monitor_task_handle.abort();
+        self.probe_task_handle.abort();
+    }
+}
+
+/// Periodically pings a capable client to measure its latency.
+async fn probe_latency(session: Weak<Session>) {
+    let mut interval = time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(session) = session.upgrade() else {
+            break;
+        };
+        if !session.capabilities().contains(Capabilities::LATENCY_PROBE) {
+            break;
+        }
+        session.user.latency.ping_sent();
+        session.try_send(ServerCommand::Ping).await;
+    }
+}
+
+/// Opens a room hosted by `user`, `practice` letting a lone player skip the ready handshake.
+async fn create_room(user: &Arc<User>, id: RoomId, practice: bool) -> Result<()> {
+    let mut room_guard = user.room.write().await;
+    if room_guard.is_some() {
+        bail!("already in room");
+    }
+    if !user.can_create_room() {
+        bail!(tl!("create-tag-required"));
+    }
+
+    let mut map_guard = user.server.rooms.write().await;
+    if user.server.at_room_limit(map_guard.len()) {
+        bail!(tl!("create-room-limit"));
     }
+    let room = Arc::new(Room::new(id.clone(), Arc::downgrade(user)));
+    room.practice.store(practice, Ordering::SeqCst);
+    match map_guard.entry(id.clone()) {
+        Entry::Vacant(entry) => {
+            entry.insert(Arc::clone(&room));
+        }
+        Entry::Occupied(_) => {
+            bail!(tl!("create-id-occupied"));
+        }
+    }
+    room.send(Message::CreateRoom { user: user.id }).await;
+    drop(map_guard);
+    user.server.stats.room_created();
+    *room_guard = Some(room);
+
+    info!(
+        user = user.id,
+        room = id.to_string(),
+        practice,
+        "user create room"
+    );
+    Ok(())
 }
 
 async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
@@ -372,7 +711,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         };
     }
     match cmd {
-        ClientCommand::Ping => unreachable!(),
+        ClientCommand::Ping | ClientCommand::Pong => unreachable!(),
         ClientCommand::Authenticate { .. } => Some(ServerCommand::Authenticate(Err(
             "repeated authenticate".to_owned(),
         ))),
@@ -385,6 +724,36 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
             .await;
             Some(ServerCommand::Chat(err_to_str(res)))
         }
+        ClientCommand::ChannelChat { channel, message } => {
+            let res: Result<()> = async move {
+                get_room!(room);
+                let channel = channel.into_inner();
+                if !room.is_in_channel(user.id, &channel).await {
+                    bail!("not in channel");
+                }
+                room.send_to_channel(user.id, &channel, message.into_inner())
+                    .await;
+                Ok(())
+            }
+            .await;
+            Some(ServerCommand::Chat(err_to_str(res)))
+        }
+        ClientCommand::JoinChannel { channel } => {
+            let res: Result<()> = async move {
+                get_room!(room);
+                room.join_channel(user.id, &channel.into_inner()).await
+            }
+            .await;
+            Some(ServerCommand::JoinChannel(err_to_str(res)))
+        }
+        ClientCommand::LeaveChannel { channel } => {
+            let res: Result<()> = async move {
+                get_room!(room);
+                room.leave_channel(user.id, &channel.into_inner()).await
+            }
+            .await;
+            Some(ServerCommand::LeaveChannel(err_to_str(res)))
+        }
         ClientCommand::Touches { frames } => {
             get_room!(~ room);
             if room.is_live() {
@@ -392,6 +761,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                 if let Some(frame) = frames.last() {
                     user.game_time.store(frame.time.to_bits(), Ordering::SeqCst);
                 }
+                room.forward_input(&user, PlayerInputFrames::Touches(Arc::clone(&frames)));
                 tokio::spawn(async move {
                     room.broadcast_monitors(ServerCommand::Touches {
                         player: user.id,
@@ -408,7 +778,9 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
             get_room!(~ room);
             if room.is_live() {
                 debug!("received {} judge events from {}", judges.len(), user.id);
+                room.forward_input(&user, PlayerInputFrames::Judges(Arc::clone(&judges)));
                 tokio::spawn(async move {
+                    room.buffer_judges(user.id, &judges).await;
                     room.broadcast_monitors(ServerCommand::Judges {
                         player: user.id,
                         judges,
@@ -421,32 +793,14 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
             None
         }
         ClientCommand::CreateRoom { id } => {
-            let res: Result<()> = async move {
-                let mut room_guard = user.room.write().await;
-                if room_guard.is_some() {
-                    bail!("already in room");
-                }
-
-                let mut map_guard = user.server.rooms.write().await;
-                let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
-                match map_guard.entry(id.clone()) {
-                    Entry::Vacant(entry) => {
-                        entry.insert(Arc::clone(&room));
-                    }
-                    Entry::Occupied(_) => {
-                        bail!(tl!("create-id-occupied"));
-                    }
-                }
-                room.send(Message::CreateRoom { user: user.id }).await;
-                drop(map_guard);
-                *room_guard = Some(room);
-
-                info!(user = user.id, room = id.to_string(), "user create room");
-                Ok(())
-            }
-            .await;
-            Some(ServerCommand::CreateRoom(err_to_str(res)))
+            let practice = user.server.config.solo_practice;
+            Some(ServerCommand::CreateRoom(err_to_str(
+                create_room(&user, id, practice).await,
+            )))
         }
+        ClientCommand::CreatePracticeRoom { id } => Some(ServerCommand::CreateRoom(err_to_str(
+            create_room(&user, id, true).await,
+        ))),
         ClientCommand::JoinRoom { id, monitor } => {
             let res: Result<JoinRoomResponse> = async move {
                 let mut room_guard = user.room.write().await;
@@ -457,8 +811,8 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                 let Some(room) = room else {
                     bail!("room not found")
                 };
-                if room.locked.load(Ordering::SeqCst) {
-                    bail!(tl!("join-room-locked"));
+                if room.is_locked() {
+                    bail!(room.locked_reason());
                 }
                 if !matches!(*room.state.read().await, InternalRoomState::SelectChart) {
                     bail!(tl!("join-game-ongoing"));
@@ -466,6 +820,15 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                 if monitor && !user.can_monitor() {
                     bail!(tl!("join-cant-monitor"));
                 }
+                if user.is_banned_from_room(&id).await {
+                    bail!(tl!("join-banned"));
+                }
+                let veto = user.server.host_api.get().and_then(|api| {
+                    api.on_user_join_room(user.id as u32, &id.to_string(), monitor)
+                });
+                if let Some(reason) = veto {
+                    bail!(reason);
+                }
                 if !room.add_user(Arc::downgrade(&user), monitor).await {
                     bail!(tl!("join-room-full"));
                 }
@@ -486,6 +849,12 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                     name: user.name.clone(),
                 })
                 .await;
+                if !monitor {
+                    room.claim_host(&user).await;
+                }
+                if let Some(cmd) = room.name_command(user.capabilities().await).await {
+                    user.try_send(cmd).await;
+                }
                 *room_guard = Some(Arc::clone(&room));
                 Ok(JoinRoomResponse {
                     state: room.client_room_state().await,
@@ -515,7 +884,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                     "user leave room"
                 );
                 if room.on_user_leave(&user).await {
-                    user.server.rooms.write().await.remove(&room.id);
+                    user.server.remove_room(&room.id).await;
                 }
                 Ok(())
             }
@@ -525,14 +894,14 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         ClientCommand::LockRoom { lock } => {
             let res: Result<()> = async move {
                 get_room!(room);
-                room.check_host(&user).await?;
+                room.check_host_or_cohost(&user).await?;
                 info!(
                     user = user.id,
                     room = room.id.to_string(),
                     lock,
                     "lock room"
                 );
-                room.locked.store(lock, Ordering::SeqCst);
+                room.set_locked(lock, Some(&user));
                 room.send(Message::LockRoom { lock }).await;
                 Ok(())
             }
@@ -559,33 +928,17 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         ClientCommand::SelectChart { id } => {
             let res: Result<()> = async move {
                 get_room!(room, InternalRoomState::SelectChart);
-                room.check_host(&user).await?;
+                room.check_host_or_cohost(&user).await?;
+                room.validate_chart(&user, id)?;
                 let span = debug_span!(
                     "select chart",
                     user = user.id,
                     room = room.id.to_string(),
-                    chart = id,
+                    chart = id.get(),
                 );
-                async move {
-                    trace!("fetch");
-                    let res: Chart = reqwest::get(format!("{HOST}/chart/{id}"))
-                        .await?
-                        .error_for_status()?
-                        .json()
-                        .await?;
-                    debug!("chart is {res:?}");
-                    room.send(Message::SelectChart {
-                        user: user.id,
-                        name: res.name.clone(),
-                        id: res.id,
-                    })
-                    .await;
-                    *room.chart.write().await = Some(res);
-                    room.on_state_change().await;
-                    Ok(())
-                }
-                .instrument(span)
-                .await
+                room.select_chart(&user.server, user.id, id)
+                    .instrument(span)
+                    .await
             }
             .await;
             Some(ServerCommand::SelectChart(err_to_str(res)))
@@ -594,18 +947,11 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         ClientCommand::RequestStart => {
             let res: Result<()> = async move {
                 get_room!(room, InternalRoomState::SelectChart);
-                room.check_host(&user).await?;
+                room.check_host_or_cohost(&user).await?;
                 if room.chart.read().await.is_none() {
                     bail!(tl!("start-no-chart-selected"));
                 }
-                debug!(room = room.id.to_string(), "room wait for ready");
-                room.reset_game_time().await;
-                room.send(Message::GameStart { user: user.id }).await;
-                *room.state.write().await = InternalRoomState::WaitForReady {
-                    started: std::iter::once(user.id).collect::<HashSet<_>>(),
-                };
-                room.on_state_change().await;
-                room.check_all_ready().await;
+                room.request_start(user.id).await;
                 Ok(())
             }
             .await;
@@ -636,7 +982,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                     if !started.remove(&user.id) {
                         bail!("not ready");
                     }
-                    if room.check_host(&user).await.is_ok() {
+                    if room.check_host_or_cohost(&user).await.is_ok() {
                         room.send(Message::CancelGame { user: user.id }).await;
                         *guard = InternalRoomState::SelectChart;
                         drop(guard);
@@ -653,11 +999,12 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         ClientCommand::Played { id } => {
             let res: Result<()> = async move {
                 get_room!(room);
-                let res: Record = reqwest::get(format!("{HOST}/record/{id}"))
-                    .await?
-                    .error_for_status()?
-                    .json()
-                    .await?;
+                let res: Record =
+                    reqwest::get(format!("{}/record/{id}", user.server.config.api_host))
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await?;
                 if res.player != user.id {
                     bail!("invalid record");
                 }
@@ -666,13 +1013,19 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                     user = user.id,
                     "user played: {res:?}"
                 );
-                room.send(Message::Played {
+                let played = Message::Played {
                     user: user.id,
                     score: res.score,
                     accuracy: res.accuracy,
                     full_combo: res.full_combo,
-                })
-                .await;
+                };
+                if !matches!(*room.state.read().await, InternalRoomState::Playing { .. }) {
+                    let grace = Duration::from_secs(user.server.config.played_grace_secs);
+                    room.submit_late_record(res, grace).await?;
+                    room.send(played).await;
+                    return Ok(());
+                }
+                room.send(played).await;
                 let mut guard = room.state.write().await;
                 if let InternalRoomState::Playing { results, aborted } = guard.deref_mut() {
                     if aborted.contains(&user.id) {
@@ -682,6 +1035,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                         bail!("already uploaded");
                     }
                     drop(guard);
+                    user.credit_playtime();
                     room.check_all_ready().await;
                 }
                 Ok(())
@@ -701,6 +1055,7 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
                         bail!("aborted");
                     }
                     drop(guard);
+                    user.credit_playtime();
                     room.send(Message::Abort { user: user.id }).await;
                     room.check_all_ready().await;
                 }
@@ -711,3 +1066,828 @@ async fn process(user: Arc<User>, cmd: ClientCommand) -> Option<ServerCommand> {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{AuthInfo, AuthProvider, ServerConfig};
+    use std::sync::atomic::AtomicU32;
+    use std::{future::Future, pin::Pin};
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_min_client_version() {
+        let check = |min, version| {
+            let config = ServerConfig {
+                min_client_version: min,
+                ..ServerConfig::default()
+            };
+            LANGUAGE.sync_scope(Arc::new(Language::default()), || {
+                check_client_version(&config, version)
+            })
+        };
+        assert!(check(None, 1).is_ok());
+        assert!(check(Some(5), 5).is_ok());
+        assert!(check(Some(5), 7).is_ok());
+        let err = check(Some(5), 4).unwrap_err().to_string();
+        assert!(err.contains("update"), "{err}");
+    }
+
+    pub(crate) type TestClient = Stream<ClientCommand, ServerCommand>;
+
+    /// Connects a client that sends `cmd` right away, returning the server side of the
+    /// session along with the commands the client receives.
+    pub(crate) async fn connect(
+        server: &Arc<ServerState>,
+        cmd: ClientCommand,
+    ) -> (
+        Result<Arc<Session>>,
+        mpsc::UnboundedReceiver<ServerCommand>,
+        tokio::task::JoinHandle<TestClient>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (resp_tx, resp_rx) = mpsc::unbounded_channel();
+        let client = tokio::spawn(async move {
+            let client = TestClient::new(
+                Some(phira_mp_common::PROTOCOL_VERSION),
+                TcpStream::connect(addr).await.unwrap(),
+                Box::new(move |_, cmd| {
+                    let _ = resp_tx.send(cmd);
+                    async {}
+                }),
+            )
+            .await
+            .unwrap();
+            client.send(cmd).await.unwrap();
+            client
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let session = Session::new(Uuid::new_v4(), stream, Arc::clone(server)).await;
+        (session, resp_rx, client)
+    }
+
+    pub(crate) fn authenticate_alice() -> ClientCommand {
+        ClientCommand::Authenticate {
+            token: "alice".to_owned().try_into().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_session_is_dropped() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        // Room commands before authenticating
+        let (session, _, client) = connect(
+            &server,
+            ClientCommand::JoinRoom {
+                id: "room".to_owned().try_into().unwrap(),
+                monitor: false,
+            },
+        )
+        .await;
+        assert!(session.is_err());
+        assert!(server.users.read().await.is_empty());
+        assert!(server.rooms.read().await.is_empty());
+        assert!(
+            server
+                .stats
+                .buckets(Duration::from_secs(3600))
+                .iter()
+                .all(|it| it.peak_users == 0)
+        );
+        drop(client.await.unwrap());
+    }
+
+    /// Authenticates the token `alice` as user 7.
+    pub(crate) struct MockAuth;
+
+    impl AuthProvider for MockAuth {
+        fn authenticate<'a>(
+            &'a self,
+            token: &'a str,
+        ) -> Pin<Box<dyn Future<Output = Result<AuthInfo>> + Send + 'a>> {
+            Box::pin(async move {
+                match token {
+                    "alice" => Ok(AuthInfo {
+                        id: 7,
+                        name: "Alice".to_owned(),
+                        language: "en-US".to_owned(),
+                    }),
+                    _ => Err(anyhow!("unknown token")),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_auth_provider() {
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let (session, mut resp_rx, client) = connect(&server, authenticate_alice()).await;
+        let session = session.unwrap();
+        assert_eq!(session.user.id, 7);
+        assert_eq!(session.user.name, "Alice");
+        assert!(server.users.read().await.contains_key(&7));
+        loop {
+            match resp_rx.recv().await.unwrap() {
+                ServerCommand::Authenticate(res) => {
+                    assert_eq!(res.unwrap().0.name, "Alice");
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_kick_user() {
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(crate::PluginBridge::new(&server)));
+        let disconnects = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&disconnects);
+        host_api
+            .subscribe_event(
+                phira_mp_plugin::event_system::predefined::USER_DISCONNECT,
+                Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }),
+                "test",
+            )
+            .unwrap();
+        assert!(server.host_api.set(Arc::clone(&host_api)).is_ok());
+
+        let (session, _, client) = connect(&server, authenticate_alice()).await;
+        let session = session.unwrap();
+        server
+            .sessions
+            .write()
+            .await
+            .insert(session.id, Arc::clone(&session));
+        let user = Arc::clone(&session.user);
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        *user.room.write().await = Some(Arc::clone(&room));
+        server.rooms.write().await.insert(id.clone(), room);
+
+        // Not online, even while the user list is busy
+        let (locked_tx, locked_rx) = oneshot::channel();
+        let release = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move {
+                let _users = server.users.write().await;
+                let _ = locked_tx.send(());
+                time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        locked_rx.await.unwrap();
+        assert!(host_api.kick_user(9).is_err());
+        release.await.unwrap();
+
+        host_api.kick_user(7).unwrap();
+        for _ in 0..100 {
+            if server.rooms.read().await.is_empty() && disconnects.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(server.users.read().await.is_empty());
+        assert!(server.sessions.read().await.is_empty());
+        assert!(server.rooms.read().await.is_empty());
+        assert!(session.stream.send(ServerCommand::Ping).await.is_err());
+        assert_eq!(disconnects.load(Ordering::SeqCst), 1);
+        assert!(host_api.kick_user(7).is_err());
+
+        // The kick is not reversible
+        let commands = phira_mp_plugin::ServerCommands::new(host_api);
+        assert!(commands.execute("kick", &["7".to_owned()]).is_err());
+        let user = Arc::new(User::new(
+            8,
+            "user".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        server.users.write().await.insert(8, user);
+        commands.execute("kick", &["8".to_owned()]).unwrap();
+        assert_eq!(
+            commands.execute("undo", &[]).unwrap(),
+            "踢出用户 8 不可撤销, 已跳过"
+        );
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_send_message_to_user() {
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig {
+                message_sender: 5,
+                ..ServerConfig::default()
+            },
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(crate::PluginBridge::new(&server)));
+
+        let (session, mut resp_rx, client) = connect(&server, authenticate_alice()).await;
+        let session = session.unwrap();
+        let commands = phira_mp_plugin::ServerCommands::new(Arc::clone(&host_api));
+        commands
+            .execute("sendmsg", &["7".to_owned(), "hello".to_owned()])
+            .unwrap();
+        loop {
+            match resp_rx.recv().await.unwrap() {
+                ServerCommand::Message(Message::Chat { user, content }) => {
+                    assert_eq!(user, 5);
+                    assert_eq!(content, "hello");
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert!(host_api.send_message_to_user(8, "hello").is_err());
+
+        // Unknown users are not reported delivered while the user list is busy
+        let (locked_tx, locked_rx) = oneshot::channel();
+        let release = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move {
+                let _users = server.users.write().await;
+                let _ = locked_tx.send(());
+                time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        locked_rx.await.unwrap();
+        assert!(host_api.send_message_to_user(8, "hello").is_err());
+        release.await.unwrap();
+
+        // Reconnecting users miss the message
+        *session.user.session.write().await = None;
+        assert_eq!(
+            host_api.send_message_to_user(7, "hello").unwrap(),
+            phira_mp_plugin::api_host::DeliveryStatus::UserOffline
+        );
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_banned_user_is_rejected() {
+        for ban_ip in [false, true] {
+            let server = Arc::new(ServerState::with_auth(
+                ServerConfig::default(),
+                mpsc::channel(1).0,
+                Arc::new(MockAuth),
+            ));
+            let dir = tempfile::tempdir().unwrap();
+            let (_plugin_manager, host_api) =
+                phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+            if ban_ip {
+                host_api.ban_user_by_ip("127.0.0.1", "cheating").unwrap();
+            } else {
+                host_api.ban_user_by_id(7, "cheating").unwrap();
+            }
+            assert!(server.host_api.set(host_api).is_ok());
+
+            let (session, mut resp_rx, client) = connect(&server, authenticate_alice()).await;
+            assert!(session.is_err());
+            assert!(server.users.read().await.is_empty());
+            loop {
+                match resp_rx.recv().await.unwrap() {
+                    ServerCommand::Authenticate(res) => {
+                        assert!(res.is_err());
+                        break;
+                    }
+                    _ => continue,
+                }
+            }
+            drop(client.await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_room_banned_user_cannot_join() {
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.ban_user_from_room_by_id(8, "lobby").unwrap();
+        assert!(server.host_api.set(host_api).is_ok());
+
+        let id: RoomId = "lobby".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Weak::new()));
+        server
+            .rooms
+            .write()
+            .await
+            .insert(id.clone(), Arc::clone(&room));
+
+        for (user_id, allowed) in [(8, false), (9, true)] {
+            let user = Arc::new(User::new(
+                user_id,
+                "guest".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ));
+            let cmd = ClientCommand::JoinRoom {
+                id: id.clone(),
+                monitor: false,
+            };
+            let resp = LANGUAGE
+                .scope(
+                    Arc::new(Language::default()),
+                    process(Arc::clone(&user), cmd),
+                )
+                .await;
+            match resp {
+                Some(ServerCommand::JoinRoom(res)) => assert_eq!(res.is_ok(), allowed),
+                _ => panic!("unexpected response"),
+            }
+            assert_eq!(user.room.read().await.is_some(), allowed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_vetoes_join() {
+        use phira_mp_plugin::event_system::{HandlerResult, predefined};
+
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api
+            .subscribe_cancellable_event(
+                predefined::USER_JOIN_ROOM,
+                Box::new(|event| {
+                    Ok(if event.data["user_id"] == 8 {
+                        HandlerResult::Cancel("banned from this room".to_owned())
+                    } else {
+                        HandlerResult::Continue
+                    })
+                }),
+                "moderation",
+                0,
+            )
+            .unwrap();
+        assert!(server.host_api.set(Arc::clone(&host_api)).is_ok());
+
+        let host = Arc::new(User::new(
+            7,
+            "host".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&host)));
+        server
+            .rooms
+            .write()
+            .await
+            .insert(id.clone(), Arc::clone(&room));
+
+        for (user_id, allowed) in [(8, false), (9, true)] {
+            let user = Arc::new(User::new(
+                user_id,
+                "guest".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ));
+            let cmd = ClientCommand::JoinRoom {
+                id: id.clone(),
+                monitor: false,
+            };
+            match process(Arc::clone(&user), cmd).await {
+                Some(ServerCommand::JoinRoom(res)) => {
+                    assert_eq!(res.is_ok(), allowed);
+                    if !allowed {
+                        assert_eq!(res.unwrap_err(), "banned from this room");
+                    }
+                }
+                _ => panic!("unexpected response"),
+            }
+            assert_eq!(user.room.read().await.is_some(), allowed);
+        }
+        assert_eq!(room.users().await.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_broadcast_message() {
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::new(MockAuth),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.set_server_bridge(Arc::new(crate::PluginBridge::new(&server)));
+
+        let (session, mut resp_rx, client) = connect(&server, authenticate_alice()).await;
+        let session = session.unwrap();
+        // Alice plays in room 1 alongside a reconnecting user, room 2 has no one connected
+        let dangling = Arc::new(User::new(
+            8,
+            "dangling".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        server.users.write().await.insert(8, Arc::clone(&dangling));
+        for (id, host) in [("1", &session.user), ("2", &dangling)] {
+            let id: RoomId = id.to_owned().try_into().unwrap();
+            let room = Arc::new(Room::new(id.clone(), Arc::downgrade(host)));
+            server.rooms.write().await.insert(id, room);
+        }
+        let id: RoomId = "1".to_owned().try_into().unwrap();
+        let room = server.rooms.read().await.get(&id).map(Arc::clone).unwrap();
+        assert!(room.add_user(Arc::downgrade(&dangling), false).await);
+        *room.state.write().await = InternalRoomState::Playing {
+            results: Default::default(),
+            aborted: Default::default(),
+        };
+
+        assert_eq!(host_api.broadcast_message_to_all("all").unwrap(), 1);
+        assert_eq!(host_api.broadcast_message_to_room(1, "room").unwrap(), 1);
+        assert_eq!(host_api.broadcast_message_to_room(2, "room").unwrap(), 0);
+        assert!(host_api.broadcast_message_to_room(3, "room").is_err());
+        assert_eq!(
+            host_api
+                .broadcast_message_to_all_rooms("rooms", false)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            host_api
+                .broadcast_message_to_all_rooms("forced", true)
+                .unwrap(),
+            1
+        );
+
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            if let ServerCommand::Message(Message::Chat { user, content }) =
+                resp_rx.recv().await.unwrap()
+            {
+                assert_eq!(user, crate::SYSTEM_SENDER);
+                received.push(content);
+            }
+        }
+        assert_eq!(received, ["all", "room", "forced"]);
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_kick_dangling_user() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        let new_user = || {
+            Arc::new(User::new(
+                1,
+                "user".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ))
+        };
+        let user = new_user();
+        let other = Arc::new(User::new(
+            2,
+            "other".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        let id: RoomId = "room".to_owned().try_into().unwrap();
+        let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&user)));
+        assert!(room.add_user(Arc::downgrade(&other), false).await);
+        for user in [&user, &other] {
+            *user.room.write().await = Some(Arc::clone(&room));
+            server.users.write().await.insert(user.id, Arc::clone(user));
+        }
+        server
+            .rooms
+            .write()
+            .await
+            .insert(id.clone(), Arc::clone(&room));
+
+        Arc::clone(&user).dangle().await;
+        user.kick().await;
+        assert!(!server.users.read().await.contains_key(&1));
+        assert_eq!(room.users().await.len(), 1);
+        assert!(room.check_host(&other).await.is_ok());
+
+        // The user logs in again before the dangle timeout would have fired
+        server.users.write().await.insert(1, new_user());
+        time::sleep(DANGLE_TIMEOUT + Duration::from_secs(1)).await;
+        assert!(server.users.read().await.contains_key(&1));
+        assert_eq!(room.users().await.len(), 1);
+        assert!(server.rooms.read().await.contains_key(&id));
+    }
+
+    /// Accepts "alice" and "bob" until throttled, then answers 429 with `Retry-After: 5`.
+    #[derive(Default)]
+    struct ThrottledAuth {
+        throttled: AtomicBool,
+        calls: AtomicU32,
+    }
+
+    impl AuthProvider for ThrottledAuth {
+        fn authenticate<'a>(
+            &'a self,
+            token: &'a str,
+        ) -> Pin<Box<dyn Future<Output = Result<AuthInfo>> + Send + 'a>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let res = if self.throttled.load(Ordering::SeqCst) {
+                Err(RateLimited {
+                    retry_after: Some(Duration::from_secs(5)),
+                }
+                .into())
+            } else {
+                Ok(AuthInfo {
+                    id: token.len() as i32,
+                    name: token.to_owned(),
+                    language: "en-US".to_owned(),
+                })
+            };
+            Box::pin(async move { res })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_auth_rate_limit_backoff() {
+        let auth = Arc::new(ThrottledAuth::default());
+        let server = Arc::new(ServerState::with_auth(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+            Arc::clone(&auth) as Arc<dyn AuthProvider>,
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        let events = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&events);
+        host_api
+            .subscribe_event(
+                phira_mp_plugin::event_system::predefined::AUTH_RATE_LIMITED,
+                Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }),
+                "test",
+            )
+            .unwrap();
+        assert!(server.host_api.set(host_api).is_ok());
+
+        assert_eq!(server.authenticate("alice").await.unwrap().name, "alice");
+        auth.throttled.store(true, Ordering::SeqCst);
+
+        // A recently authenticated user is served from the cache
+        assert_eq!(server.authenticate("alice").await.unwrap().name, "alice");
+        assert_eq!(auth.calls.load(Ordering::SeqCst), 2);
+        tokio::task::yield_now().await;
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+        let stats = server.stats.buckets(Duration::from_secs(3600));
+        assert_eq!(stats.iter().map(|it| it.auth_rate_limited).sum::<u32>(), 1);
+
+        // Others are refused without asking the auth server during the backoff
+        let err = server.authenticate("bob").await.unwrap_err();
+        assert!(err.downcast_ref::<RateLimited>().is_some());
+        assert_eq!(auth.calls.load(Ordering::SeqCst), 2);
+
+        time::sleep(Duration::from_secs(6)).await;
+        auth.throttled.store(false, Ordering::SeqCst);
+        assert_eq!(server.authenticate("bob").await.unwrap().name, "bob");
+        assert_eq!(auth.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_auth_config() {
+        use phira_mp_plugin::event_system::{HandlerConcurrency, HandlerOverflow};
+        let config: ServerConfig = serde_yaml::from_str(
+            "monitors: []\n\
+             api_host: https://staging.example\n\
+             auth:\n  kind: static\n  users:\n    secret: { id: 1, name: a, language: en-US }\n\
+             event_handlers: { max_per_plugin: 4, overflow: shed }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.event_handlers,
+            HandlerConcurrency {
+                max_per_plugin: 4,
+                overflow: HandlerOverflow::Shed,
+            }
+        );
+        assert_eq!(config.api_host, "https://staging.example");
+        assert!(matches!(config.auth, crate::AuthConfig::Static { .. }));
+        let config: ServerConfig = serde_yaml::from_str("monitors: []").unwrap();
+        assert_eq!(config.api_host, crate::DEFAULT_API_HOST);
+        assert_eq!(config.event_handlers, HandlerConcurrency::default());
+        assert_eq!(config.message_sender, crate::SYSTEM_SENDER);
+        assert!(matches!(
+            config.auth,
+            crate::AuthConfig::Http { host: None }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_room_tag() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig {
+                create_room_tag: Some("VIP".to_owned()),
+                ..ServerConfig::default()
+            },
+            mpsc::channel(1).0,
+        ));
+        let user = |id| {
+            User::new(
+                id,
+                "user".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            )
+        };
+        // Nobody has tags without the plugin system
+        assert!(!user(1).can_create_room());
+
+        let dir = tempfile::tempdir().unwrap();
+        let (_plugin_manager, host_api) =
+            phira_mp_plugin::create_plugin_system(dir.path()).unwrap();
+        host_api.add_user_tag(1, "VIP").unwrap();
+        assert!(server.host_api.set(host_api).is_ok());
+        assert!(user(1).can_create_room());
+        assert!(!user(2).can_create_room());
+    }
+
+    #[tokio::test]
+    async fn test_create_practice_room() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        for (id, practice) in [("plain", false), ("practice", true)] {
+            let user = Arc::new(User::new(
+                id.len() as i32,
+                "user".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ));
+            let id: RoomId = id.to_owned().try_into().unwrap();
+            let cmd = if practice {
+                ClientCommand::CreatePracticeRoom { id: id.clone() }
+            } else {
+                ClientCommand::CreateRoom { id: id.clone() }
+            };
+            match process(Arc::clone(&user), cmd).await {
+                Some(ServerCommand::CreateRoom(res)) => assert!(res.is_ok()),
+                _ => panic!("unexpected response"),
+            }
+            assert_eq!(server.rooms.read().await[&id].is_practice(), practice);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_disconnected_users_are_released() {
+        let server = Arc::new(ServerState::new(
+            ServerConfig::default(),
+            mpsc::channel(1).0,
+        ));
+        for _ in 0..3 {
+            let user = Arc::new(User::new(
+                1,
+                "user".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ));
+            server.users.write().await.insert(1, Arc::clone(&user));
+            user.dangle().await;
+            assert_eq!(server.users.read().await.len(), 1);
+            time::sleep(DANGLE_TIMEOUT + Duration::from_secs(1)).await;
+            assert!(server.users.read().await.is_empty());
+        }
+
+        // Reconnecting in time keeps the user
+        let user = Arc::new(User::new(
+            1,
+            "user".to_owned(),
+            Language::default(),
+            Arc::clone(&server),
+        ));
+        server.users.write().await.insert(1, Arc::clone(&user));
+        Arc::clone(&user).dangle().await;
+        user.set_session(Weak::new()).await;
+        time::sleep(DANGLE_TIMEOUT + Duration::from_secs(1)).await;
+        assert_eq!(server.users.read().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_latency_from_delayed_pong() {
+        let latency = Latency::default();
+        assert!(latency.pong_received().is_none());
+        assert!(latency.rtt().is_none());
+
+        latency.ping_sent();
+        time::sleep(Duration::from_millis(20)).await;
+        let sample = latency.pong_received().unwrap();
+        assert!(sample >= Duration::from_millis(20));
+        assert!(sample < Duration::from_secs(1));
+        assert_eq!(
+            latency.rtt(),
+            Some(Duration::from_micros(sample.as_micros() as u64))
+        );
+
+        // Unsolicited pongs are ignored
+        assert!(latency.pong_received().is_none());
+
+        // Later samples are smoothed
+        latency.ping_sent();
+        let second = latency.pong_received().unwrap();
+        let rtt = latency.rtt().unwrap();
+        assert!(second < rtt && rtt < sample);
+
+        latency.reset();
+        assert!(latency.rtt().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_session_membership() {
+        for policy in [
+            DuplicateSessionPolicy::Inherit,
+            DuplicateSessionPolicy::Fresh,
+        ] {
+            let (lost_con_tx, mut lost_con_rx) = mpsc::channel(1);
+            let server = Arc::new(ServerState::with_auth(
+                ServerConfig {
+                    duplicate_session_policy: policy,
+                    ..ServerConfig::default()
+                },
+                lost_con_tx,
+                Arc::new(MockAuth),
+            ));
+            let (first, _, first_client) = connect(&server, authenticate_alice()).await;
+            let first = first.unwrap();
+            let user = Arc::clone(&first.user);
+
+            let host = Arc::new(User::new(
+                1,
+                "host".to_owned(),
+                Language::default(),
+                Arc::clone(&server),
+            ));
+            let id: RoomId = "room".to_owned().try_into().unwrap();
+            let room = Arc::new(Room::new(id.clone(), Arc::downgrade(&host)));
+            assert!(room.add_user(Arc::downgrade(&user), false).await);
+            *host.room.write().await = Some(Arc::clone(&room));
+            *user.room.write().await = Some(Arc::clone(&room));
+            server.rooms.write().await.insert(id, Arc::clone(&room));
+
+            // The same account connects again
+            let (second, _, second_client) = connect(&server, authenticate_alice()).await;
+            let second = second.unwrap();
+            assert!(Arc::ptr_eq(&second.user, &user));
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(lost_con_rx.recv().await, Some(first.id));
+            assert!(first.stream.send(ServerCommand::Ping).await.is_err());
+            assert!(second.stream.send(ServerCommand::Ping).await.is_ok());
+            assert!(user.is_current_session(&second).await);
+
+            let members: Vec<_> = room.users().await.iter().map(|it| it.id).collect();
+            match policy {
+                DuplicateSessionPolicy::Inherit => {
+                    assert_eq!(members, vec![1, 7]);
+                    assert!(user.room.read().await.is_some());
+                }
+                DuplicateSessionPolicy::Fresh => {
+                    assert_eq!(members, vec![1]);
+                    assert!(user.room.read().await.is_none());
+                }
+            }
+            assert_eq!(server.rooms.read().await.len(), 1);
+            drop(first_client.await.unwrap());
+            drop(second_client.await.unwrap());
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Game-level server statistics in fixed time buckets.
+
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Length of a statistics bucket.
+pub const STATS_BUCKET: Duration = Duration::from_secs(60 * 60);
+
+/// Counters of one bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatsBucket {
+    /// Start of the bucket, in seconds since the Unix epoch.
+    pub start: u64,
+    pub peak_users: usize,
+    pub rooms_created: u32,
+    pub games_completed: u32,
+    /// Authentications refused by the auth server's rate limiting.
+    pub auth_rate_limited: u32,
+}
+
+/// Time-bucketed counters, keeping at most `retention` buckets.
+pub struct ServerStats {
+    buckets: parking_lot::Mutex<VecDeque<StatsBucket>>,
+    retention: usize,
+}
+
+impl ServerStats {
+    pub fn new(retention: usize) -> Self {
+        Self {
+            buckets: parking_lot::Mutex::default(),
+            retention: retention.max(1),
+        }
+    }
+
+    /// Records the current number of online users.
+    pub fn record_users(&self, users: usize) {
+        self.update(SystemTime::now(), |it| {
+            it.peak_users = it.peak_users.max(users)
+        });
+    }
+
+    pub fn room_created(&self) {
+        self.update(SystemTime::now(), |it| it.rooms_created += 1);
+    }
+
+    pub fn game_completed(&self) {
+        self.update(SystemTime::now(), |it| it.games_completed += 1);
+    }
+
+    pub fn auth_rate_limited(&self) {
+        self.update(SystemTime::now(), |it| it.auth_rate_limited += 1);
+    }
+
+    /// Buckets overlapping the last `window`, oldest first.
+    pub fn buckets(&self, window: Duration) -> Vec<StatsBucket> {
+        self.buckets_at(SystemTime::now(), window)
+    }
+
+    fn update(&self, now: SystemTime, f: impl FnOnce(&mut StatsBucket)) {
+        let start = bucket_start(now);
+        let mut buckets = self.buckets.lock();
+        if buckets.back().is_none_or(|it| it.start < start) {
+            buckets.push_back(StatsBucket {
+                start,
+                ..StatsBucket::default()
+            });
+            while buckets.len() > self.retention {
+                buckets.pop_front();
+            }
+        }
+        // Clock going backwards counts towards the latest bucket
+        f(buckets.back_mut().unwrap());
+    }
+
+    fn buckets_at(&self, now: SystemTime, window: Duration) -> Vec<StatsBucket> {
+        let since = bucket_start(now.checked_sub(window).unwrap_or(UNIX_EPOCH));
+        self.buckets
+            .lock()
+            .iter()
+            .filter(|it| it.start >= since)
+            .cloned()
+            .collect()
+    }
+}
+
+fn bucket_start(time: SystemTime) -> u64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    secs - secs % STATS_BUCKET.as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucketed_counts() {
+        let stats = ServerStats::new(2);
+        let hour = |n: u64| UNIX_EPOCH + STATS_BUCKET * n as u32 + Duration::from_secs(n);
+
+        stats.update(hour(1), |it| it.peak_users = it.peak_users.max(3));
+        stats.update(hour(1), |it| it.rooms_created += 1);
+        stats.update(hour(1), |it| it.peak_users = it.peak_users.max(2));
+        stats.update(hour(2), |it| it.games_completed += 1);
+        stats.update(hour(2), |it| it.rooms_created += 1);
+
+        let start = STATS_BUCKET.as_secs();
+        assert_eq!(
+            stats.buckets_at(hour(2), STATS_BUCKET * 2),
+            vec![
+                StatsBucket {
+                    start,
+                    peak_users: 3,
+                    rooms_created: 1,
+                    games_completed: 0,
+                    auth_rate_limited: 0,
+                },
+                StatsBucket {
+                    start: start * 2,
+                    peak_users: 0,
+                    rooms_created: 1,
+                    games_completed: 1,
+                    auth_rate_limited: 0,
+                },
+            ]
+        );
+        assert_eq!(stats.buckets_at(hour(2), Duration::ZERO).len(), 1);
+
+        // Only the latest buckets are retained
+        stats.update(hour(3), |it| it.rooms_created += 1);
+        let buckets = stats.buckets_at(hour(3), STATS_BUCKET * 10);
+        assert_eq!(
+            buckets.iter().map(|it| it.start).collect::<Vec<_>>(),
+            vec![start * 2, start * 3]
+        );
+    }
+}